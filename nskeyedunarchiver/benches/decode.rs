@@ -0,0 +1,92 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use nskeyedunarchiver::{KeyedArchive, derive::Decodable};
+use plist::{Dictionary, Uid, Value};
+use std::hint::black_box;
+
+#[derive(Decodable)]
+#[allow(dead_code)]
+struct Note {
+    title: String,
+    count: i64,
+    published: bool,
+    tags: Vec<String>,
+}
+
+fn uid(uid: usize) -> Value {
+    Value::Uid(Uid::new(uid as u64))
+}
+
+fn classes(names: &[&str]) -> Value {
+    let mut dict = Dictionary::new();
+    dict.insert("$classname".into(), names[0].into());
+    dict.insert(
+        "$classes".into(),
+        Value::Array(names.iter().map(|n| Value::from(*n)).collect()),
+    );
+    dict.into()
+}
+
+/// Builds an archive with an array of `count` notes, each of them has
+/// a title string and an array of tags (about 5 values per note).
+fn archive(count: usize) -> Value {
+    let mut objects = vec![
+        "$null".into(),
+        Value::Dictionary(Dictionary::new()), // the root array, filled below
+        classes(&["NSArray", "NSObject"]),
+        classes(&["Note", "NSObject"]),
+        "tag".into(),
+    ];
+    let mut notes = Vec::with_capacity(count);
+    for i in 0..count {
+        let note = objects.len();
+        notes.push(uid(note));
+        let mut dict = Dictionary::new();
+        dict.insert("$class".into(), uid(3));
+        dict.insert("title".into(), uid(note + 1));
+        dict.insert("count".into(), (i as i64).into());
+        dict.insert("published".into(), (i % 2 == 0).into());
+        dict.insert("tags".into(), uid(note + 2));
+        objects.push(dict.into());
+        objects.push(format!("Note #{i}").into());
+        let mut tags = Dictionary::new();
+        tags.insert("$class".into(), uid(2));
+        tags.insert("NS.objects".into(), Value::Array(vec![uid(4), uid(4)]));
+        objects.push(tags.into());
+    }
+    let mut root = Dictionary::new();
+    root.insert("$class".into(), uid(2));
+    root.insert("NS.objects".into(), Value::Array(notes));
+    objects[1] = root.into();
+
+    let mut top = Dictionary::new();
+    top.insert("root".into(), uid(1));
+    let mut dict = Dictionary::new();
+    dict.insert("$archiver".into(), "NSKeyedArchiver".into());
+    dict.insert("$version".into(), 100000.into());
+    dict.insert("$top".into(), top.into());
+    dict.insert("$objects".into(), Value::Array(objects));
+    dict.into()
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let plist = archive(30_000);
+    let mut bytes = Vec::new();
+    plist.to_writer_binary(&mut bytes).unwrap();
+
+    let mut group = c.benchmark_group("100k objects");
+    group.sample_size(20);
+    group.bench_function("parse", |b| {
+        b.iter(|| KeyedArchive::from_plist(black_box(plist.clone())).unwrap())
+    });
+    group.bench_function("parse binary plist", |b| {
+        b.iter(|| KeyedArchive::from_bytes(black_box(&bytes)).unwrap())
+    });
+    let archive = KeyedArchive::from_plist(plist.clone()).unwrap();
+    group.bench_function("decode", |b| {
+        b.iter(|| archive.decode_top::<Vec<Note>>("root").unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
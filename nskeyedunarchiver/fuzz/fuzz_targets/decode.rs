@@ -0,0 +1,61 @@
+//! Decodes every value of an archive parsed from arbitrary bytes as
+//! built-in types and edits it.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nskeyedunarchiver::{
+    Data, Decodable, KeyedArchive, ObjectValue, SkipNulls, UniqueId, ValueRef, foundation::*,
+    nib::*,
+};
+use std::collections::HashMap;
+
+fn decode_all(value: &ObjectValue) {
+    macro_rules! decode {
+        ($($typ:ty),* $(,)?) => {
+            $(let _ = <$typ as Decodable>::decode(value);)*
+        };
+    }
+    decode!(
+        String,
+        Data,
+        f64,
+        i32,
+        char,
+        Vec<ValueRef>,
+        SkipNulls<String>,
+        HashMap<String, ValueRef>,
+        NSAttributedString,
+        NSColor,
+        NSPoint,
+        NSSize,
+        NSRect,
+        NSRange,
+        NSPredicate,
+        NSExpression,
+        NSSortDescriptor,
+        NSURL,
+        NSUUID,
+        NSDate,
+        NSClassSwapper,
+        NibConnection,
+        NibObjectData,
+    );
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(archive) = KeyedArchive::from_bytes(data) else {
+        return;
+    };
+    for value in archive.values() {
+        decode_all(&value.into());
+        let _ = effective_class(value);
+    }
+    let _ = NibObjectData::from_archive(&archive);
+
+    let mut editor = archive.edit();
+    if !editor.is_empty() {
+        let _ = editor.delete_subtree(UniqueId::new(data.len() % editor.len()));
+    }
+    let _ = editor.into_archive();
+});
@@ -0,0 +1,20 @@
+//! Parses arbitrary bytes as a plist and a keyed archive.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nskeyedunarchiver::KeyedArchive;
+use plist::Value;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(plist) = Value::from_reader(std::io::Cursor::new(data)) else {
+        return;
+    };
+    let _ = KeyedArchive::validate(&plist);
+    let _ = KeyedArchive::find_embedded(&plist);
+    if let Ok(archive) = KeyedArchive::from_plist(plist) {
+        for value in archive.values() {
+            let _ = value.to_string();
+        }
+    }
+});
@@ -0,0 +1,376 @@
+//! A parser of URL bookmark data.
+//!
+//! `-[NSURL bookmarkDataWithOptions:...]` serializes a reference to a file
+//! as a binary blob starting with `book`. Such blobs are often stored in
+//! data fields of keyed archives (recent items, sandbox extensions, etc.)
+//! and may be parsed without resolving them:
+//!
+//! ```ignore
+//! let bookmark: Bookmark = archive.decode_top("bookmark")?;
+//! println!("{:?} on {:?}", bookmark.path(), bookmark.volume_name());
+//! ```
+//!
+//! A bookmark is a table of contents (TOC) mapping numeric keys (like
+//! [PATH_COMPONENTS]) to typed values. [Bookmark] parses all entries into
+//! [BookmarkValue]s and provides accessors for the common ones. The format
+//! is undocumented, so unknown keys and types are kept as is.
+
+use crate::decodable::borrow_bytes;
+use crate::foundation::AppleTime;
+use crate::{DEFAULT_MAX_DEPTH, DeError, Decodable, DecodeContext, Error, ObjectValue};
+use std::collections::{BTreeMap, HashSet, btree_map::Entry};
+use std::path::PathBuf;
+
+/// The magic of bookmark data.
+const MAGIC: &[u8] = b"book";
+/// The magic of a table of contents.
+const TOC_MAGIC: u32 = 0xffff_fffe;
+/// Keys with this bit refer to a string key instead of a numeric one.
+const STRING_KEY_BIT: u32 = 0x8000_0000;
+
+// Value types. The low byte of a type is its subtype
+const TYPE_STRING: u32 = 0x0100;
+const TYPE_DATA: u32 = 0x0200;
+const TYPE_NUMBER: u32 = 0x0300;
+const TYPE_DATE: u32 = 0x0400;
+const TYPE_BOOLEAN: u32 = 0x0500;
+const TYPE_ARRAY: u32 = 0x0600;
+const TYPE_DICTIONARY: u32 = 0x0700;
+const TYPE_UUID: u32 = 0x0800;
+const TYPE_URL: u32 = 0x0900;
+const TYPE_NULL: u32 = 0x0a00;
+
+/// Names of path components of a target, from the root of its volume.
+pub const PATH_COMPONENTS: u32 = 0x1004;
+/// File IDs (inode numbers) of path components.
+pub const FILE_IDS: u32 = 0x1005;
+/// A creation date of a target.
+pub const CREATION_DATE: u32 = 0x1040;
+/// A path of a volume of a target, as a URL.
+pub const VOLUME_PATH: u32 = 0x2002;
+/// A URL of a volume of a target.
+pub const VOLUME_URL: u32 = 0x2005;
+/// A name of a volume of a target.
+pub const VOLUME_NAME: u32 = 0x2010;
+/// A UUID string of a volume of a target.
+pub const VOLUME_UUID: u32 = 0x2011;
+/// A capacity of a volume of a target in bytes.
+pub const VOLUME_SIZE: u32 = 0x2012;
+/// A creation date of a volume of a target.
+pub const VOLUME_CREATION_DATE: u32 = 0x2013;
+/// Whether a volume of a target is a boot volume.
+pub const VOLUME_IS_BOOT: u32 = 0x2030;
+/// An index of a containing folder in [PATH_COMPONENTS].
+pub const CONTAINING_FOLDER_INDEX: u32 = 0xc001;
+/// A name of a user that created a bookmark.
+pub const CREATOR_USER_NAME: u32 = 0xc011;
+/// A uid of a user that created a bookmark.
+pub const CREATOR_UID: u32 = 0xc012;
+/// A display name of a target.
+pub const DISPLAY_NAME: u32 = 0xf017;
+/// A sandbox extension granting read-write access to a target.
+pub const SANDBOX_RW_EXTENSION: u32 = 0xf080;
+/// A sandbox extension granting read-only access to a target.
+pub const SANDBOX_RO_EXTENSION: u32 = 0xf081;
+
+/// Checks if `bytes` start with the bookmark data magic.
+pub fn is_bookmark(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// A value of a bookmark entry.
+#[derive(Debug, PartialEq, Clone)]
+pub enum BookmarkValue {
+    String(String),
+    Data(Vec<u8>),
+    Integer(i64),
+    Float(f64),
+    /// A date as seconds since 1 January 2001 00:00:00 UTC.
+    Date(AppleTime),
+    Boolean(bool),
+    Array(Vec<BookmarkValue>),
+    Dictionary(Vec<(BookmarkValue, BookmarkValue)>),
+    Uuid([u8; 16]),
+    /// An absolute URL string. Relative URLs are joined with their base.
+    Url(String),
+    Null,
+    /// A value of an unknown type with its raw bytes.
+    Unknown {
+        ty: u32,
+        bytes: Vec<u8>,
+    },
+}
+
+impl BookmarkValue {
+    /// Returns a string if it's [BookmarkValue::String] or
+    /// [BookmarkValue::Url].
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) | Self::Url(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns an integer if it's [BookmarkValue::Integer].
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Returns a date if it's [BookmarkValue::Date].
+    pub fn as_date(&self) -> Option<AppleTime> {
+        match self {
+            Self::Date(date) => Some(*date),
+            _ => None,
+        }
+    }
+
+    /// Returns elements if it's [BookmarkValue::Array].
+    pub fn as_array(&self) -> Option<&[BookmarkValue]> {
+        match self {
+            Self::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// Returns bytes if it's [BookmarkValue::Data].
+    pub fn as_data(&self) -> Option<&[u8]> {
+        match self {
+            Self::Data(data) => Some(data),
+            _ => None,
+        }
+    }
+}
+
+/// Parsed bookmark data. See the [module docs](self).
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Bookmark {
+    entries: BTreeMap<u32, BookmarkValue>,
+}
+
+impl Bookmark {
+    /// Parses bookmark data. Entries of all tables of contents are merged,
+    /// the first one of duplicate keys is kept. Entries with string keys
+    /// are skipped.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if !is_bookmark(bytes) {
+            return Err(incorrect("Missing the `book` magic"));
+        }
+        let header_size = read_u32(bytes, 12)? as usize;
+        let data = bytes
+            .get(header_size..)
+            .ok_or_else(|| incorrect("The header is out of bounds"))?;
+        let reader = Reader { data };
+
+        let mut entries = BTreeMap::new();
+        let mut visited = HashSet::new();
+        let mut toc = reader.u32(0)? as usize;
+        // The last table of contents has no next one
+        while toc != 0 && visited.insert(toc) {
+            if reader.u32(toc + 4)? != TOC_MAGIC {
+                return Err(incorrect("Incorrect table of contents magic"));
+            }
+            let next = reader.u32(toc + 12)? as usize;
+            let count = reader.u32(toc + 16)? as usize;
+            for i in 0..count {
+                let entry = toc + 20 + i * 12;
+                let key = reader.u32(entry)?;
+                if key & STRING_KEY_BIT != 0 {
+                    continue;
+                }
+                let offset = reader.u32(entry + 4)? as usize;
+                if let Entry::Vacant(vacant) = entries.entry(key) {
+                    vacant.insert(reader.value(offset, 0)?);
+                }
+            }
+            toc = next;
+        }
+        Ok(Self { entries })
+    }
+
+    /// Returns a value of an entry with a given key.
+    pub fn get(&self, key: u32) -> Option<&BookmarkValue> {
+        self.entries.get(&key)
+    }
+
+    /// Returns all entries by their keys.
+    pub fn entries(&self) -> &BTreeMap<u32, BookmarkValue> {
+        &self.entries
+    }
+
+    /// Returns names of path components of a target ([PATH_COMPONENTS]).
+    pub fn path_components(&self) -> Option<Vec<&str>> {
+        self.get(PATH_COMPONENTS)?
+            .as_array()?
+            .iter()
+            .map(BookmarkValue::as_str)
+            .collect()
+    }
+
+    /// Returns an absolute path of a target built from its components.
+    pub fn path(&self) -> Option<PathBuf> {
+        let mut path = PathBuf::from("/");
+        path.extend(self.path_components()?);
+        Some(path)
+    }
+
+    /// Returns a creation date of a target ([CREATION_DATE]).
+    pub fn creation_date(&self) -> Option<AppleTime> {
+        self.get(CREATION_DATE)?.as_date()
+    }
+
+    /// Returns a name of a volume of a target ([VOLUME_NAME]).
+    pub fn volume_name(&self) -> Option<&str> {
+        self.get(VOLUME_NAME)?.as_str()
+    }
+
+    /// Returns a URL of a volume of a target, like `file:///`
+    /// ([VOLUME_PATH]).
+    pub fn volume_path(&self) -> Option<&str> {
+        self.get(VOLUME_PATH)?.as_str()
+    }
+
+    /// Returns a UUID string of a volume of a target ([VOLUME_UUID]).
+    pub fn volume_uuid(&self) -> Option<&str> {
+        self.get(VOLUME_UUID)?.as_str()
+    }
+
+    /// Returns a capacity of a volume of a target ([VOLUME_SIZE]).
+    pub fn volume_size(&self) -> Option<i64> {
+        self.get(VOLUME_SIZE)?.as_i64()
+    }
+
+    /// Returns a creation date of a volume of a target
+    /// ([VOLUME_CREATION_DATE]).
+    pub fn volume_creation_date(&self) -> Option<AppleTime> {
+        self.get(VOLUME_CREATION_DATE)?.as_date()
+    }
+
+    /// Returns a name of a user that created the bookmark
+    /// ([CREATOR_USER_NAME]).
+    pub fn creator_user_name(&self) -> Option<&str> {
+        self.get(CREATOR_USER_NAME)?.as_str()
+    }
+}
+
+/// A data (or NSData) value is parsed as bookmark data.
+impl Decodable for Bookmark {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        Self::parse(borrow_bytes(value, ctx.is_probing())?)
+            .map_err(|e| DeError::Custom(e.to_string()))
+    }
+}
+
+/// Reads values of the data section. Offsets are relative to its start.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&[u8], Error> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| incorrect("An offset is out of bounds"))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, Error> {
+        read_u32(self.data, offset)
+    }
+
+    fn value(&self, offset: usize, depth: usize) -> Result<BookmarkValue, Error> {
+        if depth >= DEFAULT_MAX_DEPTH {
+            return Err(incorrect("Values are nested too deep"));
+        }
+        let len = self.u32(offset)? as usize;
+        let ty = self.u32(offset + 4)?;
+        let bytes = self.bytes(offset + 8, len)?;
+        let offsets = || -> Result<Vec<usize>, Error> {
+            (0..len / 4)
+                .map(|i| Ok(read_u32(bytes, i * 4)? as usize))
+                .collect()
+        };
+        Ok(match (ty & 0xffff_ff00, ty & 0xff) {
+            (TYPE_STRING, _) => BookmarkValue::String(String::from_utf8_lossy(bytes).into()),
+            (TYPE_DATA, _) => BookmarkValue::Data(bytes.to_vec()),
+            // Subtypes are CFNumberType values, floats are 5, 6, 12 and 13
+            (TYPE_NUMBER, 5 | 6 | 12 | 13) => match len {
+                4 => BookmarkValue::Float(f32::from_le_bytes(bytes.try_into().unwrap()).into()),
+                8 => BookmarkValue::Float(f64::from_le_bytes(bytes.try_into().unwrap())),
+                _ => return Err(incorrect("Incorrect number size")),
+            },
+            (TYPE_NUMBER, _) => match len {
+                1 => BookmarkValue::Integer(bytes[0] as i8 as i64),
+                2 => BookmarkValue::Integer(i16::from_le_bytes(bytes.try_into().unwrap()) as i64),
+                4 => BookmarkValue::Integer(i32::from_le_bytes(bytes.try_into().unwrap()) as i64),
+                8 => BookmarkValue::Integer(i64::from_le_bytes(bytes.try_into().unwrap())),
+                _ => return Err(incorrect("Incorrect number size")),
+            },
+            // Dates are big-endian, unlike everything else
+            (TYPE_DATE, _) => {
+                let bytes = bytes
+                    .try_into()
+                    .map_err(|_| incorrect("Incorrect date size"))?;
+                BookmarkValue::Date(AppleTime::from_seconds(f64::from_be_bytes(bytes)))
+            }
+            (TYPE_BOOLEAN, subtype) => BookmarkValue::Boolean(subtype == 1),
+            (TYPE_ARRAY, _) => BookmarkValue::Array(
+                offsets()?
+                    .into_iter()
+                    .map(|offset| self.value(offset, depth + 1))
+                    .collect::<Result<_, _>>()?,
+            ),
+            (TYPE_DICTIONARY, _) => BookmarkValue::Dictionary(
+                offsets()?
+                    .chunks_exact(2)
+                    .map(|pair| {
+                        Ok((
+                            self.value(pair[0], depth + 1)?,
+                            self.value(pair[1], depth + 1)?,
+                        ))
+                    })
+                    .collect::<Result<_, Error>>()?,
+            ),
+            (TYPE_UUID, _) => BookmarkValue::Uuid(
+                bytes
+                    .try_into()
+                    .map_err(|_| incorrect("Incorrect UUID size"))?,
+            ),
+            // A relative URL refers to its base and a relative string
+            (TYPE_URL, 2) => {
+                let offsets = offsets()?;
+                let [base, relative] = offsets[..] else {
+                    return Err(incorrect("Incorrect relative URL"));
+                };
+                let base = self.value(base, depth + 1)?;
+                let relative = self.value(relative, depth + 1)?;
+                let (Some(base), Some(relative)) = (base.as_str(), relative.as_str()) else {
+                    return Err(incorrect("Incorrect relative URL"));
+                };
+                BookmarkValue::Url(format!("{base}{relative}"))
+            }
+            (TYPE_URL, _) => BookmarkValue::Url(String::from_utf8_lossy(bytes).into()),
+            (TYPE_NULL, _) => BookmarkValue::Null,
+            _ => BookmarkValue::Unknown {
+                ty,
+                bytes: bytes.to_vec(),
+            },
+        })
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    let bytes = offset
+        .checked_add(4)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or_else(|| incorrect("An offset is out of bounds"))?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn incorrect(message: &str) -> Error {
+    Error::IncorrectBookmark(message.into())
+}
@@ -0,0 +1,867 @@
+use crate::nib::NSClassSwapper;
+use crate::{DeError, Decodable, Limits, Object, ObjectValue, UniqueId, ValueRef};
+use std::{any::Any, any::TypeId, cell::Cell, collections::HashMap, fmt::Write, rc::Rc};
+
+thread_local! {
+    // A depth of values being decoded on the current thread by all contexts
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// An object being decoded as a type with a given name.
+type Entry = (UniqueId, &'static str);
+
+/// Increases the thread depth until it's dropped, even if decoding panics.
+struct DepthGuard;
+
+impl DepthGuard {
+    fn enter() -> Self {
+        DEPTH.set(DEPTH.get() + 1);
+        Self
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.set(DEPTH.get() - 1);
+    }
+}
+
+/// A state shared between [Decodable] calls while decoding a single archive
+/// value tree.
+///
+/// It remembers values decoded into [Rc] pointers by their [UniqueId], so
+/// several `Rc<T>` fields referring to the same archived object point to
+/// the same decoded value, just like in the original object graph.
+///
+/// It also keeps track of objects that are being decoded, so circular
+/// references result in a [DeError::CircularReference] instead of a stack
+/// overflow. An object may still be decoded as a different type while it's
+/// being decoded (e.g. as a [Lazy](crate::Lazy) or a [ValueRef](crate::ValueRef)),
+/// since that can't recurse infinitely.
+///
+/// The depth of nested values is limited (by
+/// [DEFAULT_MAX_DEPTH](crate::DEFAULT_MAX_DEPTH) unless other [Limits] are
+/// given), so deeply nested archives can't exhaust the stack. The depth is
+/// counted per thread, so contexts created while decoding (e.g. by
+/// [Decodable::decode] in user types that don't pass a context along)
+/// continue counting the depth of an outer one. Their limits are default
+/// ones though, so pass a context along to nested values.
+/// Built-in recursive types ([DynamicValue](crate::DynamicValue) and plist
+/// values) decode nested values without recursive calls, so they can't
+/// exhaust the stack even without a depth limit.
+///
+/// It also tracks a path to a value being decoded (see
+/// [DecodeContext::path]) to point at a failed value in errors.
+///
+/// In a lossy mode (see [DecodeContext::set_lossy]) failing optional values
+/// are replaced with defaults and their errors are collected instead.
+///
+/// Non-fatal anomalies may be collected as [DecodeWarning]s (see
+/// [DecodeContext::set_collect_warnings]).
+///
+/// Dictionaries with duplicate keys are handled according to a
+/// [DuplicateKeys] policy (see [DecodeContext::set_duplicate_keys]).
+///
+/// Data stored outside of an archive (e.g. in sidecar files of a document
+/// package) may be provided by a resolver (see
+/// [DecodeContext::set_external_data]).
+///
+/// Class swappers of Interface Builder archives are unwrapped transparently
+/// (see [Object::matches_class]), and the context remembers swappers it has
+/// gone through (see [DecodeContext::class_swapper]).
+///
+/// It may also carry user contexts (see [DecodeContext::insert_user]): any
+/// external state decodables need, like a font table or a string interner.
+/// There is one user context per type.
+pub struct DecodeContext {
+    shared: HashMap<(UniqueId, TypeId), Rc<dyn Any>>,
+    decoding: Vec<Entry>,
+    limits: Limits,
+    // The current path and lengths of it before each segment was added.
+    // A single buffer avoids allocating a string for every decoded field.
+    path: String,
+    path_lengths: Vec<usize>,
+    lossy: bool,
+    errors: Vec<DeError>,
+    warnings: Option<Vec<DecodeWarning>>,
+    // A number of nested `try_decode` calls
+    probing: usize,
+    duplicate_keys: DuplicateKeys,
+    coerce_numeric_bool: bool,
+    coerce_real_to_int: bool,
+    external_data: Option<ExternalDataResolver>,
+    class_swappers: HashMap<UniqueId, NSClassSwapper>,
+    user: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl DecodeContext {
+    /// Creates an empty context with default [Limits].
+    pub fn new() -> Self {
+        Self::with_limits(&Limits::default())
+    }
+
+    /// Creates an empty context with given [Limits].
+    pub fn with_limits(limits: &Limits) -> Self {
+        Self {
+            shared: HashMap::new(),
+            decoding: Vec::new(),
+            limits: *limits,
+            path: String::new(),
+            path_lengths: Vec::new(),
+            lossy: false,
+            errors: Vec::new(),
+            warnings: None,
+            probing: 0,
+            duplicate_keys: DuplicateKeys::default(),
+            coerce_numeric_bool: false,
+            coerce_real_to_int: false,
+            external_data: None,
+            class_swappers: HashMap::new(),
+            user: HashMap::new(),
+        }
+    }
+
+    /// Returns a previously decoded shared value of an archive object with
+    /// a given `id` if there is one.
+    pub fn shared<T: Any>(&self, id: UniqueId) -> Option<Rc<T>> {
+        let value = self.shared.get(&(id, TypeId::of::<T>()))?;
+        value.clone().downcast::<T>().ok()
+    }
+
+    /// Returns [Limits] of the context. Decodables parsing nested archives
+    /// apply them as well.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Remembers a shared decoded value of an archive object with a given `id`.
+    pub fn insert_shared<T: Any>(&mut self, id: UniqueId, value: Rc<T>) {
+        self.shared.insert((id, TypeId::of::<T>()), value);
+    }
+
+    /// Decodes a nested value as `<T>`. Decodables should use it for decoding
+    /// values they contain.
+    ///
+    /// Returns a [DeError::CircularReference] if an object refers to itself
+    /// (directly or not) and is decoded as the same type again. Such
+    /// references may be decoded with [Lazy](crate::Lazy).
+    ///
+    /// Returns a [DeError::LimitExceeded] if values are nested too deep.
+    ///
+    /// Errors are wrapped into [DeError::Context] with a current path
+    /// and an id of a failed object.
+    pub fn decode<T: Decodable>(&mut self, value: &ObjectValue) -> Result<T, DeError> {
+        self.decode_inner(value, T::decode_with)
+            .map_err(|e| self.with_context(e, referenced(value)))
+    }
+
+    /// Decodes a referenced archive value (like an array element) as `<T>`
+    /// with [Decodable::decode_ref], so the reference isn't cloned into
+    /// an [ObjectValue]. It's tracked just like with [DecodeContext::decode].
+    pub fn decode_ref<T: Decodable>(&mut self, value: &ValueRef) -> Result<T, DeError> {
+        self.track(Some(value), std::any::type_name::<T>(), |ctx| {
+            T::decode_ref(value, ctx)
+        })
+        .map_err(|e| self.with_context(e, Some(value)))
+    }
+
+    /// Decodes a value of an object field with a given `key`, adding the key
+    /// to the current path.
+    pub fn decode_field<T: Decodable>(
+        &mut self,
+        value: &ObjectValue,
+        key: &str,
+    ) -> Result<T, DeError> {
+        self.decode_field_with(value, key, T::decode_with)
+    }
+
+    /// Decodes a value of an object field with a given `key` using
+    /// a function `f` instead of [Decodable::decode_with]. It's used for
+    /// `#[decodable(with = "...")]` fields.
+    ///
+    /// The value is tracked just like with [DecodeContext::decode_field].
+    pub fn decode_field_with<T, F>(
+        &mut self,
+        value: &ObjectValue,
+        key: &str,
+        f: F,
+    ) -> Result<T, DeError>
+    where
+        F: FnOnce(&ObjectValue, &mut DecodeContext) -> Result<T, DeError>,
+    {
+        self.decode_field_tracked(value, key, std::any::type_name::<T>(), f)
+    }
+
+    /// Decodes a nested value into an existing `target`, reusing its
+    /// allocations (see [Decodable::decode_into]). The value is tracked
+    /// just like with [DecodeContext::decode].
+    pub fn decode_into<T: Decodable>(
+        &mut self,
+        target: &mut T,
+        value: &ObjectValue,
+    ) -> Result<(), DeError> {
+        self.decode_tracked(value, std::any::type_name::<T>(), |value, ctx| {
+            target.decode_into_with(value, ctx)
+        })
+        .map_err(|e| self.with_context(e, referenced(value)))
+    }
+
+    /// Decodes a value of an object field with a given `key` into an
+    /// existing `target`. See [DecodeContext::decode_field].
+    pub fn decode_field_into<T: Decodable>(
+        &mut self,
+        target: &mut T,
+        value: &ObjectValue,
+        key: &str,
+    ) -> Result<(), DeError> {
+        self.decode_field_tracked(value, key, std::any::type_name::<T>(), |value, ctx| {
+            target.decode_into_with(value, ctx)
+        })
+    }
+
+    fn decode_field_tracked<R, F>(
+        &mut self,
+        value: &ObjectValue,
+        key: &str,
+        ty: &'static str,
+        f: F,
+    ) -> Result<R, DeError>
+    where
+        F: FnOnce(&ObjectValue, &mut DecodeContext) -> Result<R, DeError>,
+    {
+        self.path_lengths.push(self.path.len());
+        if !self.path.is_empty() {
+            self.path.push('.');
+        }
+        self.path.push_str(key);
+        let result = self
+            .decode_tracked(value, ty, f)
+            .map_err(|e| self.with_context(e, referenced(value)));
+        self.pop_path();
+        result
+    }
+
+    /// Decodes an element of an array with a given `index`, adding the index
+    /// to the current path.
+    pub fn decode_element<T: Decodable>(
+        &mut self,
+        value: &ObjectValue,
+        index: usize,
+    ) -> Result<T, DeError> {
+        self.path_lengths.push(self.path.len());
+        let _ = write!(self.path, "[{index}]");
+        let result = self.decode(value);
+        self.pop_path();
+        result
+    }
+
+    /// Decodes a referenced value of a dictionary with a given `key` with
+    /// [Decodable::decode_ref]. See [DecodeContext::decode_field].
+    pub fn decode_field_ref<T: Decodable>(
+        &mut self,
+        value: &ValueRef,
+        key: &str,
+    ) -> Result<T, DeError> {
+        self.path_lengths.push(self.path.len());
+        if !self.path.is_empty() {
+            self.path.push('.');
+        }
+        self.path.push_str(key);
+        let result = self.decode_ref(value);
+        self.pop_path();
+        result
+    }
+
+    /// Decodes a referenced element of an array with a given `index` with
+    /// [Decodable::decode_ref]. See [DecodeContext::decode_element].
+    pub fn decode_element_ref<T: Decodable>(
+        &mut self,
+        value: &ValueRef,
+        index: usize,
+    ) -> Result<T, DeError> {
+        self.path_lengths.push(self.path.len());
+        let _ = write!(self.path, "[{index}]");
+        let result = self.decode_ref(value);
+        self.pop_path();
+        result
+    }
+
+    /// Decodes an element of an array with a given `index` into an existing
+    /// `target`. See [DecodeContext::decode_element].
+    pub fn decode_element_into<T: Decodable>(
+        &mut self,
+        target: &mut T,
+        value: &ObjectValue,
+        index: usize,
+    ) -> Result<(), DeError> {
+        self.path_lengths.push(self.path.len());
+        let _ = write!(self.path, "[{index}]");
+        let result = self.decode_into(target, value);
+        self.pop_path();
+        result
+    }
+
+    /// Decodes an optional value of an object field with a given `key`
+    /// (like an [Option] or a `#[decodable(default)]` field).
+    ///
+    /// In a lossy mode a default value is returned if decoding fails and the
+    /// error is collected. [DeError::LimitExceeded] errors are never ignored.
+    pub fn decode_optional_field<T: Decodable + Default>(
+        &mut self,
+        value: &ObjectValue,
+        key: &str,
+    ) -> Result<T, DeError> {
+        self.decode_optional_field_with(value, key, T::decode_with)
+    }
+
+    /// Decodes an optional value of an object field with a given `key` into
+    /// an existing `target`. In a lossy mode it's reset to a default value if
+    /// decoding fails. See [DecodeContext::decode_optional_field].
+    pub fn decode_optional_field_into<T: Decodable + Default>(
+        &mut self,
+        target: &mut T,
+        value: &ObjectValue,
+        key: &str,
+    ) -> Result<(), DeError> {
+        match self.decode_field_into(target, value, key) {
+            Err(e) if self.lossy && !matches!(e.inner(), DeError::LimitExceeded(_)) => {
+                self.errors.push(e);
+                *target = T::default();
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Decodes an optional value of an object field with a given `key` using
+    /// a function `f`. See [DecodeContext::decode_optional_field].
+    pub fn decode_optional_field_with<T, F>(
+        &mut self,
+        value: &ObjectValue,
+        key: &str,
+        f: F,
+    ) -> Result<T, DeError>
+    where
+        T: Default,
+        F: FnOnce(&ObjectValue, &mut DecodeContext) -> Result<T, DeError>,
+    {
+        self.decode_optional_field_or_else(value, key, f, T::default)
+    }
+
+    /// Decodes an optional value of an object field with a given `key` with
+    /// a function like [DecodeContext::decode_optional_field_with]. In a
+    /// lossy mode `default` is called if decoding fails (e.g. for a
+    /// `#[decodable(default = "...")]` field).
+    pub fn decode_optional_field_or_else<T, F, D>(
+        &mut self,
+        value: &ObjectValue,
+        key: &str,
+        f: F,
+        default: D,
+    ) -> Result<T, DeError>
+    where
+        F: FnOnce(&ObjectValue, &mut DecodeContext) -> Result<T, DeError>,
+        D: FnOnce() -> T,
+    {
+        match self.decode_field_with(value, key, f) {
+            Err(e) if self.lossy && !matches!(e.inner(), DeError::LimitExceeded(_)) => {
+                self.errors.push(e);
+                Ok(default())
+            }
+            result => result,
+        }
+    }
+
+    /// Tries to decode a value as `<T>` and returns [None] if it fails.
+    ///
+    /// Unlike [DecodeContext::decode], errors collected in a lossy mode
+    /// during a failed attempt are discarded. It's useful for trying
+    /// several types one by one (e.g. enum variants).
+    ///
+    /// Since errors are thrown away, the context is probing while the value
+    /// is decoded (see [DecodeContext::is_probing]): errors aren't wrapped
+    /// into [DeError::Context], so failed attempts don't allocate paths.
+    pub fn try_decode<T: Decodable>(&mut self, value: &ObjectValue) -> Option<T> {
+        self.probing += 1;
+        let result = self.attempt(value).ok();
+        self.probing -= 1;
+        result
+    }
+
+    /// Tries to decode a value as `<T>` like [DecodeContext::try_decode],
+    /// but returns an error of a failed attempt. It's useful for reporting
+    /// why every attempt failed after probing.
+    pub fn attempt<T: Decodable>(&mut self, value: &ObjectValue) -> Result<T, DeError> {
+        let errors_len = self.errors.len();
+        let warnings_len = self.warnings.as_ref().map_or(0, Vec::len);
+        let result = self
+            .decode_inner(value, T::decode_with)
+            .map_err(|e| self.with_context(e, referenced(value)));
+        if result.is_err() {
+            self.errors.truncate(errors_len);
+            if let Some(warnings) = &mut self.warnings {
+                warnings.truncate(warnings_len);
+            }
+        }
+        result
+    }
+
+    /// Returns `true` while values are decoded with
+    /// [DecodeContext::try_decode]. Their errors are discarded, so decodables
+    /// may return cheap errors (like [DeError::ExpectedClass]) instead of
+    /// formatting detailed ones.
+    pub fn is_probing(&self) -> bool {
+        self.probing > 0
+    }
+
+    /// Turns a lossy mode on or off.
+    pub fn set_lossy(&mut self, lossy: bool) {
+        self.lossy = lossy;
+    }
+
+    /// Returns `true` if a lossy mode is on.
+    pub fn is_lossy(&self) -> bool {
+        self.lossy
+    }
+
+    /// Returns errors collected in a lossy mode and clears them.
+    pub fn take_errors(&mut self) -> Vec<DeError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Turns collecting of [DecodeWarning]s on or off. It's off by default,
+    /// so anomalies cost nothing unless someone is interested in them.
+    pub fn set_collect_warnings(&mut self, collect: bool) {
+        match (collect, &self.warnings) {
+            (true, None) => self.warnings = Some(Vec::new()),
+            (false, _) => self.warnings = None,
+            _ => (),
+        }
+    }
+
+    /// Returns `true` if warnings are collected. Decodables should check it
+    /// before building a warning.
+    pub fn collects_warnings(&self) -> bool {
+        self.warnings.is_some()
+    }
+
+    /// Records a warning if warnings are collected. Like errors, warnings
+    /// of a failed [DecodeContext::try_decode] attempt are discarded.
+    pub fn warn(&mut self, warning: DecodeWarning) {
+        if let Some(warnings) = &mut self.warnings {
+            warnings.push(warning);
+        }
+    }
+
+    /// Returns collected warnings and clears them.
+    pub fn take_warnings(&mut self) -> Vec<DecodeWarning> {
+        self.warnings
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Sets a policy for dictionaries with duplicate keys decoded as
+    /// [HashMap] or [BTreeMap](std::collections::BTreeMap).
+    pub fn set_duplicate_keys(&mut self, policy: DuplicateKeys) {
+        self.duplicate_keys = policy;
+    }
+
+    /// Returns a policy for dictionaries with duplicate keys.
+    pub fn duplicate_keys(&self) -> DuplicateKeys {
+        self.duplicate_keys
+    }
+
+    /// Allows `bool` values to be decoded from integers `0` and `1`, which
+    /// some encoders emit instead of booleans. Off by default.
+    pub fn set_coerce_numeric_bool(&mut self, coerce: bool) {
+        self.coerce_numeric_bool = coerce;
+    }
+
+    /// Returns `true` if `bool` values may be decoded from integers.
+    pub fn coerce_numeric_bool(&self) -> bool {
+        self.coerce_numeric_bool
+    }
+
+    /// Allows integers to be decoded from reals without a fractional part,
+    /// like `3.0`. Reals that aren't exact integers are still rejected.
+    /// Off by default.
+    pub fn set_coerce_real_to_int(&mut self, coerce: bool) {
+        self.coerce_real_to_int = coerce;
+    }
+
+    /// Returns `true` if integers may be decoded from exact reals.
+    pub fn coerce_real_to_int(&self) -> bool {
+        self.coerce_real_to_int
+    }
+
+    /// Sets a function providing bytes of data stored outside of an archive.
+    ///
+    /// When a [Data](crate::Data) value is decoded from an object that
+    /// doesn't hold its bytes inline (an `NSData` without `NS.data` or an
+    /// object of any other class), the object is passed to the resolver. It
+    /// returns [None] if the object isn't a placeholder it knows about, so
+    /// the usual error is returned.
+    ///
+    /// ```ignore
+    /// let mut ctx = archive.decode_context();
+    /// ctx.set_external_data(move |obj| {
+    ///     let Ok(name) = obj.get_str("fileName") else {
+    ///         return Ok(None);
+    ///     };
+    ///     std::fs::read(package.join(name))
+    ///         .map(Some)
+    ///         .map_err(|e| DeError::Custom(e.to_string()))
+    /// });
+    /// ```
+    pub fn set_external_data<F>(&mut self, resolver: F)
+    where
+        F: FnMut(&Object) -> Result<Option<Vec<u8>>, DeError> + 'static,
+    {
+        self.external_data = Some(Box::new(resolver));
+    }
+
+    /// Removes a resolver set with [DecodeContext::set_external_data].
+    pub fn clear_external_data(&mut self) {
+        self.external_data = None;
+    }
+
+    /// Returns bytes of an externally stored data placeholder `obj` or
+    /// [None] if there is no resolver or it doesn't know the object.
+    pub fn resolve_external_data(&mut self, obj: &Object) -> Result<Option<Vec<u8>>, DeError> {
+        match &mut self.external_data {
+            Some(resolver) => resolver(obj),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns a class swapper with a given `id` if a value referring to it
+    /// has been decoded in this context. It tells custom and original class
+    /// names of an object decoded through a swapper.
+    pub fn class_swapper(&self, id: UniqueId) -> Option<&NSClassSwapper> {
+        self.class_swappers.get(&id)
+    }
+
+    /// Sets a user context of type `<T>`, replacing a previous one of the
+    /// same type. Decodables may access it with [DecodeContext::user] and
+    /// [DecodeContext::user_mut].
+    pub fn insert_user<T: Any>(&mut self, value: T) {
+        self.user.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    /// Returns a user context of type `<T>` if it's set.
+    pub fn user<T: Any>(&self) -> Option<&T> {
+        self.user.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    /// Returns a mutable user context of type `<T>` if it's set.
+    pub fn user_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.user.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    /// Returns a user context of type `<T>` or a [DeError::MissingContext]
+    /// if it isn't set.
+    pub fn require_user<T: Any>(&mut self) -> Result<&mut T, DeError> {
+        self.user_mut()
+            .ok_or_else(|| DeError::MissingContext(std::any::type_name::<T>().into()))
+    }
+
+    /// Removes a user context of type `<T>` and returns it.
+    pub fn remove_user<T: Any>(&mut self) -> Option<T> {
+        let value = self.user.remove(&TypeId::of::<T>())?;
+        value.downcast().ok().map(|value| *value)
+    }
+
+    /// Returns a path to a value being decoded, like `root.NSAttributes[0].NSColor`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn pop_path(&mut self) {
+        if let Some(len) = self.path_lengths.pop() {
+            self.path.truncate(len);
+        }
+    }
+
+    fn decode_inner<T, F>(&mut self, value: &ObjectValue, f: F) -> Result<T, DeError>
+    where
+        F: FnOnce(&ObjectValue, &mut DecodeContext) -> Result<T, DeError>,
+    {
+        self.decode_tracked(value, std::any::type_name::<T>(), f)
+    }
+
+    /// Decodes a value as a type named `ty` with a function `f`, tracking
+    /// depth and circular references.
+    fn decode_tracked<R, F>(
+        &mut self,
+        value: &ObjectValue,
+        ty: &'static str,
+        f: F,
+    ) -> Result<R, DeError>
+    where
+        F: FnOnce(&ObjectValue, &mut DecodeContext) -> Result<R, DeError>,
+    {
+        self.track(referenced(value), ty, |ctx| f(value, ctx))
+    }
+
+    /// Decodes a value with a function `f`, tracking depth and circular
+    /// references of a `referenced` archive value.
+    fn track<R, F>(
+        &mut self,
+        referenced: Option<&ValueRef>,
+        ty: &'static str,
+        f: F,
+    ) -> Result<R, DeError>
+    where
+        F: FnOnce(&mut DecodeContext) -> Result<R, DeError>,
+    {
+        let (entry, guard) = self.enter(referenced, ty)?;
+        span!(
+            TRACE,
+            "decode",
+            ty = ty,
+            uid = entry.map(|(id, _)| id.get()),
+            class = referenced.and_then(|value| value.as_object().map(|obj| obj.class())),
+            path = self.path.as_str(),
+        );
+        let result = f(self);
+        drop(guard);
+        // Only an error of the innermost value is reported, outer ones
+        // are wrapped into a context
+        if let Err(e) = &result
+            && !matches!(e, DeError::Context { .. })
+        {
+            event!(DEBUG, error = %e, "Unable to decode a value");
+        }
+        if entry.is_some() {
+            self.decoding.pop();
+        }
+        result
+    }
+
+    /// Checks the depth limit and circular references before decoding
+    /// a value as a type named `ty`. Returns an entry of an object being
+    /// decoded, it should be popped afterwards.
+    fn enter(
+        &mut self,
+        referenced: Option<&ValueRef>,
+        ty: &'static str,
+    ) -> Result<(Option<Entry>, DepthGuard), DeError> {
+        if let Some(max_depth) = self.limits.max_depth
+            && DEPTH.get() >= max_depth
+        {
+            return Err(DeError::LimitExceeded(format!(
+                "Values are nested deeper than {max_depth} levels"
+            )));
+        }
+        let entry = referenced
+            .filter(|value| value.is_object())
+            .map(|value| (*value.unique_id(), ty));
+        if let Some(entry) = entry {
+            if self.decoding.contains(&entry) {
+                return Err(DeError::CircularReference(entry.0));
+            }
+            self.decoding.push(entry);
+            if let Some(object) = referenced
+                && object
+                    .as_object()
+                    .is_some_and(|obj| obj.class() == "NSClassSwapper")
+                && !self.class_swappers.contains_key(&entry.0)
+                && let Ok(swapper) = NSClassSwapper::decode_with(&object.into(), self)
+            {
+                self.class_swappers.insert(entry.0, swapper);
+            }
+        }
+        Ok((entry, DepthGuard::enter()))
+    }
+
+    /// Enters a nested value of a type named `ty` without decoding it, like
+    /// [DecodeContext::decode_field] and [DecodeContext::decode_element] do.
+    ///
+    /// Built-in recursive types (like [DynamicValue](crate::DynamicValue))
+    /// decode nested values with a work stack instead of recursive calls,
+    /// so deeply nested archives can't overflow the stack even without
+    /// a depth limit. The value should be left with
+    /// [DecodeContext::leave_nested] or [DecodeContext::fail_nested].
+    /// Errors are wrapped into a context.
+    pub(crate) fn enter_nested(
+        &mut self,
+        value: &ObjectValue,
+        ty: &'static str,
+        segment: PathSegment,
+    ) -> Result<NestedValue, DeError> {
+        self.path_lengths.push(self.path.len());
+        match segment {
+            PathSegment::Key(key) => {
+                if !self.path.is_empty() {
+                    self.path.push('.');
+                }
+                self.path.push_str(key);
+            }
+            PathSegment::Index(index) => {
+                let _ = write!(self.path, "[{index}]");
+            }
+        }
+        match self.enter(referenced(value), ty) {
+            Ok((entry, guard)) => Ok(NestedValue {
+                entry,
+                _guard: guard,
+            }),
+            Err(e) => {
+                let e = self.with_context(e, referenced(value));
+                self.pop_path();
+                Err(e)
+            }
+        }
+    }
+
+    /// Leaves a nested value entered with [DecodeContext::enter_nested].
+    pub(crate) fn leave_nested(&mut self, nested: NestedValue) {
+        if nested.entry.is_some() {
+            self.decoding.pop();
+        }
+        drop(nested);
+        self.pop_path();
+    }
+
+    /// Leaves a nested value that failed to decode, wrapping its error into
+    /// a context.
+    pub(crate) fn fail_nested(
+        &mut self,
+        nested: NestedValue,
+        value: &ObjectValue,
+        error: DeError,
+    ) -> DeError {
+        event!(DEBUG, error = %error, "Unable to decode a value");
+        let error = self.with_context(error, referenced(value));
+        self.leave_nested(nested);
+        error
+    }
+
+    /// Wraps an error into [DeError::Context] unless it's already wrapped
+    /// or the context is probing.
+    fn with_context(&self, error: DeError, referenced: Option<&ValueRef>) -> DeError {
+        if self.is_probing() || matches!(error, DeError::Context { .. }) {
+            return error;
+        }
+        let unique_id = match referenced {
+            Some(value) => Some(*value.unique_id()),
+            None => self.decoding.last().map(|(id, _)| *id),
+        };
+        if self.path.is_empty() && unique_id.is_none() {
+            return error;
+        }
+        DeError::Context {
+            path: self.path.clone(),
+            unique_id,
+            source: Box::new(error),
+        }
+    }
+}
+
+/// Returns an archive value referenced by a value.
+fn referenced(value: &ObjectValue) -> Option<&ValueRef> {
+    match value {
+        ObjectValue::Ref(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// A segment of a path to a nested value.
+pub(crate) enum PathSegment<'a> {
+    /// A key of an object field or a dictionary.
+    Key(&'a str),
+    /// An index of an array element.
+    Index(usize),
+}
+
+/// A nested value entered with [DecodeContext::enter_nested]. It keeps
+/// the state a recursive decoding call would keep on the stack.
+pub(crate) struct NestedValue {
+    entry: Option<Entry>,
+    _guard: DepthGuard,
+}
+
+impl Default for DecodeContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for DecodeContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecodeContext")
+            .field("shared", &self.shared.len())
+            .field("decoding", &self.decoding)
+            .field("limits", &self.limits)
+            .field("path", &self.path())
+            .field("lossy", &self.lossy)
+            .field("errors", &self.errors)
+            .field("warnings", &self.warnings)
+            .field("external_data", &self.external_data.is_some())
+            .field("class_swappers", &self.class_swappers.len())
+            .field("user", &self.user.len())
+            .finish()
+    }
+}
+
+/// A function resolving externally stored data, see
+/// [DecodeContext::set_external_data].
+type ExternalDataResolver = Box<dyn FnMut(&Object) -> Result<Option<Vec<u8>>, DeError>>;
+
+/// A non-fatal anomaly found while decoding, see
+/// [DecodeContext::set_collect_warnings].
+///
+/// Paths are the same as [DecodeContext::path].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecodeWarning {
+    /// An object has a key that isn't mapped to any field of a derived
+    /// struct (without `deny_unknown_fields` and `unhandled` fields).
+    UnknownKey {
+        path: String,
+        class: String,
+        key: String,
+    },
+    /// A number was decoded as another type, see
+    /// [DecodeContext::set_coerce_numeric_bool] and
+    /// [DecodeContext::set_coerce_real_to_int].
+    NumericCoercion {
+        path: String,
+        value: String,
+        ty: &'static str,
+    },
+    /// A `$null` element of an array was skipped by
+    /// [SkipNulls](crate::SkipNulls).
+    SkippedNull { path: String, index: usize },
+}
+
+impl std::fmt::Display for DecodeWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownKey { path, class, key } => {
+                write!(f, "{path}: Unknown key `{key}` of {class}")
+            }
+            Self::NumericCoercion { path, value, ty } => {
+                write!(f, "{path}: Coerced {value} to {ty}")
+            }
+            Self::SkippedNull { path, index } => {
+                write!(f, "{path}: Skipped a null element at index {index}")
+            }
+        }
+    }
+}
+
+/// A policy for dictionaries that contain duplicate keys, e.g. after lossy
+/// edits. To keep all values of duplicate keys decode a dictionary as
+/// [Entries](crate::Entries).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// Fail with [DeError::DuplicateKeys].
+    Error,
+    /// Keep the first value of a key.
+    FirstWins,
+    /// Keep the last value of a key.
+    #[default]
+    LastWins,
+}
@@ -0,0 +1,211 @@
+//! Keyed archives stored by Core Data.
+//!
+//! Transformable attributes of Core Data entities are stored as keyed
+//! archives in blob columns of SQLite stores. Rows of such stores (e.g.
+//! exported into a plist) are dictionaries with `Z_PK`, `Z_ENT` and `Z_OPT`
+//! keys along with attribute columns like `ZTITLE`:
+//!
+//! ```ignore
+//! let rows = plist::Value::from_file("notes.plist")?;
+//! for (path, archive) in core_data::find_archives(&rows) {
+//!     println!("{path}: {}", archive.root()?);
+//! }
+//! ```
+//!
+//! Attributes allowing external storage prefix a blob with a marker byte,
+//! see [Blob]. Persistent history tokens are archived as well and decoded
+//! with [PersistentHistoryToken].
+
+use crate::embedded::looks_like_plist;
+use crate::foundation::expect_object;
+use crate::{DeError, Decodable, DecodeContext, Error, KeyedArchive, ObjectValue, ParseOptions};
+use plist::{Dictionary as PlistDictionary, Value as PlistValue};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// The primary key column of a Core Data row.
+pub const PRIMARY_KEY_COLUMN: &str = "Z_PK";
+
+/// A blob of an attribute allowing external storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blob<'a> {
+    /// Data stored in the blob itself.
+    Inline(&'a [u8]),
+    /// A uuid of a file in the `_EXTERNAL_DATA` directory next to a store.
+    External(&'a str),
+}
+
+impl<'a> Blob<'a> {
+    /// Parses a blob of a column. A blob starting with `1` keeps data
+    /// inline, a blob starting with `2` keeps a uuid of an external file.
+    /// Other blobs (like archives of attributes without external storage)
+    /// are returned as is.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, Error> {
+        match bytes.split_first() {
+            Some((1, data)) => Ok(Self::Inline(data)),
+            Some((2, uuid)) => {
+                let uuid = uuid.strip_suffix(&[0]).unwrap_or(uuid);
+                let uuid = std::str::from_utf8(uuid).map_err(|_| {
+                    Error::IncorrectFormat("Expected an external data reference to be UTF-8".into())
+                })?;
+                Ok(Self::External(uuid))
+            }
+            _ => Ok(Self::Inline(bytes)),
+        }
+    }
+}
+
+/// Returns `true` if a dictionary looks like a row of a Core Data store,
+/// i.e. it has a [PRIMARY_KEY_COLUMN] key.
+pub fn is_row(dict: &PlistDictionary) -> bool {
+    dict.contains_key(PRIMARY_KEY_COLUMN)
+}
+
+/// Parses a blob of a column as a keyed archive with default
+/// [ParseOptions]. Data stored in an external file results in an error
+/// with its uuid, such a file should be read and parsed separately.
+pub fn archive_from_blob(bytes: &[u8]) -> Result<KeyedArchive, Error> {
+    archive_from_blob_with_options(bytes, &ParseOptions::default())
+}
+
+/// Parses a blob of a column as a keyed archive with given [ParseOptions].
+pub fn archive_from_blob_with_options(
+    bytes: &[u8],
+    options: &ParseOptions,
+) -> Result<KeyedArchive, Error> {
+    match Blob::parse(bytes)? {
+        Blob::Inline(data) => KeyedArchive::from_bytes_with_options(data, options),
+        Blob::External(uuid) => Err(Error::IncorrectFormat(format!(
+            "Data is stored in an external file `{uuid}`"
+        ))),
+    }
+}
+
+/// Parses data columns of a row containing keyed archives. Returns archives
+/// along with their column names.
+///
+/// Columns that aren't keyed archives are skipped.
+pub fn row_archives(row: &PlistDictionary) -> Vec<(String, KeyedArchive)> {
+    row_archives_with_options(row, &ParseOptions::default())
+}
+
+/// Parses data columns of a row containing keyed archives with given
+/// [ParseOptions].
+///
+/// See [row_archives].
+pub fn row_archives_with_options(
+    row: &PlistDictionary,
+    options: &ParseOptions,
+) -> Vec<(String, KeyedArchive)> {
+    row.iter()
+        .filter_map(|(column, value)| {
+            let Blob::Inline(data) = Blob::parse(value.as_data()?).ok()? else {
+                return None;
+            };
+            if !looks_like_plist(data) {
+                return None;
+            }
+            let archive = KeyedArchive::from_bytes_with_options(data, options).ok()?;
+            Some((column.clone(), archive))
+        })
+        .collect()
+}
+
+/// Scans a plist for Core Data rows (see [is_row]) and parses their columns
+/// containing keyed archives. Returns archives with their paths inside of
+/// the plist, like `[3].ZPAYLOAD`.
+pub fn find_archives(plist: &PlistValue) -> Vec<(String, KeyedArchive)> {
+    find_archives_with_options(plist, &ParseOptions::default())
+}
+
+/// Scans a plist for Core Data rows with given [ParseOptions].
+///
+/// See [find_archives].
+pub fn find_archives_with_options(
+    plist: &PlistValue,
+    options: &ParseOptions,
+) -> Vec<(String, KeyedArchive)> {
+    let mut archives = Vec::new();
+    let mut path = String::new();
+    find_archives_inner(plist, options, &mut path, &mut archives);
+    archives
+}
+
+fn find_archives_inner(
+    value: &PlistValue,
+    options: &ParseOptions,
+    path: &mut String,
+    archives: &mut Vec<(String, KeyedArchive)>,
+) {
+    let len = path.len();
+    match value {
+        PlistValue::Dictionary(dict) if is_row(dict) => {
+            for (column, archive) in row_archives_with_options(dict, options) {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(&column);
+                archives.push((path.clone(), archive));
+                path.truncate(len);
+            }
+        }
+        PlistValue::Dictionary(dict) => {
+            for (key, value) in dict {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+                find_archives_inner(value, options, path, archives);
+                path.truncate(len);
+            }
+        }
+        PlistValue::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                let _ = write!(path, "[{index}]");
+                find_archives_inner(value, options, path, archives);
+                path.truncate(len);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// A decoded `NSPersistentHistoryToken`.
+///
+/// A token keeps the last transaction number of every persistent store it
+/// was created for. Apps archive tokens to resume fetching history changes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PersistentHistoryToken {
+    store_tokens: BTreeMap<String, i64>,
+}
+
+impl PersistentHistoryToken {
+    /// Returns transaction numbers by store identifiers.
+    pub fn store_tokens(&self) -> &BTreeMap<String, i64> {
+        &self.store_tokens
+    }
+
+    /// Returns a transaction number of a store with a given identifier.
+    pub fn transaction_number(&self, store_id: &str) -> Option<i64> {
+        self.store_tokens.get(store_id).copied()
+    }
+}
+
+impl Decodable for PersistentHistoryToken {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(
+            value,
+            &["_NSPersistentHistoryToken", "NSPersistentHistoryToken"],
+            ctx.is_probing(),
+        )?;
+        let store_tokens = obj
+            .as_map()
+            .get("storeTokens")
+            .ok_or_else(|| DeError::MissingObjectKey(obj.class().into(), "storeTokens".into()))?;
+        Ok(Self {
+            store_tokens: ctx.decode_field(store_tokens, "storeTokens")?,
+        })
+    }
+}
@@ -1,74 +1,292 @@
-use crate::{DeError, Integer, Object, ObjectValue, UniqueId, ValueRef};
-use std::collections::HashMap;
+use crate::pretty::data_preview;
+use crate::{
+    ArchiveValue, Base64, Date, DeError, DecodeContext, DecodeWarning, DuplicateKeys, Integer,
+    Object, ObjectValue, UniqueId, ValueRef,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::cell::OnceCell;
+use std::rc::{Rc, Weak};
+use std::num::{
+    NonZeroI8, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroIsize, NonZeroU8, NonZeroU16, NonZeroU32,
+    NonZeroU64, NonZeroUsize,
+};
+
+/// Implements [Decodable::decode] of a type implementing
+/// [Decodable::decode_with]: a value is decoded with a new [DecodeContext].
+macro_rules! decode_with_new_context {
+    () => {
+        fn decode(value: &$crate::ObjectValue) -> Result<Self, $crate::DeError> {
+            $crate::DecodeContext::new().decode(value)
+        }
+    };
+}
 
 /// A data structure that can be decoded from a keyed archive object value.
+///
+/// Implement [Decodable::decode]. Types containing other decodables should
+/// also implement [Decodable::decode_with] and pass the [DecodeContext]
+/// along. Their `decode` may just decode a value with a new context
+/// (see [DecodeContext::decode]).
 pub trait Decodable {
     /// Decodes a keyed archive object value to your structure.
     fn decode(value: &ObjectValue) -> Result<Self, DeError>
     where
         Self: Sized;
+
+    /// Decodes a keyed archive object value to your structure sharing
+    /// a [DecodeContext] with nested decodables. By default it calls
+    /// [Decodable::decode].
+    ///
+    /// Nested values should be decoded with [DecodeContext::decode].
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        let _ = ctx;
+        Self::decode(value)
+    }
+
+    /// Decodes a referenced archive value to your structure sharing
+    /// a [DecodeContext]. Collections decode their elements with it (see
+    /// [DecodeContext::decode_ref]). By default it calls
+    /// [Decodable::decode_with] with a cloned reference.
+    ///
+    /// Types that only read a referenced value (like strings, numbers and
+    /// derived structs) implement it to avoid cloning a reference of every
+    /// element.
+    fn decode_ref(value: &ValueRef, ctx: &mut DecodeContext) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        Self::decode_with(&ObjectValue::Ref(value.clone()), ctx)
+    }
+
+    /// Decodes a keyed archive object value in a lossy mode: failing optional
+    /// values are replaced with defaults. Returns a decoded value along with
+    /// errors of such values.
+    ///
+    /// See [DecodeContext::decode_optional_field].
+    fn decode_lossy(value: &ObjectValue) -> Result<(Self, Vec<DeError>), DeError>
+    where
+        Self: Sized,
+    {
+        let mut ctx = DecodeContext::new();
+        ctx.set_lossy(true);
+        let decoded = ctx.decode(value)?;
+        Ok((decoded, ctx.take_errors()))
+    }
+
+    /// Decodes a keyed archive object value into an existing value, reusing
+    /// its allocations (like capacities of strings and vectors) where
+    /// possible. It's handy for decoding many archives of the same shape,
+    /// e.g. state snapshots.
+    ///
+    /// If decoding fails, the value may be left partially updated.
+    fn decode_into(&mut self, value: &ObjectValue) -> Result<(), DeError>
+    where
+        Self: Sized,
+    {
+        DecodeContext::new().decode_into(self, value)
+    }
+
+    /// Decodes a keyed archive object value into an existing value sharing
+    /// a [DecodeContext] with nested decodables. By default it decodes a new
+    /// value and assigns it.
+    ///
+    /// Nested values should be decoded with [DecodeContext::decode_into].
+    fn decode_into_with(
+        &mut self,
+        value: &ObjectValue,
+        ctx: &mut DecodeContext,
+    ) -> Result<(), DeError>
+    where
+        Self: Sized,
+    {
+        *self = Self::decode_with(value, ctx)?;
+        Ok(())
+    }
 }
 
-impl Decodable for String {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
-        // A string can be encoded as a plain String type
-        if let ObjectValue::String(s) = value {
-            return Ok(s.to_string());
+/// Returns a [DeError::ClassMismatch] for a value or a cheap
+/// [DeError::ExpectedClass] while probing (see [DecodeContext::is_probing]).
+pub(crate) fn class_mismatch(
+    value: &ArchiveValue,
+    expected: &'static [&'static str],
+    probing: bool,
+) -> DeError {
+    if probing {
+        return DeError::ExpectedClass(expected[0]);
+    }
+    DeError::class_mismatch(value, expected)
+}
+
+/// Borrows a string from a plain string value or an NSString object
+/// without copying it.
+pub(crate) fn borrow_str(value: &ObjectValue, probing: bool) -> Result<&str, DeError> {
+    // A string can be encoded as a plain String type
+    if let ObjectValue::String(s) = value {
+        return Ok(s);
+    }
+
+    // ... or as an Object with `NS.bytes` data or `NS.string` string (NIB Archives)
+    let ObjectValue::Ref(value) = value else {
+        return Err(DeError::ExpectedString);
+    };
+    borrow_str_ref(value, probing)
+}
+
+/// Borrows a string from a referenced plain string value or an NSString
+/// object without copying it.
+pub(crate) fn borrow_str_ref(value: &ArchiveValue, probing: bool) -> Result<&str, DeError> {
+    if let Some(s) = value.as_string() {
+        return Ok(s);
+    }
+
+    if !value.is_object() {
+        return Err(DeError::ExpectedString);
+    }
+
+    let obj = value.as_object().unwrap(); // safe, checked with is_object()
+    if !obj.matches_class("NSString") && !obj.matches_class("NSMutableString") {
+        return Err(class_mismatch(
+            value,
+            &["NSString", "NSMutableString"],
+            probing,
+        ));
+    }
+
+    if !obj.contains_key("NS.bytes") && !obj.contains_key("NS.string") {
+        return Err(DeError::ExpectedString);
+    }
+    if let Some(ObjectValue::Data(data)) = obj.as_map().get("NS.bytes") {
+        std::str::from_utf8(data)
+            .map_err(|e| DeError::Custom(format!("Unable to parse a UTF-8 string: {e}")))
+    } else if let Some(ObjectValue::String(data)) = obj.as_map().get("NS.string") {
+        Ok(data)
+    } else {
+        Err(DeError::ExpectedString)
+    }
+}
+
+/// Borrows bytes from a plain data value or an NSData object without
+/// copying them.
+pub(crate) fn borrow_bytes(value: &ObjectValue, probing: bool) -> Result<&[u8], DeError> {
+    if let ObjectValue::Data(value) = value {
+        return Ok(value);
+    }
+    if let ObjectValue::Ref(value) = value {
+        if let Some(v) = value.as_data() {
+            return Ok(v);
+        }
+        // Decoding NSData
+        if let Some(v) = value.as_object() {
+            if !v.matches_class("NSData") && !v.matches_class("NSMutableData") {
+                return Err(class_mismatch(value, &["NSData", "NSMutableData"], probing));
+            }
+            if let Some(ObjectValue::Data(data)) = v.as_map().get("NS.data") {
+                return Ok(data);
+            }
         }
+    }
+    Err(DeError::ExpectedData)
+}
 
-        // ... or as an Object with `NS.bytes` data or `NS.string` string (NIB Archives)
-        let ObjectValue::Ref(value) = value else {
-            return Err(DeError::ExpectedString);
-        };
+impl Decodable for String {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        borrow_str(value, ctx.is_probing()).map(String::from)
+    }
+
+    fn decode_ref(value: &ValueRef, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        borrow_str_ref(value, ctx.is_probing()).map(String::from)
+    }
+
+    fn decode_into_with(
+        &mut self,
+        value: &ObjectValue,
+        ctx: &mut DecodeContext,
+    ) -> Result<(), DeError> {
+        let s = borrow_str(value, ctx.is_probing())?;
+        self.clear();
+        self.push_str(s);
+        Ok(())
+    }
+}
 
-        if let Some(s) = value.as_string() {
-            return Ok(s.into());
+/// Plain string values are shared with an archive instead of being copied.
+impl Decodable for Rc<str> {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        if let ObjectValue::Ref(value) = value
+            && let Some(s) = value.as_shared_string()
+        {
+            return Ok(s);
         }
+        borrow_str(value, ctx.is_probing()).map(Rc::from)
+    }
 
-        if !value.is_object() {
-            return Err(DeError::ExpectedString);
+    fn decode_ref(value: &ValueRef, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        match value.as_shared_string() {
+            Some(s) => Ok(s),
+            None => borrow_str_ref(value, ctx.is_probing()).map(Rc::from),
         }
+    }
+}
+
+/// Plain data values are shared with an archive instead of being copied.
+impl Decodable for Rc<[u8]> {
+    decode_with_new_context!();
 
-        let obj = value.as_object().unwrap(); // safe, checked with is_object()
-        if obj.class() != "NSString" && obj.class() != "NSMutableString" {
-            return Err(DeError::UnexpectedClass(
-                obj.class().into(),
-                "NSString or NSMutableString".into(),
-            ));
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        if let ObjectValue::Ref(value) = value
+            && let Some(data) = value.as_shared_data()
+        {
+            return Ok(data);
         }
+        borrow_bytes(value, ctx.is_probing()).map(Rc::from)
+    }
+}
 
-        if !obj.contains_key("NS.bytes") && !obj.contains_key("NS.string") {
-            return Err(DeError::ExpectedString);
+impl Decodable for Date {
+    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
+        if let ObjectValue::Date(value) = value {
+            return Ok(*value);
         }
-        let s = if let Some(ObjectValue::Data(data)) = obj.as_map().get("NS.bytes") {
-            let parsed = String::from_utf8(data.to_vec());
-            if let Err(e) = parsed {
-                return Err(DeError::Custom(format!(
-                    "Unable to parse a UTF-8 string: {e}"
-                )));
-            }
-            parsed.unwrap() // safe
-        } else if let Some(ObjectValue::String(data)) = obj.as_map().get("NS.string") {
-            data.clone()
-        } else {
-            return Err(DeError::ExpectedString);
-        };
-        Ok(s)
+        Err(DeError::ExpectedDate)
     }
 }
 
 impl Decodable for bool {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError>
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
     where
         Self: Sized,
     {
         if let ObjectValue::Boolean(value) = value {
             return Ok(*value);
         }
-        if let ObjectValue::Ref(value) = value {
-            if let Some(v) = value.as_boolean() {
-                return Ok(v);
-            }
+        if let ObjectValue::Ref(value) = value
+            && let Some(v) = value.as_boolean()
+        {
+            return Ok(v);
+        }
+        if ctx.coerce_numeric_bool()
+            && let Ok(integer) = Integer::decode(value)
+        {
+            let value = match integer.as_unsigned() {
+                Some(0) => false,
+                Some(1) => true,
+                _ => {
+                    return Err(DeError::Custom(format!(
+                        "Integer {integer} can't be represented as bool"
+                    )));
+                }
+            };
+            warn_coercion(ctx, integer, "bool");
+            return Ok(value);
         }
         Err(DeError::ExpectedBoolean)
     }
@@ -76,7 +294,10 @@ impl Decodable for bool {
 
 /// A byte buffer used for decoding from the plist data type and NSData
 /// (NSMutableData) class.
-#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+///
+/// It dereferences to a byte slice. Its [Debug] output is a hex preview of
+/// the first bytes, so large blobs don't flood logs.
+#[derive(PartialEq, Eq, Hash, Clone)]
 pub struct Data(Vec<u8>);
 
 impl Data {
@@ -89,6 +310,33 @@ impl Data {
     pub fn into_vec(self) -> Vec<u8> {
         self.0
     }
+
+    /// Returns a [Display](std::fmt::Display) wrapper encoding the bytes in
+    /// base64. Use `data.base64().to_string()` to get a string.
+    pub fn base64(&self) -> Base64<'_> {
+        Base64(&self.0)
+    }
+
+    /// Consumes itself and returns a reader over the bytes, e.g. for
+    /// parsing an embedded file with an [std::io::Read]-based parser.
+    pub fn into_reader(self) -> std::io::Cursor<Vec<u8>> {
+        std::io::Cursor::new(self.0)
+    }
+}
+
+impl std::fmt::Debug for Data {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Data")?;
+        data_preview(f, &self.0)
+    }
+}
+
+impl std::ops::Deref for Data {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
 }
 
 impl From<Vec<u8>> for Data {
@@ -97,6 +345,12 @@ impl From<Vec<u8>> for Data {
     }
 }
 
+impl From<&[u8]> for Data {
+    fn from(value: &[u8]) -> Self {
+        Self(value.to_vec())
+    }
+}
+
 impl From<Data> for Vec<u8> {
     fn from(value: Data) -> Self {
         value.0
@@ -120,65 +374,201 @@ impl Decodable for Data {
     where
         Self: Sized,
     {
-        if let ObjectValue::Data(value) = value {
-            return Ok(Data(value.to_vec()));
+        borrow_bytes(value, false).map(|bytes| Data(bytes.to_vec()))
+    }
+
+    /// Objects without inline bytes are passed to an external data resolver
+    /// of a context if there is one (see [DecodeContext::set_external_data]).
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let error = match borrow_bytes(value, ctx.is_probing()) {
+            Ok(bytes) => return Ok(Data(bytes.to_vec())),
+            Err(e) => e,
+        };
+        if let ObjectValue::Ref(value) = value
+            && let Some(obj) = value.as_object()
+            && let Some(bytes) = ctx.resolve_external_data(obj)?
+        {
+            return Ok(Data(bytes));
         }
-        if let ObjectValue::Ref(value) = value {
-            if let Some(v) = value.as_data() {
-                return Ok(Data(v.to_vec()));
-            }
-            // Decoding NSData
-            if let Some(v) = value.as_object() {
-                if v.class() != "NSData" && v.class() != "NSMutableData" {
-                    return Err(DeError::UnexpectedClass(
-                        v.class().into(),
-                        "NSData or NSMutableData".into(),
-                    ));
-                }
-                if let Some(ObjectValue::Data(data)) = v.as_map().get("NS.data") {
-                    return Ok(data.to_vec().into());
-                }
+        Err(error)
+    }
+
+    fn decode_into_with(
+        &mut self,
+        value: &ObjectValue,
+        ctx: &mut DecodeContext,
+    ) -> Result<(), DeError> {
+        match borrow_bytes(value, ctx.is_probing()) {
+            Ok(bytes) => {
+                self.0.clear();
+                self.0.extend_from_slice(bytes);
             }
+            Err(_) => *self = Self::decode_with(value, ctx)?,
         }
-        Err(DeError::ExpectedData)
+        Ok(())
     }
 }
 
 /// Decodes NS.objects array to a vector of decodables.
-/// Used by Vec and Hashmap impls.
-fn refs_to_t<T: Decodable>(obj: &Object) -> Result<Vec<T>, DeError> {
+/// Used by Vec and set impls.
+///
+/// If `skip_nulls` is set, null references are omitted.
+fn refs_to_t<T: Decodable>(
+    obj: &Object,
+    skip_nulls: bool,
+    ctx: &mut DecodeContext,
+) -> Result<Vec<T>, DeError> {
     let Ok(inner_objs) = obj.decode_array("NS.objects") else {
         return Err(DeError::Custom("Missing NS.objects key".to_string()));
     };
     let mut result = Vec::with_capacity(inner_objs.len());
-    for inner_obj in inner_objs {
-        result.push(T::decode(&ObjectValue::Ref(inner_obj.clone()))?);
+    for (index, inner_obj) in inner_objs.iter().enumerate() {
+        if skip_nulls && inner_obj.is_null_ref() {
+            warn_skipped_null(ctx, index);
+            continue;
+        }
+        result.push(ctx.decode_element_ref(inner_obj, index)?);
     }
     Ok(result)
 }
 
+/// Decodes NS.objects array into an existing vector, decoding elements
+/// into existing ones. Used by Vec and SkipNulls impls.
+fn refs_into_t<T: Decodable>(
+    target: &mut Vec<T>,
+    obj: &Object,
+    skip_nulls: bool,
+    ctx: &mut DecodeContext,
+) -> Result<(), DeError> {
+    let Ok(inner_objs) = obj.decode_array("NS.objects") else {
+        return Err(DeError::Custom("Missing NS.objects key".to_string()));
+    };
+    let mut len = 0;
+    for (index, inner_obj) in inner_objs.iter().enumerate() {
+        if skip_nulls && inner_obj.is_null_ref() {
+            warn_skipped_null(ctx, index);
+            continue;
+        }
+        let value = ObjectValue::Ref(inner_obj.clone());
+        if let Some(element) = target.get_mut(len) {
+            ctx.decode_element_into(element, &value, index)?;
+        } else {
+            target.push(ctx.decode_element(&value, index)?);
+        }
+        len += 1;
+    }
+    target.truncate(len);
+    Ok(())
+}
+
+/// Records a [DecodeWarning::SkippedNull] of an array being decoded.
+fn warn_skipped_null(ctx: &mut DecodeContext, index: usize) {
+    if ctx.collects_warnings() {
+        let path = ctx.path().to_string();
+        ctx.warn(DecodeWarning::SkippedNull { path, index });
+    }
+}
+
+/// Returns an array (or set) object of a value.
+fn array_object(value: &ObjectValue, probing: bool) -> Result<&Object, DeError> {
+    let ObjectValue::Ref(value) = value else {
+        return Err(DeError::ExpectedObject);
+    };
+    array_object_ref(value, probing)
+}
+
+/// Returns an array (or set) object of a referenced value.
+fn array_object_ref(value: &ArchiveValue, probing: bool) -> Result<&Object, DeError> {
+    let obj = value.as_object().ok_or(DeError::ExpectedObject)?;
+
+    if !obj.matches_class("NSArray")
+        && !obj.matches_class("NSMutableArray")
+        && !obj.matches_class("NSSet")
+        && !obj.matches_class("NSMutableSet")
+    {
+        return Err(class_mismatch(
+            value,
+            &["NSArray", "NSMutableArray", "NSSet", "NSMutableSet"],
+            probing,
+        ));
+    }
+    Ok(obj)
+}
+
 impl<T: Decodable> Decodable for Vec<T> {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError>
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
     where
         Self: Sized,
     {
-        let ObjectValue::Ref(value) = value else {
-            return Err(DeError::ExpectedObject);
-        };
-        let obj = value.as_object().ok_or(DeError::ExpectedObject)?;
+        refs_to_t(array_object(value, ctx.is_probing())?, false, ctx)
+    }
 
-        if obj.class() != "NSArray"
-            && obj.class() != "NSMutableArray"
-            && obj.class() != "NSSet"
-            && obj.class() != "NSMutableSet"
-        {
-            return Err(DeError::UnexpectedClass(
-                obj.class().into(),
-                "NSArray, NSMutableArray, NSSet or NSMutableSet".into(),
-            ));
-        }
+    fn decode_ref(value: &ValueRef, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        refs_to_t(array_object_ref(value, ctx.is_probing())?, false, ctx)
+    }
+
+    fn decode_into_with(
+        &mut self,
+        value: &ObjectValue,
+        ctx: &mut DecodeContext,
+    ) -> Result<(), DeError> {
+        refs_into_t(self, array_object(value, ctx.is_probing())?, false, ctx)
+    }
+}
+
+/// A vector that skips null references of an array while decoding.
+///
+/// `Vec<T>` fails if an array contains `$null` entries (unless `T` is an
+/// [Option]). Use `SkipNulls<T>` to decode only non-null ones.
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Default)]
+pub struct SkipNulls<T>(pub Vec<T>);
+
+impl<T> SkipNulls<T> {
+    /// Consumes itself and returns a vector of decoded values.
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T> std::ops::Deref for SkipNulls<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> From<SkipNulls<T>> for Vec<T> {
+    fn from(value: SkipNulls<T>) -> Self {
+        value.0
+    }
+}
+
+impl<T: Decodable> Decodable for SkipNulls<T> {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        let obj = array_object(value, ctx.is_probing())?;
+        Ok(Self(refs_to_t(obj, true, ctx)?))
+    }
+
+    fn decode_ref(value: &ValueRef, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = array_object_ref(value, ctx.is_probing())?;
+        Ok(Self(refs_to_t(obj, true, ctx)?))
+    }
 
-        refs_to_t(obj)
+    fn decode_into_with(
+        &mut self,
+        value: &ObjectValue,
+        ctx: &mut DecodeContext,
+    ) -> Result<(), DeError> {
+        let obj = array_object(value, ctx.is_probing())?;
+        refs_into_t(&mut self.0, obj, true, ctx)
     }
 }
 
@@ -192,6 +582,10 @@ impl Decodable for ValueRef {
         };
         Ok(value.clone())
     }
+
+    fn decode_ref(value: &ValueRef, _ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        Ok(value.clone())
+    }
 }
 
 impl Decodable for UniqueId {
@@ -207,7 +601,9 @@ impl Decodable for UniqueId {
 }
 
 impl<T: Decodable> Decodable for Option<T> {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError>
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
     where
         Self: Sized,
     {
@@ -218,7 +614,230 @@ impl<T: Decodable> Decodable for Option<T> {
         if value == &ObjectValue::NullRef {
             return Ok(None);
         }
-        Ok(Some(T::decode(value)?))
+        // Null references inside of arrays point to the `$null` value
+        if let ObjectValue::Ref(value) = value
+            && value.is_null_ref()
+        {
+            return Ok(None);
+        }
+        Ok(Some(T::decode_with(value, ctx)?))
+    }
+
+    fn decode_into_with(
+        &mut self,
+        value: &ObjectValue,
+        ctx: &mut DecodeContext,
+    ) -> Result<(), DeError> {
+        let is_null = match value {
+            ObjectValue::NullRef => true,
+            ObjectValue::Ref(value) => value.is_null_ref(),
+            _ => false,
+        };
+        match self {
+            _ if is_null => *self = None,
+            Some(inner) => inner.decode_into_with(value, ctx)?,
+            None => *self = Some(T::decode_with(value, ctx)?),
+        }
+        Ok(())
+    }
+}
+
+/// A lazily decoded value.
+///
+/// Decoding of a value is deferred until it's accessed, so it can be used
+/// to break circular references, e.g. a view that points back to its
+/// superview:
+///
+/// ```ignore
+/// #[derive(Decodable)]
+/// struct View {
+///     superview: Option<Lazy<View>>,
+///     subviews: Vec<View>,
+/// }
+/// ```
+pub struct Lazy<T> {
+    value: ObjectValue,
+    // Boxed, so `T` may contain `Lazy<T>` itself
+    decoded: OnceCell<Box<T>>,
+}
+
+impl<T: Decodable> Lazy<T> {
+    /// Decodes a value on first access and returns a reference to it.
+    pub fn get(&self) -> Result<&T, DeError> {
+        if let Some(decoded) = self.decoded.get() {
+            return Ok(decoded);
+        }
+        let decoded = Box::new(self.decode()?);
+        Ok(self.decoded.get_or_init(|| decoded))
+    }
+
+    /// Decodes a new instance of a value every time it's called.
+    pub fn decode(&self) -> Result<T, DeError> {
+        T::decode(&self.value)
+    }
+}
+
+impl<T> Lazy<T> {
+    /// Returns an undecoded value.
+    pub fn value(&self) -> &ObjectValue {
+        &self.value
+    }
+
+    /// Returns a [UniqueId] of a referenced archive value.
+    pub fn unique_id(&self) -> Option<UniqueId> {
+        match &self.value {
+            ObjectValue::Ref(value) => Some(*value.unique_id()),
+            _ => None,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Lazy<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Values may have circular references, so only the id is printed
+        match self.unique_id() {
+            Some(id) => f.debug_tuple("Lazy").field(&id).finish(),
+            None => f.debug_tuple("Lazy").field(&self.value).finish(),
+        }
+    }
+}
+
+impl<T> PartialEq for Lazy<T> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.unique_id(), other.unique_id()) {
+            (Some(a), Some(b)) => a == b,
+            (None, None) => self.value == other.value,
+            _ => false,
+        }
+    }
+}
+
+impl<T> Decodable for Lazy<T> {
+    fn decode(value: &ObjectValue) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        Ok(Self {
+            value: value.clone(),
+            decoded: OnceCell::new(),
+        })
+    }
+}
+
+/// A conditionally encoded reference (`encodeConditionalObject:forKey:`).
+///
+/// Archives don't mark conditional references: an object that isn't
+/// encoded unconditionally anywhere else is simply replaced with `$null`.
+/// Such references usually point back in a graph (e.g. to a delegate or a
+/// parent), so [ConditionalRef] holds a weak pointer to an archive value
+/// instead of decoding it. It never triggers circular reference errors and
+/// doesn't keep the value alive.
+pub struct ConditionalRef<T> {
+    value: Option<Weak<ArchiveValue>>,
+    unique_id: Option<UniqueId>,
+    marker: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T> ConditionalRef<T> {
+    /// Returns `true` if a referenced object wasn't encoded (`$null`).
+    pub fn is_null(&self) -> bool {
+        self.value.is_none()
+    }
+
+    /// Returns a [UniqueId] of a referenced archive value.
+    pub fn unique_id(&self) -> Option<UniqueId> {
+        self.unique_id
+    }
+
+    /// Returns a referenced archive value. Returns [None] if the reference
+    /// is null or the archive has been dropped.
+    pub fn upgrade(&self) -> Option<ValueRef> {
+        self.value.as_ref()?.upgrade()
+    }
+}
+
+impl<T: Decodable> ConditionalRef<T> {
+    /// Decodes a referenced value. Returns [None] if the reference is null
+    /// or the archive has been dropped.
+    pub fn decode(&self) -> Option<Result<T, DeError>> {
+        self.upgrade().map(|value| T::decode(&value.into()))
+    }
+}
+
+impl<T> Default for ConditionalRef<T> {
+    /// Creates a null reference.
+    fn default() -> Self {
+        Self {
+            value: None,
+            unique_id: None,
+            marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for ConditionalRef<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            unique_id: self.unique_id,
+            marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for ConditionalRef<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.unique_id {
+            Some(id) => f.debug_tuple("ConditionalRef").field(&id).finish(),
+            None => f.write_str("ConditionalRef(null)"),
+        }
+    }
+}
+
+impl<T> PartialEq for ConditionalRef<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.unique_id == other.unique_id
+    }
+}
+
+impl<T> Decodable for ConditionalRef<T> {
+    fn decode(value: &ObjectValue) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        match value {
+            ObjectValue::NullRef => Ok(Self::default()),
+            ObjectValue::Ref(value) if value.is_null_ref() => Ok(Self::default()),
+            ObjectValue::Ref(value) => Ok(Self {
+                value: Some(Rc::downgrade(value)),
+                unique_id: Some(value.unique_id),
+                marker: std::marker::PhantomData,
+            }),
+            _ => Err(DeError::ExpectedObject),
+        }
+    }
+}
+
+/// Values referring to the same archive object are decoded only once and
+/// share the same [Rc] within a [DecodeContext].
+impl<T: Decodable + 'static> Decodable for Rc<T> {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        // Plain values aren't shared
+        let ObjectValue::Ref(value_ref) = value else {
+            return Ok(Rc::new(T::decode_with(value, ctx)?));
+        };
+        let id = *value_ref.unique_id();
+        if let Some(shared) = ctx.shared::<T>(id) {
+            return Ok(shared);
+        }
+        let decoded = Rc::new(T::decode_with(value, ctx)?);
+        ctx.insert_shared(id, decoded.clone());
+        Ok(decoded)
     }
 }
 
@@ -227,32 +846,86 @@ impl Decodable for f64 {
         if let ObjectValue::Real(value) = value {
             return Ok(*value);
         }
-        if let ObjectValue::Ref(value) = value {
-            if let Some(v) = value.as_float() {
-                return Ok(v);
-            }
+        if let ObjectValue::Ref(value) = value
+            && let Some(v) = value.as_float()
+        {
+            return Ok(v);
         }
         Err(DeError::ExpectedFloat)
     }
+
+    fn decode_ref(value: &ValueRef, _ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        value.as_float().ok_or(DeError::ExpectedFloat)
+    }
 }
 
 impl Decodable for Integer {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
         if let ObjectValue::Integer(value) = value {
             return Ok(*value);
         }
-        if let ObjectValue::Ref(value) = value {
-            if let Some(v) = value.as_integer() {
-                return Ok(*v);
-            }
+        if let ObjectValue::Ref(value) = value
+            && let Some(v) = value.as_integer()
+        {
+            return Ok(*v);
+        }
+        if ctx.coerce_real_to_int()
+            && let Ok(real) = f64::decode(value)
+        {
+            let integer = real_to_integer(real)?;
+            warn_coercion(ctx, real, "integer");
+            return Ok(integer);
         }
         Err(DeError::ExpectedInteger)
     }
+
+    fn decode_ref(value: &ValueRef, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        match value.as_integer() {
+            Some(v) => Ok(*v),
+            // Coercions are rare, so the reference may be cloned
+            None => Self::decode_with(&value.into(), ctx),
+        }
+    }
+}
+
+/// Records a [DecodeWarning::NumericCoercion] of a value being decoded.
+fn warn_coercion(ctx: &mut DecodeContext, value: impl std::fmt::Display, ty: &'static str) {
+    if ctx.collects_warnings() {
+        let path = ctx.path().to_string();
+        ctx.warn(DecodeWarning::NumericCoercion {
+            path,
+            value: value.to_string(),
+            ty,
+        });
+    }
+}
+
+/// Converts a real to an integer if it has no fractional part and fits
+/// into i64 or u64.
+fn real_to_integer(real: f64) -> Result<Integer, DeError> {
+    // 2^63 and 2^64 are exactly representable, so the bounds are exact
+    const I64_END: f64 = 9_223_372_036_854_775_808.0;
+    const U64_END: f64 = 18_446_744_073_709_551_616.0;
+    if real.fract() == 0.0 {
+        if (-I64_END..I64_END).contains(&real) {
+            return Ok((real as i64).into());
+        }
+        if (0.0..U64_END).contains(&real) {
+            return Ok((real as u64).into());
+        }
+    }
+    Err(DeError::Custom(format!(
+        "Real {real} can't be represented as an integer"
+    )))
 }
 
 impl Decodable for u64 {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
-        let integer = Integer::decode(value)?;
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let integer = Integer::decode_with(value, ctx)?;
         integer.as_unsigned().ok_or(DeError::Custom(
             "Unable to represent an integer as u64".into(),
         ))
@@ -260,32 +933,40 @@ impl Decodable for u64 {
 }
 
 impl Decodable for u8 {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
-        u64::decode(value)?
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        u64::decode_with(value, ctx)?
             .try_into()
             .map_err(|e| DeError::Custom(format!("{e}")))
     }
 }
 
 impl Decodable for u16 {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
-        u64::decode(value)?
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        u64::decode_with(value, ctx)?
             .try_into()
             .map_err(|e| DeError::Custom(format!("{e}")))
     }
 }
 
 impl Decodable for u32 {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
-        u64::decode(value)?
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        u64::decode_with(value, ctx)?
             .try_into()
             .map_err(|e| DeError::Custom(format!("{e}")))
     }
 }
 
 impl Decodable for i64 {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
-        let integer = Integer::decode(value)?;
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let integer = Integer::decode_with(value, ctx)?;
         integer.as_signed().ok_or(DeError::Custom(
             "Unable to represent an integer as i64".into(),
         ))
@@ -293,65 +974,283 @@ impl Decodable for i64 {
 }
 
 impl Decodable for i8 {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
-        i64::decode(value)?
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        i64::decode_with(value, ctx)?
             .try_into()
             .map_err(|e| DeError::Custom(format!("{e}")))
     }
 }
 
 impl Decodable for i16 {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
-        i64::decode(value)?
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        i64::decode_with(value, ctx)?
             .try_into()
             .map_err(|e| DeError::Custom(format!("{e}")))
     }
 }
 
 impl Decodable for i32 {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
-        i64::decode(value)?
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        i64::decode_with(value, ctx)?
             .try_into()
             .map_err(|e| DeError::Custom(format!("{e}")))
     }
 }
 
+impl Decodable for usize {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let v = u64::decode_with(value, ctx)?;
+        v.try_into()
+            .map_err(|_| DeError::Custom(format!("Integer {v} is out of range for usize")))
+    }
+}
+
+impl Decodable for isize {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let v = i64::decode_with(value, ctx)?;
+        v.try_into()
+            .map_err(|_| DeError::Custom(format!("Integer {v} is out of range for isize")))
+    }
+}
+
+impl Decodable for f32 {
+    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
+        let v = f64::decode(value)?;
+        let converted = v as f32;
+        // Precision loss is expected, but a finite value shouldn't become infinite
+        if v.is_finite() && converted.is_infinite() {
+            return Err(DeError::Custom(format!(
+                "Float {v:e} is out of range for f32"
+            )));
+        }
+        Ok(converted)
+    }
+}
+
+impl Decodable for char {
+    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
+        // A character may be encoded as a string containing it...
+        if let Ok(s) = borrow_str(value, false) {
+            let mut chars = s.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(c),
+                _ => Err(DeError::Custom(format!(
+                    "Expected a string with a single character, found `{s}`"
+                ))),
+            };
+        }
+        // ... or as an integer code point (e.g. `unichar`)
+        let v = u64::decode(value).map_err(|_| DeError::Custom("Expected character".into()))?;
+        u32::try_from(v)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| DeError::Custom(format!("Integer {v} is not a valid character")))
+    }
+}
+
+macro_rules! impl_decodable_non_zero {
+    ($($non_zero:ty => $int:ty),* $(,)?) => {
+        $(
+            impl Decodable for $non_zero {
+                decode_with_new_context!();
+
+                fn decode_with(
+                    value: &ObjectValue,
+                    ctx: &mut DecodeContext,
+                ) -> Result<Self, DeError> {
+                    let v = <$int>::decode_with(value, ctx)?;
+                    <$non_zero>::new(v).ok_or_else(|| {
+                        DeError::Custom(format!("Expected non-zero {}", stringify!($int)))
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_decodable_non_zero!(
+    NonZeroU8 => u8,
+    NonZeroU16 => u16,
+    NonZeroU32 => u32,
+    NonZeroU64 => u64,
+    NonZeroUsize => usize,
+    NonZeroI8 => i8,
+    NonZeroI16 => i16,
+    NonZeroI32 => i32,
+    NonZeroI64 => i64,
+    NonZeroIsize => isize,
+);
+
+/// Decodes NS.keys and NS.objects arrays of an NSDictionary to pairs of decodables.
+/// Used by HashMap and BTreeMap impls.
+fn dict_to_pairs<K: Decodable, V: Decodable>(
+    value: &ObjectValue,
+    ctx: &mut DecodeContext,
+) -> Result<Vec<(K, V)>, DeError> {
+    let ObjectValue::Ref(obj_value) = value else {
+        return Err(DeError::ExpectedObject);
+    };
+    let obj = obj_value.as_object().ok_or(DeError::ExpectedObject)?;
+
+    if !obj.matches_class("NSDictionary") && !obj.matches_class("NSMutableDictionary") {
+        return Err(class_mismatch(
+            obj_value,
+            &["NSDictionary", "NSMutableDictionary"],
+            ctx.is_probing(),
+        ));
+    }
+
+    let raw_keys = obj.decode_array("NS.keys")?;
+    let Ok(raw_objects) = obj.decode_array("NS.objects") else {
+        return Err(DeError::Custom("Missing NS.objects key".to_string()));
+    };
+    if raw_keys.len() != raw_objects.len() {
+        return Err(DeError::Custom(
+            "NSDictionary: The number of keys is not equal to the number of values".to_string(),
+        ));
+    }
+
+    let mut pairs = Vec::with_capacity(raw_keys.len());
+    for (index, (raw_key, raw_object)) in raw_keys.iter().zip(raw_objects).enumerate() {
+        let key = ctx.decode_element_ref(raw_key, index)?;
+        // String keys are used as path segments, like object keys
+        let object = match raw_key.as_string() {
+            Some(s) => ctx.decode_field_ref(raw_object, s)?,
+            None => ctx.decode_element_ref(raw_object, index)?,
+        };
+        pairs.push((key, object));
+    }
+    Ok(pairs)
+}
+
 // FIXME: A HashMap key should implement Eq and Hash. It's not possible for any Rust struct,
 // so some amount of dicts aren't decodable. Usually a key is a String anyway.
 impl<K: Decodable + std::hash::Hash + Eq, V: Decodable> Decodable for HashMap<K, V> {
-    fn decode(value: &ObjectValue) -> Result<Self, DeError>
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
     where
         Self: Sized,
     {
-        let ObjectValue::Ref(obj_value) = value else {
-            return Err(DeError::ExpectedObject);
-        };
-        let obj = obj_value.as_object().ok_or(DeError::ExpectedObject)?;
+        let pairs = dict_to_pairs(value, ctx)?;
+        let len = pairs.len();
+        let map: Self = collect_pairs(pairs, ctx.duplicate_keys());
+        check_duplicates(map.len(), len, ctx.duplicate_keys())?;
+        Ok(map)
+    }
+}
 
-        if obj.class() != "NSDictionary" && obj.class() != "NSMutableDictionary" {
-            return Err(DeError::UnexpectedClass(
-                obj.class().into(),
-                "NSDictionary or NSMutableDictionary".into(),
-            ));
-        }
+impl<K: Decodable + Ord, V: Decodable> Decodable for BTreeMap<K, V> {
+    decode_with_new_context!();
 
-        let raw_keys = obj.decode_array("NS.keys")?;
-        let mut keys = Vec::with_capacity(raw_keys.len());
-        for key in raw_keys {
-            keys.push(K::decode(&key.into())?);
-        }
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        let pairs = dict_to_pairs(value, ctx)?;
+        let len = pairs.len();
+        let map: Self = collect_pairs(pairs, ctx.duplicate_keys());
+        check_duplicates(map.len(), len, ctx.duplicate_keys())?;
+        Ok(map)
+    }
+}
 
-        let mut objects = refs_to_t(obj)?;
+/// Collects dictionary pairs into a map. Maps keep the last value of
+/// a key, so pairs are reversed to keep the first one.
+fn collect_pairs<K, V, M: FromIterator<(K, V)>>(
+    mut pairs: Vec<(K, V)>,
+    policy: DuplicateKeys,
+) -> M {
+    if policy == DuplicateKeys::FirstWins {
+        pairs.reverse();
+    }
+    pairs.into_iter().collect()
+}
 
-        if keys.len() != objects.len() {
-            return Err(DeError::Custom(
-                "NSDictionary: The number of keys is not equal to the number of values".to_string(),
-            ));
-        }
-        let mut hashmap = HashMap::with_capacity(keys.len());
-        for _ in 0..keys.len() {
-            hashmap.insert(keys.pop().unwrap(), objects.pop().unwrap());
+fn check_duplicates(
+    map_len: usize,
+    pairs_len: usize,
+    policy: DuplicateKeys,
+) -> Result<(), DeError> {
+    if policy == DuplicateKeys::Error && map_len != pairs_len {
+        return Err(DeError::DuplicateKeys);
+    }
+    Ok(())
+}
+
+/// Key-value pairs of a dictionary in the order of an archive. Unlike maps
+/// it keeps all values of duplicate keys.
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Default)]
+pub struct Entries<K, V>(pub Vec<(K, V)>);
+
+impl<K, V> Entries<K, V> {
+    /// Consumes itself and returns a vector of pairs.
+    pub fn into_inner(self) -> Vec<(K, V)> {
+        self.0
+    }
+
+    /// Groups values by their keys.
+    pub fn into_grouped(self) -> HashMap<K, Vec<V>>
+    where
+        K: std::hash::Hash + Eq,
+    {
+        let mut grouped: HashMap<K, Vec<V>> = HashMap::new();
+        for (key, value) in self.0 {
+            grouped.entry(key).or_default().push(value);
         }
-        Ok(hashmap)
+        grouped
+    }
+}
+
+impl<K, V> std::ops::Deref for Entries<K, V> {
+    type Target = Vec<(K, V)>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K: Decodable, V: Decodable> Decodable for Entries<K, V> {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        Ok(Self(dict_to_pairs(value, ctx)?))
+    }
+}
+
+impl<T: Decodable + std::hash::Hash + Eq> Decodable for HashSet<T> {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        let obj = array_object(value, ctx.is_probing())?;
+        Ok(refs_to_t(obj, false, ctx)?.into_iter().collect())
+    }
+}
+
+impl<T: Decodable + Ord> Decodable for BTreeSet<T> {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        let obj = array_object(value, ctx.is_probing())?;
+        Ok(refs_to_t(obj, false, ctx)?.into_iter().collect())
     }
 }
@@ -0,0 +1,547 @@
+use crate::{
+    Date, DeError, Decodable, DecodeContext, Fields, Integer, KeyedArchive, NestedValue,
+    ObjectValue, PathSegment, UniqueId, ValueRef,
+    decodable::{borrow_bytes, borrow_str},
+};
+use plist::{Dictionary as PlistDictionary, Uid, Value as PlistValue};
+use std::collections::BTreeMap;
+
+/// An owned value of any archived type, like `serde_json::Value`.
+///
+/// It decodes any value tree without writing types for it. Unlike
+/// [ValueRef](crate::ValueRef)s it doesn't keep an archive alive and can be
+/// compared. Foundation collections and strings are turned into plain
+/// values:
+///
+/// - `NSString` becomes [DynamicValue::String] and `NSData` becomes
+///   [DynamicValue::Data];
+/// - `NSArray` and `NSSet` (mutable ones too) become [DynamicValue::Array];
+/// - `NSDictionary` becomes [DynamicValue::Dict];
+/// - `NSNull` and `$null` references become [DynamicValue::Null];
+/// - any other object becomes [DynamicValue::Object].
+///
+/// A circular reference to an object that is being decoded becomes
+/// [DynamicValue::Ref].
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicValue {
+    Null,
+    Bool(bool),
+    Int(Integer),
+    Float(f64),
+    String(String),
+    Data(Vec<u8>),
+    Date(Date),
+    Array(Vec<DynamicValue>),
+    /// Key-value pairs of a dictionary in the archived order. Keys are
+    /// usually strings, but may be of any type.
+    Dict(Vec<(DynamicValue, DynamicValue)>),
+    Object {
+        class: String,
+        fields: BTreeMap<String, DynamicValue>,
+    },
+    /// A circular reference to an object with a given uid.
+    Ref(UniqueId),
+}
+
+impl DynamicValue {
+    /// Returns `true` if it's [DynamicValue::Null].
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    /// Returns a boolean if it's [DynamicValue::Bool].
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Returns an integer if it's [DynamicValue::Int] and fits into [i64].
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int(i) => i.as_signed(),
+            _ => None,
+        }
+    }
+
+    /// Returns a float if it's [DynamicValue::Float].
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// Returns a string if it's [DynamicValue::String].
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns bytes if it's [DynamicValue::Data].
+    pub fn as_data(&self) -> Option<&[u8]> {
+        match self {
+            Self::Data(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// Returns elements if it's [DynamicValue::Array].
+    pub fn as_array(&self) -> Option<&[DynamicValue]> {
+        match self {
+            Self::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// Returns a class name if it's [DynamicValue::Object].
+    pub fn class(&self) -> Option<&str> {
+        match self {
+            Self::Object { class, .. } => Some(class),
+            _ => None,
+        }
+    }
+
+    /// Returns a value of a dictionary with a string `key` or a value of
+    /// an object field with a given `key`.
+    pub fn get(&self, key: &str) -> Option<&DynamicValue> {
+        match self {
+            Self::Dict(pairs) => pairs
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v),
+            Self::Object { fields, .. } => fields.get(key),
+            _ => None,
+        }
+    }
+}
+
+impl Decodable for DynamicValue {
+    decode_with_new_context!();
+
+    /// Nested values are decoded with a work stack instead of recursive
+    /// calls, so deeply nested archives don't overflow the stack even if
+    /// there's no depth limit. They're tracked like with
+    /// [DecodeContext::decode_field] and [DecodeContext::decode_element],
+    /// but aren't traced separately.
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        decode_dynamic(Nested::Value(value), ctx)
+    }
+
+    fn decode_ref(value: &ValueRef, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        decode_dynamic(Nested::Ref(value), ctx)
+    }
+}
+
+/// Decodes a value and its nested values with a work stack.
+fn decode_dynamic(value: Nested, ctx: &mut DecodeContext) -> Result<DynamicValue, DeError> {
+    let ty = std::any::type_name::<DynamicValue>();
+    let container = match start(value, ctx.is_probing())? {
+        Step::Done(value) => return Ok(value),
+        Step::Container(container) => container,
+    };
+    // The root value has already been entered by a caller
+    let mut stack: Vec<(Option<NestedValue>, Container)> = vec![(None, container)];
+    let error = loop {
+        let (_, container) = stack.last_mut().unwrap(); // safe, the root is popped last
+        let Some((nested, segment)) = container.next() else {
+            let (entered, container) = stack.pop().unwrap(); // safe, checked above
+            let value = container.finish();
+            match entered {
+                Some(entered) => ctx.leave_nested(entered),
+                None => return Ok(value),
+            }
+            stack.last_mut().unwrap().1.push(value); // safe, the root isn't popped
+            continue;
+        };
+        let object_value = nested.to_object_value();
+        let entered = match ctx.enter_nested(&object_value, ty, segment) {
+            Ok(entered) => entered,
+            Err(e) => match e.inner() {
+                DeError::CircularReference(uid) => {
+                    container.push(DynamicValue::Ref(*uid));
+                    continue;
+                }
+                _ => break e,
+            },
+        };
+        match start(nested, ctx.is_probing()) {
+            Ok(Step::Done(value)) => {
+                ctx.leave_nested(entered);
+                container.push(value);
+            }
+            Ok(Step::Container(nested)) => stack.push((Some(entered), nested)),
+            Err(e) => break ctx.fail_nested(entered, &object_value, e),
+        }
+    };
+    // Outer values are left in the reverse order
+    for (entered, _) in stack.into_iter().rev() {
+        if let Some(entered) = entered {
+            ctx.leave_nested(entered);
+        }
+    }
+    Err(error)
+}
+
+/// A nested value of a [DynamicValue] being decoded.
+#[derive(Clone, Copy)]
+enum Nested<'a> {
+    Ref(&'a ValueRef),
+    Value(&'a ObjectValue),
+}
+
+impl Nested<'_> {
+    fn to_object_value(self) -> ObjectValue {
+        match self {
+            Self::Ref(value) => value.into(),
+            Self::Value(value) => value.clone(),
+        }
+    }
+}
+
+/// A result of starting to decode a value.
+enum Step<'a> {
+    Done(DynamicValue),
+    Container(Container<'a>),
+}
+
+/// A container value with nested values that are left to decode.
+enum Container<'a> {
+    Array {
+        values: &'a [ValueRef],
+        array: Vec<DynamicValue>,
+    },
+    Dict {
+        keys: &'a [ValueRef],
+        objects: &'a [ValueRef],
+        key: Option<DynamicValue>,
+        pairs: Vec<(DynamicValue, DynamicValue)>,
+    },
+    Object {
+        class: &'a str,
+        fields: &'a Fields,
+        decoded: BTreeMap<String, DynamicValue>,
+        index: usize,
+    },
+}
+
+impl<'a> Container<'a> {
+    fn array(values: &'a [ValueRef]) -> Self {
+        Self::Array {
+            values,
+            array: Vec::with_capacity(values.len()),
+        }
+    }
+
+    /// Returns the next nested value along with its path segment.
+    fn next(&self) -> Option<(Nested<'a>, PathSegment<'_>)> {
+        match self {
+            Self::Array { values, array } => values
+                .get(array.len())
+                .map(|value| (Nested::Ref(value), PathSegment::Index(array.len()))),
+            Self::Dict {
+                keys,
+                objects,
+                key,
+                pairs,
+            } => {
+                let index = pairs.len();
+                match key {
+                    None => keys
+                        .get(index)
+                        .map(|key| (Nested::Ref(key), PathSegment::Index(index))),
+                    // String keys are used as path segments, like object keys
+                    Some(key) => Some((
+                        Nested::Ref(&objects[index]),
+                        match key.as_str() {
+                            Some(key) => PathSegment::Key(key),
+                            None => PathSegment::Index(index),
+                        },
+                    )),
+                }
+            }
+            Self::Object { fields, index, .. } => fields
+                .get_index(*index)
+                .map(|(key, value)| (Nested::Value(value), PathSegment::Key(key))),
+        }
+    }
+
+    /// Adds a decoded value returned by [Container::next].
+    fn push(&mut self, value: DynamicValue) {
+        match self {
+            Self::Array { array, .. } => array.push(value),
+            Self::Dict { key, pairs, .. } => match key.take() {
+                Some(key) => pairs.push((key, value)),
+                None => *key = Some(value),
+            },
+            Self::Object {
+                fields,
+                decoded,
+                index,
+                ..
+            } => {
+                let (key, _) = fields.get_index(*index).unwrap(); // safe, returned by `next`
+                decoded.insert(key.to_string(), value);
+                *index += 1;
+            }
+        }
+    }
+
+    fn finish(self) -> DynamicValue {
+        match self {
+            Self::Array { array, .. } => DynamicValue::Array(array),
+            Self::Dict { pairs, .. } => DynamicValue::Dict(pairs),
+            Self::Object { class, decoded, .. } => DynamicValue::Object {
+                class: class.into(),
+                fields: decoded,
+            },
+        }
+    }
+}
+
+/// Decodes a plain value or returns a container of nested values.
+fn start(value: Nested<'_>, probing: bool) -> Result<Step<'_>, DeError> {
+    let value = match value {
+        Nested::Value(ObjectValue::String(s)) => {
+            return Ok(Step::Done(DynamicValue::String(s.clone())));
+        }
+        Nested::Value(ObjectValue::Integer(i)) => return Ok(Step::Done(DynamicValue::Int(*i))),
+        Nested::Value(ObjectValue::Real(f)) => return Ok(Step::Done(DynamicValue::Float(*f))),
+        Nested::Value(ObjectValue::Boolean(b)) => return Ok(Step::Done(DynamicValue::Bool(*b))),
+        Nested::Value(ObjectValue::Data(data)) => {
+            return Ok(Step::Done(DynamicValue::Data(data.clone())));
+        }
+        Nested::Value(ObjectValue::Date(date)) => return Ok(Step::Done(DynamicValue::Date(*date))),
+        Nested::Value(ObjectValue::NullRef) => return Ok(Step::Done(DynamicValue::Null)),
+        Nested::Value(ObjectValue::RefArray(values)) => {
+            return Ok(Step::Container(Container::array(values)));
+        }
+        Nested::Value(ObjectValue::Ref(value)) | Nested::Ref(value) => value,
+    };
+
+    let done = |value| Ok(Step::Done(value));
+    if let Some(s) = value.as_string() {
+        return done(DynamicValue::String(s.into()));
+    } else if let Some(i) = value.as_integer() {
+        return done(DynamicValue::Int(*i));
+    } else if let Some(f) = value.as_float() {
+        return done(DynamicValue::Float(f));
+    } else if let Some(b) = value.as_boolean() {
+        return done(DynamicValue::Bool(b));
+    } else if let Some(data) = value.as_data() {
+        return done(DynamicValue::Data(data.into()));
+    } else if value.is_null_ref() {
+        return done(DynamicValue::Null);
+    }
+    let obj = value.as_object().ok_or(DeError::ExpectedObject)?;
+    let object_value = ObjectValue::Ref(value.clone());
+    match obj.class() {
+        "NSString" | "NSMutableString" => done(DynamicValue::String(
+            borrow_str(&object_value, probing)?.into(),
+        )),
+        "NSData" | "NSMutableData" => done(DynamicValue::Data(
+            borrow_bytes(&object_value, probing)?.into(),
+        )),
+        "NSArray" | "NSMutableArray" | "NSSet" | "NSMutableSet" => Ok(Step::Container(
+            Container::array(obj.decode_array("NS.objects")?),
+        )),
+        "NSDictionary" | "NSMutableDictionary" => {
+            let keys = obj.decode_array("NS.keys")?;
+            let objects = obj.decode_array("NS.objects")?;
+            if keys.len() != objects.len() {
+                return Err(DeError::Custom(
+                    "NSDictionary: The number of keys is not equal to the number of values"
+                        .to_string(),
+                ));
+            }
+            Ok(Step::Container(Container::Dict {
+                keys,
+                objects,
+                key: None,
+                pairs: Vec::with_capacity(keys.len()),
+            }))
+        }
+        "NSNull" => done(DynamicValue::Null),
+        class => Ok(Step::Container(Container::Object {
+            class,
+            fields: obj.as_map(),
+            decoded: BTreeMap::new(),
+            index: 0,
+        })),
+    }
+}
+
+impl KeyedArchive {
+    /// Decodes all `$top` entries as [DynamicValue]s, consuming the archive.
+    ///
+    /// Unlike the archive, which is built on [Rc](std::rc::Rc) pointers,
+    /// the result is `Send + 'static`, so it may be moved to other threads
+    /// or async tasks. Circular references become [DynamicValue::Ref]s.
+    pub fn into_owned_tree(self) -> Result<BTreeMap<String, DynamicValue>, DeError> {
+        let mut ctx = self.decode_context();
+        self.top_keys()
+            .into_iter()
+            .map(|key| Ok((key.to_string(), self.decode_top_with(key, &mut ctx)?)))
+            .collect()
+    }
+}
+
+/// Reconstructs a plain plist subtree, e.g. to hand it to code working with
+/// [plist::Value]s. Values are converted like [DynamicValue]s, and then:
+///
+/// - arrays and sets become [PlistValue::Array]s;
+/// - dictionaries become [PlistValue::Dictionary]s, their keys must be
+///   strings;
+/// - other objects become dictionaries of their fields with an additional
+///   `$class` key holding a class name;
+/// - null fields and dictionary values are omitted, other nulls result in
+///   an error;
+/// - circular references become [PlistValue::Uid]s.
+impl Decodable for PlistValue {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        // Tracked as a `DynamicValue`, so references back to the value
+        // become uids
+        plist_value(ctx.decode::<DynamicValue>(value)?)
+    }
+}
+
+/// Converts a value into a plist. Nested values are converted with a work
+/// stack, like they're decoded.
+fn plist_value(value: DynamicValue) -> Result<PlistValue, DeError> {
+    let mut stack: Vec<PlistContainer> = Vec::new();
+    let mut value = value;
+    loop {
+        match plist_step(value)? {
+            PlistStep::Done(converted) => match stack.last_mut() {
+                Some(container) => container.push(converted),
+                None => return Ok(converted),
+            },
+            PlistStep::Container(container) => stack.push(container),
+        }
+        // Finish containers until there's a value left to convert
+        value = loop {
+            let container = stack.last_mut().unwrap(); // safe, never empty here
+            if let Some(value) = container.next() {
+                break value;
+            }
+            let converted = stack.pop().unwrap().finish(); // safe, checked above
+            match stack.last_mut() {
+                Some(parent) => parent.push(converted),
+                None => return Ok(converted),
+            }
+        };
+    }
+}
+
+/// A result of starting to convert a value into a plist.
+enum PlistStep {
+    Done(PlistValue),
+    Container(PlistContainer),
+}
+
+/// A container value with nested values that are left to convert.
+enum PlistContainer {
+    Array {
+        values: std::vec::IntoIter<DynamicValue>,
+        array: Vec<PlistValue>,
+    },
+    Dict {
+        values: std::vec::IntoIter<(String, DynamicValue)>,
+        key: Option<String>,
+        dict: PlistDictionary,
+    },
+}
+
+impl PlistContainer {
+    fn next(&mut self) -> Option<DynamicValue> {
+        match self {
+            Self::Array { values, .. } => values.next(),
+            Self::Dict { values, key, .. } => values.next().map(|(k, value)| {
+                *key = Some(k);
+                value
+            }),
+        }
+    }
+
+    fn push(&mut self, value: PlistValue) {
+        match self {
+            Self::Array { array, .. } => array.push(value),
+            Self::Dict { key, dict, .. } => {
+                dict.insert(key.take().unwrap(), value); // safe, set by `next`
+            }
+        }
+    }
+
+    fn finish(self) -> PlistValue {
+        match self {
+            Self::Array { array, .. } => PlistValue::Array(array),
+            Self::Dict { dict, .. } => PlistValue::Dictionary(dict),
+        }
+    }
+}
+
+/// Converts a plain value or returns a container of nested values.
+fn plist_step(value: DynamicValue) -> Result<PlistStep, DeError> {
+    Ok(PlistStep::Done(match value {
+        DynamicValue::Null => {
+            return Err(DeError::Custom(
+                "A null value can't be represented in a plist".to_string(),
+            ));
+        }
+        DynamicValue::Bool(b) => PlistValue::Boolean(b),
+        DynamicValue::Int(i) => PlistValue::Integer(i),
+        DynamicValue::Float(f) => PlistValue::Real(f),
+        DynamicValue::String(s) => PlistValue::String(s),
+        DynamicValue::Data(data) => PlistValue::Data(data),
+        DynamicValue::Date(date) => PlistValue::Date(date),
+        DynamicValue::Array(array) => {
+            return Ok(PlistStep::Container(PlistContainer::Array {
+                array: Vec::with_capacity(array.len()),
+                values: array.into_iter(),
+            }));
+        }
+        DynamicValue::Dict(pairs) => {
+            let mut values = Vec::with_capacity(pairs.len());
+            for (key, value) in pairs {
+                let DynamicValue::String(key) = key else {
+                    return Err(DeError::Custom(
+                        "NSDictionary: Only string keys can be represented in a plist".to_string(),
+                    ));
+                };
+                if !value.is_null() {
+                    values.push((key, value));
+                }
+            }
+            return Ok(PlistStep::Container(PlistContainer::Dict {
+                values: values.into_iter(),
+                key: None,
+                dict: PlistDictionary::new(),
+            }));
+        }
+        DynamicValue::Object { class, fields } => {
+            let mut dict = PlistDictionary::new();
+            dict.insert("$class".into(), PlistValue::String(class));
+            let values: Vec<_> = fields
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .collect();
+            return Ok(PlistStep::Container(PlistContainer::Dict {
+                values: values.into_iter(),
+                key: None,
+                dict,
+            }));
+        }
+        DynamicValue::Ref(uid) => PlistValue::Uid(Uid::new(uid.get() as u64)),
+    }))
+}
@@ -0,0 +1,367 @@
+use crate::{
+    ARCHIVER, ARCHIVER_KEY_NAME, ARCHIVER_VERSION, ArchiveValue, ArchiveValueVariant, Error,
+    KeyedArchive, NULL_OBJECT_REFERENCE_NAME, OBJECTS_KEY_NAME, ObjectValue, ParseOptions,
+    TOP_KEY_NAME, UniqueId, VERSION_KEY_NAME,
+};
+use plist::{Dictionary as PlistDictionary, Uid, Value as PlistValue};
+
+/// An editable copy of a keyed archive.
+///
+/// Decoded archive values are shared with [Rc](std::rc::Rc) pointers, so they
+/// can't be changed in place. An editor keeps values as plist values indexed
+/// by their uids instead: object fields refer to other values with
+/// [plist::Uid] values, just like in an archive file. Once edits are done,
+/// [ArchiveEditor::into_plist] returns a plist that may be written with the
+/// `plist` crate, and [ArchiveEditor::into_archive] parses it again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveEditor {
+    pub(crate) top: PlistDictionary,
+    pub(crate) objects: Vec<PlistValue>,
+    header: PlistDictionary,
+}
+
+impl ArchiveEditor {
+    /// Returns a value with a given `uid` if it exists.
+    pub fn value(&self, uid: UniqueId) -> Option<&PlistValue> {
+        self.objects.get(uid.get())
+    }
+
+    /// Returns the number of values.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Returns `true` if there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Returns a uid of a `$top` entry with a given `key` if it exists and
+    /// refers to a value of `$objects`.
+    pub fn top(&self, key: &str) -> Option<UniqueId> {
+        let uid = self.top.get(key)?.as_uid()?;
+        Some(UniqueId::new(uid.get() as usize))
+    }
+
+    /// Sets a `$top` entry with a given `key` to refer to a value with
+    /// a given `uid`.
+    pub fn set_top(&mut self, key: &str, uid: UniqueId) -> Result<(), Error> {
+        self.check_uid(uid)?;
+        self.top.insert(key.into(), uid_value(uid));
+        Ok(())
+    }
+
+    /// Replaces a plain value (a string, a number, data, etc.) with a given
+    /// `uid`. Objects may only be replaced with other objects.
+    pub fn set_value(&mut self, uid: UniqueId, value: PlistValue) -> Result<(), Error> {
+        self.check_uid(uid)?;
+        if uid.get() == 0 {
+            return Err(Error::InvalidEdit("`$null` can't be replaced".into()));
+        }
+        let is_object = |value: &PlistValue| value.as_dictionary().is_some();
+        if is_object(&self.objects[uid.get()]) != is_object(&value) {
+            return Err(Error::InvalidEdit(format!(
+                "Value (uid: {}) can't be replaced with a value of another kind",
+                uid.get()
+            )));
+        }
+        if let Some(dict) = value.as_dictionary() {
+            self.check_references(dict.values(), self.objects.len())?;
+        }
+        self.objects[uid.get()] = value;
+        Ok(())
+    }
+
+    /// Appends a new value and returns its uid. An object should refer to
+    /// other values with [plist::Uid] values.
+    pub fn add_value(&mut self, value: PlistValue) -> Result<UniqueId, Error> {
+        if let Some(dict) = value.as_dictionary() {
+            // A new object may refer to itself
+            self.check_references(dict.values(), self.objects.len() + 1)?;
+        }
+        self.objects.push(value);
+        Ok(UniqueId::new(self.objects.len() - 1))
+    }
+
+    /// Sets a field of an object with a given `uid`. A value may be a plain
+    /// value or a [plist::Uid] reference to another value.
+    pub fn set_field(&mut self, uid: UniqueId, key: &str, value: PlistValue) -> Result<(), Error> {
+        if key == "$class" {
+            return Err(Error::InvalidEdit("`$class` can't be changed".into()));
+        }
+        self.check_references([&value], self.objects.len())?;
+        self.object_mut(uid)?.insert(key.into(), value);
+        Ok(())
+    }
+
+    /// Removes a field of an object with a given `uid` and returns its value.
+    pub fn remove_field(&mut self, uid: UniqueId, key: &str) -> Result<Option<PlistValue>, Error> {
+        if key == "$class" {
+            return Err(Error::InvalidEdit("`$class` can't be removed".into()));
+        }
+        Ok(self.object_mut(uid)?.remove(key))
+    }
+
+    /// Deletes a value with a given `uid` along with all values that are
+    /// only reachable through it.
+    ///
+    /// References to the value are replaced with `$null` references (array
+    /// elements aren't removed, since arrays of dictionary keys and values
+    /// should have equal lengths) and `$top` entries referring to it are
+    /// removed. Uids of remaining values change (see [ArchiveEditor::compact]).
+    pub fn delete_subtree(&mut self, uid: UniqueId) -> Result<(), Error> {
+        self.check_uid(uid)?;
+        if uid.get() == 0 {
+            return Err(Error::InvalidEdit("`$null` can't be deleted".into()));
+        }
+        let target = uid.get() as u64;
+        let null = Uid::new(0);
+        for value in &mut self.objects {
+            let Some(dict) = value.as_dictionary_mut() else {
+                continue;
+            };
+            for (key, field) in dict.iter_mut() {
+                match field {
+                    PlistValue::Uid(uid) if uid.get() == target && key != "$class" => *uid = null,
+                    PlistValue::Array(array) => {
+                        for item in array {
+                            if let PlistValue::Uid(uid) = item
+                                && uid.get() == target
+                            {
+                                *uid = null;
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        self.top
+            .retain(|_, value| value.as_uid().map(|uid| uid.get()) != Some(target));
+        self.compact();
+        Ok(())
+    }
+
+    /// Removes values that can't be reached from `$top` and renumbers uids
+    /// of remaining ones. `$null` is always kept at uid 0.
+    pub fn compact(&mut self) {
+        let mut reachable = vec![false; self.objects.len()];
+        if !reachable.is_empty() {
+            reachable[0] = true;
+        }
+        let mut queue: Vec<usize> = self.top.values().filter_map(uid_index).collect();
+        while let Some(index) = queue.pop() {
+            if index >= reachable.len() || std::mem::replace(&mut reachable[index], true) {
+                continue;
+            }
+            if let Some(dict) = self.objects[index].as_dictionary() {
+                for value in dict.values() {
+                    match value {
+                        PlistValue::Array(array) => {
+                            queue.extend(array.iter().filter_map(uid_index))
+                        }
+                        value => queue.extend(uid_index(value)),
+                    }
+                }
+            }
+        }
+
+        let mut new_uids = vec![0; self.objects.len()];
+        let mut objects = Vec::with_capacity(self.objects.len());
+        for (index, value) in std::mem::take(&mut self.objects).into_iter().enumerate() {
+            if reachable[index] {
+                new_uids[index] = objects.len() as u64;
+                objects.push(value);
+            }
+        }
+        let remap = |value: &mut PlistValue| {
+            if let PlistValue::Uid(uid) = value {
+                *uid = Uid::new(new_uids[uid.get() as usize]);
+            }
+        };
+        for value in &mut objects {
+            let Some(dict) = value.as_dictionary_mut() else {
+                continue;
+            };
+            for field in dict.values_mut() {
+                match field {
+                    PlistValue::Array(array) => array.iter_mut().for_each(remap),
+                    field => remap(field),
+                }
+            }
+        }
+        self.top.values_mut().for_each(remap);
+        self.objects = objects;
+    }
+
+    /// Returns extra header keys of the archive plist (see
+    /// [KeyedArchive::extra_header]) that are written by
+    /// [ArchiveEditor::into_plist].
+    pub fn extra_header_mut(&mut self) -> &mut PlistDictionary {
+        &mut self.header
+    }
+
+    /// Returns a plist with a keyed archive structure.
+    pub fn into_plist(self) -> PlistValue {
+        let mut dict = PlistDictionary::new();
+        dict.insert(ARCHIVER_KEY_NAME.into(), ARCHIVER.into());
+        dict.insert(VERSION_KEY_NAME.into(), ARCHIVER_VERSION.into());
+        dict.insert(TOP_KEY_NAME.into(), self.top.into());
+        dict.insert(OBJECTS_KEY_NAME.into(), PlistValue::Array(self.objects));
+        for (key, value) in self.header {
+            if !dict.contains_key(&key) {
+                dict.insert(key, value);
+            }
+        }
+        dict.into()
+    }
+
+    /// Creates a [KeyedArchive] from edited values with default
+    /// [ParseOptions].
+    pub fn into_archive(self) -> Result<KeyedArchive, Error> {
+        KeyedArchive::from_plist(self.into_plist())
+    }
+
+    /// Creates a [KeyedArchive] from edited values with given
+    /// [ParseOptions].
+    pub fn into_archive_with_options(self, options: &ParseOptions) -> Result<KeyedArchive, Error> {
+        KeyedArchive::from_plist_with_options(self.into_plist(), options)
+    }
+
+    fn check_uid(&self, uid: UniqueId) -> Result<(), Error> {
+        if uid.get() >= self.objects.len() {
+            return Err(Error::InvalidEdit(format!(
+                "Uid {} is out of range",
+                uid.get()
+            )));
+        }
+        Ok(())
+    }
+
+    fn object_mut(&mut self, uid: UniqueId) -> Result<&mut PlistDictionary, Error> {
+        self.check_uid(uid)?;
+        self.objects[uid.get()]
+            .as_dictionary_mut()
+            .filter(|dict| dict.contains_key("$class"))
+            .ok_or_else(|| {
+                Error::InvalidEdit(format!("Value (uid: {}) isn't an object", uid.get()))
+            })
+    }
+
+    /// Checks that all references of object fields are less than `len`.
+    fn check_references<'a>(
+        &self,
+        values: impl IntoIterator<Item = &'a PlistValue>,
+        len: usize,
+    ) -> Result<(), Error> {
+        for value in values {
+            let uids: Vec<usize> = match value {
+                PlistValue::Array(array) => array.iter().filter_map(uid_index).collect(),
+                value => uid_index(value).into_iter().collect(),
+            };
+            if let Some(uid) = uids.into_iter().find(|uid| *uid >= len) {
+                return Err(Error::InvalidEdit(format!("Uid {uid} is out of range")));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn uid_value(uid: UniqueId) -> PlistValue {
+    PlistValue::Uid(Uid::new(uid.get() as u64))
+}
+
+fn uid_index(value: &PlistValue) -> Option<usize> {
+    value.as_uid().map(|uid| uid.get() as usize)
+}
+
+/// Converts an archive value back into a plist value.
+fn to_plist(value: &ArchiveValue) -> PlistValue {
+    match &value.value {
+        ArchiveValueVariant::Boolean(b) => (*b).into(),
+        ArchiveValueVariant::Classes { classes, hints } => {
+            let mut dict = PlistDictionary::new();
+            dict.insert("$classname".into(), classes[0].clone().into());
+            dict.insert(
+                "$classes".into(),
+                PlistValue::Array(classes.iter().map(|c| c.clone().into()).collect()),
+            );
+            if !hints.is_empty() {
+                dict.insert(
+                    "$classhints".into(),
+                    PlistValue::Array(hints.iter().map(|c| c.clone().into()).collect()),
+                );
+            }
+            dict.into()
+        }
+        ArchiveValueVariant::Data(data) => PlistValue::Data(data.to_vec()),
+        ArchiveValueVariant::Integer(i) => PlistValue::Integer(*i),
+        ArchiveValueVariant::NullRef => NULL_OBJECT_REFERENCE_NAME.into(),
+        ArchiveValueVariant::Real(r) => PlistValue::Real(*r),
+        ArchiveValueVariant::String(s) => PlistValue::String(s.to_string()),
+        ArchiveValueVariant::Object(obj) => {
+            let mut dict = PlistDictionary::new();
+            if !obj.is_anonymous() {
+                dict.insert("$class".into(), uid_value(obj.classes_uid()));
+            }
+            for (key, field) in obj.as_map().iter() {
+                let field = match field {
+                    ObjectValue::String(s) => s.clone().into(),
+                    ObjectValue::Integer(i) => PlistValue::Integer(*i),
+                    ObjectValue::Real(r) => PlistValue::Real(*r),
+                    ObjectValue::Boolean(b) => (*b).into(),
+                    ObjectValue::Data(d) => PlistValue::Data(d.clone()),
+                    ObjectValue::Date(d) => PlistValue::Date(*d),
+                    ObjectValue::RefArray(values) => PlistValue::Array(
+                        values.iter().map(|v| uid_value(*v.unique_id())).collect(),
+                    ),
+                    ObjectValue::Ref(v) => uid_value(*v.unique_id()),
+                    ObjectValue::NullRef => NULL_OBJECT_REFERENCE_NAME.into(),
+                };
+                dict.insert(key.into(), field);
+            }
+            dict.into()
+        }
+    }
+}
+
+impl KeyedArchive {
+    /// Creates an [ArchiveEditor] with a copy of all values of the archive.
+    pub fn edit(&self) -> ArchiveEditor {
+        let mut keys = self.top_keys();
+        keys.sort();
+        let mut top = PlistDictionary::new();
+        for key in keys {
+            let value = &self.top()[key];
+            let uid = *value.unique_id();
+            // Plain `$top` values aren't a part of `$objects`
+            let entry = if uid.get() < self.values().len() {
+                uid_value(uid)
+            } else {
+                to_plist(value)
+            };
+            top.insert(key.into(), entry);
+        }
+        ArchiveEditor {
+            top,
+            objects: self.values().iter().map(|v| to_plist(v)).collect(),
+            header: self.extra_header().clone(),
+        }
+    }
+
+    /// Copies a value with a given `uid` and all values it refers to into
+    /// a new self-contained archive. The value becomes its `root`, uids are
+    /// renumbered and extra header keys are kept. The new archive is parsed
+    /// with the same [ParseOptions] as this one.
+    pub fn extract_subtree(&self, uid: UniqueId) -> Result<KeyedArchive, Error> {
+        let mut editor = self.edit();
+        editor.check_uid(uid)?;
+        if uid.get() == 0 {
+            return Err(Error::InvalidEdit("`$null` can't be extracted".into()));
+        }
+        editor.top.clear();
+        editor.top.insert("root".into(), uid_value(uid));
+        editor.compact();
+        editor.into_archive_with_options(&self.options)
+    }
+}
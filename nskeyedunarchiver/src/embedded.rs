@@ -0,0 +1,159 @@
+use crate::decodable::borrow_bytes;
+use crate::{
+    Data, DeError, Decodable, DecodeContext, Error, KeyedArchive, Object, ObjectValue, ParseOptions,
+};
+use plist::Value as PlistValue;
+use std::fmt::Write;
+
+impl KeyedArchive {
+    /// Creates a [KeyedArchive] from a `Data` value of another plist. Apple
+    /// often stores keyed archives this way (e.g. in SFL2 files).
+    pub fn from_plist_data(value: &PlistValue) -> Result<Self, Error> {
+        Self::from_plist_data_with_options(value, &ParseOptions::default())
+    }
+
+    /// Creates a [KeyedArchive] from a `Data` value of another plist with
+    /// given [ParseOptions].
+    pub fn from_plist_data_with_options(
+        value: &PlistValue,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        let Some(bytes) = value.as_data() else {
+            return Err(Error::IncorrectFormat(
+                "Expected an embedded archive to be a type of `Data`".into(),
+            ));
+        };
+        Self::from_bytes_with_options(bytes, options)
+    }
+
+    /// Scans a plist for `Data` values containing keyed archives and parses
+    /// them. Returns archives with their paths inside of the plist, like
+    /// `items[0].Bookmark`.
+    ///
+    /// Data values that aren't keyed archives are skipped.
+    pub fn find_embedded(plist: &PlistValue) -> Vec<(String, KeyedArchive)> {
+        Self::find_embedded_with_options(plist, &ParseOptions::default())
+    }
+
+    /// Scans a plist for `Data` values containing keyed archives and parses
+    /// them with given [ParseOptions].
+    ///
+    /// See [KeyedArchive::find_embedded].
+    pub fn find_embedded_with_options(
+        plist: &PlistValue,
+        options: &ParseOptions,
+    ) -> Vec<(String, KeyedArchive)> {
+        let mut archives = Vec::new();
+        let mut path = String::new();
+        find_embedded(plist, options, &mut path, &mut archives);
+        archives
+    }
+}
+
+impl Data {
+    /// Parses data as a nested keyed archive with default [ParseOptions].
+    ///
+    /// A nested archive is always smaller than data it's stored in, so
+    /// archives nested into each other can't form a cycle. Use
+    /// [Data::as_keyed_archive_with_options] to limit their size.
+    pub fn as_keyed_archive(&self) -> Result<KeyedArchive, Error> {
+        self.as_keyed_archive_with_options(&ParseOptions::default())
+    }
+
+    /// Parses data as a nested keyed archive with given [ParseOptions].
+    pub fn as_keyed_archive_with_options(
+        &self,
+        options: &ParseOptions,
+    ) -> Result<KeyedArchive, Error> {
+        nested_archive(self.as_ref(), options)
+    }
+}
+
+impl Object {
+    /// Parses a data (or NSData) value with a given `key` as a nested keyed
+    /// archive with default [ParseOptions]. Apple often stores archives
+    /// this way (e.g. in UserDefaults blobs).
+    pub fn decode_nested_archive(&self, key: &str) -> Result<KeyedArchive, DeError> {
+        self.decode_nested_archive_with_options(key, &ParseOptions::default())
+    }
+
+    /// Parses a data (or NSData) value with a given `key` as a nested keyed
+    /// archive with given [ParseOptions].
+    pub fn decode_nested_archive_with_options(
+        &self,
+        key: &str,
+        options: &ParseOptions,
+    ) -> Result<KeyedArchive, DeError> {
+        nested_archive(self.get_bytes(key)?, options)
+            .map_err(|e| DeError::NestedArchive(Box::new(e)))
+    }
+}
+
+/// A data (or NSData) value is decoded as a nested keyed archive with
+/// default [ParseOptions] and [Limits](crate::Limits) of a [DecodeContext].
+impl Decodable for KeyedArchive {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError>
+    where
+        Self: Sized,
+    {
+        let options = ParseOptions::default().with_limits(*ctx.limits());
+        nested_archive(borrow_bytes(value, ctx.is_probing())?, &options)
+            .map_err(|e| DeError::NestedArchive(Box::new(e)))
+    }
+}
+
+/// Parses a nested archive checking its size against [Limits](crate::Limits)
+/// first, so a huge blob isn't even parsed.
+fn nested_archive(bytes: &[u8], options: &ParseOptions) -> Result<KeyedArchive, Error> {
+    if let Some(max) = options.limits().max_data_size
+        && bytes.len() > max
+    {
+        return Err(Error::LimitExceeded(format!(
+            "A nested archive is larger than {max} bytes"
+        )));
+    }
+    KeyedArchive::from_bytes_with_options(bytes, options)
+}
+
+fn find_embedded(
+    value: &PlistValue,
+    options: &ParseOptions,
+    path: &mut String,
+    archives: &mut Vec<(String, KeyedArchive)>,
+) {
+    let len = path.len();
+    match value {
+        PlistValue::Dictionary(dict) => {
+            for (key, value) in dict {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+                find_embedded(value, options, path, archives);
+                path.truncate(len);
+            }
+        }
+        PlistValue::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                let _ = write!(path, "[{index}]");
+                find_embedded(value, options, path, archives);
+                path.truncate(len);
+            }
+        }
+        PlistValue::Data(bytes) if looks_like_plist(bytes) => {
+            if let Ok(archive) = KeyedArchive::from_bytes_with_options(bytes, options) {
+                archives.push((path.clone(), archive));
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Checks if data starts like a binary or an XML plist, so parsing of
+/// other data (images, bookmarks, etc.) isn't even attempted.
+pub(crate) fn looks_like_plist(bytes: &[u8]) -> bool {
+    let trimmed = bytes.trim_ascii_start();
+    bytes.starts_with(b"bplist") || trimmed.starts_with(b"<?xml") || trimmed.starts_with(b"<plist")
+}
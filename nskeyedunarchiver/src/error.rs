@@ -1,3 +1,4 @@
+use crate::{ArchiveValue, UniqueId};
 use thiserror::Error;
 
 /// An error that can happen during parsing an archive.
@@ -7,9 +8,64 @@ pub enum Error {
     #[error(transparent)]
     PlistError(#[from] plist::Error),
 
+    /// Happens if reading from a stream fails.
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+
     /// Happens if an archive itself has an incorrent structure.
     #[error("Incorrect NSKeyedArchive format: {0}")]
     IncorrectFormat(String),
+
+    /// Happens if a field of an object (`from_uid`) refers to a uid outside
+    /// of `$objects` (`to_uid`), e.g. in a truncated archive. See
+    /// [ParseOptions::with_dangling_refs_as_null](crate::ParseOptions::with_dangling_refs_as_null).
+    #[error("Object (uid: {}) field `{key}` refers to a missing value (uid: {to_uid})", from_uid.get())]
+    DanglingReference {
+        from_uid: UniqueId,
+        key: String,
+        to_uid: u64,
+    },
+
+    /// Happens if a legacy `NSArchiver` archive (a typedstream) is given
+    /// instead of a keyed archive. It may be decoded with the
+    /// [typedstream](crate::typedstream) module.
+    #[error("Legacy NSArchiver archives (typedstreams) aren't keyed archives, use the `typedstream` module")]
+    UnsupportedLegacyArchiver,
+
+    /// Happens if a plist is serialized in a variant that can't be parsed,
+    /// like binary property lists 2.0 (`bplist16`) emitted by some newer OS
+    /// components. It contains a name of the variant.
+    #[error("Unsupported property list variant: {0}")]
+    UnsupportedPlistVariant(String),
+
+    /// Happens if a typedstream has an incorrect structure.
+    #[error("Incorrect typedstream format: {0}")]
+    IncorrectTypedStream(String),
+
+    /// Happens if bookmark data has an incorrect structure, see
+    /// [Bookmark](crate::bookmark::Bookmark).
+    #[error("Incorrect bookmark data: {0}")]
+    IncorrectBookmark(String),
+
+    /// Happens if an archive exceeds one of [Limits](crate::Limits).
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    /// Happens if an [ArchiveEditor](crate::ArchiveEditor) edit is invalid.
+    #[error("Invalid edit: {0}")]
+    InvalidEdit(String),
+
+    /// Happens if an archive has no `$top` entries at all.
+    #[error("The `$top` dictionary is empty")]
+    EmptyTop,
+
+    /// Happens if `$top` has no entry with a given key (usually `root`).
+    #[error("The `$top` dictionary has no `{0}` entry")]
+    MissingTopKey(String),
+
+    /// Happens if an archive value can't be decoded into a Rust type.
+    #[error(transparent)]
+    DecodeError(#[from] DeError),
 }
 
 /// An error that may happen during decoding an [Object](crate::Object).
@@ -28,20 +84,163 @@ pub enum DeError {
     ExpectedBoolean,
     #[error("Expected data")]
     ExpectedData,
+    #[error("Expected date")]
+    ExpectedDate,
     #[error("Expected object")]
     ExpectedObject,
     #[error("Expected null reference")]
     ExpectedNullRef,
     #[error("{0}: Missing object key `{1}`")]
     MissingObjectKey(String, String),
+    /// An object contains a key that isn't expected, see
+    /// `#[decodable(deny_unknown_fields)]`.
+    #[error("{0}: Unknown object key `{1}`")]
+    UnknownObjectKey(String, String),
+    /// An object has an unexpected class. Decodables of this crate return
+    /// a more detailed [DeError::ClassMismatch] instead.
     #[error("Expected class `{1}`, found `{0}`")]
     UnexpectedClass(String, String),
+    /// An object has none of expected classes.
+    #[error("{}", class_mismatch(expected, found, uid))]
+    ClassMismatch {
+        /// Classes a decodable accepts.
+        expected: Vec<String>,
+        /// A class chain of the object, from its class up to the root class.
+        /// It's empty for anonymous objects.
+        found: Vec<String>,
+        /// An id of the object.
+        uid: UniqueId,
+    },
+    /// A cheap version of [DeError::ClassMismatch] returned while
+    /// probing several types (see
+    /// [DecodeContext::try_decode](crate::DecodeContext::try_decode)).
+    #[error("Expected class `{0}`")]
+    ExpectedClass(&'static str),
+    /// A cheap version of [DeError::MissingObjectKey] returned while
+    /// probing several types.
+    #[error("Missing object key `{0}`")]
+    MissingKey(&'static str),
+    #[error(
+        "Circular reference to an object (uid: {}) is found. Use `Lazy<T>` to decode it",
+        .0.get()
+    )]
+    CircularReference(UniqueId),
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+    /// A user context required by a decodable isn't set, see
+    /// [DecodeContext::insert_user](crate::DecodeContext::insert_user).
+    #[error("Missing a decoding context of type `{0}`")]
+    MissingContext(String),
+    /// A data value can't be parsed as a nested keyed archive.
+    #[error("Unable to parse a nested archive: {0}")]
+    NestedArchive(#[source] Box<Error>),
+    /// An archive has no value with a given uid, see
+    /// [KeyedArchive::decode_value_as](crate::KeyedArchive::decode_value_as).
+    #[error("Missing a value with uid {}", .0.get())]
+    MissingValue(UniqueId),
+    /// A dictionary contains duplicate keys, see
+    /// [DecodeContext::set_duplicate_keys](crate::DecodeContext::set_duplicate_keys).
+    #[error("Dictionary contains duplicate keys")]
+    DuplicateKeys,
+    /// Errors of several failed attempts to decode a value, e.g. of every
+    /// variant of a derived enum. It's empty if errors weren't collected
+    /// while probing.
+    #[error("{}", multiple(.0))]
+    Multiple(Vec<DeError>),
+    /// An error with a location of a failed value: a path like
+    /// `root.NSAttributes[0].NSColor` and an id of a failed archive value.
+    #[error("{}: {source}", location(path, unique_id))]
+    Context {
+        path: String,
+        unique_id: Option<UniqueId>,
+        source: Box<DeError>,
+    },
+    /// An error of decoding an archive opened from a file.
+    #[error("{}: {source}", file.display())]
+    InFile {
+        file: std::path::PathBuf,
+        source: Box<DeError>,
+    },
+}
+
+impl DeError {
+    /// Creates a [DeError::ClassMismatch] for an archive value that has none
+    /// of `expected` classes.
+    pub fn class_mismatch(value: &ArchiveValue, expected: &[&str]) -> Self {
+        let found = match value.as_object() {
+            Some(obj) => obj.classes().to_vec(),
+            None => Vec::new(),
+        };
+        Self::ClassMismatch {
+            expected: expected.iter().map(|class| class.to_string()).collect(),
+            found,
+            uid: *value.unique_id(),
+        }
+    }
+
+    /// Returns the underlying error without a [DeError::Context] (and
+    /// [DeError::InFile]).
+    pub fn inner(&self) -> &DeError {
+        match self {
+            Self::Context { source, .. } | Self::InFile { source, .. } => source.inner(),
+            e => e,
+        }
+    }
+
+    /// Returns a path to a failed value if it's known.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::Context { path, .. } if !path.is_empty() => Some(path),
+            Self::InFile { source, .. } => source.path(),
+            _ => None,
+        }
+    }
+
+    /// Returns a path of a file an archive is opened from if it's known.
+    pub fn file(&self) -> Option<&std::path::Path> {
+        match self {
+            Self::InFile { file, .. } => Some(file),
+            _ => None,
+        }
+    }
+
+    /// Returns an id of a failed archive value if it's known.
+    pub fn unique_id(&self) -> Option<UniqueId> {
+        match self {
+            Self::Context { unique_id, .. } => *unique_id,
+            Self::InFile { source, .. } => source.unique_id(),
+            _ => None,
+        }
+    }
+}
+
+fn location(path: &str, unique_id: &Option<UniqueId>) -> String {
+    match (path.is_empty(), unique_id) {
+        (false, Some(id)) => format!("{path} (uid: {})", id.get()),
+        (false, None) => path.to_string(),
+        (true, Some(id)) => format!("uid: {}", id.get()),
+        (true, None) => String::new(),
+    }
+}
+
+fn class_mismatch(expected: &[String], found: &[String], uid: &UniqueId) -> String {
+    let expected: Vec<String> = expected.iter().map(|class| format!("`{class}`")).collect();
+    let found = if found.is_empty() {
+        "an anonymous object".to_string()
+    } else {
+        format!("`{}`", found.join(" : "))
+    };
+    format!(
+        "Expected class {}, found {found} (uid: {})",
+        expected.join(" or "),
+        uid.get()
+    )
 }
 
-#[doc(hidden)]
-pub fn error_beautifier<T>(result: Result<T, DeError>, class: impl std::fmt::Display, field: impl std::fmt::Display) -> Result<T, DeError> {
-    match result {
-        Ok(ok) => Ok(ok),
-        Err(e) => Err(DeError::Custom(format!("{class}->{field}: {e}")))
+fn multiple(errors: &[DeError]) -> String {
+    if errors.is_empty() {
+        return "Unable to decode a value".into();
     }
+    let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    errors.join("; ")
 }
@@ -0,0 +1,92 @@
+use crate::{Error, KeyedArchive, ParseOptions};
+use plist::Value as PlistValue;
+use std::io::{Read, Seek, SeekFrom};
+
+/// A number of bytes used to detect a [Format].
+const DETECT_LEN: u64 = 64;
+
+/// A serialization format of a plist.
+///
+/// An archive remembers a format it's parsed from (see
+/// [KeyedArchive::source_format]), so it may be written back in the same
+/// format (e.g. after editing it with [ArchiveEditor](crate::ArchiveEditor)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    Xml,
+    Binary,
+    /// An OpenStep (ASCII) plist. It may only be read.
+    Ascii,
+}
+
+impl Format {
+    /// Detects a format of a plist by its first bytes.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(b"bplist") {
+            return Self::Binary;
+        }
+        let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+        match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'<') => Self::Xml,
+            _ => Self::Ascii,
+        }
+    }
+
+    /// Detects a format of a plist at a given `start` of a reader.
+    pub(crate) fn detect_reader<R: Read + Seek>(reader: &mut R, start: u64) -> Option<Self> {
+        let mut header = Vec::new();
+        reader.seek(SeekFrom::Start(start)).ok()?;
+        reader.take(DETECT_LEN).read_to_end(&mut header).ok()?;
+        Some(Self::detect(&header))
+    }
+}
+
+/// Returns a name of a binary plist variant other than `bplist00` (like
+/// `bplist16`) if `bytes` start with one. Only `bplist00` can be parsed.
+pub(crate) fn unsupported_binary_variant(bytes: &[u8]) -> Option<String> {
+    let version = bytes.strip_prefix(b"bplist")?.get(..2)?;
+    if version == b"00" || !version.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    Some(format!("bplist{}", String::from_utf8_lossy(version)))
+}
+
+impl KeyedArchive {
+    /// Returns a format of a plist the archive is parsed from. It's [None]
+    /// if the archive is created from a [plist::Value].
+    pub fn source_format(&self) -> Option<Format> {
+        self.format
+    }
+
+    /// Parses a plist of a given format from bytes and creates
+    /// a [KeyedArchive] from it with default [ParseOptions]. Unlike
+    /// [KeyedArchive::from_bytes] a format isn't detected, so plists of
+    /// other formats are rejected.
+    pub fn from_bytes_with_format(bytes: &[u8], format: Format) -> Result<Self, Error> {
+        Self::from_bytes_with_format_and_options(bytes, format, &ParseOptions::default())
+    }
+
+    /// Parses a plist of a given format from bytes and creates
+    /// a [KeyedArchive] from it with given [ParseOptions].
+    pub fn from_bytes_with_format_and_options(
+        bytes: &[u8],
+        format: Format,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        let plist = match format {
+            Format::Xml => PlistValue::from_reader_xml(bytes)?,
+            Format::Ascii => PlistValue::from_reader_ascii(bytes)?,
+            Format::Binary => {
+                if Format::detect(bytes) != Format::Binary {
+                    return Err(Error::IncorrectFormat("Expected a binary plist".into()));
+                }
+                if let Some(variant) = unsupported_binary_variant(bytes) {
+                    return Err(Error::UnsupportedPlistVariant(variant));
+                }
+                PlistValue::from_reader(std::io::Cursor::new(bytes))?
+            }
+        };
+        let mut archive = Self::from_plist_with_options(plist, options)?;
+        archive.format = Some(format);
+        Ok(archive)
+    }
+}
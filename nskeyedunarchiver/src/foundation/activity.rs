@@ -0,0 +1,115 @@
+use super::{NSURL, expect_object};
+use crate::{DeError, Decodable, DecodeContext, DynamicValue, ObjectValue};
+use std::collections::BTreeMap;
+
+/// A decoded `NSUserActivity`.
+///
+/// Activities are archived with their property names as keys:
+/// `activityType`, `title`, `webpageURL`, `persistentIdentifier` and
+/// `userInfo`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NSUserActivity {
+    activity_type: String,
+    title: Option<String>,
+    webpage_url: Option<NSURL>,
+    persistent_identifier: Option<String>,
+    user_info: BTreeMap<String, DynamicValue>,
+}
+
+impl NSUserActivity {
+    /// Returns an activity type, like `com.example.app.viewing-note`.
+    pub fn activity_type(&self) -> &str {
+        &self.activity_type
+    }
+
+    /// Returns a user-visible title.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Returns a webpage to load in a browser if the activity can't be
+    /// continued in an app.
+    pub fn webpage_url(&self) -> Option<&NSURL> {
+        self.webpage_url.as_ref()
+    }
+
+    /// Returns an identifier used to delete the activity later.
+    pub fn persistent_identifier(&self) -> Option<&str> {
+        self.persistent_identifier.as_deref()
+    }
+
+    /// Returns app-specific state of the activity.
+    pub fn user_info(&self) -> &BTreeMap<String, DynamicValue> {
+        &self.user_info
+    }
+}
+
+impl Decodable for NSUserActivity {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(value, &["NSUserActivity"], ctx.is_probing())?;
+        Ok(Self {
+            activity_type: obj.decode_string("activityType")?,
+            title: obj.get_as("title")?,
+            webpage_url: obj.get_as("webpageURL")?,
+            persistent_identifier: obj.get_as("persistentIdentifier")?,
+            user_info: obj.get_as("userInfo")?.unwrap_or_default(),
+        })
+    }
+}
+
+/// A decoded `UIApplicationShortcutItem` (a Home Screen quick action).
+///
+/// Items are archived with their property names as keys: `type`,
+/// `localizedTitle`, `localizedSubtitle` and `userInfo`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct UIApplicationShortcutItem {
+    item_type: String,
+    localized_title: String,
+    localized_subtitle: Option<String>,
+    user_info: BTreeMap<String, DynamicValue>,
+}
+
+impl UIApplicationShortcutItem {
+    /// Returns an app-specific type of the action.
+    pub fn item_type(&self) -> &str {
+        &self.item_type
+    }
+
+    /// Returns a title displayed on the Home Screen.
+    pub fn localized_title(&self) -> &str {
+        &self.localized_title
+    }
+
+    /// Returns a subtitle displayed under the title.
+    pub fn localized_subtitle(&self) -> Option<&str> {
+        self.localized_subtitle.as_deref()
+    }
+
+    /// Returns app-specific information passed to the app.
+    pub fn user_info(&self) -> &BTreeMap<String, DynamicValue> {
+        &self.user_info
+    }
+}
+
+impl Decodable for UIApplicationShortcutItem {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(
+            value,
+            &[
+                "UIApplicationShortcutItem",
+                "UIMutableApplicationShortcutItem",
+            ],
+            ctx.is_probing(),
+        )?;
+        Ok(Self {
+            item_type: obj.decode_string("type")?,
+            localized_title: obj.decode_string("localizedTitle")?,
+            localized_subtitle: obj.get_as("localizedSubtitle")?,
+            user_info: obj.get_as("userInfo")?.unwrap_or_default(),
+        })
+    }
+}
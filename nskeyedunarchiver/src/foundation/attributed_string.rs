@@ -0,0 +1,169 @@
+use super::expect_object;
+use crate::decodable::borrow_bytes;
+use crate::{DeError, Decodable, DecodeContext, ObjectValue, ValueRef};
+use std::{collections::HashMap, ops::Range};
+
+/// A decoded `NSAttributedString` (or `NSMutableAttributedString`).
+///
+/// The string is split into runs, each of them has a range and a set of
+/// attributes `A`. By default attributes are decoded as a [HashMap] of
+/// undecoded values, but any [Decodable] type representing an `NSDictionary`
+/// may be used instead.
+///
+/// Like `NSRange`, run ranges are measured in UTF-16 code units. Use
+/// [NSAttributedString::substring] to get the text of a run.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NSAttributedString<A = HashMap<String, ValueRef>> {
+    string: String,
+    attributes: Vec<A>,
+    runs: Vec<(Range<usize>, usize)>,
+}
+
+impl<A> NSAttributedString<A> {
+    /// Returns the plain string.
+    pub fn string(&self) -> &str {
+        &self.string
+    }
+
+    /// Returns all distinct attribute sets. Several runs may share the same one.
+    pub fn attributes(&self) -> &[A] {
+        &self.attributes
+    }
+
+    /// Returns an iterator over runs: ranges (in UTF-16 code units) and
+    /// their attributes.
+    pub fn runs(&self) -> impl Iterator<Item = (Range<usize>, &A)> {
+        self.runs
+            .iter()
+            .map(|(range, index)| (range.clone(), &self.attributes[*index]))
+    }
+
+    /// Returns a part of the string within a given `range` of UTF-16 code units.
+    ///
+    /// Returns [None] if the range is out of bounds or splits a surrogate pair.
+    pub fn substring(&self, range: Range<usize>) -> Option<&str> {
+        let start = utf16_to_byte_offset(&self.string, range.start)?;
+        let end = utf16_to_byte_offset(&self.string, range.end)?;
+        self.string.get(start..end)
+    }
+}
+
+impl<A: Decodable> Decodable for NSAttributedString<A> {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(
+            value,
+            &["NSAttributedString", "NSMutableAttributedString"],
+            ctx.is_probing(),
+        )?;
+        let Some(string) = obj.as_map().get("NSString") else {
+            return Err(DeError::MissingObjectKey(
+                obj.class().into(),
+                "NSString".into(),
+            ));
+        };
+        let string: String = ctx.decode_field(string, "NSString")?;
+        let length = string.encode_utf16().count();
+
+        // A string without any attributes
+        let Some(raw_attributes) = obj.as_map().get("NSAttributes") else {
+            return Ok(Self {
+                string,
+                attributes: Vec::new(),
+                runs: Vec::new(),
+            });
+        };
+
+        // If there's only one run covering the whole string, NSAttributeInfo
+        // is omitted and NSAttributes is a single dictionary
+        let Some(attribute_info) = obj.as_map().get("NSAttributeInfo") else {
+            return Ok(Self {
+                string,
+                attributes: vec![ctx.decode_field(raw_attributes, "NSAttributes")?],
+                runs: vec![(0..length, 0)],
+            });
+        };
+
+        let attributes: Vec<A> = ctx.decode_field(raw_attributes, "NSAttributes")?;
+        let runs = decode_attribute_info(
+            borrow_bytes(attribute_info, ctx.is_probing())?,
+            attributes.len(),
+        )?;
+        let covered = runs.last().map(|(range, _)| range.end).unwrap_or(0);
+        if covered != length {
+            return Err(DeError::Custom(format!(
+                "NSAttributeInfo: runs cover {covered} characters, but the string has {length}"
+            )));
+        }
+
+        Ok(Self {
+            string,
+            attributes,
+            runs,
+        })
+    }
+}
+
+/// Decodes NSAttributeInfo data. It's a sequence of varint pairs:
+/// a run length and an index inside of the NSAttributes array.
+fn decode_attribute_info(
+    mut bytes: &[u8],
+    attributes_len: usize,
+) -> Result<Vec<(Range<usize>, usize)>, DeError> {
+    let mut runs = Vec::new();
+    let mut location: usize = 0;
+    while !bytes.is_empty() {
+        let length = read_varint(&mut bytes)?;
+        let index = read_varint(&mut bytes)?;
+        if index >= attributes_len {
+            return Err(DeError::Custom(format!(
+                "NSAttributeInfo: attributes index {index} is out of range"
+            )));
+        }
+        let Some(end) = location.checked_add(length) else {
+            return Err(DeError::Custom(
+                "NSAttributeInfo: run length overflow".into(),
+            ));
+        };
+        runs.push((location..end, index));
+        location = end;
+    }
+    Ok(runs)
+}
+
+/// Reads an unsigned little-endian base 128 integer.
+fn read_varint(bytes: &mut &[u8]) -> Result<usize, DeError> {
+    let mut result: usize = 0;
+    let mut shift = 0;
+    loop {
+        let Some((byte, rest)) = bytes.split_first() else {
+            return Err(DeError::Custom(
+                "NSAttributeInfo: unexpected end of data".into(),
+            ));
+        };
+        *bytes = rest;
+        if shift >= usize::BITS {
+            return Err(DeError::Custom("NSAttributeInfo: integer overflow".into()));
+        }
+        result |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+    }
+}
+
+fn utf16_to_byte_offset(s: &str, offset: usize) -> Option<usize> {
+    let mut units = 0;
+    for (index, c) in s.char_indices() {
+        if units == offset {
+            return Some(index);
+        }
+        units += c.len_utf16();
+        if units > offset {
+            return None;
+        }
+    }
+    (units == offset).then_some(s.len())
+}
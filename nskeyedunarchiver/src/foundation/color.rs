@@ -0,0 +1,274 @@
+use super::expect_object;
+use crate::{DeError, Decodable, DecodeContext, Object, ObjectValue};
+
+/// A color space of an archived color.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ColorSpace {
+    CalibratedRgb,
+    DeviceRgb,
+    CalibratedWhite,
+    DeviceWhite,
+    DeviceCmyk,
+    /// A named color from a color catalog (like `System`/`controlColor`).
+    Catalog {
+        catalog: String,
+        name: String,
+    },
+    Pattern,
+    /// A color defined by components of a custom color space.
+    Custom,
+    /// An unknown `NSColorSpace` value.
+    Unknown(i64),
+}
+
+/// Red, green, blue and alpha components in the `0.0..=1.0` range.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Rgba {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    pub alpha: f64,
+}
+
+/// A decoded `NSColor` (or `UIColor`).
+///
+/// AppKit archives colors in a number of ways depending on a color space:
+/// as C-strings of components (`NSRGB`, `NSWhite`, `NSCMYK`), as components
+/// of a custom color space (`NSComponents`, `NSCustomColorSpace`) or as
+/// catalog names with a fallback color. This type handles all of them and
+/// exposes normalized RGBA components with [NSColor::rgba].
+#[derive(Debug, PartialEq, Clone)]
+pub struct NSColor {
+    color_space: ColorSpace,
+    components: Vec<f64>,
+    custom_color_space_id: Option<i64>,
+    rgba: Option<Rgba>,
+}
+
+impl NSColor {
+    /// Returns a color space of the color.
+    pub fn color_space(&self) -> &ColorSpace {
+        &self.color_space
+    }
+
+    /// Returns components as they're archived: `NSComponents` if present,
+    /// otherwise components of a color space model (e.g. `NSRGB`).
+    pub fn components(&self) -> &[f64] {
+        &self.components
+    }
+
+    /// Returns an `NSID` of a custom color space if the color has one.
+    pub fn custom_color_space_id(&self) -> Option<i64> {
+        self.custom_color_space_id
+    }
+
+    /// Returns normalized RGBA components or [None] if the color can't be
+    /// represented as RGBA (e.g. a pattern).
+    pub fn rgba(&self) -> Option<Rgba> {
+        self.rgba
+    }
+}
+
+impl Rgba {
+    fn from_rgb(c: &[f64]) -> Option<Self> {
+        match c {
+            [red, green, blue] => Some(Self {
+                red: *red,
+                green: *green,
+                blue: *blue,
+                alpha: 1.0,
+            }),
+            [red, green, blue, alpha] => Some(Self {
+                red: *red,
+                green: *green,
+                blue: *blue,
+                alpha: *alpha,
+            }),
+            _ => None,
+        }
+    }
+
+    fn from_white(c: &[f64]) -> Option<Self> {
+        let (white, alpha) = match c {
+            [white] => (*white, 1.0),
+            [white, alpha] => (*white, *alpha),
+            _ => return None,
+        };
+        Some(Self {
+            red: white,
+            green: white,
+            blue: white,
+            alpha,
+        })
+    }
+
+    fn from_cmyk(c: &[f64]) -> Option<Self> {
+        let (cyan, magenta, yellow, black, alpha) = match c {
+            [c, m, y, k] => (*c, *m, *y, *k, 1.0),
+            [c, m, y, k, a] => (*c, *m, *y, *k, *a),
+            _ => return None,
+        };
+        Some(Self {
+            red: (1.0 - cyan) * (1.0 - black),
+            green: (1.0 - magenta) * (1.0 - black),
+            blue: (1.0 - yellow) * (1.0 - black),
+            alpha,
+        })
+    }
+}
+
+/// Parses a C-string of space-separated components, like `0.5 0 1\0`.
+fn parse_components(obj: &Object, key: &str) -> Result<Option<Vec<f64>>, DeError> {
+    if !obj.contains_key(key) {
+        return Ok(None);
+    }
+    let bytes = obj.get_bytes(key)?;
+    let bytes = bytes.split(|b| *b == 0).next().unwrap_or_default();
+    let s = std::str::from_utf8(bytes)
+        .map_err(|e| DeError::Custom(format!("{}: Incorrect `{key}` string: {e}", obj.class())))?;
+    let mut components = Vec::new();
+    for component in s.split_whitespace() {
+        components.push(component.parse().map_err(|_| {
+            DeError::Custom(format!(
+                "{}: Incorrect `{key}` component `{component}`",
+                obj.class()
+            ))
+        })?);
+    }
+    Ok(Some(components))
+}
+
+fn missing_key(obj: &Object, key: &str) -> DeError {
+    DeError::MissingObjectKey(obj.class().into(), key.into())
+}
+
+fn decode_uicolor(obj: &Object) -> Result<NSColor, DeError> {
+    let map = obj.as_map();
+    let get =
+        |key: &str| -> Result<Option<f64>, DeError> { map.get(key).map(f64::decode).transpose() };
+    let alpha = get("UIAlpha")?.unwrap_or(1.0);
+    let (color_space, components) = if let Some(white) = get("UIWhite")? {
+        (ColorSpace::DeviceWhite, vec![white, alpha])
+    } else {
+        let red = get("UIRed")?.ok_or_else(|| missing_key(obj, "UIRed"))?;
+        let green = get("UIGreen")?.ok_or_else(|| missing_key(obj, "UIGreen"))?;
+        let blue = get("UIBlue")?.ok_or_else(|| missing_key(obj, "UIBlue"))?;
+        (ColorSpace::DeviceRgb, vec![red, green, blue, alpha])
+    };
+    let rgba = match color_space {
+        ColorSpace::DeviceWhite => Rgba::from_white(&components),
+        _ => Rgba::from_rgb(&components),
+    };
+    Ok(NSColor {
+        color_space,
+        components,
+        custom_color_space_id: None,
+        rgba,
+    })
+}
+
+impl Decodable for NSColor {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(value, &["NSColor", "UIColor"], ctx.is_probing())?;
+        let map = obj.as_map();
+        if !map.contains_key("NSColorSpace")
+            && (map.contains_key("UIRed") || map.contains_key("UIWhite"))
+        {
+            return decode_uicolor(obj);
+        }
+
+        let space_id = map
+            .get("NSColorSpace")
+            .ok_or_else(|| missing_key(obj, "NSColorSpace"))?;
+        let space_id: i64 = ctx.decode_field(space_id, "NSColorSpace")?;
+
+        let rgb = parse_components(obj, "NSRGB")?;
+        let white = parse_components(obj, "NSWhite")?;
+        let cmyk = parse_components(obj, "NSCMYK")?;
+        let custom_components = parse_components(obj, "NSComponents")?;
+
+        let custom_color_space_id = match map.get("NSCustomColorSpace") {
+            Some(ObjectValue::Ref(space)) => space
+                .as_object()
+                .and_then(|space| space.as_map().get("NSID"))
+                .map(|id| ctx.decode_field(id, "NSID"))
+                .transpose()?,
+            _ => None,
+        };
+
+        let (color_space, model_components, rgba) = match space_id {
+            1 | 2 => {
+                let rgb = rgb.ok_or_else(|| missing_key(obj, "NSRGB"))?;
+                let rgba = Rgba::from_rgb(&rgb);
+                let space = if space_id == 1 {
+                    ColorSpace::CalibratedRgb
+                } else {
+                    ColorSpace::DeviceRgb
+                };
+                (space, rgb, rgba)
+            }
+            3 | 4 => {
+                let white = white.ok_or_else(|| missing_key(obj, "NSWhite"))?;
+                let rgba = Rgba::from_white(&white);
+                let space = if space_id == 3 {
+                    ColorSpace::CalibratedWhite
+                } else {
+                    ColorSpace::DeviceWhite
+                };
+                (space, white, rgba)
+            }
+            5 => {
+                let cmyk = cmyk.ok_or_else(|| missing_key(obj, "NSCMYK"))?;
+                let rgba = Rgba::from_cmyk(&cmyk);
+                (ColorSpace::DeviceCmyk, cmyk, rgba)
+            }
+            6 => {
+                let catalog = map
+                    .get("NSCatalogName")
+                    .ok_or_else(|| missing_key(obj, "NSCatalogName"))?;
+                let name = map
+                    .get("NSColorName")
+                    .ok_or_else(|| missing_key(obj, "NSColorName"))?;
+                // A catalog color may refer to itself, so the fallback is
+                // decoded with the context to catch cycles
+                let fallback: Option<NSColor> = map
+                    .get("NSColor")
+                    .map(|color| ctx.decode_field(color, "NSColor"))
+                    .transpose()?;
+                let space = ColorSpace::Catalog {
+                    catalog: ctx.decode_field(catalog, "NSCatalogName")?,
+                    name: ctx.decode_field(name, "NSColorName")?,
+                };
+                let rgba = fallback.as_ref().and_then(|c| c.rgba);
+                let components = fallback.map(|c| c.components).unwrap_or_default();
+                (space, components, rgba)
+            }
+            7 => (ColorSpace::Pattern, Vec::new(), None),
+            10 => {
+                let components = custom_components
+                    .clone()
+                    .ok_or_else(|| missing_key(obj, "NSComponents"))?;
+                // Prefer fallback model components, they're always in a known space
+                let rgba = rgb
+                    .as_deref()
+                    .and_then(Rgba::from_rgb)
+                    .or_else(|| white.as_deref().and_then(Rgba::from_white))
+                    .or_else(|| match components.len() {
+                        1 | 2 => Rgba::from_white(&components),
+                        _ => Rgba::from_rgb(&components),
+                    });
+                (ColorSpace::Custom, components, rgba)
+            }
+            _ => (ColorSpace::Unknown(space_id), Vec::new(), None),
+        };
+
+        Ok(Self {
+            color_space,
+            components: custom_components.unwrap_or(model_components),
+            custom_color_space_id,
+            rgba,
+        })
+    }
+}
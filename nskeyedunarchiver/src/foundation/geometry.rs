@@ -0,0 +1,394 @@
+use super::expect_object;
+use crate::{DeError, Decodable, DecodeContext, Integer, Object, ObjectValue};
+use std::str::FromStr;
+
+/// A point decoded from an `NSPoint` (`CGPoint`).
+///
+/// It may be archived as a string like `{3, 4}`, as an `NSValue` with
+/// `NS.pointval` or as an object with `x` and `y` keys.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct NSPoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// A size decoded from an `NSSize` (`CGSize`).
+///
+/// It may be archived as a string like `{3, 4}`, as an `NSValue` with
+/// `NS.sizeval` or as an object with `width` and `height` keys.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct NSSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// A rectangle decoded from an `NSRect` (`CGRect`).
+///
+/// It may be archived as a string like `{{1, 2}, {3, 4}}`, as an `NSValue`
+/// with `NS.rectval` or as an object with `x`, `y`, `width` and `height` keys.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct NSRect {
+    pub origin: NSPoint,
+    pub size: NSSize,
+}
+
+/// A range decoded from an `NSRange`.
+///
+/// It may be archived as a string like `{3, 4}`, as an `NSValue` with
+/// `NS.rangeval.location` and `NS.rangeval.length` keys or as an object with
+/// `location` and `length` keys.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct NSRange {
+    pub location: u64,
+    pub length: u64,
+}
+
+/// Edge insets decoded from an `NSEdgeInsets` (`UIEdgeInsets`).
+///
+/// It may be archived as a string like `{1, 2, 3, 4}` (top, left, bottom,
+/// right), as an `NSValue` with `NS.edgeval.*` keys or as an object with
+/// `top`, `left`, `bottom` and `right` keys.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct NSEdgeInsets {
+    pub top: f64,
+    pub left: f64,
+    pub bottom: f64,
+    pub right: f64,
+}
+
+/// A geometry value boxed into an `NSValue`.
+///
+/// The variant is picked by the `NS.special` code of an archived value,
+/// other codes (e.g. values holding pointers or custom structs) fail to
+/// decode.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NSValue {
+    /// `NS.special = 1`, `NS.pointval`
+    Point(NSPoint),
+    /// `NS.special = 2`, `NS.sizeval`
+    Size(NSSize),
+    /// `NS.special = 3`, `NS.rectval`
+    Rect(NSRect),
+    /// `NS.special = 4`, `NS.rangeval.location` and `NS.rangeval.length`
+    Range(NSRange),
+    /// `NS.special = 12`, `NS.edgeval.top`, `NS.edgeval.left`,
+    /// `NS.edgeval.bottom` and `NS.edgeval.right`
+    EdgeInsets(NSEdgeInsets),
+}
+
+impl NSValue {
+    /// Returns the `NS.special` code of the variant.
+    pub fn special(&self) -> u64 {
+        match self {
+            Self::Point(_) => 1,
+            Self::Size(_) => 2,
+            Self::Rect(_) => 3,
+            Self::Range(_) => 4,
+            Self::EdgeInsets(_) => 12,
+        }
+    }
+}
+
+/// A parsed struct string, like `{{1, 2}, {3, 4}}`.
+enum StructNode {
+    Number(f64),
+    Struct(Vec<StructNode>),
+}
+
+impl StructNode {
+    /// Geometry structs are nested two levels deep at most, deeper strings
+    /// are rejected instead of exhausting the stack.
+    const MAX_DEPTH: usize = 4;
+
+    fn parse(s: &str) -> Option<Self> {
+        let (node, rest) = Self::parse_node(s.trim(), 0)?;
+        rest.trim().is_empty().then_some(node)
+    }
+
+    fn parse_node(s: &str, depth: usize) -> Option<(Self, &str)> {
+        let Some(mut s) = s.strip_prefix('{') else {
+            let end = s.find([',', '}']).unwrap_or(s.len());
+            let number = s[..end].trim().parse().ok()?;
+            return Some((Self::Number(number), &s[end..]));
+        };
+        if depth >= Self::MAX_DEPTH {
+            return None;
+        }
+        let mut members = Vec::new();
+        loop {
+            let (member, rest) = Self::parse_node(s.trim_start(), depth + 1)?;
+            members.push(member);
+            let rest = rest.trim_start();
+            if let Some(rest) = rest.strip_prefix(',') {
+                s = rest;
+            } else {
+                return Some((Self::Struct(members), rest.strip_prefix('}')?));
+            }
+        }
+    }
+
+    fn as_pair(&self) -> Option<(f64, f64)> {
+        match self {
+            Self::Struct(members) => match members.as_slice() {
+                [Self::Number(a), Self::Number(b)] => Some((*a, *b)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn as_quad(&self) -> Option<[f64; 4]> {
+        match self {
+            Self::Struct(members) => match members.as_slice() {
+                [
+                    Self::Number(a),
+                    Self::Number(b),
+                    Self::Number(c),
+                    Self::Number(d),
+                ] => Some([*a, *b, *c, *d]),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn as_pair_of_pairs(&self) -> Option<((f64, f64), (f64, f64))> {
+        match self {
+            Self::Struct(members) => match members.as_slice() {
+                [a, b] => Some((a.as_pair()?, b.as_pair()?)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+fn parse_error(type_name: &str, s: &str) -> DeError {
+    DeError::Custom(format!("Unable to parse {type_name} from `{s}`"))
+}
+
+impl FromStr for NSPoint {
+    type Err = DeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (x, y) = StructNode::parse(s)
+            .and_then(|node| node.as_pair())
+            .ok_or_else(|| parse_error("NSPoint", s))?;
+        Ok(Self { x, y })
+    }
+}
+
+impl FromStr for NSSize {
+    type Err = DeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = StructNode::parse(s)
+            .and_then(|node| node.as_pair())
+            .ok_or_else(|| parse_error("NSSize", s))?;
+        Ok(Self { width, height })
+    }
+}
+
+impl FromStr for NSRect {
+    type Err = DeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ((x, y), (width, height)) = StructNode::parse(s)
+            .and_then(|node| node.as_pair_of_pairs())
+            .ok_or_else(|| parse_error("NSRect", s))?;
+        Ok(Self {
+            origin: NSPoint { x, y },
+            size: NSSize { width, height },
+        })
+    }
+}
+
+impl FromStr for NSRange {
+    type Err = DeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (location, length) = StructNode::parse(s)
+            .and_then(|node| node.as_pair())
+            .filter(|(a, b)| *a >= 0.0 && *b >= 0.0 && a.fract() == 0.0 && b.fract() == 0.0)
+            .ok_or_else(|| parse_error("NSRange", s))?;
+        Ok(Self {
+            location: location as u64,
+            length: length as u64,
+        })
+    }
+}
+
+impl FromStr for NSEdgeInsets {
+    type Err = DeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [top, left, bottom, right] = StructNode::parse(s)
+            .and_then(|node| node.as_quad())
+            .ok_or_else(|| parse_error("NSEdgeInsets", s))?;
+        Ok(Self {
+            top,
+            left,
+            bottom,
+            right,
+        })
+    }
+}
+
+/// Decodes a number that may be archived either as a real or as an integer.
+fn decode_number(obj: &Object, key: &str, ctx: &mut DecodeContext) -> Result<f64, DeError> {
+    let Some(value) = obj.as_map().get(key) else {
+        return Err(DeError::MissingObjectKey(obj.class().into(), key.into()));
+    };
+    if let Some(integer) = ctx.try_decode::<Integer>(value) {
+        return Ok(match integer.as_signed() {
+            Some(v) => v as f64,
+            None => integer.as_unsigned().unwrap_or_default() as f64,
+        });
+    }
+    ctx.decode_field(value, key)
+}
+
+/// Decodes a geometry value that is either a struct string or an object.
+///
+/// `nsvalue_key` is a key of a struct string inside of an `NSValue` (if any),
+/// `from_object` decodes any other object with plain keys.
+fn decode_geometry<T: FromStr<Err = DeError>>(
+    value: &ObjectValue,
+    ctx: &mut DecodeContext,
+    nsvalue_key: Option<&str>,
+    from_object: impl FnOnce(&Object, &mut DecodeContext) -> Result<T, DeError>,
+) -> Result<T, DeError> {
+    if let Some(s) = ctx.try_decode::<String>(value) {
+        return s.parse();
+    }
+    let ObjectValue::Ref(value) = value else {
+        return Err(DeError::ExpectedObject);
+    };
+    let obj = value.as_object().ok_or(DeError::ExpectedObject)?;
+    if obj.matches_class("NSValue")
+        && let Some(key) = nsvalue_key
+        && let Some(s) = obj.as_map().get(key)
+    {
+        return ctx.decode_field::<String>(s, key)?.parse();
+    }
+    from_object(obj, ctx)
+}
+
+impl Decodable for NSPoint {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        decode_geometry(value, ctx, Some("NS.pointval"), |obj, ctx| {
+            Ok(Self {
+                x: decode_number(obj, "x", ctx)?,
+                y: decode_number(obj, "y", ctx)?,
+            })
+        })
+    }
+}
+
+impl Decodable for NSSize {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        decode_geometry(value, ctx, Some("NS.sizeval"), |obj, ctx| {
+            Ok(Self {
+                width: decode_number(obj, "width", ctx)?,
+                height: decode_number(obj, "height", ctx)?,
+            })
+        })
+    }
+}
+
+impl Decodable for NSRect {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        decode_geometry(value, ctx, Some("NS.rectval"), |obj, ctx| {
+            Ok(Self {
+                origin: NSPoint {
+                    x: decode_number(obj, "x", ctx)?,
+                    y: decode_number(obj, "y", ctx)?,
+                },
+                size: NSSize {
+                    width: decode_number(obj, "width", ctx)?,
+                    height: decode_number(obj, "height", ctx)?,
+                },
+            })
+        })
+    }
+}
+
+impl Decodable for NSRange {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        decode_geometry(value, ctx, Some("NS.rangeval"), |obj, ctx| {
+            let (location_key, length_key) = if obj.contains_key("NS.rangeval.location") {
+                ("NS.rangeval.location", "NS.rangeval.length")
+            } else {
+                ("location", "length")
+            };
+            let Some(location) = obj.as_map().get(location_key) else {
+                return Err(DeError::MissingObjectKey(
+                    obj.class().into(),
+                    location_key.into(),
+                ));
+            };
+            let Some(length) = obj.as_map().get(length_key) else {
+                return Err(DeError::MissingObjectKey(
+                    obj.class().into(),
+                    length_key.into(),
+                ));
+            };
+            Ok(Self {
+                location: ctx.decode_field(location, location_key)?,
+                length: ctx.decode_field(length, length_key)?,
+            })
+        })
+    }
+}
+
+impl Decodable for NSEdgeInsets {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        decode_geometry(value, ctx, None, |obj, ctx| {
+            let prefix = if obj.contains_key("NS.edgeval.top") {
+                "NS.edgeval."
+            } else {
+                ""
+            };
+            Ok(Self {
+                top: decode_number(obj, &format!("{prefix}top"), ctx)?,
+                left: decode_number(obj, &format!("{prefix}left"), ctx)?,
+                bottom: decode_number(obj, &format!("{prefix}bottom"), ctx)?,
+                right: decode_number(obj, &format!("{prefix}right"), ctx)?,
+            })
+        })
+    }
+}
+
+impl Decodable for NSValue {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(value, &["NSValue"], ctx.is_probing())?;
+        let Some(special) = obj.as_map().get("NS.special") else {
+            return Err(DeError::MissingObjectKey(
+                obj.class().into(),
+                "NS.special".into(),
+            ));
+        };
+        match ctx.decode_field::<u64>(special, "NS.special")? {
+            1 => ctx.decode(value).map(Self::Point),
+            2 => ctx.decode(value).map(Self::Size),
+            3 => ctx.decode(value).map(Self::Rect),
+            4 => ctx.decode(value).map(Self::Range),
+            12 => ctx.decode(value).map(Self::EdgeInsets),
+            code => Err(DeError::Custom(format!(
+                "Unsupported NSValue special type {code}"
+            ))),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use super::expect_object;
+use crate::{Data, DeError, Decodable, DecodeContext, ObjectValue};
+
+/// A decoded `NSLocale`. Only its identifier (`NS.identifier`) is archived.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct NSLocale {
+    identifier: String,
+}
+
+impl NSLocale {
+    /// Returns a locale identifier, like `en_US`.
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+}
+
+impl Decodable for NSLocale {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(value, &["NSLocale", "__NSCFLocale"], ctx.is_probing())?;
+        Ok(Self {
+            identifier: obj.decode_string("NS.identifier")?,
+        })
+    }
+}
+
+/// A decoded `NSTimeZone`.
+///
+/// A time zone is archived as its name (`NS.name`) and usually its rules
+/// (`NS.data`) in the TZif format of the zoneinfo database.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct NSTimeZone {
+    name: String,
+    data: Option<Data>,
+}
+
+impl NSTimeZone {
+    /// Returns a name of the time zone, like `Europe/Berlin`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns TZif data of the time zone if it's archived.
+    pub fn data(&self) -> Option<&[u8]> {
+        self.data.as_ref().map(AsRef::as_ref)
+    }
+}
+
+impl Decodable for NSTimeZone {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(
+            value,
+            &["NSTimeZone", "__NSTimeZone", "NSLocalTimeZone"],
+            ctx.is_probing(),
+        )?;
+        Ok(Self {
+            name: obj.decode_string("NS.name")?,
+            data: obj.get_as("NS.data")?,
+        })
+    }
+}
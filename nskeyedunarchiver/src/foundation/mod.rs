@@ -0,0 +1,44 @@
+//! Decoders for commonly archived Foundation classes.
+
+mod activity;
+mod attributed_string;
+mod color;
+mod geometry;
+mod locale;
+mod predicate;
+mod sort_descriptor;
+mod text_attachment;
+mod url;
+mod url_request;
+
+pub use activity::*;
+pub use attributed_string::*;
+pub use color::*;
+pub use geometry::*;
+pub use locale::*;
+pub use predicate::*;
+pub use sort_descriptor::*;
+pub use text_attachment::*;
+pub use url::*;
+pub use url_request::*;
+
+use crate::decodable::class_mismatch;
+use crate::{DeError, Object, ObjectValue};
+
+/// Returns an [Object] behind a value reference if its class is one of
+/// `classes` or a [DeError] otherwise. While probing the error is a cheap
+/// [DeError::ExpectedClass].
+pub(crate) fn expect_object<'a>(
+    value: &'a ObjectValue,
+    classes: &'static [&'static str],
+    probing: bool,
+) -> Result<&'a Object, DeError> {
+    let ObjectValue::Ref(value) = value else {
+        return Err(DeError::ExpectedObject);
+    };
+    let obj = value.as_object().ok_or(DeError::ExpectedObject)?;
+    if !classes.iter().any(|class| obj.matches_class(class)) {
+        return Err(class_mismatch(value, classes, probing));
+    }
+    Ok(obj)
+}
@@ -0,0 +1,412 @@
+use super::expect_object;
+use crate::{DeError, Decodable, DecodeContext, Object, ObjectValue};
+use std::fmt::Display;
+
+/// A kind of an [NSPredicate::Compound] predicate.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CompoundKind {
+    Not,
+    And,
+    Or,
+}
+
+/// An operator of an [NSPredicate::Comparison] predicate
+/// (`NSPredicateOperatorType`).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ComparisonOperator {
+    LessThan,
+    LessThanOrEqualTo,
+    GreaterThan,
+    GreaterThanOrEqualTo,
+    EqualTo,
+    NotEqualTo,
+    Matches,
+    Like,
+    BeginsWith,
+    EndsWith,
+    In,
+    CustomSelector,
+    Contains,
+    Between,
+    /// An unknown operator type.
+    Unknown(i64),
+}
+
+/// A modifier of an [NSPredicate::Comparison] predicate
+/// (`NSComparisonPredicateModifier`).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ComparisonModifier {
+    Direct,
+    All,
+    Any,
+    /// An unknown modifier.
+    Unknown(i64),
+}
+
+/// A decoded `NSPredicate`.
+///
+/// Predicates are archived as trees of `NSCompoundPredicate` and
+/// `NSComparisonPredicate` objects. Its [Display] implementation produces
+/// a predicate format string, like `name ==[c] "Notes" AND count > 2`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum NSPredicate {
+    True,
+    False,
+    Comparison {
+        left: NSExpression,
+        operator: ComparisonOperator,
+        right: NSExpression,
+        modifier: ComparisonModifier,
+        /// `NSComparisonPredicateOptions`, a combination of
+        /// [NSPredicate::CASE_INSENSITIVE], [NSPredicate::DIACRITIC_INSENSITIVE]
+        /// and [NSPredicate::NORMALIZED].
+        options: u64,
+        /// A selector of a [ComparisonOperator::CustomSelector] operator.
+        selector: Option<String>,
+    },
+    Compound {
+        kind: CompoundKind,
+        subpredicates: Vec<NSPredicate>,
+    },
+}
+
+/// A decoded `NSExpression` of a predicate.
+#[derive(Debug, PartialEq, Clone)]
+pub enum NSExpression {
+    /// A constant value (`NSConstantValueExpression`). It's kept undecoded,
+    /// since it may be of any type.
+    Constant(ObjectValue),
+    /// `SELF`.
+    EvaluatedObject,
+    /// A variable, like `$name`.
+    Variable(String),
+    /// A key path, like `address.city`.
+    KeyPath(String),
+    /// A function, like `count:` applied to an operand.
+    Function {
+        selector: String,
+        operand: Box<NSExpression>,
+        arguments: Vec<NSExpression>,
+    },
+    /// A collection of expressions, like `{1, 2}`.
+    Aggregate(Vec<NSExpression>),
+    /// Any other expression.
+    Other { class: String },
+}
+
+impl NSPredicate {
+    /// A case insensitive comparison option (`[c]`).
+    pub const CASE_INSENSITIVE: u64 = 1;
+    /// A diacritic insensitive comparison option (`[d]`).
+    pub const DIACRITIC_INSENSITIVE: u64 = 2;
+    /// A normalized comparison option (`[n]`).
+    pub const NORMALIZED: u64 = 8;
+}
+
+impl CompoundKind {
+    fn from_raw(value: i64) -> Result<Self, DeError> {
+        match value {
+            0 => Ok(Self::Not),
+            1 => Ok(Self::And),
+            2 => Ok(Self::Or),
+            _ => Err(DeError::Custom(format!(
+                "NSCompoundPredicate: unknown predicate type {value}"
+            ))),
+        }
+    }
+}
+
+impl ComparisonOperator {
+    fn from_raw(value: i64) -> Self {
+        match value {
+            0 => Self::LessThan,
+            1 => Self::LessThanOrEqualTo,
+            2 => Self::GreaterThan,
+            3 => Self::GreaterThanOrEqualTo,
+            4 => Self::EqualTo,
+            5 => Self::NotEqualTo,
+            6 => Self::Matches,
+            7 => Self::Like,
+            8 => Self::BeginsWith,
+            9 => Self::EndsWith,
+            10 => Self::In,
+            11 => Self::CustomSelector,
+            99 => Self::Contains,
+            100 => Self::Between,
+            _ => Self::Unknown(value),
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Self::LessThan => "<",
+            Self::LessThanOrEqualTo => "<=",
+            Self::GreaterThan => ">",
+            Self::GreaterThanOrEqualTo => ">=",
+            Self::EqualTo => "==",
+            Self::NotEqualTo => "!=",
+            Self::Matches => "MATCHES",
+            Self::Like => "LIKE",
+            Self::BeginsWith => "BEGINSWITH",
+            Self::EndsWith => "ENDSWITH",
+            Self::In => "IN",
+            Self::CustomSelector => "",
+            Self::Contains => "CONTAINS",
+            Self::Between => "BETWEEN",
+            Self::Unknown(_) => "?",
+        }
+    }
+}
+
+impl ComparisonModifier {
+    fn from_raw(value: i64) -> Self {
+        match value {
+            0 => Self::Direct,
+            1 => Self::All,
+            2 => Self::Any,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+fn required<'a>(obj: &'a Object, key: &str) -> Result<&'a ObjectValue, DeError> {
+    obj.as_map()
+        .get(key)
+        .ok_or_else(|| DeError::MissingObjectKey(obj.class().into(), key.into()))
+}
+
+impl Decodable for NSPredicate {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(
+            value,
+            &[
+                "NSComparisonPredicate",
+                "NSCompoundPredicate",
+                "NSTruePredicate",
+                "NSFalsePredicate",
+            ],
+            ctx.is_probing(),
+        )?;
+        match obj.class() {
+            "NSTruePredicate" => Ok(Self::True),
+            "NSFalsePredicate" => Ok(Self::False),
+            "NSCompoundPredicate" => {
+                let kind = CompoundKind::from_raw(i64::decode(required(
+                    obj,
+                    "NSCompoundPredicateType",
+                )?)?)?;
+                let subpredicates =
+                    ctx.decode_field(required(obj, "NSSubpredicates")?, "NSSubpredicates")?;
+                Ok(Self::Compound {
+                    kind,
+                    subpredicates,
+                })
+            }
+            _ => {
+                let left =
+                    ctx.decode_field(required(obj, "NSLeftExpression")?, "NSLeftExpression")?;
+                let right =
+                    ctx.decode_field(required(obj, "NSRightExpression")?, "NSRightExpression")?;
+                let ObjectValue::Ref(operator) = required(obj, "NSPredicateOperator")? else {
+                    return Err(DeError::ExpectedObject);
+                };
+                let operator = operator.as_object().ok_or(DeError::ExpectedObject)?;
+                Ok(Self::Comparison {
+                    left,
+                    operator: ComparisonOperator::from_raw(i64::decode(required(
+                        operator,
+                        "NSOperatorType",
+                    )?)?),
+                    right,
+                    modifier: ComparisonModifier::from_raw(
+                        operator.get_as("NSModifier")?.unwrap_or(0),
+                    ),
+                    options: operator.get_as::<i64>("NSFlags")?.unwrap_or(0) as u64,
+                    selector: operator.get_as("NSSelector")?,
+                })
+            }
+        }
+    }
+}
+
+impl Decodable for NSExpression {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let ObjectValue::Ref(value) = value else {
+            return Err(DeError::ExpectedObject);
+        };
+        let obj = value.as_object().ok_or(DeError::ExpectedObject)?;
+        let expression = match obj.class() {
+            "NSConstantValueExpression" => match obj.as_map().get("NSConstantValue") {
+                Some(constant) => Self::Constant(constant.clone()),
+                None => Self::Constant(ObjectValue::NullRef),
+            },
+            "NSSelfExpression" => Self::EvaluatedObject,
+            "NSVariableExpression" => Self::Variable(obj.decode_string("NSVariable")?),
+            "NSKeyPathSpecifierExpression" => Self::KeyPath(obj.decode_string("NSKeyPath")?),
+            "NSAggregateExpression" => {
+                Self::Aggregate(ctx.decode_field(required(obj, "NSCollection")?, "NSCollection")?)
+            }
+            "NSFunctionExpression" | "NSKeyPathExpression" => {
+                let selector = obj.decode_string("NSSelectorName")?;
+                let operand: Self = ctx.decode_field(required(obj, "NSOperand")?, "NSOperand")?;
+                let arguments: Vec<Self> = match obj.as_map().get("NSArguments") {
+                    Some(arguments) => ctx.decode_field(arguments, "NSArguments")?,
+                    None => Vec::new(),
+                };
+                // A key path is archived as `valueForKeyPath:` applied to `SELF`
+                match (selector.as_str(), &operand, arguments.as_slice()) {
+                    ("valueForKeyPath:", Self::EvaluatedObject, [Self::KeyPath(path)]) => {
+                        Self::KeyPath(path.clone())
+                    }
+                    ("valueForKeyPath:", Self::EvaluatedObject, [Self::Constant(path)]) => {
+                        Self::KeyPath(String::decode(path)?)
+                    }
+                    _ => Self::Function {
+                        selector,
+                        operand: Box::new(operand),
+                        arguments,
+                    },
+                }
+            }
+            class => Self::Other {
+                class: class.into(),
+            },
+        };
+        Ok(expression)
+    }
+}
+
+impl Display for NSExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Constant(value) => write_constant(f, value),
+            Self::EvaluatedObject => write!(f, "SELF"),
+            Self::Variable(name) => write!(f, "${name}"),
+            Self::KeyPath(path) => write!(f, "{path}"),
+            Self::Function {
+                selector,
+                operand,
+                arguments,
+            } => {
+                write!(f, "FUNCTION({operand}, \"{selector}\"")?;
+                for argument in arguments {
+                    write!(f, ", {argument}")?;
+                }
+                write!(f, ")")
+            }
+            Self::Aggregate(items) => {
+                write!(f, "{{")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "}}")
+            }
+            Self::Other { class } => write!(f, "<{class}>"),
+        }
+    }
+}
+
+fn write_constant(f: &mut std::fmt::Formatter<'_>, value: &ObjectValue) -> std::fmt::Result {
+    if let Ok(s) = String::decode(value) {
+        return write!(f, "{s:?}");
+    }
+    if let Ok(b) = bool::decode(value) {
+        return write!(f, "{}", if b { "YES" } else { "NO" });
+    }
+    if let Ok(i) = i64::decode(value) {
+        return write!(f, "{i}");
+    }
+    if let Ok(r) = f64::decode(value) {
+        return write!(f, "{r}");
+    }
+    match value {
+        ObjectValue::Ref(value) if value.is_null_ref() => write!(f, "nil"),
+        ObjectValue::NullRef => write!(f, "nil"),
+        ObjectValue::Ref(value) => write!(f, "<uid {}>", value.unique_id().get()),
+        _ => write!(f, "<{}>", value.as_plain_type()),
+    }
+}
+
+impl Display for NSPredicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::True => write!(f, "TRUEPREDICATE"),
+            Self::False => write!(f, "FALSEPREDICATE"),
+            Self::Compound {
+                kind: CompoundKind::Not,
+                subpredicates,
+            } => match subpredicates.as_slice() {
+                [predicate] => write!(f, "NOT {}", Parenthesized(predicate)),
+                _ => write!(f, "NOT ()"),
+            },
+            Self::Compound {
+                kind,
+                subpredicates,
+            } => {
+                let separator = if *kind == CompoundKind::And {
+                    " AND "
+                } else {
+                    " OR "
+                };
+                for (i, predicate) in subpredicates.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "{separator}")?;
+                    }
+                    write!(f, "{}", Parenthesized(predicate))?;
+                }
+                Ok(())
+            }
+            Self::Comparison {
+                left,
+                operator,
+                right,
+                modifier,
+                options,
+                selector,
+            } => {
+                match modifier {
+                    ComparisonModifier::All => write!(f, "ALL ")?,
+                    ComparisonModifier::Any => write!(f, "ANY ")?,
+                    _ => (),
+                }
+                if *operator == ComparisonOperator::CustomSelector {
+                    let selector = selector.as_deref().unwrap_or("?");
+                    return write!(f, "{left} {selector} {right}");
+                }
+                write!(f, "{left} {}", operator.symbol())?;
+                let flags: String = [
+                    (Self::CASE_INSENSITIVE, 'c'),
+                    (Self::DIACRITIC_INSENSITIVE, 'd'),
+                    (Self::NORMALIZED, 'n'),
+                ]
+                .iter()
+                .filter(|(flag, _)| options & flag != 0)
+                .map(|(_, c)| *c)
+                .collect();
+                if !flags.is_empty() {
+                    write!(f, "[{flags}]")?;
+                }
+                write!(f, " {right}")
+            }
+        }
+    }
+}
+
+/// Wraps compound subpredicates into parentheses.
+struct Parenthesized<'a>(&'a NSPredicate);
+
+impl Display for Parenthesized<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            NSPredicate::Compound { .. } => write!(f, "({})", self.0),
+            predicate => write!(f, "{predicate}"),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use super::expect_object;
+use crate::{DeError, Decodable, DecodeContext, ObjectValue};
+
+/// A decoded `NSSortDescriptor`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NSSortDescriptor {
+    key: Option<String>,
+    ascending: bool,
+    selector: Option<String>,
+}
+
+impl NSSortDescriptor {
+    /// Returns a key path to sort by.
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+
+    /// Returns `true` if values are sorted in an ascending order.
+    pub fn ascending(&self) -> bool {
+        self.ascending
+    }
+
+    /// Returns a selector used to compare values, like `compare:`.
+    pub fn selector(&self) -> Option<&str> {
+        self.selector.as_deref()
+    }
+}
+
+impl Decodable for NSSortDescriptor {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(value, &["NSSortDescriptor"], ctx.is_probing())?;
+        let map = obj.as_map();
+        Ok(Self {
+            key: match map.get("NSKey") {
+                Some(key) => ctx.decode_optional_field(key, "NSKey")?,
+                None => None,
+            },
+            ascending: match map.get("NSAscending") {
+                Some(ascending) => ctx.decode_field(ascending, "NSAscending")?,
+                None => true,
+            },
+            selector: match map.get("NSSelector") {
+                Some(selector) => ctx.decode_optional_field(selector, "NSSelector")?,
+                None => None,
+            },
+        })
+    }
+}
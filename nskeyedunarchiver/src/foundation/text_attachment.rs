@@ -0,0 +1,112 @@
+use super::{NSAttributedString, NSRect, expect_object};
+use crate::{Data, DeError, Decodable, DecodeContext, ObjectValue, ValueRef};
+use std::{collections::HashMap, ops::Range};
+
+/// An attribute of an attributed string holding an [NSTextAttachment].
+pub const ATTACHMENT_ATTRIBUTE: &str = "NSAttachment";
+
+/// A decoded `NSFileWrapper`.
+///
+/// A file wrapper is archived as its serialized representation
+/// (`NSFileWrapperSerializedRepresentation`), the same bytes
+/// `-[NSFileWrapper serializedRepresentation]` returns.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NSFileWrapper {
+    serialized_representation: Data,
+}
+
+impl NSFileWrapper {
+    /// Returns the serialized representation of the wrapper.
+    pub fn serialized_representation(&self) -> &[u8] {
+        self.serialized_representation.as_ref()
+    }
+}
+
+impl Decodable for NSFileWrapper {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(value, &["NSFileWrapper"], ctx.is_probing())?;
+        let Some(representation) = obj.get("NSFileWrapperSerializedRepresentation") else {
+            return Err(DeError::MissingObjectKey(
+                obj.class().into(),
+                "NSFileWrapperSerializedRepresentation".into(),
+            ));
+        };
+        Ok(Self {
+            serialized_representation: Data::decode(representation)?,
+        })
+    }
+}
+
+/// A decoded `NSTextAttachment`: an inline image or file of an attributed
+/// string (e.g. in Notes or TextEdit documents).
+///
+/// Attachments created from data keep it in `NSContents` along with its
+/// type (`NSFileType`, a uniform type identifier like `public.png`).
+/// Attachments created from files keep an [NSFileWrapper] instead.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NSTextAttachment {
+    contents: Option<Data>,
+    file_type: Option<String>,
+    file_wrapper: Option<NSFileWrapper>,
+    bounds: Option<NSRect>,
+    image: Option<ValueRef>,
+}
+
+impl NSTextAttachment {
+    /// Returns bytes of the attachment.
+    pub fn contents(&self) -> Option<&Data> {
+        self.contents.as_ref()
+    }
+
+    /// Returns a uniform type identifier of the contents.
+    pub fn file_type(&self) -> Option<&str> {
+        self.file_type.as_deref()
+    }
+
+    /// Returns a file wrapper of the attachment.
+    pub fn file_wrapper(&self) -> Option<&NSFileWrapper> {
+        self.file_wrapper.as_ref()
+    }
+
+    /// Returns bounds of the attachment in a line of text.
+    pub fn bounds(&self) -> Option<NSRect> {
+        self.bounds
+    }
+
+    /// Returns an archived image object (like `UIImage` or `NSImage`).
+    /// Image classes differ between platforms, so it's left undecoded.
+    pub fn image(&self) -> Option<&ValueRef> {
+        self.image.as_ref()
+    }
+}
+
+impl Decodable for NSTextAttachment {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(value, &["NSTextAttachment"], ctx.is_probing())?;
+        Ok(Self {
+            contents: obj.get_as("NSContents")?,
+            file_type: obj.get_as("NSFileType")?,
+            file_wrapper: obj.get_as("NSFileWrapper")?,
+            bounds: obj.get_as("NSBounds")?,
+            image: obj.get_as("NSImage")?,
+        })
+    }
+}
+
+impl NSAttributedString<HashMap<String, ValueRef>> {
+    /// Decodes attachments of the string (values of an
+    /// [ATTACHMENT_ATTRIBUTE] attribute) along with ranges of their runs.
+    /// An attachment usually takes a single `U+FFFC` character.
+    pub fn attachments(&self) -> Result<Vec<(Range<usize>, NSTextAttachment)>, DeError> {
+        self.runs()
+            .filter_map(|(range, attributes)| {
+                let attachment = attributes.get(ATTACHMENT_ATTRIBUTE)?;
+                Some(NSTextAttachment::decode(&attachment.into()).map(|a| (range, a)))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,330 @@
+use super::expect_object;
+use crate::decodable::borrow_str;
+use crate::{Date, DeError, Decodable, DecodeContext, ObjectValue};
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// A decoded `NSURL`.
+///
+/// URLs are archived as a relative string (`NS.relative`) and an optional
+/// base URL (`NS.base`).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NSURL {
+    base: Option<Box<NSURL>>,
+    relative: String,
+}
+
+impl NSURL {
+    /// Returns a base URL.
+    pub fn base(&self) -> Option<&NSURL> {
+        self.base.as_deref()
+    }
+
+    /// Returns a URL string relative to the base URL.
+    pub fn relative(&self) -> &str {
+        &self.relative
+    }
+
+    /// Returns an absolute URL string. A relative string is simply
+    /// appended to a base one (without resolving `..` segments).
+    pub fn absolute(&self) -> String {
+        let Some(base) = &self.base else {
+            return self.relative.clone();
+        };
+        if self.relative.contains("://") {
+            return self.relative.clone();
+        }
+        let base = base.absolute();
+        match (base.ends_with('/'), self.relative.starts_with('/')) {
+            (true, true) => format!("{}{}", base, &self.relative[1..]),
+            (false, false) => format!("{base}/{}", self.relative),
+            _ => format!("{base}{}", self.relative),
+        }
+    }
+
+    /// Converts a `file://` URL into a path, percent-decoding it. Returns
+    /// [None] for URLs of other schemes and for file URLs of remote hosts.
+    pub fn to_file_path(&self) -> Option<PathBuf> {
+        file_path(&self.absolute())
+    }
+}
+
+/// Converts a `file://` URL string into a path.
+fn file_path(url: &str) -> Option<PathBuf> {
+    let path = url.strip_prefix("file://")?;
+    let path = path.strip_prefix("localhost").unwrap_or(path);
+    if !path.starts_with('/') {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(escaped) if byte == b'%' => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    // Paths of Apple platforms are arbitrary bytes
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        Some(OsString::from_vec(bytes).into())
+    }
+    #[cfg(not(unix))]
+    {
+        String::from_utf8(bytes).ok().map(PathBuf::from)
+    }
+}
+
+/// Decodes a path string (a plain string or an `NSString`) or a `file://`
+/// URL (an `NSURL` or a string). URLs are percent-decoded, plain paths are
+/// kept as is.
+impl Decodable for PathBuf {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        if let ObjectValue::Ref(value_ref) = value
+            && value_ref
+                .as_object()
+                .is_some_and(|obj| obj.matches_class("NSURL"))
+        {
+            let url: NSURL = ctx.decode(value)?;
+            return url.to_file_path().ok_or_else(|| {
+                DeError::Custom(format!("NSURL: `{}` isn't a file URL", url.absolute()))
+            });
+        }
+        let path = borrow_str(value, ctx.is_probing())?;
+        if path.starts_with("file://") {
+            return file_path(path)
+                .ok_or_else(|| DeError::Custom(format!("`{path}` isn't a local file URL")));
+        }
+        Ok(PathBuf::from(path))
+    }
+}
+
+/// Decodes a path like a [PathBuf].
+impl Decodable for OsString {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        PathBuf::decode_with(value, ctx).map(PathBuf::into_os_string)
+    }
+}
+
+impl Decodable for NSURL {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(value, &["NSURL"], ctx.is_probing())?;
+        // A base is decoded with the context, so a URL referring to itself
+        // fails instead of recursing
+        let base = match obj.as_map().get("NS.base") {
+            Some(ObjectValue::Ref(base)) if base.is_null_ref() => None,
+            Some(base) => Some(Box::new(ctx.decode_field(base, "NS.base")?)),
+            None => None,
+        };
+        let Some(relative) = obj.as_map().get("NS.relative") else {
+            return Err(DeError::MissingObjectKey(
+                obj.class().into(),
+                "NS.relative".into(),
+            ));
+        };
+        Ok(Self {
+            base,
+            relative: ctx.decode_field(relative, "NS.relative")?,
+        })
+    }
+}
+
+/// A decoded `NSUUID`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct NSUUID([u8; 16]);
+
+impl NSUUID {
+    /// Returns bytes of the UUID.
+    pub fn bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for NSUUID {
+    /// Formats the UUID like `E621E1F8-C36C-495A-93FC-0C247A3E6E5F`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                write!(f, "-")?;
+            }
+            write!(f, "{byte:02X}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Decodable for NSUUID {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(value, &["NSUUID", "__NSConcreteUUID"], ctx.is_probing())?;
+        let bytes = obj.get_bytes("NS.uuidbytes")?;
+        let bytes = bytes.try_into().map_err(|_| {
+            DeError::Custom(format!(
+                "{}: expected 16 bytes of `NS.uuidbytes`, found {}",
+                obj.class(),
+                bytes.len()
+            ))
+        })?;
+        Ok(Self(bytes))
+    }
+}
+
+/// A decoded `NSDate`. It's archived as a number of seconds since
+/// 1 January 2001 00:00:00 UTC (`NS.time`). Plain plist dates are decoded
+/// as well.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct NSDate {
+    time_interval: f64,
+}
+
+/// Seconds between 1 January 1970 and 1 January 2001.
+const APPLE_EPOCH_OFFSET: f64 = 978_307_200.0;
+
+impl NSDate {
+    /// Returns a number of seconds since 1 January 2001 00:00:00 UTC.
+    pub fn time_interval_since_reference_date(&self) -> f64 {
+        self.time_interval
+    }
+
+    /// Returns a number of seconds since 1 January 1970 00:00:00 UTC.
+    pub fn time_interval_since_1970(&self) -> f64 {
+        self.time_interval + APPLE_EPOCH_OFFSET
+    }
+
+    /// Converts the date into a [SystemTime]. Returns [None] if it can't be
+    /// represented.
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        system_time(self.time_interval_since_1970())
+    }
+}
+
+/// Converts a number of seconds since 1 January 1970 into a [SystemTime].
+fn system_time(since_1970: f64) -> Option<SystemTime> {
+    let duration = Duration::try_from_secs_f64(since_1970.abs()).ok()?;
+    if since_1970 >= 0.0 {
+        SystemTime::UNIX_EPOCH.checked_add(duration)
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(duration)
+    }
+}
+
+impl From<Date> for NSDate {
+    fn from(date: Date) -> Self {
+        let since_1970 = match SystemTime::from(date).duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs_f64(),
+            Err(e) => -e.duration().as_secs_f64(),
+        };
+        Self {
+            time_interval: since_1970 - APPLE_EPOCH_OFFSET,
+        }
+    }
+}
+
+impl Decodable for NSDate {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        if let ObjectValue::Date(date) = value {
+            return Ok((*date).into());
+        }
+        let obj = expect_object(value, &["NSDate"], ctx.is_probing())?;
+        let time = obj
+            .as_map()
+            .get("NS.time")
+            .ok_or_else(|| DeError::MissingObjectKey(obj.class().into(), "NS.time".into()))?;
+        Ok(Self {
+            time_interval: ctx.decode_field(time, "NS.time")?,
+        })
+    }
+}
+
+/// Decodes either an `NSDate` or a plain plist date.
+impl Decodable for SystemTime {
+    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
+        if let ObjectValue::Date(date) = value {
+            return Ok((*date).into());
+        }
+        NSDate::decode(value)?
+            .to_system_time()
+            .ok_or_else(|| DeError::Custom("NSDate is out of SystemTime range".into()))
+    }
+}
+
+/// A timestamp stored as a plain number of seconds since 1 January 2001
+/// 00:00:00 UTC (the Apple epoch), like creation dates of Notes or Photos
+/// metadata. Both reals (with sub-second precision) and integers are
+/// decoded, as well as `NSDate` objects and plain plist dates.
+///
+/// Unlike a Unix timestamp it's counted from 2001, so it shouldn't be
+/// passed to Unix time functions as is. Use [AppleTime::to_system_time] or
+/// [AppleTime::seconds_since_1970].
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct AppleTime(f64);
+
+impl AppleTime {
+    /// Creates a timestamp from a number of seconds since 1 January 2001.
+    pub fn from_seconds(seconds: f64) -> Self {
+        Self(seconds)
+    }
+
+    /// Returns a number of seconds since 1 January 2001 00:00:00 UTC.
+    pub fn seconds_since_reference_date(&self) -> f64 {
+        self.0
+    }
+
+    /// Returns a number of seconds since 1 January 1970 00:00:00 UTC.
+    pub fn seconds_since_1970(&self) -> f64 {
+        self.0 + APPLE_EPOCH_OFFSET
+    }
+
+    /// Converts the timestamp into a [SystemTime]. Returns [None] if it
+    /// can't be represented.
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        system_time(self.seconds_since_1970())
+    }
+}
+
+impl From<NSDate> for AppleTime {
+    fn from(date: NSDate) -> Self {
+        Self(date.time_interval)
+    }
+}
+
+impl From<AppleTime> for NSDate {
+    fn from(time: AppleTime) -> Self {
+        Self {
+            time_interval: time.0,
+        }
+    }
+}
+
+impl Decodable for AppleTime {
+    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
+        if let Some(time) = value.as_apple_timestamp() {
+            return Ok(time);
+        }
+        match value {
+            ObjectValue::Date(_) | ObjectValue::Ref(_) => NSDate::decode(value).map(Into::into),
+            _ => Err(DeError::ExpectedFloat),
+        }
+    }
+}
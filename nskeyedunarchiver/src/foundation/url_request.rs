@@ -0,0 +1,73 @@
+use super::{NSURL, expect_object};
+use crate::{Data, DeError, Decodable, DecodeContext, ObjectValue};
+use std::collections::BTreeMap;
+
+/// A decoded `NSURLRequest` or `NSMutableURLRequest`.
+///
+/// Requests are archived with `NS.url`, `NS.httpMethod`,
+/// `NS.allHTTPHeaderFields`, `NS.httpBody` and `NS.timeoutInterval` keys.
+/// Missing values get Foundation defaults: the `GET` method and a 60 seconds
+/// timeout.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NSURLRequest {
+    url: Option<NSURL>,
+    method: String,
+    headers: BTreeMap<String, String>,
+    body: Option<Data>,
+    timeout_interval: f64,
+}
+
+impl NSURLRequest {
+    /// Returns a requested URL.
+    pub fn url(&self) -> Option<&NSURL> {
+        self.url.as_ref()
+    }
+
+    /// Returns an HTTP method, like `GET` or `POST`.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Returns all HTTP header fields.
+    pub fn headers(&self) -> &BTreeMap<String, String> {
+        &self.headers
+    }
+
+    /// Returns a value of an HTTP header field. Field names are
+    /// case-insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns a request body.
+    pub fn body(&self) -> Option<&[u8]> {
+        self.body.as_ref().map(|body| body.as_ref())
+    }
+
+    /// Returns a timeout interval in seconds.
+    pub fn timeout_interval(&self) -> f64 {
+        self.timeout_interval
+    }
+}
+
+impl Decodable for NSURLRequest {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(
+            value,
+            &["NSURLRequest", "NSMutableURLRequest"],
+            ctx.is_probing(),
+        )?;
+        Ok(Self {
+            url: obj.get_as("NS.url")?,
+            method: obj.get_as("NS.httpMethod")?.unwrap_or_else(|| "GET".into()),
+            headers: obj.get_as("NS.allHTTPHeaderFields")?.unwrap_or_default(),
+            body: obj.get_as("NS.httpBody")?,
+            timeout_interval: obj.get_as("NS.timeoutInterval")?.unwrap_or(60.0),
+        })
+    }
+}
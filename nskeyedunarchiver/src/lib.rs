@@ -1,19 +1,96 @@
+#[macro_use]
+mod trace;
+
+mod context;
+#[macro_use]
 mod decodable;
+mod dynamic;
+mod editor;
+mod embedded;
 mod error;
+mod format;
 mod object;
+mod options;
+mod patch;
+mod pretty;
+mod sanitize;
+mod schema;
+mod stats;
+mod stream;
+mod structural;
+mod table;
+mod validate;
+
+pub mod bookmark;
+pub mod core_data;
+pub mod foundation;
+pub mod nib;
+pub mod raw;
+pub mod typedstream;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+pub use context::*;
 pub use decodable::*;
+pub use dynamic::*;
+pub use editor::*;
 pub use error::*;
+pub use format::*;
 pub use object::*;
-pub use plist::Integer;
+pub use options::*;
+pub use patch::*;
+pub use pretty::*;
+pub use sanitize::*;
+pub use schema::*;
+pub use stats::*;
+pub use stream::*;
+pub use table::*;
+pub use validate::*;
+pub use plist::{Date, Integer};
 use plist::{Dictionary as PlistDictionary, Value as PlistValue};
-use std::{collections::HashMap, rc::Rc};
+use std::{
+    borrow::Cow,
+    cell::OnceCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
 
 #[cfg(feature = "derive")]
 pub mod derive {
     pub use nskeyedunarchiver_derive::Decodable;
 }
 
+/// Reads a plist file and decodes its `root` object as `<T>`.
+///
+/// It's a shortcut for creating a [KeyedArchive], getting its root
+/// and decoding it.
+pub fn decode_file<T: Decodable>(path: impl AsRef<std::path::Path>) -> Result<T, Error> {
+    let archive = KeyedArchive::from_file(path)?;
+    Ok(archive.decode_root()?)
+}
+
+/// Reads a plist from a byte slice and decodes its `root` object as `<T>`.
+///
+/// It's a shortcut for creating a [KeyedArchive], getting its root
+/// and decoding it.
+pub fn decode_bytes<T: Decodable>(bytes: &[u8]) -> Result<T, Error> {
+    let archive = KeyedArchive::from_bytes(bytes)?;
+    Ok(archive.decode_root()?)
+}
+
+/// Reads a plist from a seekable reader and decodes its `root` object as `<T>`.
+///
+/// It's a shortcut for creating a [KeyedArchive], getting its root
+/// and decoding it.
+pub fn decode_reader<T: Decodable, R: std::io::Read + std::io::Seek>(
+    reader: R,
+) -> Result<T, Error> {
+    let archive = KeyedArchive::from_reader(reader)?;
+    Ok(archive.decode_root()?)
+}
+
 const ARCHIVER: &str = "NSKeyedArchiver";
 const ARCHIVER_VERSION: u64 = 100000;
 
@@ -22,6 +99,7 @@ const TOP_KEY_NAME: &str = "$top";
 const OBJECTS_KEY_NAME: &str = "$objects";
 const VERSION_KEY_NAME: &str = "$version";
 const NULL_OBJECT_REFERENCE_NAME: &str = "$null";
+const ROOT_KEY_NAME: &str = "root";
 
 /// An [Rc] smart pointer to an [ArchiveValue]
 pub type ValueRef = Rc<ArchiveValue>;
@@ -30,7 +108,7 @@ pub type ValueRef = Rc<ArchiveValue>;
 ///
 /// When decoding complex structures this it may help with indentifying repeatable
 /// values.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct UniqueId(usize);
 impl UniqueId {
     pub fn new(id: usize) -> Self {
@@ -41,24 +119,68 @@ impl UniqueId {
     }
 }
 
+/// A value of `$objects` that is checked and converted from a plist value,
+/// but has no [UniqueId] and references applied yet. It contains no [Rc]
+/// pointers, so values may be converted on several threads.
+enum RawValue {
+    Object(RawObject),
+    Classes {
+        classes: Vec<String>,
+        hints: Vec<String>,
+    },
+    Boolean(bool),
+    Data(Vec<u8>),
+    Real(f64),
+    Integer(Integer),
+    String(String),
+    NullRef,
+}
+
+/// A minimal number of values converted by a single thread.
+#[cfg(feature = "parallel")]
+const PARALLEL_MIN_LEN: usize = 1024;
+
 /// Possible values inside of $objects
 #[derive(Debug, PartialEq)]
 pub(crate) enum ArchiveValueVariant {
     Boolean(bool),
-    Classes(Vec<String>),
-    Data(Vec<u8>),
+    Classes {
+        classes: Vec<String>,
+        hints: Vec<String>,
+    },
+    Data(Rc<[u8]>),
     Integer(Integer),
     NullRef,
     Object(Object),
     Real(f64),
-    String(String),
+    String(Rc<str>),
+}
+
+impl ArchiveValueVariant {
+    /// Deep-clones a value. References of an object are turned back into
+    /// uids, so it should be linked to other values afterwards.
+    fn clone_unlinked(&self, keys: &mut KeyInterner) -> Self {
+        match self {
+            Self::Boolean(b) => Self::Boolean(*b),
+            Self::Classes { classes, hints } => Self::Classes {
+                classes: classes.clone(),
+                hints: hints.clone(),
+            },
+            Self::Data(data) => Self::Data(data.to_vec().into()),
+            Self::Integer(integer) => Self::Integer(*integer),
+            Self::NullRef => Self::NullRef,
+            Self::Object(obj) => Self::Object(obj.clone_unlinked(keys)),
+            Self::Real(real) => Self::Real(*real),
+            Self::String(string) => Self::String(string.to_string().into()),
+        }
+    }
 }
 
 /// Represents a single value contained inside of an archive.
 ///
 /// The possible values are: [String], [Integer], [f64], Vec\<u8\>, [bool],
 /// `NullRef` (a `$null` reference), `Classes` (an array of class strings), [Object].
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub struct ArchiveValue {
     value: ArchiveValueVariant,
     unique_id: UniqueId,
@@ -85,7 +207,17 @@ impl ArchiveValue {
     /// Returns [Some] with a reference to a contained [String] if a value represents it or [None] if it doesn't.
     pub fn as_string(&self) -> Option<&str> {
         if let ArchiveValueVariant::String(v) = &self.value {
-            Some(v.as_str())
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    /// Returns [Some] with a shared pointer to a contained string if a value
+    /// represents it or [None] if it doesn't. The string isn't copied.
+    pub fn as_shared_string(&self) -> Option<Rc<str>> {
+        if let ArchiveValueVariant::String(v) = &self.value {
+            Some(v.clone())
         } else {
             None
         }
@@ -124,7 +256,7 @@ impl ArchiveValue {
         matches!(&self.value, ArchiveValueVariant::Real(_))
     }
 
-    /// Returns [Some] with a reference to a contained [Integer] if a value represents it or [None] if it doesn't.
+    /// Returns [Some] with a reference to a contained data if a value represents it or [None] if it doesn't.
     pub fn as_data(&self) -> Option<&[u8]> {
         if let ArchiveValueVariant::Data(v) = &self.value {
             Some(v)
@@ -133,7 +265,17 @@ impl ArchiveValue {
         }
     }
 
-    /// Checks if a contained value is an [Integer].
+    /// Returns [Some] with a shared pointer to a contained data if a value
+    /// represents it or [None] if it doesn't. The data isn't copied.
+    pub fn as_shared_data(&self) -> Option<Rc<[u8]>> {
+        if let ArchiveValueVariant::Data(v) = &self.value {
+            Some(v.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Checks if a contained value is a data.
     pub fn is_data(&self) -> bool {
         matches!(&self.value, ArchiveValueVariant::Data(_))
     }
@@ -162,8 +304,19 @@ impl ArchiveValue {
 
     /// Returns [Some] with a slice of class strings if a value represents it or [None] if it doesn't.
     pub fn as_classes(&self) -> Option<&[String]> {
-        if let ArchiveValueVariant::Classes(v) = &self.value {
-            Some(v)
+        if let ArchiveValueVariant::Classes { classes, .. } = &self.value {
+            Some(classes)
+        } else {
+            None
+        }
+    }
+
+    /// Returns [Some] with a slice of class hints (`$classhints`) if a value
+    /// represents class strings or [None] if it doesn't. Hints are usually
+    /// present if a class is unavailable on some platforms.
+    pub fn as_class_hints(&self) -> Option<&[String]> {
+        if let ArchiveValueVariant::Classes { hints, .. } = &self.value {
+            Some(hints)
         } else {
             None
         }
@@ -171,7 +324,7 @@ impl ArchiveValue {
 
     /// Checks if a contained value is class strings.
     pub fn is_classes(&self) -> bool {
-        matches!(&self.value, ArchiveValueVariant::Classes(_))
+        matches!(&self.value, ArchiveValueVariant::Classes { .. })
     }
 
     /// Checks if a contained value is a null reference.
@@ -190,8 +343,66 @@ impl ArchiveValue {
 /// It contains *top* objects (the entry point of any archive) and
 /// the objects itself.
 pub struct KeyedArchive {
-    objects: Vec<ValueRef>,
+    // `top` is dropped before `objects`, so objects are dropped one by one
+    // instead of recursively through references of the root object
     top: HashMap<String, ValueRef>,
+    objects: Vec<ValueRef>,
+    top_raw: PlistDictionary,
+    header: PlistDictionary,
+    // Options the archive was parsed with, reused by edits
+    options: ParseOptions,
+    file: Option<std::path::PathBuf>,
+    format: Option<Format>,
+    // Built on the first call to `class_index`
+    class_index: OnceCell<HashMap<String, Vec<UniqueId>>>,
+    // Built on the first call to `incoming_ref_count`
+    ref_counts: OnceCell<Vec<usize>>,
+}
+
+impl Clone for KeyedArchive {
+    /// Deep-clones an archive. The copy shares no [Rc] pointers with the
+    /// original one, references between its values are recreated.
+    fn clone(&self) -> Self {
+        let mut keys = KeyInterner::new();
+        let objects = self
+            .objects
+            .iter()
+            .map(|value| {
+                let variant = value.value.clone_unlinked(&mut keys);
+                Rc::new(ArchiveValue::new(variant, value.unique_id))
+            })
+            .collect();
+        // All uids have been checked when the original archive was created,
+        // dangling array elements refer to a placeholder past `$objects`
+        let objects = Self::link_objects(objects, self.options.dangling_refs_as_null())
+            .expect("cloned objects should be linked");
+        let top = self
+            .top
+            .iter()
+            .map(|(key, value)| {
+                let value = match objects.get(value.unique_id.get()) {
+                    Some(value) => value.clone(),
+                    // A plain value of `$top`
+                    None => Rc::new(ArchiveValue::new(
+                        value.value.clone_unlinked(&mut keys),
+                        value.unique_id,
+                    )),
+                };
+                (key.clone(), value)
+            })
+            .collect();
+        KeyedArchive {
+            top,
+            objects,
+            top_raw: self.top_raw.clone(),
+            header: self.header.clone(),
+            options: self.options.clone(),
+            file: self.file.clone(),
+            format: self.format,
+            class_index: self.class_index.clone(),
+            ref_counts: self.ref_counts.clone(),
+        }
+    }
 }
 
 impl KeyedArchive {
@@ -199,13 +410,251 @@ impl KeyedArchive {
     ///
     /// This method is useful for some keyed archives with a complex structure,
     /// containing several values inside of `$top`.
+    ///
+    /// Plain values stored in `$top` directly (rather than referenced from
+    /// `$objects`) are included too. Their uids are past the end of
+    /// [KeyedArchive::values].
     pub fn top(&self) -> &HashMap<String, ValueRef> {
         &self.top
     }
 
+    /// Returns the `$top` dictionary as it's stored in the plist: entries are
+    /// [plist::Uid] references to `$objects` or plain values.
+    pub fn top_raw(&self) -> &PlistDictionary {
+        &self.top_raw
+    }
+
+    /// Returns keys of the archive plist other than `$archiver`, `$version`,
+    /// `$top` and `$objects`. Some archivers store vendor-specific metadata
+    /// this way. They're kept by [KeyedArchive::edit].
+    pub fn extra_header(&self) -> &PlistDictionary {
+        &self.header
+    }
+
     /// Returns the `root` entry point from the `$top` key if it exists.
     pub fn root(&self) -> Option<ValueRef> {
-        self.top.get("root").cloned()
+        self.top_value(ROOT_KEY_NAME)
+    }
+
+    /// Returns the `root` entry point from the `$top` key. Unlike
+    /// [KeyedArchive::root], it tells why there's none: [Error::EmptyTop]
+    /// if `$top` has no entries at all, [Error::MissingTopKey] otherwise.
+    pub fn try_root(&self) -> Result<ValueRef, Error> {
+        if let Some(root) = self.root() {
+            return Ok(root);
+        }
+        if self.top.is_empty() {
+            return Err(Error::EmptyTop);
+        }
+        Err(Error::MissingTopKey(ROOT_KEY_NAME.into()))
+    }
+
+    /// Returns the `root` entry point or the only `$top` entry if there's
+    /// no `root` one. Archives created with `encodeObject:forKey:` often
+    /// contain a single entry with a custom key.
+    pub fn root_or_single(&self) -> Result<ValueRef, Error> {
+        match self.try_root() {
+            Err(Error::MissingTopKey(_)) if self.top.len() == 1 => {
+                Ok(self.top.values().next().unwrap().clone())
+            }
+            result => result,
+        }
+    }
+
+    /// Returns the keys of all `$top` entries.
+    ///
+    /// Archives created with `encodeObject:forKey:` may use any custom keys
+    /// instead of (or along with) `root`.
+    pub fn top_keys(&self) -> Vec<&str> {
+        self.top.keys().map(|k| k.as_str()).collect()
+    }
+
+    /// Returns a `$top` entry with a given `key` if it exists.
+    pub fn top_value(&self, key: &str) -> Option<ValueRef> {
+        self.top.get(key).cloned()
+    }
+
+    /// Tries to decode a `$top` entry with a given `key` as `<T>`.
+    /// If it doesn't exist or can't be decoded a [DeError] is returned.
+    ///
+    /// If the archive is opened from a file, errors are wrapped into
+    /// [DeError::InFile].
+    pub fn decode_top<T: Decodable>(&self, key: &str) -> Result<T, DeError> {
+        self.decode_top_with(key, &mut self.decode_context())
+    }
+
+    /// Decodes a `$top` entry with a given `key` as `<T>` sharing a given
+    /// [DecodeContext]. It's useful for passing user contexts to decodables
+    /// (see [DecodeContext::insert_user]) or decoding several entries with
+    /// shared values.
+    pub fn decode_top_with<T: Decodable>(
+        &self,
+        key: &str,
+        ctx: &mut DecodeContext,
+    ) -> Result<T, DeError> {
+        let Some(value) = self.top.get(key) else {
+            return Err(self.with_file(DeError::MissingObjectKey(
+                TOP_KEY_NAME.into(),
+                key.into(),
+            )));
+        };
+        span!(DEBUG, "decode_top", key, ty = std::any::type_name::<T>());
+        ctx.decode_field(&value.into(), key)
+            .map_err(|e| self.with_file(e))
+    }
+
+    /// Tries to decode the `root` entry of `$top` as `<T>`.
+    /// If it doesn't exist or can't be decoded a [DeError] is returned.
+    ///
+    /// It's a shortcut for [KeyedArchive::decode_top] with the `root` key.
+    pub fn decode_root<T: Decodable>(&self) -> Result<T, DeError> {
+        self.decode_top(ROOT_KEY_NAME)
+    }
+
+    /// Tries to decode the `root` entry of `$top` as `<T>` sharing a given
+    /// [DecodeContext]. See [KeyedArchive::decode_top_with].
+    pub fn decode_root_with<T: Decodable>(&self, ctx: &mut DecodeContext) -> Result<T, DeError> {
+        self.decode_top_with(ROOT_KEY_NAME, ctx)
+    }
+
+    /// Decodes a value of `$objects` with a given uid as `<T>`. It's handy for
+    /// decoding a single subtree found with [KeyedArchive::values] or
+    /// [KeyedArchive::stats] without walking from the root.
+    ///
+    /// Returns [DeError::MissingValue] if there's no such value.
+    pub fn decode_value_as<T: Decodable>(&self, uid: UniqueId) -> Result<T, DeError> {
+        self.decode_value_with(uid, &mut self.decode_context())
+    }
+
+    /// Decodes a value of `$objects` with a given uid as `<T>` sharing a given
+    /// [DecodeContext]. See [KeyedArchive::decode_value_as].
+    pub fn decode_value_with<T: Decodable>(
+        &self,
+        uid: UniqueId,
+        ctx: &mut DecodeContext,
+    ) -> Result<T, DeError> {
+        let Some(value) = self.value_by_uid(uid) else {
+            return Err(self.with_file(DeError::MissingValue(uid)));
+        };
+        span!(DEBUG, "decode_value", uid = uid.get(), ty = std::any::type_name::<T>());
+        ctx.decode(&value.into()).map_err(|e| self.with_file(e))
+    }
+
+    /// Creates an empty [DecodeContext] with limits of the archive.
+    pub fn decode_context(&self) -> DecodeContext {
+        DecodeContext::with_limits(self.options.limits())
+    }
+
+    /// Decodes a `$top` entry with a given `key` as `<T>` in a lossy mode.
+    /// Returns a decoded value along with errors of optional values that
+    /// are replaced with defaults.
+    ///
+    /// See [Decodable::decode_lossy].
+    pub fn decode_top_lossy<T: Decodable>(&self, key: &str) -> Result<(T, Vec<DeError>), DeError> {
+        let Some(value) = self.top.get(key) else {
+            return Err(self.with_file(DeError::MissingObjectKey(
+                TOP_KEY_NAME.into(),
+                key.into(),
+            )));
+        };
+        span!(DEBUG, "decode_top", key, ty = std::any::type_name::<T>(), lossy = true);
+        let mut ctx = self.decode_context();
+        ctx.set_lossy(true);
+        let decoded = ctx
+            .decode_field(&value.into(), key)
+            .map_err(|e| self.with_file(e))?;
+        Ok((decoded, ctx.take_errors()))
+    }
+
+    /// Returns a path of a file the archive is opened from (see
+    /// [KeyedArchive::from_file]).
+    pub fn file(&self) -> Option<&std::path::Path> {
+        self.file.as_deref()
+    }
+
+    /// Wraps an error into [DeError::InFile] if the archive is opened from a file.
+    fn with_file(&self, error: DeError) -> DeError {
+        match &self.file {
+            Some(file) => DeError::InFile {
+                file: file.clone(),
+                source: Box::new(error),
+            },
+            None => error,
+        }
+    }
+
+    /// Returns a value of `$objects` with a given uid if it exists.
+    pub fn value_by_uid(&self, uid: UniqueId) -> Option<&ValueRef> {
+        self.objects.get(uid.get())
+    }
+
+    /// Returns the number of values in `$objects` (including `$null` and
+    /// class descriptions).
+    pub fn objects_len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Returns `true` if `$objects` is empty.
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Returns a value of `$objects` at a given index if it exists. Indexes
+    /// are the same as uids, see [KeyedArchive::value_by_uid].
+    pub fn get(&self, index: usize) -> Option<&ValueRef> {
+        self.objects.get(index)
+    }
+
+    /// Returns an iterator over values of `$objects` along with their uids.
+    pub fn iter(&self) -> impl Iterator<Item = (UniqueId, &ValueRef)> {
+        self.objects
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (UniqueId::new(index), value))
+    }
+
+    /// Returns names of all classes used in the archive along with the number
+    /// of objects of each class.
+    ///
+    /// Classes that no object refers to are included with a zero count.
+    /// It's handy for exploring an archive of an unknown format.
+    pub fn classes(&self) -> BTreeMap<String, usize> {
+        let mut classes = BTreeMap::new();
+        for value in &self.objects {
+            if let Some(names) = value.as_classes() {
+                classes.entry(names[0].clone()).or_insert(0);
+            } else if let Some(obj) = value.as_object() {
+                *classes.entry(obj.class().to_string()).or_insert(0) += 1;
+            }
+        }
+        classes
+    }
+
+    /// Returns uids of objects grouped by their classes (see [Object::class]),
+    /// in ascending order.
+    ///
+    /// The index is built on the first call and cached, so repeated lookups
+    /// don't scan all objects of an archive.
+    pub fn class_index(&self) -> &HashMap<String, Vec<UniqueId>> {
+        self.class_index.get_or_init(|| {
+            let mut index: HashMap<String, Vec<UniqueId>> = HashMap::new();
+            for (uid, value) in self.iter() {
+                if let Some(obj) = value.as_object() {
+                    index.entry(obj.class().to_string()).or_default().push(uid);
+                }
+            }
+            index
+        })
+    }
+
+    /// Returns all objects of a given class. Objects of its subclasses aren't
+    /// included.
+    pub fn objects_of_class(&self, class: &str) -> impl Iterator<Item = &ValueRef> + use<'_> {
+        self.class_index()
+            .get(class)
+            .into_iter()
+            .flatten()
+            .map(|uid| &self.objects[uid.get()])
     }
 
     /// Returns all values contained inside of an archive. One may rarely use this.
@@ -237,77 +686,184 @@ impl KeyedArchive {
         }
     }
 
-    /// Decodes all values into a vector of Rc<[ArchiveValue]>. Returns an [Error]
-    /// if something went wrong.
-    fn decode_objects(objects: Vec<PlistValue>) -> Result<Vec<ValueRef>, Error> {
-        let mut decoded_objects = Vec::with_capacity(objects.len());
-
-        for (index, obj) in objects.into_iter().enumerate() {
-            let decoded_obj = match obj {
-                PlistValue::Dictionary(mut dict) => {
-                    if Self::is_container(&dict) {
-                        ArchiveValue::new(
-                            ArchiveValueVariant::Object(Object::from_dict(dict)?),
-                            UniqueId::new(index),
-                        )
-                    } else if dict.contains_key("$classes") {
-                        match dict.remove("$classes").unwrap().into_array() {
-                            Some(classes_arr) => {
-                                let mut classes = Vec::with_capacity(classes_arr.len());
-                                for class in classes_arr {
-                                    match class.into_string() {
-                                        Some(s) => classes.push(s),
-                                        _ => {
-                                            return Err(Error::IncorrectFormat(
-                                                "Incorrect Classes object".into(),
-                                            ));
-                                        }
-                                    }
-                                }
-                                ArchiveValue::new(
-                                    ArchiveValueVariant::Classes(classes),
-                                    UniqueId::new(index),
-                                )
-                            }
-                            _ => {
-                                return Err(Error::IncorrectFormat(
-                                    "Incorrect Classes object".into(),
-                                ));
-                            }
+    /// Returns a size of data and string values inside of a [plist::Value]
+    /// (including values of an object dictionary).
+    fn data_size(value: &PlistValue) -> usize {
+        match value {
+            PlistValue::Data(data) => data.len(),
+            PlistValue::String(s) => s.len(),
+            PlistValue::Dictionary(dict) => dict
+                .values()
+                .map(|value| match value {
+                    PlistValue::Data(data) => data.len(),
+                    PlistValue::String(s) => s.len(),
+                    _ => 0,
+                })
+                .sum(),
+            _ => 0,
+        }
+    }
+
+    /// Converts an array of strings (like `$classes`) or returns [None] if
+    /// it's something else.
+    fn string_array(value: PlistValue) -> Option<Vec<String>> {
+        value
+            .into_array()?
+            .into_iter()
+            .map(|value| value.into_string())
+            .collect()
+    }
+
+    /// Converts a plain plist value (a boolean, a number, a string or data)
+    /// into an [ArchiveValueVariant].
+    fn plain_variant(value: PlistValue) -> Option<ArchiveValueVariant> {
+        let variant = match value {
+            PlistValue::Boolean(b) => ArchiveValueVariant::Boolean(b),
+            PlistValue::Data(data) => ArchiveValueVariant::Data(data.into()),
+            PlistValue::Real(real) => ArchiveValueVariant::Real(real),
+            PlistValue::Integer(integer) => ArchiveValueVariant::Integer(integer),
+            PlistValue::String(string) => ArchiveValueVariant::String(string.into()),
+            _ => return None,
+        };
+        Some(variant)
+    }
+
+    /// Checks if a [plist::Value] may be an object without a `$class` reference.
+    fn is_anonymous_container(val: &PlistDictionary) -> bool {
+        !val.is_empty() && !val.contains_key("$class") && !val.contains_key("$classes")
+    }
+
+    /// Checks and converts a value of `$objects` into a [RawValue]. Dictionaries
+    /// without `$class` are converted into objects if `anonymous` is `true`.
+    fn raw_value(obj: PlistValue, anonymous: bool) -> Result<RawValue, Error> {
+        let raw = match obj {
+            PlistValue::Dictionary(mut dict) => {
+                if Self::is_container(&dict) || (anonymous && Self::is_anonymous_container(&dict)) {
+                    RawValue::Object(RawObject::from_dict(dict)?)
+                } else if let Some(classes) = dict.remove("$classes") {
+                    let hints = match dict.remove("$classhints") {
+                        Some(hints) => Self::string_array(hints),
+                        None => Some(Vec::new()),
+                    };
+                    match (Self::string_array(classes), hints) {
+                        // Objects take their class name from the first entry
+                        (Some(classes), Some(hints)) if !classes.is_empty() => {
+                            RawValue::Classes { classes, hints }
+                        }
+                        _ => {
+                            return Err(Error::IncorrectFormat(
+                                "Incorrect Classes object".into(),
+                            ));
                         }
-                    } else {
-                        return Err(Error::IncorrectFormat("Unexpected object type".into()));
                     }
+                } else {
+                    return Err(Error::IncorrectFormat("Unexpected object type".into()));
                 }
-                PlistValue::Boolean(b) => {
-                    ArchiveValue::new(ArchiveValueVariant::Boolean(b), UniqueId::new(index))
-                }
-                PlistValue::Data(data) => ArchiveValue::new(
-                    ArchiveValueVariant::Data(data.to_vec()),
-                    UniqueId::new(index),
-                ),
-                PlistValue::Real(real) => {
-                    ArchiveValue::new(ArchiveValueVariant::Real(real), UniqueId::new(index))
-                }
-                PlistValue::Integer(integer) => {
-                    ArchiveValue::new(ArchiveValueVariant::Integer(integer), UniqueId::new(index))
+            }
+            PlistValue::Boolean(b) => RawValue::Boolean(b),
+            PlistValue::Data(data) => RawValue::Data(data),
+            PlistValue::Real(real) => RawValue::Real(real),
+            PlistValue::Integer(integer) => RawValue::Integer(integer),
+            PlistValue::String(string) => {
+                if string == NULL_OBJECT_REFERENCE_NAME {
+                    RawValue::NullRef
+                } else {
+                    RawValue::String(string)
                 }
-                PlistValue::String(string) => {
-                    if string == NULL_OBJECT_REFERENCE_NAME {
-                        ArchiveValue::new(ArchiveValueVariant::NullRef, UniqueId::new(index))
-                    } else {
-                        ArchiveValue::new(ArchiveValueVariant::String(string), UniqueId::new(index))
-                    }
+            }
+            _ => {
+                return Err(Error::IncorrectFormat(format!(
+                    "Unexpected object type: {obj:?}"
+                )));
+            }
+        };
+        Ok(raw)
+    }
+
+    /// Decodes all values into a vector of Rc<[ArchiveValue]>. Returns an [Error]
+    /// if something went wrong.
+    pub(crate) fn decode_objects(
+        objects: Cow<'_, [PlistValue]>,
+        options: &ParseOptions,
+    ) -> Result<Vec<ValueRef>, Error> {
+        let limits = options.limits();
+        let anonymous = options.anonymous_objects();
+        span!(DEBUG, "decode_objects", count = objects.len());
+        if let Some(max_objects) = limits.max_objects
+            && objects.len() > max_objects
+        {
+            return Err(Error::LimitExceeded(format!(
+                "An archive contains {} objects, the limit is {max_objects}",
+                objects.len()
+            )));
+        }
+        if let Some(max_data_size) = limits.max_data_size {
+            let data_size: usize = objects.iter().map(Self::data_size).sum();
+            if data_size > max_data_size {
+                return Err(Error::LimitExceeded(format!(
+                    "An archive contains {data_size} bytes of data, the limit is {max_data_size}"
+                )));
+            }
+        }
+
+        // Values are checked and converted first. It's the most expensive part
+        // and it doesn't involve any Rc pointers, so it may run in parallel.
+        // Borrowed values are cloned one by one right before they're converted
+        #[cfg(feature = "parallel")]
+        let raw_objects: Vec<RawValue> = {
+            use rayon::prelude::*;
+            match objects {
+                Cow::Owned(objects) => objects
+                    .into_par_iter()
+                    .with_min_len(PARALLEL_MIN_LEN)
+                    .map(|value| Self::raw_value(value, anonymous))
+                    .collect::<Result<_, _>>()?,
+                Cow::Borrowed(objects) => objects
+                    .par_iter()
+                    .with_min_len(PARALLEL_MIN_LEN)
+                    .map(|value| Self::raw_value(value.clone(), anonymous))
+                    .collect::<Result<_, _>>()?,
+            }
+        };
+        #[cfg(not(feature = "parallel"))]
+        let raw_objects: Vec<RawValue> = match objects {
+            Cow::Owned(objects) => objects
+                .into_iter()
+                .map(|value| Self::raw_value(value, anonymous))
+                .collect::<Result<_, _>>()?,
+            Cow::Borrowed(objects) => objects
+                .iter()
+                .map(|value| Self::raw_value(value.clone(), anonymous))
+                .collect::<Result<_, _>>()?,
+        };
+
+        let mut decoded_objects = Vec::with_capacity(raw_objects.len());
+        let mut keys = KeyInterner::new();
+        for (index, raw) in raw_objects.into_iter().enumerate() {
+            let variant = match raw {
+                RawValue::Object(obj) => {
+                    ArchiveValueVariant::Object(Object::from_raw(obj, &mut keys))
                 }
-                _ => {
-                    return Err(Error::IncorrectFormat(format!(
-                        "Unexpected object type: {obj:?}"
-                    )));
+                RawValue::Classes { classes, hints } => {
+                    ArchiveValueVariant::Classes { classes, hints }
                 }
+                RawValue::Boolean(b) => ArchiveValueVariant::Boolean(b),
+                RawValue::Data(data) => ArchiveValueVariant::Data(data.into()),
+                RawValue::Real(real) => ArchiveValueVariant::Real(real),
+                RawValue::Integer(integer) => ArchiveValueVariant::Integer(integer),
+                RawValue::String(string) => ArchiveValueVariant::String(string.into()),
+                RawValue::NullRef => ArchiveValueVariant::NullRef,
             };
-            decoded_objects.push(Rc::new(decoded_obj));
+            decoded_objects.push(Rc::new(ArchiveValue::new(variant, UniqueId::new(index))));
         }
+        Self::link_objects(decoded_objects, options.dangling_refs_as_null())
+    }
 
+    /// Replaces uids inside of objects with references to other values.
+    fn link_objects(
+        decoded_objects: Vec<ValueRef>,
+        null_dangling: bool,
+    ) -> Result<Vec<ValueRef>, Error> {
         // In order to avoid using RefCell to write object references into
         // them only once, we can use this hack
         let mut decoded_objects_raw = Vec::with_capacity(decoded_objects.len());
@@ -316,11 +872,21 @@ impl KeyedArchive {
             decoded_objects_raw.push(raw);
         }
 
-        for ptr in &decoded_objects_raw {
+        // Dangling array elements are replaced with `$null`. If `$objects`
+        // doesn't start with it, they share a placeholder instead. It isn't
+        // a part of `$objects`, so it gets the first uid past its end.
+        let null = null_dangling.then(|| match decoded_objects.first() {
+            Some(value) if value.is_null_ref() => value.clone(),
+            _ => Rc::new(ArchiveValue::new(
+                ArchiveValueVariant::NullRef,
+                UniqueId::new(decoded_objects.len()),
+            )),
+        });
+        for (index, ptr) in decoded_objects_raw.iter().enumerate() {
             // it's safe, all objects are still in memory
             let a = unsafe { &mut **ptr };
             if let Some(obj) = a.as_object_mut() {
-                obj.apply_value_refs(&decoded_objects)?
+                obj.apply_value_refs(&decoded_objects, UniqueId::new(index), null.as_ref())?
             }
         }
         Ok(decoded_objects)
@@ -331,11 +897,63 @@ impl KeyedArchive {
     ///
     /// Returns an instance of itself or an [Error] if something went wrong.
     pub fn from_plist(plist: PlistValue) -> Result<Self, Error> {
+        Self::from_plist_with_options(plist, &ParseOptions::default())
+    }
+
+    /// Creates a [KeyedArchive] from a [plist::Value] with given [ParseOptions].
+    /// It should be the root value of a plist and have a keyed archive structure.
+    ///
+    /// Returns an instance of itself or an [Error] if something went wrong.
+    pub fn from_plist_with_options(
+        plist: PlistValue,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
         let Some(mut dict) = plist.into_dictionary() else {
             return Err(Error::IncorrectFormat(
                 "Expected root key to be a type of `Dictionary`".into(),
             ));
         };
+        let objects = dict.remove(OBJECTS_KEY_NAME).map(Cow::Owned);
+        Self::from_header(dict, objects, options)
+    }
+
+    /// Creates a [KeyedArchive] from a borrowed [plist::Value]. It should be
+    /// the root value of a plist and have a keyed archive structure.
+    ///
+    /// Values of `$objects` are cloned one by one while they're converted,
+    /// so the whole plist isn't copied up front.
+    pub fn from_plist_ref(plist: &PlistValue) -> Result<Self, Error> {
+        Self::from_plist_ref_with_options(plist, &ParseOptions::default())
+    }
+
+    /// Creates a [KeyedArchive] from a borrowed [plist::Value] with given
+    /// [ParseOptions]. See [KeyedArchive::from_plist_ref].
+    pub fn from_plist_ref_with_options(
+        plist: &PlistValue,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        let Some(root) = plist.as_dictionary() else {
+            return Err(Error::IncorrectFormat(
+                "Expected root key to be a type of `Dictionary`".into(),
+            ));
+        };
+        let dict = root
+            .iter()
+            .filter(|(key, _)| key.as_str() != OBJECTS_KEY_NAME)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let objects = root.get(OBJECTS_KEY_NAME).map(Cow::Borrowed);
+        Self::from_header(dict, objects, options)
+    }
+
+    /// Checks header keys of a plist dictionary (without `$objects`, which is
+    /// passed separately) and creates a [KeyedArchive].
+    fn from_header(
+        mut dict: PlistDictionary,
+        objects: Option<Cow<'_, PlistValue>>,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        span!(DEBUG, "parse_archive");
 
         // Check $archiver key
         let archiver_key = Self::get_header_key(&mut dict, ARCHIVER_KEY_NAME)?;
@@ -345,24 +963,26 @@ impl KeyedArchive {
             )));
         };
 
-        if archiver_str != ARCHIVER {
+        if !options.is_supported_archiver(archiver_str) {
             return Err(Error::IncorrectFormat(format!(
                 "Unsupported archiver. Only `{ARCHIVER}` is supported"
             )));
         }
 
-        // Check $version key
-        let version_key = Self::get_header_key(&mut dict, VERSION_KEY_NAME)?;
-        let Some(version_num) = version_key.as_unsigned_integer() else {
-            return Err(Error::IncorrectFormat(format!(
-                "Expected `{VERSION_KEY_NAME}` key to be a type of 'Integer'"
-            )));
-        };
+        // Check $version key. Lenient options allow it to be missing.
+        if options.is_strict() || dict.contains_key(VERSION_KEY_NAME) {
+            let version_key = Self::get_header_key(&mut dict, VERSION_KEY_NAME)?;
+            let Some(version_num) = version_key.as_unsigned_integer() else {
+                return Err(Error::IncorrectFormat(format!(
+                    "Expected `{VERSION_KEY_NAME}` key to be a type of 'Integer'"
+                )));
+            };
 
-        if version_num != ARCHIVER_VERSION {
-            return Err(Error::IncorrectFormat(format!(
-                "Unsupported archiver version. Only `{ARCHIVER_VERSION}` is supported"
-            )));
+            if options.is_strict() && version_num != ARCHIVER_VERSION {
+                return Err(Error::IncorrectFormat(format!(
+                    "Unsupported archiver version. Only `{ARCHIVER_VERSION}` is supported"
+                )));
+            }
         }
 
         // Check $top key
@@ -374,42 +994,198 @@ impl KeyedArchive {
         };
 
         // Check $objects key
-        let objects_key = Self::get_header_key(&mut dict, OBJECTS_KEY_NAME)?;
-        let Some(raw_objects) = objects_key.into_array() else {
+        let Some(objects_key) = objects else {
+            return Err(Error::IncorrectFormat(format!(
+                "Missing `{OBJECTS_KEY_NAME}` header key"
+            )));
+        };
+        let raw_objects = match objects_key {
+            Cow::Owned(value) => value.into_array().map(Cow::Owned),
+            Cow::Borrowed(value) => value
+                .as_array()
+                .map(|array| Cow::Borrowed(array.as_slice())),
+        };
+        let Some(raw_objects) = raw_objects else {
             return Err(Error::IncorrectFormat(format!(
                 "Expected `{OBJECTS_KEY_NAME}` key to be a type of `Array`"
             )));
         };
 
-        let objects = Self::decode_objects(raw_objects)?;
+        let objects = Self::decode_objects(raw_objects, options)?;
         let mut top = HashMap::with_capacity(top_dict.len());
-        for (key, value) in top_dict {
-            if let Some(uid) = value.into_uid() {
-                top.insert(key.to_string(), objects[uid.get() as usize].clone());
+        let mut next_plain_uid = objects.len() + 1;
+        for (key, value) in top_dict.clone() {
+            if let Some(uid) = value.as_uid() {
+                let Some(value) = objects.get(uid.get() as usize) else {
+                    return Err(Error::IncorrectFormat(format!(
+                        "Incorrect uid of `{TOP_KEY_NAME}` entry `{key}`: {}",
+                        uid.get()
+                    )));
+                };
+                top.insert(key, value.clone());
+                continue;
             }
+            // Primitives encoded at the top level (e.g. with `encodeInteger:forKey:`)
+            // are stored as is. They aren't a part of `$objects`, so they get
+            // uids past its end (and past a placeholder of dangling elements).
+            let Some(variant) = Self::plain_variant(value) else {
+                return Err(Error::IncorrectFormat(format!(
+                    "`{TOP_KEY_NAME}` entry `{key}` is neither a reference nor a plain value"
+                )));
+            };
+            let value = ArchiveValue::new(variant, UniqueId::new(next_plain_uid));
+            top.insert(key, Rc::new(value));
+            next_plain_uid += 1;
         }
 
-        Ok(KeyedArchive { objects, top })
+        Ok(KeyedArchive {
+            top,
+            objects,
+            top_raw: top_dict,
+            // Only unknown keys are left
+            header: dict,
+            options: options.clone(),
+            file: None,
+            format: None,
+            class_index: OnceCell::new(),
+            ref_counts: OnceCell::new(),
+        })
     }
 
     /// Reads a plist file and creates a [KeyedArchive] from it.
     /// It should have a keyed archive structure.
     pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
-        let val: PlistValue = PlistValue::from_file(path)?;
-        Self::from_plist(val)
+        Self::from_file_with_options(path, &ParseOptions::default())
+    }
+
+    /// Reads a plist file and creates a [KeyedArchive] from it with given
+    /// [ParseOptions]. It should have a keyed archive structure.
+    pub fn from_file_with_options<P: AsRef<std::path::Path>>(
+        path: P,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        span!(DEBUG, "read_file", file = %path.as_ref().display());
+        // The file is read once, so its format is sniffed from the same bytes
+        let bytes = std::fs::read(&path)?;
+        let mut archive = Self::from_bytes_with_options(&bytes, options)?;
+        archive.file = Some(path.as_ref().to_path_buf());
+        Ok(archive)
     }
 
     /// Reads a plist from a byte slice and creates a [KeyedArchive] from it.
     /// It should have a keyed archive structure.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Self::from_bytes_with_options(bytes, &ParseOptions::default())
+    }
+
+    /// Reads a plist from a byte slice and creates a [KeyedArchive] from it
+    /// with given [ParseOptions]. It should have a keyed archive structure.
+    pub fn from_bytes_with_options(bytes: &[u8], options: &ParseOptions) -> Result<Self, Error> {
         let cursor = std::io::Cursor::new(bytes);
-        Self::from_reader(cursor)
+        Self::from_reader_with_options(cursor, options)
     }
 
     /// Reads a plist from a seekable byte stream and creates a [KeyedArchive] from it.
     /// It should have a keyed archive structure.
     pub fn from_reader<R: std::io::Read + std::io::Seek>(reader: R) -> Result<Self, Error> {
-        let val: PlistValue = PlistValue::from_reader(reader)?;
-        Self::from_plist(val)
+        Self::from_reader_with_options(reader, &ParseOptions::default())
+    }
+
+    /// Reads a plist from a seekable byte stream and creates a [KeyedArchive] from it
+    /// with given [ParseOptions]. It should have a keyed archive structure.
+    pub fn from_reader_with_options<R: std::io::Read + std::io::Seek>(
+        mut reader: R,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        let start = reader.stream_position()?;
+        let val: PlistValue = match PlistValue::from_reader(&mut reader) {
+            Ok(val) => val,
+            Err(e) => return Err(Self::plist_error(e, &mut reader, start)),
+        };
+        let mut archive = Self::from_plist_with_options(val, options)?;
+        archive.format = Format::detect_reader(&mut reader, start);
+        Ok(archive)
+    }
+
+    /// Converts an error of reading a plist from a stream. If the stream
+    /// turns out to be a legacy typedstream, [Error::UnsupportedLegacyArchiver]
+    /// is returned instead of a generic plist error. Binary plists of other
+    /// versions result in [Error::UnsupportedPlistVariant].
+    fn plist_error<R: std::io::Read + std::io::Seek>(
+        error: plist::Error,
+        reader: &mut R,
+        start: u64,
+    ) -> Error {
+        use std::io::Read;
+        let mut header = Vec::new();
+        let is_read = reader.seek(std::io::SeekFrom::Start(start)).is_ok()
+            && reader.by_ref().take(32).read_to_end(&mut header).is_ok();
+        if !is_read {
+            return error.into();
+        }
+        if typedstream::is_typedstream(&header) {
+            Error::UnsupportedLegacyArchiver
+        } else if let Some(variant) = unsupported_binary_variant(&header) {
+            Error::UnsupportedPlistVariant(variant)
+        } else {
+            error.into()
+        }
+    }
+
+    /// Reads a plist from a byte stream that doesn't support seeking (like
+    /// a socket) and creates a [KeyedArchive] from it. It should have a keyed
+    /// archive structure.
+    ///
+    /// See [KeyedArchive::from_stream_with_options].
+    pub fn from_stream<R: std::io::Read>(reader: R) -> Result<Self, Error> {
+        Self::from_stream_with_options(reader, &ParseOptions::default())
+    }
+
+    /// Reads a plist from a byte stream that doesn't support seeking (like
+    /// a socket) and creates a [KeyedArchive] from it with given
+    /// [ParseOptions]. It should have a keyed archive structure.
+    ///
+    /// Binary plists can only be parsed with random access, so the stream is
+    /// read into memory until its end. If an archive is a part of a larger
+    /// stream (e.g. a message payload), limit the reader to its length
+    /// with [Read::take](std::io::Read::take).
+    pub fn from_stream_with_options<R: std::io::Read>(
+        mut reader: R,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_bytes_with_options(&bytes, options)
+    }
+
+    /// Reads a plist from an asynchronous byte stream and creates
+    /// a [KeyedArchive] from it. It should have a keyed archive structure.
+    ///
+    /// See [KeyedArchive::from_async_reader_with_options].
+    #[cfg(feature = "tokio")]
+    pub async fn from_async_reader<R: tokio::io::AsyncRead + Unpin>(
+        reader: R,
+    ) -> Result<Self, Error> {
+        Self::from_async_reader_with_options(reader, &ParseOptions::default()).await
+    }
+
+    /// Reads a plist from an asynchronous byte stream and creates
+    /// a [KeyedArchive] from it with given [ParseOptions]. It should have
+    /// a keyed archive structure.
+    ///
+    /// Like [KeyedArchive::from_stream_with_options], the stream is read
+    /// into memory until its end before parsing, so limit the reader with
+    /// [AsyncReadExt::take](tokio::io::AsyncReadExt::take) if an archive is
+    /// a part of a larger stream. Use [AsyncKeyedArchiveStream] to read
+    /// several concatenated archives.
+    #[cfg(feature = "tokio")]
+    pub async fn from_async_reader_with_options<R: tokio::io::AsyncRead + Unpin>(
+        mut reader: R,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        use tokio::io::AsyncReadExt;
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Self::from_bytes_with_options(&bytes, options)
     }
 }
@@ -0,0 +1,62 @@
+use crate::foundation::expect_object;
+use crate::{ArchiveValue, DeError, Decodable, DecodeContext, ObjectValue, ValueRef};
+
+/// A decoded `NSClassSwapper`.
+///
+/// Interface Builder archives objects of custom classes as class swappers:
+/// they're decoded as an original class (e.g. `NSView`) and then replaced
+/// with an instance of a custom one (e.g. `MyView`). Fields of the original
+/// class are kept in the swapper object itself.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NSClassSwapper {
+    class_name: String,
+    original_class_name: String,
+    object: ValueRef,
+}
+
+impl NSClassSwapper {
+    /// Returns a name of the custom class.
+    pub fn class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    /// Returns a name of the original class the object is archived as.
+    pub fn original_class_name(&self) -> &str {
+        &self.original_class_name
+    }
+
+    /// Returns the swapper object. Its fields are fields of the original class.
+    pub fn object(&self) -> &ValueRef {
+        &self.object
+    }
+}
+
+impl Decodable for NSClassSwapper {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(value, &["NSClassSwapper"], ctx.is_probing())?;
+        let ObjectValue::Ref(object) = value else {
+            return Err(DeError::ExpectedObject);
+        };
+        Ok(Self {
+            class_name: obj.decode_string("NSClassName")?,
+            original_class_name: obj.decode_string("NSOriginalClassName")?,
+            object: object.clone(),
+        })
+    }
+}
+
+/// Returns a class of a nib object, taking class swappers into account:
+/// a custom class name is returned for an `NSClassSwapper`.
+///
+/// Returns [None] if the value isn't an object.
+pub fn effective_class(value: &ArchiveValue) -> Option<&str> {
+    let obj = value.as_object()?;
+    if obj.class() == "NSClassSwapper"
+        && let Ok(class_name) = obj.get_str("NSClassName")
+    {
+        return Some(class_name);
+    }
+    Some(obj.class())
+}
@@ -0,0 +1,106 @@
+use crate::{DeError, Decodable, DecodeContext, ObjectValue, ValueRef};
+
+/// A kind of a nib connection, determined by its class.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ConnectionKind {
+    /// An outlet (`NSNibOutletConnector`, `UIRuntimeOutletConnection`, etc.).
+    Outlet,
+    /// A target-action connection (`NSNibControlConnector`,
+    /// `UIRuntimeEventConnection`, etc.).
+    Action,
+    /// A Cocoa binding (`NSNibBindingConnector`).
+    Binding,
+    /// Any other connection (e.g. a plain `NSNibConnector`).
+    Other,
+}
+
+/// A decoded nib connection between two objects.
+///
+/// AppKit connectors use `NSSource`, `NSDestination` and `NSLabel` keys,
+/// UIKit ones use `UISource`, `UIDestination` and `UILabel`. The label is
+/// an outlet name, an action selector or a binding name.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NibConnection {
+    class: String,
+    kind: ConnectionKind,
+    source: Option<ValueRef>,
+    destination: Option<ValueRef>,
+    label: Option<String>,
+}
+
+impl NibConnection {
+    /// Returns a class of the connection object.
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+
+    /// Returns a kind of the connection.
+    pub fn kind(&self) -> ConnectionKind {
+        self.kind
+    }
+
+    /// Returns a source object.
+    pub fn source(&self) -> Option<&ValueRef> {
+        self.source.as_ref()
+    }
+
+    /// Returns a destination object.
+    pub fn destination(&self) -> Option<&ValueRef> {
+        self.destination.as_ref()
+    }
+
+    /// Returns a label of the connection.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+}
+
+impl Decodable for NibConnection {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let ObjectValue::Ref(value) = value else {
+            return Err(DeError::ExpectedObject);
+        };
+        let obj = value.as_object().ok_or(DeError::ExpectedObject)?;
+        let class = obj.class();
+        let prefix = if obj.contains_key("UISource") || obj.contains_key("UIDestination") {
+            "UI"
+        } else {
+            "NS"
+        };
+        let kind = if class.contains("Outlet") {
+            ConnectionKind::Outlet
+        } else if class.contains("Control") || class.contains("Event") || class.contains("Action") {
+            ConnectionKind::Action
+        } else if class.contains("Binding") {
+            ConnectionKind::Binding
+        } else {
+            ConnectionKind::Other
+        };
+
+        let mut reference = |key: &str| -> Result<Option<ValueRef>, DeError> {
+            match obj.as_map().get(key) {
+                Some(ObjectValue::Ref(value)) if value.is_null_ref() => Ok(None),
+                Some(value) => ctx.decode_field(value, key).map(Some),
+                None => Ok(None),
+            }
+        };
+        let source = reference(&format!("{prefix}Source"))?;
+        let destination = reference(&format!("{prefix}Destination"))?;
+        let label_key = format!("{prefix}Label");
+        let label = match obj.as_map().get(&label_key) {
+            Some(ObjectValue::Ref(value)) if value.is_null_ref() => None,
+            Some(value) => Some(ctx.decode_field(value, &label_key)?),
+            None => None,
+        };
+
+        Ok(Self {
+            class: class.into(),
+            kind,
+            source,
+            destination,
+            label,
+        })
+    }
+}
@@ -0,0 +1,17 @@
+//! Decoders for compiled NIB (and storyboard) keyed archives.
+//!
+//! AppKit nibs keep their objects in an `NSIBObjectData` object under the
+//! `IB.objectdata` top key, while UIKit nibs store them as `UINib*Key` top
+//! keys. [NibObjectData::from_archive] handles both of them.
+//!
+//! Objects of a nib are kept as [ValueRef](crate::ValueRef) values, since
+//! they may be of any class and usually refer to each other (e.g. a view and
+//! its superview). They may be decoded further as needed.
+
+mod class_swapper;
+mod connection;
+mod object_data;
+
+pub use class_swapper::*;
+pub use connection::*;
+pub use object_data::*;
@@ -0,0 +1,209 @@
+use super::NibConnection;
+use crate::foundation::expect_object;
+use crate::{
+    DeError, Decodable, DecodeContext, KeyedArchive, Object, ObjectValue, UniqueId, ValueRef,
+};
+use std::collections::HashMap;
+
+/// An object of a nib with its parent, name and object id if the nib
+/// has them.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NibObject {
+    object: ValueRef,
+    parent: Option<ValueRef>,
+    name: Option<String>,
+    oid: Option<i64>,
+}
+
+impl NibObject {
+    /// Returns the object itself.
+    pub fn object(&self) -> &ValueRef {
+        &self.object
+    }
+
+    /// Returns a parent object (e.g. a window of a view).
+    pub fn parent(&self) -> Option<&ValueRef> {
+        self.parent.as_ref()
+    }
+
+    /// Returns a name of the object, like `File's Owner`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns an Interface Builder object id.
+    pub fn oid(&self) -> Option<i64> {
+        self.oid
+    }
+}
+
+/// Decoded object tables of a nib: objects, their parents and names,
+/// connections and visible windows.
+///
+/// It's decoded from an `NSIBObjectData` object of AppKit nibs. Use
+/// [NibObjectData::from_archive] to get it from either an AppKit or a UIKit
+/// nib archive.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NibObjectData {
+    root: Option<ValueRef>,
+    top_level_objects: Vec<ValueRef>,
+    objects: Vec<NibObject>,
+    connections: Vec<NibConnection>,
+    visible_windows: Vec<ValueRef>,
+}
+
+impl NibObjectData {
+    /// Decodes object tables of an AppKit (`IB.objectdata` top key) or
+    /// a UIKit (`UINib*Key` top keys) nib archive.
+    pub fn from_archive(archive: &KeyedArchive) -> Result<Self, DeError> {
+        if archive.top_value("IB.objectdata").is_some() {
+            return archive.decode_top("IB.objectdata");
+        }
+        if archive.top_value("UINibObjectsKey").is_none() {
+            return Err(DeError::Custom(
+                "Not a nib archive: neither `IB.objectdata` nor `UINibObjectsKey` is found".into(),
+            ));
+        }
+        let objects: Vec<ValueRef> = archive.decode_top("UINibObjectsKey")?;
+        Ok(Self {
+            root: None,
+            top_level_objects: optional_top(archive, "UINibTopLevelObjectsKey")?,
+            objects: objects
+                .into_iter()
+                .map(|object| NibObject {
+                    object,
+                    parent: None,
+                    name: None,
+                    oid: None,
+                })
+                .collect(),
+            connections: optional_top(archive, "UINibConnectionsKey")?,
+            visible_windows: optional_top(archive, "UINibVisibleWindowsKey")?,
+        })
+    }
+
+    /// Returns a root object (`NSRoot`), usually the File's Owner.
+    /// UIKit nibs don't have one.
+    pub fn root(&self) -> Option<&ValueRef> {
+        self.root.as_ref()
+    }
+
+    /// Returns top level objects: objects whose parent is the root in
+    /// AppKit nibs or `UINibTopLevelObjectsKey` objects in UIKit ones.
+    pub fn top_level_objects(&self) -> &[ValueRef] {
+        &self.top_level_objects
+    }
+
+    /// Returns all objects of the nib.
+    pub fn objects(&self) -> &[NibObject] {
+        &self.objects
+    }
+
+    /// Returns an object with a given `id` if it's a part of the nib.
+    pub fn object(&self, id: UniqueId) -> Option<&NibObject> {
+        self.objects.iter().find(|o| *o.object.unique_id() == id)
+    }
+
+    /// Returns outlet, action and other connections.
+    pub fn connections(&self) -> &[NibConnection] {
+        &self.connections
+    }
+
+    /// Returns windows that are visible at launch.
+    pub fn visible_windows(&self) -> &[ValueRef] {
+        &self.visible_windows
+    }
+}
+
+/// Decodes an optional array top key.
+fn optional_top<T: Decodable>(archive: &KeyedArchive, key: &str) -> Result<Vec<T>, DeError> {
+    match archive.top_value(key) {
+        Some(_) => archive.decode_top(key),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Decodes a value of an optional array field.
+fn optional_array<T: Decodable>(
+    obj: &Object,
+    key: &str,
+    ctx: &mut DecodeContext,
+) -> Result<Vec<T>, DeError> {
+    match obj.as_map().get(key) {
+        Some(ObjectValue::Ref(value)) if value.is_null_ref() => Ok(Vec::new()),
+        Some(value) => ctx.decode_field(value, key),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Decodes a pair of key and value arrays (like `NSNamesKeys` and
+/// `NSNamesValues`) into a map by unique ids of keys.
+fn table<T: Decodable>(
+    obj: &Object,
+    keys: &str,
+    values: &str,
+    ctx: &mut DecodeContext,
+) -> Result<HashMap<UniqueId, T>, DeError> {
+    let table_keys: Vec<ValueRef> = optional_array(obj, keys, ctx)?;
+    let table_values: Vec<T> = optional_array(obj, values, ctx)?;
+    if table_keys.len() != table_values.len() {
+        return Err(DeError::Custom(format!(
+            "{}: `{keys}` and `{values}` have different lengths",
+            obj.class()
+        )));
+    }
+    Ok(table_keys
+        .iter()
+        .map(|key| *key.unique_id())
+        .zip(table_values)
+        .collect())
+}
+
+impl Decodable for NibObjectData {
+    decode_with_new_context!();
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        let obj = expect_object(value, &["NSIBObjectData"], ctx.is_probing())?;
+        let root = match obj.as_map().get("NSRoot") {
+            Some(ObjectValue::Ref(root)) if !root.is_null_ref() => Some(root.clone()),
+            _ => None,
+        };
+
+        let parents: Vec<ValueRef> = optional_array(obj, "NSObjectsValues", ctx)?;
+        let objects: Vec<ValueRef> = optional_array(obj, "NSObjectsKeys", ctx)?;
+        if objects.len() != parents.len() {
+            return Err(DeError::Custom(format!(
+                "{}: `NSObjectsKeys` and `NSObjectsValues` have different lengths",
+                obj.class()
+            )));
+        }
+        let mut names: HashMap<UniqueId, String> = table(obj, "NSNamesKeys", "NSNamesValues", ctx)?;
+        let oids: HashMap<UniqueId, i64> = table(obj, "NSOidsKeys", "NSOidsValues", ctx)?;
+
+        let mut top_level_objects = Vec::new();
+        let mut nib_objects = Vec::with_capacity(objects.len());
+        for (object, parent) in objects.into_iter().zip(parents) {
+            let parent = (!parent.is_null_ref()).then_some(parent);
+            if let (Some(parent), Some(root)) = (&parent, &root)
+                && parent.unique_id() == root.unique_id()
+            {
+                top_level_objects.push(object.clone());
+            }
+            let id = *object.unique_id();
+            nib_objects.push(NibObject {
+                name: names.remove(&id),
+                oid: oids.get(&id).copied(),
+                object,
+                parent,
+            });
+        }
+
+        Ok(Self {
+            root,
+            top_level_objects,
+            objects: nib_objects,
+            connections: optional_array(obj, "NSConnections", ctx)?,
+            visible_windows: optional_array(obj, "NSVisibleWindows", ctx)?,
+        })
+    }
+}
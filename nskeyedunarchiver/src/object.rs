@@ -1,6 +1,12 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::rc::Rc;
 
-use crate::{Data, DeError, Decodable, Error, Integer, ValueRef, NULL_OBJECT_REFERENCE_NAME};
+use crate::decodable::{borrow_bytes, borrow_str};
+use crate::foundation::AppleTime;
+use crate::{
+    Data, Date, DeError, Decodable, DecodeContext, Error, Integer, NULL_OBJECT_REFERENCE_NAME,
+    UniqueId, ValueRef,
+};
 use plist::{Dictionary as PlistDictionary, Value as PlistValue};
 
 macro_rules! get_key {
@@ -29,6 +35,9 @@ macro_rules! get_key {
     }};
 }
 
+/// A `classes_uid` of anonymous objects that have no `$class` reference.
+const ANONYMOUS_CLASSES_UID: u64 = u64::MAX;
+
 /// Uninitiated references to other objects (uids)
 #[derive(Debug, PartialEq, Clone)]
 enum UninitRefs {
@@ -44,6 +53,9 @@ pub enum ObjectValue {
     Real(f64),
     Boolean(bool),
     Data(Vec<u8>),
+    /// A plain plist date. `NSKeyedArchiver` archives dates as `NSDate`
+    /// objects, but some third-party encoders store them as is.
+    Date(Date),
     RefArray(Vec<ValueRef>),
     Ref(ValueRef),
     NullRef,
@@ -57,11 +69,129 @@ impl ObjectValue {
             ObjectValue::Real(_) => "f64",
             ObjectValue::Boolean(_) => "boolean",
             ObjectValue::Data(_) => "data",
+            ObjectValue::Date(_) => "date",
             ObjectValue::RefArray(_) => "array of object references",
             ObjectValue::Ref(_) => "object reference",
             ObjectValue::NullRef => "null reference",
         }
     }
+
+    /// Returns a string of a plain string value, a reference to one or an
+    /// NSString object.
+    pub fn as_str(&self) -> Option<&str> {
+        self.try_into().ok()
+    }
+
+    /// Returns an integer of a plain integer value or a reference to one
+    /// if it fits into [i64].
+    pub fn as_i64(&self) -> Option<i64> {
+        self.try_into().ok()
+    }
+
+    /// Returns a float of a plain float value or a reference to one.
+    pub fn as_f64(&self) -> Option<f64> {
+        self.try_into().ok()
+    }
+
+    /// Returns a boolean of a plain boolean value or a reference to one.
+    pub fn as_bool(&self) -> Option<bool> {
+        self.try_into().ok()
+    }
+
+    /// Returns bytes of a plain data value, a reference to one or an NSData
+    /// object.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        self.try_into().ok()
+    }
+
+    /// Interprets a plain real or integer value (or a reference to one) as
+    /// a number of seconds since 1 January 2001, see [AppleTime].
+    pub fn as_apple_timestamp(&self) -> Option<AppleTime> {
+        let seconds = match self.as_f64() {
+            Some(seconds) => seconds,
+            None => self.as_i64()? as f64,
+        };
+        Some(AppleTime::from_seconds(seconds))
+    }
+
+    /// Returns a plain date value.
+    pub fn as_date(&self) -> Option<Date> {
+        match self {
+            ObjectValue::Date(date) => Some(*date),
+            _ => None,
+        }
+    }
+
+    /// Returns a referenced value.
+    pub fn as_value_ref(&self) -> Option<&ValueRef> {
+        match self {
+            ObjectValue::Ref(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns a referenced object.
+    pub fn as_object(&self) -> Option<&Object> {
+        self.as_value_ref()?.as_object()
+    }
+
+    /// Returns referenced values of an array of references.
+    pub fn as_ref_array(&self) -> Option<&[ValueRef]> {
+        match self {
+            ObjectValue::RefArray(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if it's a null reference (either [ObjectValue::NullRef]
+    /// or a reference to `$null`).
+    pub fn is_null_ref(&self) -> bool {
+        match self {
+            ObjectValue::NullRef => true,
+            ObjectValue::Ref(value) => value.is_null_ref(),
+            _ => false,
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a ObjectValue> for &'a str {
+    type Error = DeError;
+
+    fn try_from(value: &'a ObjectValue) -> Result<Self, Self::Error> {
+        borrow_str(value, false)
+    }
+}
+
+impl<'a> TryFrom<&'a ObjectValue> for &'a [u8] {
+    type Error = DeError;
+
+    fn try_from(value: &'a ObjectValue) -> Result<Self, Self::Error> {
+        borrow_bytes(value, false)
+    }
+}
+
+impl TryFrom<&ObjectValue> for i64 {
+    type Error = DeError;
+
+    fn try_from(value: &ObjectValue) -> Result<Self, Self::Error> {
+        i64::decode(value)
+    }
+}
+
+impl TryFrom<&ObjectValue> for f64 {
+    type Error = DeError;
+
+    fn try_from(value: &ObjectValue) -> Result<Self, Self::Error> {
+        f64::decode(value)
+    }
+}
+
+impl TryFrom<&ObjectValue> for bool {
+    type Error = DeError;
+
+    fn try_from(value: &ObjectValue) -> Result<Self, Self::Error> {
+        bool::decode(value)
+    }
 }
 
 impl From<ValueRef> for ObjectValue {
@@ -76,13 +206,104 @@ impl From<&ValueRef> for ObjectValue {
     }
 }
 
+/// Values of an [Object] by their keys.
+///
+/// Objects usually have only a handful of keys, so they're kept in a vector
+/// rather than in a hash map. Fields keep the order of an archive
+/// dictionary. Keys are shared between all objects of an archive.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Fields(Vec<(Rc<str>, ObjectValue)>);
+
+impl Fields {
+    /// Returns a value with a given `key`.
+    pub fn get(&self, key: &str) -> Option<&ObjectValue> {
+        self.0.iter().find(|(k, _)| &**k == key).map(|(_, v)| v)
+    }
+
+    /// Returns a value with a given `key` ignoring ASCII case, so `NSString`
+    /// matches `nsstring`. An exact match is preferred.
+    pub fn get_ignore_case(&self, key: &str) -> Option<&ObjectValue> {
+        self.get(key).or_else(|| {
+            self.0
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v)
+        })
+    }
+
+    /// Checks if there's a value with a given `key`.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.0.iter().any(|(k, _)| &**k == key)
+    }
+
+    /// Returns an iterator over keys.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|(k, _)| &**k)
+    }
+
+    /// Returns an iterator over values.
+    pub fn values(&self) -> impl Iterator<Item = &ObjectValue> {
+        self.0.iter().map(|(_, v)| v)
+    }
+
+    /// Returns an iterator over keys and values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ObjectValue)> {
+        self.0.iter().map(|(k, v)| (&**k, v))
+    }
+
+    /// Returns a key and a value at a given `index`.
+    pub(crate) fn get_index(&self, index: usize) -> Option<(&str, &ObjectValue)> {
+        self.0.get(index).map(|(k, v)| (&**k, v))
+    }
+
+    /// Returns the number of values.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn push(&mut self, key: Rc<str>, value: ObjectValue) {
+        self.0.push((key, value));
+    }
+}
+
+impl std::ops::Index<&str> for Fields {
+    type Output = ObjectValue;
+
+    /// Returns a value with a given `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key isn't present.
+    fn index(&self, key: &str) -> &Self::Output {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+/// A set of interned object keys, shared between all objects of an archive.
+pub(crate) type KeyInterner = HashSet<Rc<str>>;
+
+fn intern(keys: &mut KeyInterner, key: String) -> Rc<str> {
+    if let Some(key) = keys.get(key.as_str()) {
+        return key.clone();
+    }
+    let key: Rc<str> = key.into();
+    keys.insert(key.clone());
+    key
+}
+
 /// A raw keyed archive object.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Object {
     classes: Option<ValueRef>,
     classes_uid: u64,
-    fields: HashMap<String, ObjectValue>,
-    uninit_fields: Option<HashMap<String, UninitRefs>>,
+    fields: Fields,
+    // Indices of fields that are references and their uids
+    uninit_fields: Option<Vec<(usize, UninitRefs)>>,
 }
 
 impl Object {
@@ -95,10 +316,23 @@ impl Object {
     /// Tries to decode a value as a data (a vector of bytes) with a given `key`.
     /// If it doesn't exist or has some other type a [DeError] is returned.
     pub fn decode_data(&self, key: &str) -> Result<Data, DeError> {
-        if self.contains_key(key) {
+        Ok(self.get_bytes(key)?.to_vec().into())
+    }
+
+    /// Returns a reference to a data (or NSData) value with a given `key`
+    /// without copying it.
+    /// If it doesn't exist or has some other type a [DeError] is returned.
+    pub fn get_bytes(&self, key: &str) -> Result<&[u8], DeError> {
+        let Some(value) = self.fields.get(key) else {
             return Err(DeError::MissingObjectKey(self.class().into(), key.into()));
-        }
-        Data::decode(self.fields.get(key).unwrap())
+        };
+        borrow_bytes(value, false)
+    }
+
+    /// Tries to decode a value as a plain plist date with a given `key`.
+    /// If it doesn't exist or has some other type a [DeError] is returned.
+    pub fn decode_date(&self, key: &str) -> Result<Date, DeError> {
+        Ok(*get_key!(self, key, "date"))
     }
 
     /// Tries to decode a value as a float with a given `key`.
@@ -119,10 +353,17 @@ impl Object {
     /// NSKeyedArchive objects don't contain plain strings, rather
     /// references to a string value. This function just makes it easy to access.
     pub fn decode_string(&self, key: &str) -> Result<String, DeError> {
-        if self.contains_key(key) {
+        Ok(self.get_str(key)?.into())
+    }
+
+    /// Returns a reference to a string (or NSString) value with a given `key`
+    /// without copying it.
+    /// If it doesn't exist or has some other type a [DeError] is returned.
+    pub fn get_str(&self, key: &str) -> Result<&str, DeError> {
+        let Some(value) = self.fields.get(key) else {
             return Err(DeError::MissingObjectKey(self.class().into(), key.into()));
-        }
-        String::decode(self.fields.get(key).unwrap())
+        };
+        borrow_str(value, false)
     }
 
     /// Tries to decode a value as an object with a given `key` and returns a
@@ -137,12 +378,14 @@ impl Object {
 
     /// Tries to decode a value as a `<T>` object with a given `key`.
     /// If it doesn't exist or has some other type a [DeError] is returned.
+    ///
+    /// The value is decoded with [Decodable::decode_ref], so its reference
+    /// isn't cloned.
     pub fn decode_object_as<T>(&self, key: &str) -> Result<T, DeError>
     where
         T: Decodable,
     {
-        let obj = get_key!(self, key, "ref");
-        T::decode(&obj.into())
+        DecodeContext::new().decode_ref(get_key!(self, key, "ref"))
     }
 
     /// Tries to decode a value as an array of value references with a given `key`.
@@ -152,6 +395,47 @@ impl Object {
         Ok(array)
     }
 
+    /// Tries to decode every element of an array with a given `key` as `<T>`.
+    /// If it doesn't exist, has some other type or any of the elements can't
+    /// be decoded a [DeError] is returned.
+    pub fn decode_array_as<T: Decodable>(&self, key: &str) -> Result<Vec<T>, DeError> {
+        self.decode_array_with(key, &mut DecodeContext::new())
+    }
+
+    /// Tries to decode every element of an array with a given `key` as `<T>`
+    /// sharing a [DecodeContext]. Custom [Decodable::decode_with] impls should
+    /// use it instead of [Self::decode_array_as].
+    pub fn decode_array_with<T: Decodable>(
+        &self,
+        key: &str,
+        ctx: &mut DecodeContext,
+    ) -> Result<Vec<T>, DeError> {
+        self.decode_array(key)?
+            .iter()
+            .enumerate()
+            .map(|(index, value)| ctx.decode_element_ref(value, index))
+            .collect()
+    }
+
+    /// Tries to decode an element of an array with a given `key` at `index`
+    /// as `<T>`. If the array doesn't exist, has some other type or has no
+    /// such element a [DeError] is returned.
+    pub fn decode_array_item_as<T: Decodable>(
+        &self,
+        key: &str,
+        index: usize,
+    ) -> Result<T, DeError> {
+        let array = self.decode_array(key)?;
+        let Some(value) = array.get(index) else {
+            return Err(DeError::Custom(format!(
+                "{}: Array `{key}` has {} elements, index {index} is out of bounds",
+                self.class(),
+                array.len()
+            )));
+        };
+        T::decode(&value.into())
+    }
+
     /// Returns the number of object's keys.
     pub fn len(&self) -> usize {
         self.fields.len()
@@ -162,11 +446,17 @@ impl Object {
         self.fields.is_empty()
     }
 
-    /// Returns an array of object's keys.
-    pub fn keys(&self) -> Vec<&String> {
+    /// Returns an array of object's keys in the order of an archive.
+    pub fn keys(&self) -> Vec<&str> {
         self.fields.keys().collect()
     }
 
+    /// Returns an iterator over keys and values of the object in the order
+    /// they're stored in an archive.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ObjectValue)> {
+        self.fields.iter()
+    }
+
     /// Checks if a value under the `key` is a null reference.
     /// Returns a [DeError] if a value doesn't exist.
     pub fn is_null_ref(&self, key: &str) -> Result<bool, DeError> {
@@ -179,105 +469,319 @@ impl Object {
         ))
     }
 
+    /// Returns a uid of a value under the `key` if it's a reference to
+    /// another archive value. Returns [None] if the value is stored inline
+    /// (e.g. a plain integer), is an array of references or doesn't exist.
+    pub fn field_uid(&self, key: &str) -> Option<UniqueId> {
+        match self.fields.get(key)? {
+            ObjectValue::Ref(value) => Some(*value.unique_id()),
+            _ => None,
+        }
+    }
+
+    /// Returns uids of values under the `key` if it's an array of references.
+    pub fn field_uids(&self, key: &str) -> Option<Vec<UniqueId>> {
+        match self.fields.get(key)? {
+            ObjectValue::RefArray(values) => {
+                Some(values.iter().map(|value| *value.unique_id()).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns a uid of the object's `$classes` entry. Anonymous objects
+    /// don't have one, so check [Object::is_anonymous] first.
+    pub fn classes_uid(&self) -> UniqueId {
+        UniqueId::new(self.classes_uid as usize)
+    }
+
+    /// Checks if the object has no `$class` reference. Such objects are only
+    /// created with [ParseOptions::with_anonymous_objects](crate::ParseOptions::with_anonymous_objects).
+    /// Their class name is empty.
+    pub fn is_anonymous(&self) -> bool {
+        self.classes_uid == ANONYMOUS_CLASSES_UID
+    }
+
     /// Checks if the object contains a value with a given `key`.
     pub fn contains_key(&self, key: &str) -> bool {
         self.fields.contains_key(key)
     }
 
-    /// Returns the underlying [HashMap] of values.
-    pub fn as_map(&self) -> &HashMap<String, ObjectValue> {
+    /// Returns a value with a given `key` if it exists.
+    pub fn get(&self, key: &str) -> Option<&ObjectValue> {
+        self.fields.get(key)
+    }
+
+    /// Returns a value with a given `key` ignoring ASCII case. Some encoders
+    /// change the case of keys between versions.
+    pub fn get_ignore_case(&self, key: &str) -> Option<&ObjectValue> {
+        self.fields.get_ignore_case(key)
+    }
+
+    /// Tries to decode a value with a given `key` as `<T>`. Returns [None]
+    /// if it doesn't exist or is a null reference, and a [DeError] if it
+    /// exists but can't be decoded.
+    pub fn get_as<T: Decodable>(&self, key: &str) -> Result<Option<T>, DeError> {
+        match self.fields.get(key) {
+            Some(value) => Option::<T>::decode(value),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the underlying [Fields] of values.
+    pub fn as_map(&self) -> &Fields {
         &self.fields
     }
 
     /// Returns classes of the object. The first one is the actual class,
     /// the other ones are its parents.
     pub fn classes(&self) -> &[String] {
-        let a = self.classes.as_ref().unwrap();
-        let b = a.as_classes().unwrap();
-        b
+        match &self.classes {
+            Some(a) => a.as_classes().unwrap(),
+            None => &[],
+        }
+    }
+
+    /// Returns parents of the object's class, from the nearest one up to
+    /// the root class (usually `NSObject`).
+    pub fn superclasses(&self) -> &[String] {
+        self.classes().get(1..).unwrap_or_default()
     }
 
     /// Returns a class of the object
     pub fn class(&self) -> &str {
-        let a = self.classes.as_ref().unwrap();
-        &a.as_classes().as_ref().unwrap()[0]
+        match self.classes().first() {
+            Some(class) => class,
+            None => "",
+        }
     }
 
-    /// Applies Rc pointers to object fields, replacing UninitRefs with normal ones
-    pub(crate) fn apply_value_refs(&mut self, tree: &[ValueRef]) -> Result<(), Error> {
-        self.classes = Some(tree[self.classes_uid as usize].clone());
-        if !self.classes.as_ref().unwrap().is_classes() {
-            return Err(Error::IncorrectFormat(format!(
-                "Incorrent Classes structure (uid: {})",
-                self.classes_uid
-            )));
+    /// Returns class hints (`$classhints`) of the object: classes that may
+    /// be used instead of its class if it's unknown.
+    pub fn class_hints(&self) -> &[String] {
+        match &self.classes {
+            Some(a) => a.as_class_hints().unwrap(),
+            None => &[],
         }
+    }
+
+    /// Checks if the object has a given class. If it doesn't, its class
+    /// hints are checked instead.
+    ///
+    /// An `NSClassSwapper` of Interface Builder archives also matches its
+    /// custom (`NSClassName`) and original (`NSOriginalClassName`) classes,
+    /// since it keeps fields of the original class in itself. So objects of
+    /// custom classes are decoded transparently.
+    pub fn matches_class(&self, class: &str) -> bool {
+        if self.class() == class || self.class_hints().iter().any(|hint| hint == class) {
+            return true;
+        }
+        self.class() == "NSClassSwapper"
+            && ["NSClassName", "NSOriginalClassName"]
+                .iter()
+                .any(|key| self.get_str(key).is_ok_and(|name| name == class))
+    }
+
+    /// Checks if the object is of a given class or of its subclass, like
+    /// `-[NSObject isKindOfClass:]`. Unlike [Object::matches_class], the
+    /// whole class chain is checked, so any view inherits from
+    /// `NSResponder`.
+    pub fn inherits_from(&self, class: &str) -> bool {
+        self.matches_class(class) || self.classes().iter().any(|c| c == class)
+    }
+
+    /// Applies Rc pointers to object fields, replacing UninitRefs with normal ones.
+    /// If `null` is given, references outside of `tree` are replaced with it
+    /// (or with [ObjectValue::NullRef]) instead of failing.
+    pub(crate) fn apply_value_refs(
+        &mut self,
+        tree: &[ValueRef],
+        uid: UniqueId,
+        null: Option<&ValueRef>,
+    ) -> Result<(), Error> {
+        // Anonymous objects have no classes
+        if !self.is_anonymous() {
+            let Some(classes) = tree.get(self.classes_uid as usize) else {
+                return Err(Error::DanglingReference {
+                    from_uid: uid,
+                    key: "$class".into(),
+                    to_uid: self.classes_uid,
+                });
+            };
+            if !classes.is_classes() {
+                return Err(Error::IncorrectFormat(format!(
+                    "Incorrent Classes structure (uid: {})",
+                    self.classes_uid
+                )));
+            }
+            self.classes = Some(classes.clone());
+        }
+
+        let dangling = |fields: &Fields, index: usize, to_uid: u64| Error::DanglingReference {
+            from_uid: uid,
+            key: fields.0[index].0.to_string(),
+            to_uid,
+        };
 
-        for (key, value) in self.uninit_fields.take().unwrap() {
-            match value {
+        // References are written into their placeholders, so fields keep
+        // the order of an archive. Array elements are kept in place, arrays
+        // of dictionary keys and values should have equal lengths
+        for (index, value) in self.uninit_fields.take().unwrap() {
+            let value = match value {
                 UninitRefs::RawRefArray(raw_ref_array) => {
                     let mut ref_arr = Vec::with_capacity(raw_ref_array.len());
                     for item in raw_ref_array {
-                        if let Some(obj_ref) = tree.get(item as usize) {
-                            ref_arr.push(obj_ref.clone())
-                        } else {
-                            return Err(Error::IncorrectFormat(format!(
-                                "Incorrent object uid: {item}"
-                            )));
+                        match tree.get(item as usize) {
+                            Some(obj_ref) => ref_arr.push(obj_ref.clone()),
+                            None => match null {
+                                Some(null) => ref_arr.push(null.clone()),
+                                None => return Err(dangling(&self.fields, index, item)),
+                            },
                         }
                     }
-                    self.fields.insert(key, ObjectValue::RefArray(ref_arr));
-                }
-                UninitRefs::RawRef(raw_ref) => {
-                    if let Some(obj_ref) = tree.get(raw_ref as usize) {
-                        self.fields.insert(key, ObjectValue::Ref(obj_ref.clone()));
-                    } else {
-                        return Err(Error::IncorrectFormat(format!(
-                            "Incorrent object uid: {raw_ref}"
-                        )));
-                    }
+                    ObjectValue::RefArray(ref_arr)
                 }
-            }
+                UninitRefs::RawRef(raw_ref) => match tree.get(raw_ref as usize) {
+                    Some(obj_ref) => ObjectValue::Ref(obj_ref.clone()),
+                    None if null.is_some() => ObjectValue::NullRef,
+                    None => return Err(dangling(&self.fields, index, raw_ref)),
+                },
+            };
+            self.fields.0[index].1 = value;
         }
         Ok(())
     }
 
-    pub(crate) fn from_dict(mut dict: PlistDictionary) -> Result<Self, Error> {
-        // unwrapping is safe, we previously check it with is_container()
-        let classes_uid = dict.remove("$class").unwrap().into_uid().unwrap().get();
-        let mut fields = HashMap::with_capacity(dict.len());
-        let mut uninit_fields = HashMap::with_capacity(dict.len());
+    /// Creates an object from a [RawObject], interning its keys.
+    pub(crate) fn from_raw(raw: RawObject, keys: &mut KeyInterner) -> Self {
+        let mut fields = Fields(Vec::with_capacity(raw.fields.len()));
+        let mut uninit_fields = Vec::new();
+        for (key, value) in raw.fields {
+            let key = intern(keys, key);
+            let value = match value {
+                RawField::Boolean(b) => ObjectValue::Boolean(b),
+                RawField::Data(d) => ObjectValue::Data(d),
+                RawField::Date(d) => ObjectValue::Date(d),
+                RawField::Real(f) => ObjectValue::Real(f),
+                RawField::Integer(i) => ObjectValue::Integer(i),
+                RawField::String(s) => ObjectValue::String(s),
+                RawField::NullRef => ObjectValue::NullRef,
+                RawField::Refs(refs) => {
+                    uninit_fields.push((fields.len(), refs));
+                    ObjectValue::NullRef
+                }
+            };
+            fields.push(key, value);
+        }
+        Self {
+            classes: None,
+            classes_uid: raw.classes_uid,
+            fields,
+            uninit_fields: Some(uninit_fields),
+        }
+    }
+
+    /// Clones an object without its references. They're turned back into
+    /// uids, so the copy should be linked with [Object::apply_value_refs].
+    pub(crate) fn clone_unlinked(&self, keys: &mut KeyInterner) -> Self {
+        let mut fields = Fields(Vec::with_capacity(self.fields.len()));
+        let mut uninit_fields = Vec::new();
+        for (key, value) in &self.fields.0 {
+            let key = intern(keys, key.to_string());
+            let refs = match value {
+                ObjectValue::Ref(value) => UninitRefs::RawRef(value.unique_id().get() as u64),
+                ObjectValue::RefArray(values) => UninitRefs::RawRefArray(
+                    values
+                        .iter()
+                        .map(|value| value.unique_id().get() as u64)
+                        .collect(),
+                ),
+                value => {
+                    fields.push(key, value.clone());
+                    continue;
+                }
+            };
+            uninit_fields.push((fields.len(), refs));
+            fields.push(key, ObjectValue::NullRef);
+        }
+        Self {
+            classes: None,
+            classes_uid: self.classes_uid,
+            fields,
+            uninit_fields: Some(uninit_fields),
+        }
+    }
+}
+
+/// A value of a [RawObject] field.
+#[derive(Debug)]
+enum RawField {
+    Boolean(bool),
+    Data(Vec<u8>),
+    Date(Date),
+    Real(f64),
+    Integer(Integer),
+    String(String),
+    NullRef,
+    Refs(UninitRefs),
+}
+
+/// An object that is checked and converted from a plist dictionary, but
+/// isn't turned into an [Object] yet. Unlike [Object] it contains no [Rc]
+/// pointers, so it can be created on any thread.
+#[derive(Debug)]
+pub(crate) struct RawObject {
+    classes_uid: u64,
+    fields: Vec<(String, RawField)>,
+}
+
+impl RawObject {
+    pub(crate) fn from_dict(dict: PlistDictionary) -> Result<Self, Error> {
+        // Dictionaries without `$class` get here only if anonymous objects are allowed
+        let classes_uid = match dict.get("$class") {
+            Some(value) => match value.as_uid() {
+                Some(uid) => uid.get(),
+                None => {
+                    return Err(Error::IncorrectFormat(
+                        "An object should have a `$class` reference".into(),
+                    ));
+                }
+            },
+            None => ANONYMOUS_CLASSES_UID,
+        };
+        let mut fields = Vec::with_capacity(dict.len());
         for (key, obj) in dict {
-            let decoded_obj = match obj {
+            // `$class` isn't removed from the dictionary, since removing
+            // changes the order of keys
+            if key == "$class" {
+                continue;
+            }
+            let field = match obj {
                 PlistValue::Array(values) => {
                     let mut arr_of_uids = Vec::with_capacity(values.len());
                     for val in values {
-                        if val.as_uid().is_none() {
+                        let Some(uid) = val.into_uid() else {
                             return Err(Error::IncorrectFormat(format!(
                                 "Array (uid: {classes_uid}) should contain only object references"
                             )));
-                        } else {
-                            arr_of_uids.push(val.into_uid().unwrap().get());
-                        }
+                        };
+                        arr_of_uids.push(uid.get());
                     }
-                    uninit_fields.insert(key, UninitRefs::RawRefArray(arr_of_uids));
-                    continue;
+                    RawField::Refs(UninitRefs::RawRefArray(arr_of_uids))
                 }
-                PlistValue::Boolean(b) => ObjectValue::Boolean(b),
-                PlistValue::Data(d) => ObjectValue::Data(d),
-                PlistValue::Real(f) => ObjectValue::Real(f),
-                PlistValue::Integer(i) => ObjectValue::Integer(i),
+                PlistValue::Boolean(b) => RawField::Boolean(b),
+                PlistValue::Data(d) => RawField::Data(d),
+                PlistValue::Date(d) => RawField::Date(d),
+                PlistValue::Real(f) => RawField::Real(f),
+                PlistValue::Integer(i) => RawField::Integer(i),
                 PlistValue::String(s) => {
                     if s == NULL_OBJECT_REFERENCE_NAME {
-                        ObjectValue::NullRef
+                        RawField::NullRef
                     } else {
-                        ObjectValue::String(s)
+                        RawField::String(s)
                     }
                 }
-                PlistValue::Uid(uid) => {
-                    uninit_fields.insert(key, UninitRefs::RawRef(uid.get()));
-                    continue;
-                }
+                PlistValue::Uid(uid) => RawField::Refs(UninitRefs::RawRef(uid.get())),
                 _ => {
                     return Err(Error::IncorrectFormat(format!(
                         "Enexpected object (uid: {classes_uid}) value type: {:?}",
@@ -285,14 +789,11 @@ impl Object {
                     )));
                 }
             };
-
-            fields.insert(key, decoded_obj);
+            fields.push((key, field));
         }
         Ok(Self {
-            classes: None,
             classes_uid,
             fields,
-            uninit_fields: Some(uninit_fields),
         })
     }
 }
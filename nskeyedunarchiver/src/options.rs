@@ -0,0 +1,189 @@
+use crate::ARCHIVER;
+
+/// A default maximum depth of nested values while decoding.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Resource limits applied to an archive. They protect against malicious
+/// archives when handling untrusted input.
+///
+/// Exceeding any of them results in [Error::LimitExceeded](crate::Error::LimitExceeded)
+/// while parsing or in [DeError::LimitExceeded](crate::DeError::LimitExceeded)
+/// while decoding. [None] means there's no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// A maximum number of values inside of `$objects`.
+    pub max_objects: Option<usize>,
+    /// A maximum total size (in bytes) of all data and string values.
+    pub max_data_size: Option<usize>,
+    /// A maximum depth of nested values while decoding them with
+    /// [Decodable](crate::Decodable). Defaults to [DEFAULT_MAX_DEPTH].
+    ///
+    /// Values of most types are decoded recursively, so without a limit
+    /// deeply nested archives overflow the stack and abort the process.
+    /// [DynamicValue](crate::DynamicValue)s and plist values are decoded
+    /// with a work stack, so they're safe to decode without a limit.
+    pub max_depth: Option<usize>,
+}
+
+impl Limits {
+    /// No limits at all, including the decoding depth.
+    pub fn unlimited() -> Self {
+        Self {
+            max_objects: None,
+            max_data_size: None,
+            max_depth: None,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_objects: None,
+            max_data_size: None,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+        }
+    }
+}
+
+/// Options that control how strictly an archive header is checked during
+/// parsing and which [Limits] are applied.
+///
+/// The default is [ParseOptions::strict] with default [Limits].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    strict: bool,
+    limits: Limits,
+    anonymous_objects: bool,
+    archivers: Vec<String>,
+    archiver_subclasses: bool,
+    dangling_refs_as_null: bool,
+}
+
+impl ParseOptions {
+    /// Only archives with an exact `NSKeyedArchiver` archiver string and
+    /// a `$version` of `100000` are accepted.
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            limits: Limits::default(),
+            anonymous_objects: false,
+            archivers: Vec::new(),
+            archiver_subclasses: false,
+            dangling_refs_as_null: false,
+        }
+    }
+
+    /// Tolerates header deviations found in real-world files: a missing
+    /// or unknown `$version` and alternate archiver strings that start with
+    /// `NSKeyedArchiver` (like `NSKeyedArchiver_compat`).
+    pub fn lenient() -> Self {
+        Self {
+            strict: false,
+            limits: Limits::default(),
+            anonymous_objects: false,
+            archivers: Vec::new(),
+            archiver_subclasses: false,
+            dangling_refs_as_null: false,
+        }
+    }
+
+    /// Sets resource limits.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Enables a recovery mode for archives of third-party encoders that omit
+    /// `$class` references. A dictionary inside of `$objects` that has
+    /// neither a `$class` nor a `$classes` key is treated as an anonymous
+    /// object with an empty class list instead of failing.
+    /// See [Object::is_anonymous](crate::Object::is_anonymous).
+    pub fn with_anonymous_objects(mut self, anonymous_objects: bool) -> Self {
+        self.anonymous_objects = anonymous_objects;
+        self
+    }
+
+    /// Accepts additional `$archiver` strings, e.g. names of custom
+    /// `NSKeyedArchiver` subclasses like `PLBatteryUsageKeyedArchiver`.
+    /// They're compared exactly, in both strict and lenient modes.
+    pub fn with_archivers<I, S>(mut self, archivers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.archivers = archivers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Accepts any `$archiver` string containing `KeyedArchiver`. Files written
+    /// by `NSKeyedArchiver` subclasses store a subclass name there, but their
+    /// format is the same.
+    pub fn with_archiver_subclasses(mut self, archiver_subclasses: bool) -> Self {
+        self.archiver_subclasses = archiver_subclasses;
+        self
+    }
+
+    /// Enables a recovery mode for truncated or damaged archives. Object
+    /// fields referring to uids outside of `$objects` become `$null`
+    /// references instead of failing with
+    /// [Error::DanglingReference](crate::Error::DanglingReference).
+    /// Dangling `$class` references still fail, since objects can't be
+    /// decoded without classes.
+    /// Use [KeyedArchive::validate](crate::KeyedArchive::validate) to find
+    /// out which references are lost.
+    pub fn with_dangling_refs_as_null(mut self, dangling_refs_as_null: bool) -> Self {
+        self.dangling_refs_as_null = dangling_refs_as_null;
+        self
+    }
+
+    /// Returns `true` if the options are strict.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Returns resource limits.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Returns `true` if dictionaries without `$class` are decoded as
+    /// anonymous objects.
+    pub fn anonymous_objects(&self) -> bool {
+        self.anonymous_objects
+    }
+
+    /// Returns additional accepted `$archiver` strings.
+    pub fn archivers(&self) -> &[String] {
+        &self.archivers
+    }
+
+    /// Returns `true` if any `$archiver` string containing `KeyedArchiver` is
+    /// accepted.
+    pub fn archiver_subclasses(&self) -> bool {
+        self.archiver_subclasses
+    }
+
+    /// Returns `true` if dangling references are replaced with `$null` ones.
+    pub fn dangling_refs_as_null(&self) -> bool {
+        self.dangling_refs_as_null
+    }
+
+    pub(crate) fn is_supported_archiver(&self, archiver: &str) -> bool {
+        if self.archivers.iter().any(|a| a == archiver)
+            || (self.archiver_subclasses && archiver.contains("KeyedArchiver"))
+        {
+            true
+        } else if self.strict {
+            archiver == ARCHIVER
+        } else {
+            archiver.starts_with(ARCHIVER)
+        }
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self::strict()
+    }
+}
@@ -0,0 +1,387 @@
+use crate::{ArchiveEditor, Error, KeyedArchive, NULL_OBJECT_REFERENCE_NAME, UniqueId};
+use plist::{Dictionary as PlistDictionary, Uid, Value as PlistValue};
+
+/// A new value of a [Patch] change.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchValue {
+    /// A plain value: a string, a number, data, etc.
+    Plain(PlistValue),
+    /// A new object.
+    Object(PatchObject),
+    /// An existing value of the archive.
+    Ref(UniqueId),
+}
+
+impl<T: Into<PlistValue>> From<T> for PatchValue {
+    fn from(value: T) -> Self {
+        Self::Plain(value.into())
+    }
+}
+
+impl From<PatchObject> for PatchValue {
+    fn from(value: PatchObject) -> Self {
+        Self::Object(value)
+    }
+}
+
+impl From<UniqueId> for PatchValue {
+    fn from(value: UniqueId) -> Self {
+        Self::Ref(value)
+    }
+}
+
+/// A new object of a [Patch] change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchObject {
+    classes: Vec<String>,
+    fields: Vec<(String, PatchValue)>,
+}
+
+impl PatchObject {
+    /// Creates an object without fields. `classes` is a class chain of the
+    /// object, starting with its own class, like `["Note", "NSObject"]`.
+    pub fn new(classes: &[&str]) -> Self {
+        Self {
+            classes: classes.iter().map(|c| c.to_string()).collect(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds a field.
+    pub fn field(mut self, key: &str, value: impl Into<PatchValue>) -> Self {
+        self.fields.push((key.into(), value.into()));
+        self
+    }
+}
+
+/// A list of changes of an archive, addressed by key paths. Changes are
+/// applied in order with [ArchiveEditor::apply] or [KeyedArchive::merge]:
+///
+/// ```
+/// # use nskeyedunarchiver::{KeyedArchive, Patch, PatchObject};
+/// # let archive = KeyedArchive::from_file("./tests_resources/plists/note.plist").unwrap();
+/// let patch = Patch::new()
+///     .set("root.title", "New title")
+///     .set("root.author", PatchObject::new(&["Author", "NSObject"]).field("name", "Me"))
+///     .remove("root.date");
+/// let archive = archive.merge(&patch).unwrap();
+/// ```
+///
+/// A key path starts with a `$top` key and goes through fields of
+/// referenced objects, like `root.settings.volume`. Keys of dictionaries
+/// (objects with `NS.keys` and `NS.objects`) are looked up by their string
+/// values. Keys containing dots can't be addressed.
+///
+/// A plain value replaces an inline field as is. Otherwise strings and data
+/// are added to `$objects` and referred to, like `NSKeyedArchiver` encodes
+/// them, and other plain values are set inline. `$top` entries and
+/// dictionary values always refer to values of `$objects`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Patch {
+    changes: Vec<(String, Option<PatchValue>)>,
+}
+
+impl Patch {
+    /// Creates a patch without changes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a value at a given key path. A missing last key is added.
+    pub fn set(mut self, key_path: &str, value: impl Into<PatchValue>) -> Self {
+        self.changes.push((key_path.into(), Some(value.into())));
+        self
+    }
+
+    /// Removes a value at a given key path. A missing last key is ignored.
+    pub fn remove(mut self, key_path: &str) -> Self {
+        self.changes.push((key_path.into(), None));
+        self
+    }
+
+    /// Returns the number of changes.
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Returns `true` if there are no changes.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Keys and values of a dictionary object.
+type Entries<'a> = (&'a [PlistValue], &'a [PlistValue]);
+
+/// A place a key path refers to.
+enum Slot<'a> {
+    Top(&'a str),
+    Field(UniqueId, &'a str),
+    Entry(UniqueId, &'a str),
+}
+
+impl ArchiveEditor {
+    /// Applies changes of a [Patch]. Values that are no longer referenced
+    /// are kept until [ArchiveEditor::compact] is called.
+    ///
+    /// If an error is returned, changes made before it are kept.
+    pub fn apply(&mut self, patch: &Patch) -> Result<(), Error> {
+        for (key_path, value) in &patch.changes {
+            let slot = self.resolve(key_path)?;
+            match value {
+                Some(value) => self.set_slot(slot, value)?,
+                None => self.remove_slot(slot)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve<'a>(&self, key_path: &'a str) -> Result<Slot<'a>, Error> {
+        let mut keys = key_path.split('.');
+        let mut slot = Slot::Top(keys.next().unwrap_or_default());
+        for key in keys {
+            let Some(uid) = self.slot_target(&slot)?.filter(|uid| self.is_object(*uid)) else {
+                return Err(Error::InvalidEdit(format!(
+                    "Key path `{key_path}` goes through a missing value or a non-object"
+                )));
+            };
+            slot = if self.dictionary(uid)?.is_some() {
+                Slot::Entry(uid, key)
+            } else {
+                Slot::Field(uid, key)
+            };
+        }
+        Ok(slot)
+    }
+
+    /// Returns a uid of a value a slot refers to.
+    fn slot_target(&self, slot: &Slot) -> Result<Option<UniqueId>, Error> {
+        let value = match slot {
+            Slot::Top(key) => self.top.get(key),
+            Slot::Field(uid, key) => self.objects[uid.get()]
+                .as_dictionary()
+                .and_then(|dict| dict.get(key)),
+            Slot::Entry(uid, key) => match self.entry_index(*uid, key)? {
+                Some(index) => self
+                    .dictionary(*uid)?
+                    .and_then(|(_, objects)| objects.get(index)),
+                None => None,
+            },
+        };
+        let uid = value
+            .and_then(|value| value.as_uid())
+            .map(|uid| uid.get() as usize);
+        Ok(uid
+            .filter(|uid| *uid < self.objects.len())
+            .map(UniqueId::new))
+    }
+
+    fn set_slot(&mut self, slot: Slot, value: &PatchValue) -> Result<(), Error> {
+        match slot {
+            Slot::Top(key) => {
+                let uid = self.add_patch_value(value)?;
+                self.set_top(key, uid)
+            }
+            Slot::Field(uid, key) => {
+                let inline = self.objects[uid.get()]
+                    .as_dictionary()
+                    .and_then(|dict| dict.get(key))
+                    .is_some_and(|field| {
+                        field.as_uid().is_none()
+                            && field.as_string() != Some(NULL_OBJECT_REFERENCE_NAME)
+                    });
+                let field = match value {
+                    PatchValue::Plain(value) if inline => value.clone(),
+                    value => self.patch_field(value)?,
+                };
+                self.set_field(uid, key, field)
+            }
+            Slot::Entry(uid, key) => {
+                let index = self.entry_index(uid, key)?;
+                let value = uid_value(self.add_patch_value(value)?);
+                match index {
+                    Some(index) => self.dictionary_mut(uid)?.1[index] = value,
+                    None => {
+                        let key = uid_value(self.add_value(key.into())?);
+                        let (keys, objects) = self.dictionary_mut(uid)?;
+                        keys.push(key);
+                        objects.push(value);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn remove_slot(&mut self, slot: Slot) -> Result<(), Error> {
+        match slot {
+            Slot::Top(key) => {
+                self.top.remove(key);
+            }
+            Slot::Field(uid, key) => {
+                self.remove_field(uid, key)?;
+            }
+            Slot::Entry(uid, key) => {
+                if let Some(index) = self.entry_index(uid, key)? {
+                    let (keys, objects) = self.dictionary_mut(uid)?;
+                    keys.remove(index);
+                    objects.remove(index);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts a patch value into an object field.
+    fn patch_field(&mut self, value: &PatchValue) -> Result<PlistValue, Error> {
+        Ok(match value {
+            PatchValue::Plain(value @ (PlistValue::String(_) | PlistValue::Data(_))) => {
+                uid_value(self.add_value(value.clone())?)
+            }
+            PatchValue::Plain(value) => value.clone(),
+            value => uid_value(self.add_patch_value(value)?),
+        })
+    }
+
+    /// Adds a patch value to `$objects` and returns its uid.
+    fn add_patch_value(&mut self, value: &PatchValue) -> Result<UniqueId, Error> {
+        match value {
+            PatchValue::Plain(value) => self.add_value(value.clone()),
+            PatchValue::Ref(uid) => {
+                if uid.get() >= self.objects.len() {
+                    return Err(Error::InvalidEdit(format!(
+                        "Uid {} is out of range",
+                        uid.get()
+                    )));
+                }
+                Ok(*uid)
+            }
+            PatchValue::Object(object) => {
+                let class = self.class_uid(&object.classes)?;
+                let mut dict = PlistDictionary::new();
+                dict.insert("$class".into(), uid_value(class));
+                for (key, value) in &object.fields {
+                    if key == "$class" {
+                        return Err(Error::InvalidEdit("`$class` can't be changed".into()));
+                    }
+                    let field = self.patch_field(value)?;
+                    dict.insert(key.clone(), field);
+                }
+                self.add_value(dict.into())
+            }
+        }
+    }
+
+    /// Returns a uid of a class chain, adding it if it doesn't exist yet.
+    fn class_uid(&mut self, classes: &[String]) -> Result<UniqueId, Error> {
+        let Some(class) = classes.first() else {
+            return Err(Error::InvalidEdit("An object should have a class".into()));
+        };
+        let existing = self.objects.iter().position(|value| {
+            value
+                .as_dictionary()
+                .and_then(|dict| dict.get("$classes"))
+                .and_then(|c| c.as_array())
+                .is_some_and(|c| {
+                    c.iter()
+                        .map(|c| c.as_string())
+                        .eq(classes.iter().map(|c| Some(c.as_str())))
+                })
+        });
+        if let Some(uid) = existing {
+            return Ok(UniqueId::new(uid));
+        }
+        let mut dict = PlistDictionary::new();
+        dict.insert("$classname".into(), class.clone().into());
+        dict.insert(
+            "$classes".into(),
+            PlistValue::Array(classes.iter().map(|c| c.clone().into()).collect()),
+        );
+        self.add_value(dict.into())
+    }
+
+    fn is_object(&self, uid: UniqueId) -> bool {
+        self.objects[uid.get()]
+            .as_dictionary()
+            .is_some_and(|dict| dict.contains_key("$class"))
+    }
+
+    /// Returns keys and values of a dictionary object or `None` if the
+    /// object isn't a dictionary. Keys and values must be of the same length.
+    fn dictionary(&self, uid: UniqueId) -> Result<Option<Entries<'_>>, Error> {
+        let dict = self.objects[uid.get()].as_dictionary();
+        let array = |key| {
+            dict.and_then(|dict| dict.get(key))
+                .and_then(|v| v.as_array())
+        };
+        let (Some(keys), Some(objects)) = (array("NS.keys"), array("NS.objects")) else {
+            return Ok(None);
+        };
+        if keys.len() != objects.len() {
+            return Err(mismatched_entries(uid));
+        }
+        Ok(Some((keys, objects)))
+    }
+
+    fn dictionary_mut(
+        &mut self,
+        uid: UniqueId,
+    ) -> Result<(&mut Vec<PlistValue>, &mut Vec<PlistValue>), Error> {
+        let mut keys = None;
+        let mut objects = None;
+        if let Some(dict) = self.objects[uid.get()].as_dictionary_mut() {
+            for (key, value) in dict.iter_mut() {
+                match (key.as_str(), value) {
+                    ("NS.keys", PlistValue::Array(array)) => keys = Some(array),
+                    ("NS.objects", PlistValue::Array(array)) => objects = Some(array),
+                    _ => (),
+                }
+            }
+        }
+        match (keys, objects) {
+            (Some(keys), Some(objects)) if keys.len() == objects.len() => Ok((keys, objects)),
+            (Some(_), Some(_)) => Err(mismatched_entries(uid)),
+            _ => Err(Error::InvalidEdit(format!(
+                "Object (uid: {}) isn't a dictionary",
+                uid.get()
+            ))),
+        }
+    }
+
+    /// Returns an index of a dictionary entry with a given string key.
+    fn entry_index(&self, uid: UniqueId, key: &str) -> Result<Option<usize>, Error> {
+        let Some((keys, _)) = self.dictionary(uid)? else {
+            return Ok(None);
+        };
+        Ok(keys.iter().position(|k| {
+            k.as_uid()
+                .and_then(|uid| self.objects.get(uid.get() as usize))
+                .and_then(|k| k.as_string())
+                == Some(key)
+        }))
+    }
+}
+
+impl KeyedArchive {
+    /// Applies changes of a [Patch] to a copy of the archive and returns
+    /// a new archive. Values that are no longer referenced are removed
+    /// (see [ArchiveEditor::compact]). The new archive is parsed with the
+    /// same [ParseOptions](crate::ParseOptions) as this one.
+    pub fn merge(&self, patch: &Patch) -> Result<KeyedArchive, Error> {
+        let mut editor = self.edit();
+        editor.apply(patch)?;
+        editor.compact();
+        editor.into_archive_with_options(&self.options)
+    }
+}
+
+fn mismatched_entries(uid: UniqueId) -> Error {
+    Error::InvalidEdit(format!(
+        "Dictionary (uid: {}) has different numbers of keys and values",
+        uid.get()
+    ))
+}
+
+fn uid_value(uid: UniqueId) -> PlistValue {
+    PlistValue::Uid(Uid::new(uid.get() as u64))
+}
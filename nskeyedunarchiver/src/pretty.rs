@@ -0,0 +1,178 @@
+use crate::{ArchiveValue, DEFAULT_MAX_DEPTH, ObjectValue};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter, Result, Write};
+
+/// The number of data bytes shown in a preview.
+const DATA_PREVIEW_LEN: usize = 16;
+
+/// A pretty-printer of an [ArchiveValue] tree returned by
+/// [ArchiveValue::pretty]. It's similar to `plutil -p`:
+///
+/// ```text
+/// Note #1 {
+///   data: <20 bytes: 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f …>
+///   tags: NSArray #4 {
+///     NS.objects: [
+///       [0]: "work" #5
+///       [1]: ↩ ref #1
+///     ]
+///   }
+///   title: "Some note" #3
+/// }
+/// ```
+///
+/// Objects are printed with their class names and uids. An object that is
+/// already printed (a shared or a circular reference) is printed as a
+/// `↩ ref #uid` marker. Data is printed as a preview of the first bytes.
+pub struct Pretty<'a>(&'a ArchiveValue);
+
+/// A [Display] wrapper encoding bytes in standard padded base64, returned by
+/// [Data::base64](crate::Data::base64). It's handy for embedding data into
+/// JSON or logs without allocating an intermediate string.
+pub struct Base64<'a>(pub &'a [u8]);
+
+impl Display for Base64<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        for chunk in self.0.chunks(3) {
+            let bytes = [
+                chunk[0],
+                *chunk.get(1).unwrap_or(&0),
+                *chunk.get(2).unwrap_or(&0),
+            ];
+            let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    f.write_char(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char)?;
+                } else {
+                    f.write_char('=')?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ArchiveValue {
+    /// Returns a pretty-printer of a value tree. See [Pretty].
+    pub fn pretty(&self) -> Pretty<'_> {
+        Pretty(self)
+    }
+}
+
+impl Display for ArchiveValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.pretty())
+    }
+}
+
+impl Display for Pretty<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let mut printer = Printer {
+            f,
+            visited: HashSet::new(),
+        };
+        printer.value(self.0, 0)
+    }
+}
+
+struct Printer<'a, 'b> {
+    f: &'a mut Formatter<'b>,
+    visited: HashSet<usize>,
+}
+
+impl Printer<'_, '_> {
+    fn indent(&mut self, depth: usize) -> Result {
+        for _ in 0..depth {
+            self.f.write_str("  ")?;
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, value: &ArchiveValue, depth: usize) -> Result {
+        let uid = value.unique_id().get();
+        if let Some(s) = value.as_string() {
+            write!(self.f, "{s:?} #{uid}")
+        } else if let Some(i) = value.as_integer() {
+            write!(self.f, "{i} #{uid}")
+        } else if let Some(r) = value.as_float() {
+            write!(self.f, "{r:?} #{uid}")
+        } else if let Some(b) = value.as_boolean() {
+            write!(self.f, "{b} #{uid}")
+        } else if let Some(data) = value.as_data() {
+            data_preview(self.f, data)?;
+            write!(self.f, " #{uid}")
+        } else if value.is_null_ref() {
+            write!(self.f, "$null")
+        } else if let Some(classes) = value.as_classes() {
+            write!(self.f, "$classes {classes:?} #{uid}")
+        } else if let Some(obj) = value.as_object() {
+            if !self.visited.insert(uid) {
+                return write!(self.f, "↩ ref #{uid}");
+            }
+            write!(self.f, "{} #{uid}", obj.class())?;
+            if depth >= DEFAULT_MAX_DEPTH {
+                return write!(self.f, " {{ … }}");
+            }
+            let mut keys = obj.keys();
+            if keys.is_empty() {
+                return write!(self.f, " {{}}");
+            }
+            keys.sort();
+            writeln!(self.f, " {{")?;
+            for key in keys {
+                self.indent(depth + 1)?;
+                write!(self.f, "{key}: ")?;
+                self.field(&obj.as_map()[key], depth + 1)?;
+                writeln!(self.f)?;
+            }
+            self.indent(depth)?;
+            write!(self.f, "}}")
+        } else {
+            Ok(())
+        }
+    }
+
+    fn field(&mut self, value: &ObjectValue, depth: usize) -> Result {
+        match value {
+            ObjectValue::String(s) => write!(self.f, "{s:?}"),
+            ObjectValue::Integer(i) => write!(self.f, "{i}"),
+            ObjectValue::Real(r) => write!(self.f, "{r:?}"),
+            ObjectValue::Boolean(b) => write!(self.f, "{b}"),
+            ObjectValue::Data(data) => data_preview(self.f, data),
+            ObjectValue::Date(date) => write!(self.f, "{}", date.to_xml_format()),
+            ObjectValue::NullRef => write!(self.f, "$null"),
+            ObjectValue::Ref(value) => self.value(value, depth),
+            ObjectValue::RefArray(values) => {
+                if values.is_empty() {
+                    return write!(self.f, "[]");
+                }
+                writeln!(self.f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    self.indent(depth + 1)?;
+                    write!(self.f, "[{index}]: ")?;
+                    self.value(value, depth + 1)?;
+                    writeln!(self.f)?;
+                }
+                self.indent(depth)?;
+                write!(self.f, "]")
+            }
+        }
+    }
+}
+
+/// Writes a preview of the first bytes of data, like `<3 bytes: 01 02 03>`.
+pub(crate) fn data_preview(f: &mut Formatter<'_>, data: &[u8]) -> Result {
+    write!(f, "<{} bytes", data.len())?;
+    if !data.is_empty() {
+        f.write_char(':')?;
+    }
+    for byte in data.iter().take(DATA_PREVIEW_LEN) {
+        write!(f, " {byte:02x}")?;
+    }
+    if data.len() > DATA_PREVIEW_LEN {
+        f.write_str(" …")?;
+    }
+    f.write_char('>')
+}
@@ -0,0 +1,48 @@
+//! Low-level parsing of object tables.
+//!
+//! Some proprietary formats reuse the `$objects` table layout of keyed
+//! archives with different headers. These functions convert such a table
+//! into values with resolved references, just like [KeyedArchive] does, but
+//! without checking `$archiver`, `$version` and `$top` keys:
+//!
+//! ```ignore
+//! let plist = plist::Value::from_file("state.plist")?;
+//! let header = plist.as_dictionary().unwrap();
+//! let objects = raw::parse_objects_ref(header["table"].as_array().unwrap(), &ParseOptions::default())?;
+//! let root = raw::resolve_uid(&header["entry"], &objects).unwrap();
+//! ```
+//!
+//! [Limits](crate::Limits) and the anonymous objects mode of [ParseOptions]
+//! are applied, other options only concern headers.
+
+use crate::{Error, KeyedArchive, ParseOptions, ValueRef};
+use plist::Value as PlistValue;
+use std::borrow::Cow;
+
+/// Converts values of an object table into [ValueRef]s. Uids inside of
+/// objects are replaced with references to other values of the table.
+///
+/// [KeyedArchive] is used to parse the table, thus it must have the same
+/// layout as `$objects`: objects are dictionaries with a `$class` uid.
+pub fn parse_objects(
+    objects: Vec<PlistValue>,
+    options: &ParseOptions,
+) -> Result<Vec<ValueRef>, Error> {
+    KeyedArchive::decode_objects(Cow::Owned(objects), options)
+}
+
+/// Converts values of a borrowed object table into [ValueRef]s. Values are
+/// cloned one by one while they're converted. See [parse_objects].
+pub fn parse_objects_ref(
+    objects: &[PlistValue],
+    options: &ParseOptions,
+) -> Result<Vec<ValueRef>, Error> {
+    KeyedArchive::decode_objects(Cow::Borrowed(objects), options)
+}
+
+/// Returns a value of a parsed table a uid points to, e.g. an entry of
+/// a custom header. Returns [None] if `uid` isn't a uid or it's out of bounds.
+pub fn resolve_uid(uid: &PlistValue, objects: &[ValueRef]) -> Option<ValueRef> {
+    let uid = uid.as_uid()?.get();
+    objects.get(usize::try_from(uid).ok()?).cloned()
+}
@@ -0,0 +1,141 @@
+use crate::{ArchiveEditor, NULL_OBJECT_REFERENCE_NAME};
+use plist::{Dictionary as PlistDictionary, Value as PlistValue};
+
+/// A placeholder redacted strings are replaced with.
+pub const REDACTED: &str = "<redacted>";
+
+type StringPredicate = Box<dyn Fn(&str) -> bool>;
+type FieldPredicate = Box<dyn Fn(&str, &str) -> bool>;
+
+/// Rules for redacting user data from an archive, e.g. before sharing it
+/// in a bug report. Rules are applied with [ArchiveEditor::sanitize]:
+///
+/// ```
+/// # use nskeyedunarchiver::{KeyedArchive, Sanitizer};
+/// # let archive = KeyedArchive::from_file("./tests_resources/plists/note.plist").unwrap();
+/// let sanitizer = Sanitizer::new()
+///     .redact_data_over(16)
+///     .redact_strings(|s| s.contains('@'))
+///     .remove_fields(|_class, key| key == "date");
+/// let mut editor = archive.edit();
+/// editor.sanitize(&sanitizer);
+/// let plist = editor.into_plist();
+/// ```
+///
+/// Redacted data keeps its length and is filled with zeros, so structures
+/// parsed from data (like geometry) stay decodable. Redacted strings are
+/// replaced with [REDACTED]. Object structure, class names and field keys
+/// are never changed, except for removed fields.
+///
+/// Predicates are plain functions, so any matcher (e.g. a compiled regex)
+/// may be used.
+#[derive(Default)]
+pub struct Sanitizer {
+    max_data_len: Option<usize>,
+    strings: Vec<StringPredicate>,
+    fields: Vec<FieldPredicate>,
+}
+
+impl Sanitizer {
+    /// Creates a sanitizer without any rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Redacts all data values longer than `len` bytes.
+    pub fn redact_data_over(mut self, len: usize) -> Self {
+        self.max_data_len = Some(len);
+        self
+    }
+
+    /// Redacts all string values matching a `predicate`.
+    pub fn redact_strings(mut self, predicate: impl Fn(&str) -> bool + 'static) -> Self {
+        self.strings.push(Box::new(predicate));
+        self
+    }
+
+    /// Removes object fields matching a `predicate`. It's called with
+    /// a class name of an object and a field key.
+    ///
+    /// Values referred to by removed fields are kept until
+    /// [ArchiveEditor::compact] is called.
+    pub fn remove_fields(mut self, predicate: impl Fn(&str, &str) -> bool + 'static) -> Self {
+        self.fields.push(Box::new(predicate));
+        self
+    }
+
+    /// Redacts a plain value. Returns `true` if it's changed.
+    fn redact(&self, value: &mut PlistValue) -> bool {
+        match value {
+            PlistValue::Data(data)
+                if self.max_data_len.is_some_and(|len| data.len() > len)
+                    && data.iter().any(|b| *b != 0) =>
+            {
+                data.fill(0);
+                true
+            }
+            PlistValue::String(s)
+                if s != NULL_OBJECT_REFERENCE_NAME
+                    && s != REDACTED
+                    && self.strings.iter().any(|p| p(s)) =>
+            {
+                *s = REDACTED.into();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn remove_field(&self, class: &str, key: &str) -> bool {
+        key != "$class" && self.fields.iter().any(|p| p(class, key))
+    }
+}
+
+impl std::fmt::Debug for Sanitizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sanitizer")
+            .field("max_data_len", &self.max_data_len)
+            .field("strings", &self.strings.len())
+            .field("fields", &self.fields.len())
+            .finish()
+    }
+}
+
+impl ArchiveEditor {
+    /// Redacts and removes values matching rules of a [Sanitizer]. Returns
+    /// the number of redacted values and removed fields.
+    pub fn sanitize(&mut self, sanitizer: &Sanitizer) -> usize {
+        let mut count = 0;
+        for value in self.top.values_mut() {
+            count += sanitizer.redact(value) as usize;
+        }
+        let class_names: Vec<Option<String>> = self.objects.iter().map(class_name).collect();
+        // `$null` is kept as is
+        for value in self.objects.iter_mut().skip(1) {
+            let Some(dict) = value.as_dictionary_mut() else {
+                count += sanitizer.redact(value) as usize;
+                continue;
+            };
+            let Some(class) = dict
+                .get("$class")
+                .and_then(|uid| uid.as_uid())
+                .and_then(|uid| class_names.get(uid.get() as usize)?.as_deref())
+            else {
+                // Classes aren't redacted
+                continue;
+            };
+            let len = dict.len();
+            dict.retain(|key, _| !sanitizer.remove_field(class, key));
+            count += len - dict.len();
+            for field in dict.values_mut() {
+                count += sanitizer.redact(field) as usize;
+            }
+        }
+        count
+    }
+}
+
+fn class_name(value: &PlistValue) -> Option<String> {
+    let dict: &PlistDictionary = value.as_dictionary()?;
+    dict.get("$classname")?.as_string().map(String::from)
+}
@@ -0,0 +1,273 @@
+use crate::{ArchiveValue, KeyedArchive, ObjectValue, UniqueId};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+/// A kind of a field value expected by a [ClassSchema]. Values behind
+/// references are checked, so a field referring to a string is a
+/// [ValueKind::String].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    Integer,
+    Real,
+    Boolean,
+    Data,
+    Date,
+    /// An object of any class.
+    Object,
+    /// An array of references.
+    Array,
+    /// A `$null` reference.
+    Null,
+    /// Any value.
+    Any,
+}
+
+impl ValueKind {
+    fn of(value: &ObjectValue) -> Self {
+        match value {
+            ObjectValue::String(_) => Self::String,
+            ObjectValue::Integer(_) => Self::Integer,
+            ObjectValue::Real(_) => Self::Real,
+            ObjectValue::Boolean(_) => Self::Boolean,
+            ObjectValue::Data(_) => Self::Data,
+            ObjectValue::Date(_) => Self::Date,
+            ObjectValue::RefArray(_) => Self::Array,
+            ObjectValue::NullRef => Self::Null,
+            ObjectValue::Ref(value) => Self::of_value(value),
+        }
+    }
+
+    fn of_value(value: &ArchiveValue) -> Self {
+        if value.is_string() {
+            Self::String
+        } else if value.is_integer() {
+            Self::Integer
+        } else if value.is_float() {
+            Self::Real
+        } else if value.is_boolean() {
+            Self::Boolean
+        } else if value.is_data() {
+            Self::Data
+        } else if value.as_object().is_some() {
+            Self::Object
+        } else {
+            Self::Null
+        }
+    }
+}
+
+impl Display for ValueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Real => "real",
+            Self::Boolean => "boolean",
+            Self::Data => "data",
+            Self::Date => "date",
+            Self::Object => "object",
+            Self::Array => "array",
+            Self::Null => "null",
+            Self::Any => "any",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FieldSchema {
+    kind: ValueKind,
+    required: bool,
+}
+
+/// Expected keys of objects of one class.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClassSchema {
+    fields: BTreeMap<String, FieldSchema>,
+    deny_unknown_keys: bool,
+}
+
+impl ClassSchema {
+    /// Creates a schema without any keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a key that every object should have.
+    pub fn required(mut self, key: impl Into<String>, kind: ValueKind) -> Self {
+        let field = FieldSchema {
+            kind,
+            required: true,
+        };
+        self.fields.insert(key.into(), field);
+        self
+    }
+
+    /// Adds a key that objects may omit. Its value may also be a `$null`
+    /// reference.
+    pub fn optional(mut self, key: impl Into<String>, kind: ValueKind) -> Self {
+        let field = FieldSchema {
+            kind,
+            required: false,
+        };
+        self.fields.insert(key.into(), field);
+        self
+    }
+
+    /// Reports keys that aren't described by the schema.
+    pub fn deny_unknown_keys(mut self) -> Self {
+        self.deny_unknown_keys = true;
+        self
+    }
+}
+
+/// A description of expected objects of an archive, checked with
+/// [KeyedArchive::check_schema].
+///
+/// It lets apps pin the structure of archives they produce and catch
+/// changes between OS versions early:
+///
+/// ```ignore
+/// let schema = Schema::new().class(
+///     "Note",
+///     ClassSchema::new()
+///         .required("title", ValueKind::String)
+///         .optional("tags", ValueKind::Object),
+/// );
+/// assert!(archive.check_schema(&schema).is_valid());
+/// ```
+///
+/// Objects of classes that aren't described are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schema {
+    classes: BTreeMap<String, ClassSchema>,
+}
+
+impl Schema {
+    /// Creates a schema without any classes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Describes objects of a class.
+    pub fn class(mut self, name: impl Into<String>, class: ClassSchema) -> Self {
+        self.classes.insert(name.into(), class);
+        self
+    }
+}
+
+/// A single mismatch between an object and a [Schema].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SchemaViolation {
+    /// A required key is missing.
+    MissingKey { object: UniqueId, key: String },
+    /// A value has an unexpected kind.
+    UnexpectedKind {
+        object: UniqueId,
+        key: String,
+        expected: ValueKind,
+        found: ValueKind,
+    },
+    /// A key isn't described by a schema denying unknown keys.
+    UnknownKey { object: UniqueId, key: String },
+}
+
+impl Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingKey { object, key } => {
+                write!(f, "Object (uid: {}): missing key `{key}`", object.get())
+            }
+            Self::UnexpectedKind {
+                object,
+                key,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Object (uid: {})->{key}: expected {expected}, found {found}",
+                object.get()
+            ),
+            Self::UnknownKey { object, key } => {
+                write!(f, "Object (uid: {}): unknown key `{key}`", object.get())
+            }
+        }
+    }
+}
+
+/// A result of [KeyedArchive::check_schema].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaReport {
+    violations: Vec<SchemaViolation>,
+}
+
+impl SchemaReport {
+    /// Returns `true` if all objects match a schema.
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Returns all found violations, ordered by uids of objects.
+    pub fn violations(&self) -> &[SchemaViolation] {
+        &self.violations
+    }
+}
+
+impl Display for SchemaReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for violation in &self.violations {
+            writeln!(f, "{violation}")?;
+        }
+        Ok(())
+    }
+}
+
+impl KeyedArchive {
+    /// Checks objects of classes described by a [Schema] and returns
+    /// a [SchemaReport] with all mismatches.
+    pub fn check_schema(&self, schema: &Schema) -> SchemaReport {
+        let mut violations = Vec::new();
+        for (uid, value) in self.iter() {
+            let Some(obj) = value.as_object() else {
+                continue;
+            };
+            let Some(class) = schema.classes.get(obj.class()) else {
+                continue;
+            };
+            for (key, field) in &class.fields {
+                let Some(value) = obj.get(key) else {
+                    if field.required {
+                        violations.push(SchemaViolation::MissingKey {
+                            object: uid,
+                            key: key.clone(),
+                        });
+                    }
+                    continue;
+                };
+                let found = ValueKind::of(value);
+                let null_allowed = !field.required && found == ValueKind::Null;
+                if field.kind != ValueKind::Any && field.kind != found && !null_allowed {
+                    violations.push(SchemaViolation::UnexpectedKind {
+                        object: uid,
+                        key: key.clone(),
+                        expected: field.kind,
+                        found,
+                    });
+                }
+            }
+            if class.deny_unknown_keys {
+                for key in obj.as_map().keys() {
+                    if !class.fields.contains_key(key) {
+                        violations.push(SchemaViolation::UnknownKey {
+                            object: uid,
+                            key: key.into(),
+                        });
+                    }
+                }
+            }
+        }
+        SchemaReport { violations }
+    }
+}
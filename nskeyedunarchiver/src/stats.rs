@@ -0,0 +1,270 @@
+use crate::{ArchiveValueVariant, KeyedArchive, ObjectValue, UniqueId, ValueRef};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// The number of most referenced values kept in [ArchiveStats].
+pub const MOST_REFERENCED_LEN: usize = 10;
+
+/// Statistics of an archive returned by [KeyedArchive::stats].
+///
+/// It helps to find pathological archives (like huge data values or
+/// values referenced thousands of times) and to decide whether values
+/// should be decoded lazily.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveStats {
+    /// The number of values inside of `$objects`.
+    pub values: usize,
+    /// The number of values of each kind: `string`, `integer`, `real`,
+    /// `boolean`, `data`, `object`, `classes` and `null`.
+    pub kinds: BTreeMap<&'static str, usize>,
+    /// The number of objects of each class (see [KeyedArchive::classes]).
+    pub classes: BTreeMap<String, usize>,
+    /// The total size of data values and data fields of objects.
+    pub data_bytes: usize,
+    /// The total size (in UTF-8 bytes) of string values and string fields
+    /// of objects.
+    pub string_bytes: usize,
+    /// The number of values in the longest chain of references starting at
+    /// `$top`. Circular references aren't followed.
+    pub max_depth: usize,
+    /// Uids of the most referenced values along with the number of
+    /// references to them (by object fields and `$top` entries), most
+    /// referenced first. At most [MOST_REFERENCED_LEN] values are kept.
+    pub most_referenced: Vec<(UniqueId, usize)>,
+    /// The number of values that can't be reached from `$top` (see
+    /// [KeyedArchive::unreachable_objects]).
+    pub unreachable: usize,
+}
+
+impl KeyedArchive {
+    /// Collects [ArchiveStats] of the archive.
+    pub fn stats(&self) -> ArchiveStats {
+        let values = self.values();
+        let mut stats = ArchiveStats {
+            values: values.len(),
+            classes: self.classes(),
+            ..Default::default()
+        };
+
+        // References of each value
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); values.len()];
+        for (uid, value) in values.iter().enumerate() {
+            let kind = match &value.value {
+                ArchiveValueVariant::String(s) => {
+                    stats.string_bytes += s.len();
+                    "string"
+                }
+                ArchiveValueVariant::Data(data) => {
+                    stats.data_bytes += data.len();
+                    "data"
+                }
+                ArchiveValueVariant::Integer(_) => "integer",
+                ArchiveValueVariant::Real(_) => "real",
+                ArchiveValueVariant::Boolean(_) => "boolean",
+                ArchiveValueVariant::NullRef => "null",
+                ArchiveValueVariant::Classes { .. } => "classes",
+                ArchiveValueVariant::Object(obj) => {
+                    for field in obj.as_map().values() {
+                        match field {
+                            ObjectValue::String(s) => stats.string_bytes += s.len(),
+                            ObjectValue::Data(data) => stats.data_bytes += data.len(),
+                            ObjectValue::Ref(value) => {
+                                children[uid].push(value.unique_id().get());
+                            }
+                            ObjectValue::RefArray(array) => children[uid]
+                                .extend(array.iter().map(|value| value.unique_id().get())),
+                            _ => (),
+                        }
+                    }
+                    "object"
+                }
+            };
+            *stats.kinds.entry(kind).or_insert(0) += 1;
+        }
+
+        let mut roots = Vec::new();
+        for value in self.top().values() {
+            let uid = value.unique_id().get();
+            // Plain `$top` values aren't a part of `$objects`
+            if uid < values.len() {
+                roots.push(uid);
+            } else {
+                stats.max_depth = stats.max_depth.max(1);
+            }
+        }
+        stats.max_depth = stats.max_depth.max(max_depth(&children, &roots));
+
+        let mut most_referenced = self.referenced_values(1);
+        most_referenced.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.get().cmp(&b.0.get())));
+        most_referenced.truncate(MOST_REFERENCED_LEN);
+        stats.most_referenced = most_referenced;
+        stats.unreachable = self.unreachable_objects().len();
+        stats
+    }
+
+    /// Returns uids of values that can't be reached from `$top` through
+    /// object fields, ordered by uids. `$null` isn't included, and classes
+    /// entries are reached through `$class` references of objects.
+    ///
+    /// Archives edited by buggy tools may accumulate such garbage. It may be
+    /// stripped with [ArchiveEditor::compact](crate::ArchiveEditor::compact).
+    pub fn unreachable_objects(&self) -> Vec<UniqueId> {
+        let values = self.values();
+        let mut reachable = vec![false; values.len()];
+        let mut queue: Vec<usize> = self
+            .top()
+            .values()
+            .map(|value| value.unique_id().get())
+            // Plain `$top` values aren't a part of `$objects`
+            .filter(|uid| *uid < values.len())
+            .collect();
+        while let Some(uid) = queue.pop() {
+            if std::mem::replace(&mut reachable[uid], true) {
+                continue;
+            }
+            let Some(obj) = values[uid].as_object() else {
+                continue;
+            };
+            if !obj.is_anonymous() {
+                queue.push(obj.classes_uid().get());
+            }
+            for field in obj.as_map().values() {
+                match field {
+                    ObjectValue::Ref(value) => queue.push(value.unique_id().get()),
+                    ObjectValue::RefArray(array) => {
+                        queue.extend(array.iter().map(|value| value.unique_id().get()))
+                    }
+                    _ => (),
+                }
+            }
+        }
+        reachable
+            .iter()
+            .enumerate()
+            .filter(|(uid, reachable)| !**reachable && !values[*uid].is_null_ref())
+            .map(|(uid, _)| UniqueId::new(uid))
+            .collect()
+    }
+
+    /// Returns the number of references to a value with a given uid by
+    /// object fields and `$top` entries. Values referenced more than once
+    /// are shared between objects, like `NSKeyedArchiver` does for objects
+    /// it encodes more than once.
+    ///
+    /// Counts are computed on the first call and cached.
+    pub fn incoming_ref_count(&self, uid: UniqueId) -> usize {
+        self.ref_counts().get(uid.get()).copied().unwrap_or(0)
+    }
+
+    /// Returns uids of values referenced more than once along with the
+    /// number of references to them, ordered by uids. `$null` isn't
+    /// included, since it's referenced by every missing value.
+    ///
+    /// It helps to find accidental massive sharing, like a huge data value
+    /// referenced thousands of times.
+    pub fn shared_values(&self) -> Vec<(UniqueId, usize)> {
+        self.referenced_values(2)
+    }
+
+    /// Returns values (except `$null`) referenced at least `min` times.
+    fn referenced_values(&self, min: usize) -> Vec<(UniqueId, usize)> {
+        self.ref_counts()
+            .iter()
+            .enumerate()
+            .filter(|(uid, count)| *uid != 0 && **count >= min)
+            .map(|(uid, count)| (UniqueId::new(uid), *count))
+            .collect()
+    }
+
+    fn ref_counts(&self) -> &[usize] {
+        self.ref_counts.get_or_init(|| {
+            let values = self.values();
+            let mut references = vec![0; values.len()];
+            for value in values {
+                let Some(obj) = value.as_object() else {
+                    continue;
+                };
+                // Plain `$top` values and a placeholder of dangling array
+                // elements aren't a part of `$objects`
+                let mut count = |value: &ValueRef| {
+                    if let Some(count) = references.get_mut(value.unique_id().get()) {
+                        *count += 1;
+                    }
+                };
+                for field in obj.as_map().values() {
+                    match field {
+                        ObjectValue::Ref(value) => count(value),
+                        ObjectValue::RefArray(array) => array.iter().for_each(&mut count),
+                        _ => (),
+                    }
+                }
+            }
+            for value in self.top().values() {
+                if let Some(count) = references.get_mut(value.unique_id().get()) {
+                    *count += 1;
+                }
+            }
+            references
+        })
+    }
+}
+
+/// Returns the number of values in the longest chain of references starting
+/// at `roots`. References to values that are being visited (circular ones)
+/// are ignored. It doesn't recurse, so deep archives can't overflow the stack.
+fn max_depth(children: &[Vec<usize>], roots: &[usize]) -> usize {
+    const UNVISITED: usize = 0;
+    const VISITING: usize = usize::MAX;
+    // Depths of visited values
+    let mut depths = vec![UNVISITED; children.len()];
+    let mut max = 0;
+    for &root in roots {
+        let mut stack = vec![(root, 0)];
+        while let Some((uid, index)) = stack.pop() {
+            if index == 0 {
+                if depths[uid] != UNVISITED {
+                    continue;
+                }
+                depths[uid] = VISITING;
+            }
+            if let Some(&child) = children[uid].get(index) {
+                stack.push((uid, index + 1));
+                if child < children.len() && depths[child] == UNVISITED {
+                    stack.push((child, 0));
+                }
+                continue;
+            }
+            let depth = 1 + children[uid]
+                .iter()
+                .filter(|child| **child < depths.len() && depths[**child] != VISITING)
+                .map(|child| depths[*child])
+                .max()
+                .unwrap_or(0);
+            depths[uid] = depth;
+        }
+        max = max.max(depths[root]);
+    }
+    max
+}
+
+impl Display for ArchiveStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Values: {}", self.values)?;
+        for (kind, count) in &self.kinds {
+            writeln!(f, "  {kind}: {count}")?;
+        }
+        writeln!(f, "Classes: {}", self.classes.len())?;
+        for (class, count) in &self.classes {
+            writeln!(f, "  {class}: {count}")?;
+        }
+        writeln!(f, "Data bytes: {}", self.data_bytes)?;
+        writeln!(f, "String bytes: {}", self.string_bytes)?;
+        writeln!(f, "Max depth: {}", self.max_depth)?;
+        writeln!(f, "Unreachable: {}", self.unreachable)?;
+        write!(f, "Most referenced:")?;
+        for (uid, count) in &self.most_referenced {
+            write!(f, "\n  #{}: {count}", uid.get())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{Error, KeyedArchive, ParseOptions};
+
+/// A magic number of binary plists.
+const BPLIST_MAGIC: &[u8] = b"bplist00";
+/// A size of a binary plist trailer.
+const TRAILER_LEN: usize = 32;
+/// A size of chunks read by [AsyncKeyedArchiveStream].
+#[cfg(feature = "tokio")]
+const CHUNK_LEN: usize = 8192;
+
+/// An iterator over keyed archives concatenated in one byte stream.
+///
+/// Some files (like `*.savedState` data or DTX frames) contain several binary
+/// plists one after another. Documents are split at `bplist00` magic numbers:
+/// a document ends where its trailer says it ends, so magic bytes inside
+/// of data values don't split a document.
+///
+/// Each document yields a [KeyedArchive] or an [Error]. A document that
+/// fails to parse doesn't stop the iteration, but bytes that don't start
+/// with a binary plist magic number do.
+///
+/// ```
+/// # use nskeyedunarchiver::KeyedArchiveStream;
+/// # let bytes = std::fs::read("./tests_resources/plists/note.plist").unwrap();
+/// for archive in KeyedArchiveStream::new(&bytes) {
+///     let archive = archive.unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyedArchiveStream<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    options: ParseOptions,
+}
+
+impl<'a> KeyedArchiveStream<'a> {
+    /// Creates an iterator over archives in `bytes` with default
+    /// [ParseOptions].
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self::with_options(bytes, ParseOptions::default())
+    }
+
+    /// Creates an iterator over archives in `bytes` with given
+    /// [ParseOptions].
+    pub fn with_options(bytes: &'a [u8], options: ParseOptions) -> Self {
+        Self {
+            bytes,
+            offset: 0,
+            options,
+        }
+    }
+
+    /// Returns an offset of the next document.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the next document as a byte slice without parsing it.
+    pub fn next_document(&mut self) -> Option<Result<&'a [u8], Error>> {
+        let rest = &self.bytes[self.offset..];
+        if rest.is_empty() {
+            return None;
+        }
+        if !rest.starts_with(BPLIST_MAGIC) {
+            let offset = self.offset;
+            self.offset = self.bytes.len();
+            return Some(Err(Error::IncorrectFormat(format!(
+                "Expected a binary plist at offset {offset}"
+            ))));
+        }
+        let len = document_len(rest);
+        self.offset += len;
+        Some(Ok(&rest[..len]))
+    }
+}
+
+impl Iterator for KeyedArchiveStream<'_> {
+    type Item = Result<KeyedArchive, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let document = match self.next_document()? {
+            Ok(document) => document,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(KeyedArchive::from_bytes_with_options(
+            document,
+            &self.options,
+        ))
+    }
+}
+
+/// An asynchronous reader of keyed archives concatenated in one byte stream.
+///
+/// It's an async counterpart of [KeyedArchiveStream] for
+/// [tokio](https://crates.io/crates/tokio) readers (like sockets or pipes).
+/// Documents are split the same way, but the reader is only read as far as
+/// needed to find the end of the next document, so archives are yielded
+/// as they arrive.
+///
+/// Bytes that don't start with a binary plist magic number yield an [Error]
+/// and stop reading.
+///
+/// ```
+/// # use nskeyedunarchiver::AsyncKeyedArchiveStream;
+/// # async fn read(socket: impl tokio::io::AsyncRead + Unpin) {
+/// let mut stream = AsyncKeyedArchiveStream::new(socket);
+/// while let Some(archive) = stream.next().await {
+///     let archive = archive.unwrap();
+/// }
+/// # }
+/// ```
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct AsyncKeyedArchiveStream<R> {
+    reader: R,
+    /// Read bytes that don't belong to yielded documents yet.
+    buffer: Vec<u8>,
+    /// A position in `buffer` to continue searching magic numbers from.
+    scanned: usize,
+    offset: usize,
+    eof: bool,
+    options: ParseOptions,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead + Unpin> AsyncKeyedArchiveStream<R> {
+    /// Creates a reader of archives from `reader` with default
+    /// [ParseOptions].
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, ParseOptions::default())
+    }
+
+    /// Creates a reader of archives from `reader` with given
+    /// [ParseOptions].
+    pub fn with_options(reader: R, options: ParseOptions) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            scanned: BPLIST_MAGIC.len(),
+            offset: 0,
+            eof: false,
+            options,
+        }
+    }
+
+    /// Returns an offset of the next document.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Reads the next archive. Returns `None` at the end of the stream.
+    pub async fn next(&mut self) -> Option<Result<KeyedArchive, Error>> {
+        let document = match self.next_document().await? {
+            Ok(document) => document,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(KeyedArchive::from_bytes_with_options(
+            &document,
+            &self.options,
+        ))
+    }
+
+    /// Reads the next document without parsing it.
+    pub async fn next_document(&mut self) -> Option<Result<Vec<u8>, Error>> {
+        loop {
+            let prefix = self.buffer.len().min(BPLIST_MAGIC.len());
+            if self.buffer[..prefix] != BPLIST_MAGIC[..prefix] {
+                return Some(Err(self.stop()));
+            }
+            if let Some(len) = self.complete_document_len() {
+                return Some(Ok(self.take(len)));
+            }
+            if self.eof {
+                break;
+            }
+            if let Err(e) = self.fill().await {
+                self.eof = true;
+                self.buffer.clear();
+                return Some(Err(e.into()));
+            }
+        }
+        if self.buffer.is_empty() {
+            return None;
+        }
+        if !self.buffer.starts_with(BPLIST_MAGIC) {
+            return Some(Err(self.stop()));
+        }
+        let len = document_len(&self.buffer);
+        Some(Ok(self.take(len)))
+    }
+
+    /// Returns a length of the first document in the buffer if the start of
+    /// the next one has already been read.
+    fn complete_document_len(&mut self) -> Option<usize> {
+        while let Some(position) = self
+            .buffer
+            .get(self.scanned..)
+            .and_then(|rest| find(rest, BPLIST_MAGIC))
+        {
+            let end = self.scanned + position;
+            self.scanned = end + 1;
+            if has_trailer(&self.buffer[..end]) {
+                return Some(end);
+            }
+        }
+        // A magic number may be split between chunks
+        let partial = self.buffer.len().saturating_sub(BPLIST_MAGIC.len() - 1);
+        self.scanned = self.scanned.max(partial);
+        None
+    }
+
+    fn take(&mut self, len: usize) -> Vec<u8> {
+        let document = self.buffer.drain(..len).collect();
+        self.offset += len;
+        self.scanned = BPLIST_MAGIC.len();
+        document
+    }
+
+    async fn fill(&mut self) -> std::io::Result<()> {
+        use tokio::io::AsyncReadExt;
+        let mut chunk = [0; CHUNK_LEN];
+        let read = self.reader.read(&mut chunk).await?;
+        if read == 0 {
+            self.eof = true;
+        }
+        self.buffer.extend_from_slice(&chunk[..read]);
+        Ok(())
+    }
+
+    /// Stops reading at unexpected bytes.
+    fn stop(&mut self) -> Error {
+        self.eof = true;
+        self.buffer.clear();
+        Error::IncorrectFormat(format!("Expected a binary plist at offset {}", self.offset))
+    }
+}
+
+/// Returns a length of a binary plist at the start of `bytes`.
+///
+/// Candidate ends are positions of the next magic numbers and the end of
+/// `bytes`. The first one that has a matching trailer right before it is
+/// chosen. If there's none (e.g. trailing bytes follow the last document),
+/// every position is tried.
+fn document_len(bytes: &[u8]) -> usize {
+    let mut start = BPLIST_MAGIC.len();
+    while let Some(position) = find(&bytes[start..], BPLIST_MAGIC) {
+        let end = start + position;
+        if has_trailer(&bytes[..end]) {
+            return end;
+        }
+        start = end + 1;
+    }
+    if has_trailer(bytes) {
+        return bytes.len();
+    }
+    (BPLIST_MAGIC.len() + TRAILER_LEN..bytes.len())
+        .find(|end| has_trailer(&bytes[..*end]))
+        .unwrap_or(bytes.len())
+}
+
+/// Checks if a binary plist trailer at the end of `bytes` describes
+/// a document of exactly this length.
+fn has_trailer(bytes: &[u8]) -> bool {
+    let Some(trailer) = bytes.len().checked_sub(TRAILER_LEN).map(|i| &bytes[i..]) else {
+        return false;
+    };
+    let offset_size = trailer[6] as u64;
+    let number = |range: std::ops::Range<usize>| {
+        u64::from_be_bytes(trailer[range].try_into().unwrap()) // safe, 8 bytes
+    };
+    let objects = number(8..16);
+    let offset_table = number(24..32);
+    objects
+        .checked_mul(offset_size)
+        .and_then(|len| len.checked_add(offset_table))
+        .and_then(|len| len.checked_add(TRAILER_LEN as u64))
+        == Some(bytes.len() as u64)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,212 @@
+//! Structural equality and hashing of archive values.
+//!
+//! Two values are equal if their resolved subtrees are: plain values are
+//! compared as is, objects are compared by their classes and fields (in any
+//! order). Uids and [Rc](std::rc::Rc) pointers are ignored, so equal values
+//! may come from different archives.
+//!
+//! A reference back to a value that is already being compared (a circular
+//! reference) is compared by the number of levels it points up, so cyclic
+//! graphs are compared and hashed without infinite recursion.
+
+use crate::{ArchiveValue, ArchiveValueVariant, ObjectValue};
+use std::hash::{Hash, Hasher};
+
+/// Values being compared or hashed, from a root value down to the current one.
+type Path = Vec<*const ArchiveValue>;
+
+/// Returns how many levels up a value is if it's already on a path.
+fn back_reference(path: &Path, value: &ArchiveValue) -> Option<usize> {
+    let index = path.iter().rposition(|p| std::ptr::eq(*p, value))?;
+    Some(path.len() - index)
+}
+
+fn values_eq(a: &ArchiveValue, b: &ArchiveValue, path_a: &mut Path, path_b: &mut Path) -> bool {
+    let (back_a, back_b) = (back_reference(path_a, a), back_reference(path_b, b));
+    if back_a.is_some() || back_b.is_some() {
+        return back_a == back_b;
+    }
+    use ArchiveValueVariant as V;
+    match (&a.value, &b.value) {
+        (V::Object(obj_a), V::Object(obj_b)) => {
+            if obj_a.classes() != obj_b.classes()
+                || obj_a.class_hints() != obj_b.class_hints()
+                || obj_a.len() != obj_b.len()
+            {
+                return false;
+            }
+            let fields_a = sorted_fields(obj_a.as_map().iter());
+            let fields_b = sorted_fields(obj_b.as_map().iter());
+            path_a.push(a);
+            path_b.push(b);
+            let eq = fields_a
+                .into_iter()
+                .zip(fields_b)
+                .all(|((key_a, a), (key_b, b))| key_a == key_b && fields_eq(a, b, path_a, path_b));
+            path_a.pop();
+            path_b.pop();
+            eq
+        }
+        (V::Real(a), V::Real(b)) => a.to_bits() == b.to_bits(),
+        (V::Object(_), _) | (_, V::Object(_)) => false,
+        (a, b) => a == b,
+    }
+}
+
+fn fields_eq(a: &ObjectValue, b: &ObjectValue, path_a: &mut Path, path_b: &mut Path) -> bool {
+    match (a, b) {
+        (ObjectValue::Ref(a), ObjectValue::Ref(b)) => values_eq(a, b, path_a, path_b),
+        (ObjectValue::RefArray(a), ObjectValue::RefArray(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|(a, b)| values_eq(a, b, path_a, path_b))
+        }
+        (ObjectValue::Real(a), ObjectValue::Real(b)) => a.to_bits() == b.to_bits(),
+        (ObjectValue::Ref(_) | ObjectValue::RefArray(_), _)
+        | (_, ObjectValue::Ref(_) | ObjectValue::RefArray(_)) => false,
+        (a, b) => a == b,
+    }
+}
+
+fn sorted_fields<'a>(
+    fields: impl Iterator<Item = (&'a str, &'a ObjectValue)>,
+) -> Vec<(&'a str, &'a ObjectValue)> {
+    let mut fields: Vec<_> = fields.collect();
+    fields.sort_by_key(|(key, _)| *key);
+    fields
+}
+
+fn hash_value<H: Hasher>(value: &ArchiveValue, path: &mut Path, state: &mut H) {
+    if let Some(levels) = back_reference(path, value) {
+        state.write_u8(0);
+        state.write_u64(levels as u64);
+        return;
+    }
+    use ArchiveValueVariant as V;
+    match &value.value {
+        V::Boolean(b) => {
+            state.write_u8(1);
+            b.hash(state);
+        }
+        V::Classes { classes, hints } => {
+            state.write_u8(2);
+            classes.hash(state);
+            hints.hash(state);
+        }
+        V::Data(data) => {
+            state.write_u8(3);
+            data.hash(state);
+        }
+        V::Integer(integer) => {
+            state.write_u8(4);
+            integer.hash(state);
+        }
+        V::NullRef => state.write_u8(5),
+        V::Object(obj) => {
+            state.write_u8(6);
+            obj.classes().hash(state);
+            obj.class_hints().hash(state);
+            let fields = sorted_fields(obj.as_map().iter());
+            state.write_u64(fields.len() as u64);
+            path.push(value);
+            for (key, field) in fields {
+                key.hash(state);
+                hash_field(field, path, state);
+            }
+            path.pop();
+        }
+        V::Real(real) => {
+            state.write_u8(7);
+            state.write_u64(real.to_bits());
+        }
+        V::String(string) => {
+            state.write_u8(8);
+            string.hash(state);
+        }
+    }
+}
+
+fn hash_field<H: Hasher>(value: &ObjectValue, path: &mut Path, state: &mut H) {
+    match value {
+        ObjectValue::String(string) => {
+            state.write_u8(8);
+            string.hash(state);
+        }
+        ObjectValue::Integer(integer) => {
+            state.write_u8(4);
+            integer.hash(state);
+        }
+        ObjectValue::Real(real) => {
+            state.write_u8(7);
+            state.write_u64(real.to_bits());
+        }
+        ObjectValue::Boolean(b) => {
+            state.write_u8(1);
+            b.hash(state);
+        }
+        ObjectValue::Data(data) => {
+            state.write_u8(3);
+            data.hash(state);
+        }
+        ObjectValue::Date(date) => {
+            state.write_u8(9);
+            date.to_xml_format().hash(state);
+        }
+        ObjectValue::RefArray(values) => {
+            state.write_u8(10);
+            state.write_u64(values.len() as u64);
+            for value in values {
+                hash_value(value, path, state);
+            }
+        }
+        ObjectValue::Ref(value) => hash_value(value, path, state),
+        ObjectValue::NullRef => state.write_u8(5),
+    }
+}
+
+/// Compares resolved subtrees of values, ignoring uids.
+impl PartialEq for ArchiveValue {
+    fn eq(&self, other: &Self) -> bool {
+        values_eq(self, other, &mut Vec::new(), &mut Vec::new())
+    }
+}
+
+impl Eq for ArchiveValue {}
+
+/// Hashes a resolved subtree of a value, ignoring uids.
+impl Hash for ArchiveValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_value(self, &mut Vec::new(), state);
+    }
+}
+
+/// A 64-bit FNV-1a hasher. Unlike [DefaultHasher](std::hash::DefaultHasher)
+/// its output doesn't change between Rust releases.
+struct FnvHasher(u64);
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+impl ArchiveValue {
+    /// Returns a hash of a resolved subtree of the value. Structurally equal
+    /// values get the same fingerprint even if they're taken from different
+    /// archives, so it may be used to dedupe objects or cache decoded results.
+    ///
+    /// Fingerprints are stable between runs on the same platform.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = FnvHasher(0xcbf29ce484222325);
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
@@ -0,0 +1,144 @@
+use crate::decodable::borrow_str;
+use crate::{ArchiveValue, Date, Decodable, KeyedArchive, ObjectValue};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::time::SystemTime;
+
+/// Fields of objects of one class laid out as a table, returned by
+/// [KeyedArchive::table].
+///
+/// Many archives are arrays of homogeneous objects (log entries, history
+/// items and so on). A table turns them into rows of strings, e.g. to
+/// analyze them in a spreadsheet with [Table::write_csv].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    /// Names of columns, the same as requested fields.
+    pub columns: Vec<String>,
+    /// Cells of each object, in the order of uids.
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Writes the table as CSV (RFC 4180) with a header row.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writer.write_all(self.to_csv().as_bytes())
+    }
+
+    /// Returns the table as CSV (RFC 4180) with a header row.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::new();
+        for row in std::iter::once(&self.columns).chain(&self.rows) {
+            for (i, cell) in row.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                if cell.contains([',', '"', '\n', '\r']) {
+                    let _ = write!(out, "\"{}\"", cell.replace('"', "\"\""));
+                } else {
+                    out.push_str(cell);
+                }
+            }
+            out.push_str("\r\n");
+        }
+        out
+    }
+}
+
+impl KeyedArchive {
+    /// Returns an iterator over rows of all objects of a given class (see
+    /// [KeyedArchive::objects_of_class]). Each row contains a cell of every
+    /// field, see [KeyedArchive::table].
+    pub fn table_rows<'a>(
+        &'a self,
+        class: &str,
+        fields: &'a [&str],
+    ) -> impl Iterator<Item = Vec<String>> + 'a {
+        self.objects_of_class(class).map(move |value| {
+            fields
+                .iter()
+                .map(|field| key_path_cell(value, field))
+                .collect()
+        })
+    }
+
+    /// Collects fields of all objects of a given class into a [Table].
+    ///
+    /// A field may be a key path going through referenced objects, like
+    /// `author.name`. Cells are formatted as follows:
+    ///
+    /// - strings (including `NSString`s) are kept as is;
+    /// - numbers and booleans are printed with [Display](std::fmt::Display);
+    /// - dates (including `NSDate`s) are printed in the ISO 8601 format;
+    /// - data is printed as a hex string;
+    /// - arrays are printed as cells of their elements separated by `, `;
+    /// - other objects are printed as `<ClassName #uid>`;
+    /// - missing fields and `$null` references are empty.
+    pub fn table(&self, class: &str, fields: &[&str]) -> Table {
+        Table {
+            columns: fields.iter().map(|field| field.to_string()).collect(),
+            rows: self.table_rows(class, fields).collect(),
+        }
+    }
+}
+
+fn key_path_cell(value: &ArchiveValue, key_path: &str) -> String {
+    let mut value = value;
+    let mut keys = key_path.split('.').peekable();
+    while let Some(key) = keys.next() {
+        let Some(field) = value.as_object().and_then(|obj| obj.as_map().get(key)) else {
+            return String::new();
+        };
+        match field {
+            ObjectValue::Ref(next) if keys.peek().is_some() => value = &**next,
+            _ if keys.peek().is_some() => return String::new(),
+            field => return cell(field),
+        }
+    }
+    String::new()
+}
+
+fn cell(value: &ObjectValue) -> String {
+    if let Ok(s) = borrow_str(value, false) {
+        return s.to_string();
+    }
+    if let Ok(time) = SystemTime::decode(value) {
+        return Date::from(time).to_xml_format();
+    }
+    match value {
+        ObjectValue::Integer(i) => i.to_string(),
+        ObjectValue::Real(r) => r.to_string(),
+        ObjectValue::Boolean(b) => b.to_string(),
+        ObjectValue::Data(data) => hex(data),
+        ObjectValue::RefArray(values) => values
+            .iter()
+            .map(|value| cell(&ObjectValue::Ref(value.clone())))
+            .collect::<Vec<_>>()
+            .join(", "),
+        ObjectValue::Ref(value) => value_cell(value),
+        ObjectValue::Date(_) | ObjectValue::String(_) | ObjectValue::NullRef => String::new(),
+    }
+}
+
+fn value_cell(value: &ArchiveValue) -> String {
+    if let Some(i) = value.as_integer() {
+        i.to_string()
+    } else if let Some(r) = value.as_float() {
+        r.to_string()
+    } else if let Some(b) = value.as_boolean() {
+        b.to_string()
+    } else if let Some(data) = value.as_data() {
+        hex(data)
+    } else if let Some(obj) = value.as_object() {
+        format!("<{} #{}>", obj.class(), value.unique_id().get())
+    } else {
+        String::new()
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len() * 2);
+    for byte in data {
+        let _ = write!(out, "{byte:02x}");
+    }
+    out
+}
@@ -0,0 +1,167 @@
+//! Snapshot testing helpers.
+//!
+//! Archive values are rendered into a stable text form that may be used with
+//! snapshot testing tools like [insta](https://crates.io/crates/insta):
+//!
+//! ```text
+//! root: Note @1 {
+//!   data: <3 bytes: 00 01 02>
+//!   tags: NSArray @2 {
+//!     NS.objects: [
+//!       "work"
+//!       ↩ @1
+//!     ]
+//!   }
+//!   title: "Some note"
+//! }
+//! ```
+//!
+//! Unlike [Pretty](crate::Pretty) the output doesn't depend on the layout of
+//! an archive: keys are sorted, uids are replaced with `@n` labels numbered
+//! in the order objects are rendered, and data is printed in full. A shared
+//! or circular reference to an already rendered object becomes `↩ @n`.
+//!
+//! ```ignore
+//! let archive = KeyedArchive::from_file("note.plist")?;
+//! insta::assert_snapshot!(nskeyedunarchiver::test_util::snapshot_archive(&archive));
+//! ```
+
+use crate::{ArchiveValue, DEFAULT_MAX_DEPTH, KeyedArchive, ObjectValue};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Renders all `$top` entries of an archive, sorted by their keys.
+/// Labels of objects are shared between entries.
+pub fn snapshot_archive(archive: &KeyedArchive) -> String {
+    let mut keys = archive.top_keys();
+    keys.sort();
+    let mut renderer = Renderer::default();
+    for key in keys {
+        let _ = write!(renderer.out, "{key}: ");
+        renderer.value(&archive.top()[key], 0);
+        renderer.out.push('\n');
+    }
+    renderer.out
+}
+
+/// Renders a value tree.
+pub fn snapshot(value: &ArchiveValue) -> String {
+    let mut renderer = Renderer::default();
+    renderer.value(value, 0);
+    renderer.out
+}
+
+/// Renders a value of an object field, e.g. a value passed to
+/// [Decodable::decode](crate::Decodable::decode).
+pub fn snapshot_field(value: &ObjectValue) -> String {
+    let mut renderer = Renderer::default();
+    renderer.field(value, 0);
+    renderer.out
+}
+
+#[derive(Default)]
+struct Renderer {
+    out: String,
+    // Uids of rendered objects and their labels
+    labels: HashMap<usize, usize>,
+}
+
+impl Renderer {
+    fn indent(&mut self, depth: usize) {
+        for _ in 0..depth {
+            self.out.push_str("  ");
+        }
+    }
+
+    fn value(&mut self, value: &ArchiveValue, depth: usize) {
+        if let Some(s) = value.as_string() {
+            let _ = write!(self.out, "{s:?}");
+        } else if let Some(i) = value.as_integer() {
+            let _ = write!(self.out, "{i}");
+        } else if let Some(r) = value.as_float() {
+            let _ = write!(self.out, "{r:?}");
+        } else if let Some(b) = value.as_boolean() {
+            let _ = write!(self.out, "{b}");
+        } else if let Some(data) = value.as_data() {
+            self.data(data);
+        } else if value.is_null_ref() {
+            self.out.push_str("$null");
+        } else if let Some(classes) = value.as_classes() {
+            let _ = write!(self.out, "$classes {classes:?}");
+        } else if let Some(obj) = value.as_object() {
+            let uid = value.unique_id().get();
+            if let Some(label) = self.labels.get(&uid) {
+                let _ = write!(self.out, "↩ @{label}");
+                return;
+            }
+            let label = self.labels.len() + 1;
+            self.labels.insert(uid, label);
+            let _ = write!(self.out, "{} @{label}", obj.class());
+            if depth >= DEFAULT_MAX_DEPTH {
+                self.out.push_str(" { … }");
+                return;
+            }
+            let mut keys = obj.keys();
+            if keys.is_empty() {
+                self.out.push_str(" {}");
+                return;
+            }
+            keys.sort();
+            self.out.push_str(" {\n");
+            for key in keys {
+                self.indent(depth + 1);
+                let _ = write!(self.out, "{key}: ");
+                self.field(&obj.as_map()[key], depth + 1);
+                self.out.push('\n');
+            }
+            self.indent(depth);
+            self.out.push('}');
+        }
+    }
+
+    fn field(&mut self, value: &ObjectValue, depth: usize) {
+        match value {
+            ObjectValue::String(s) => {
+                let _ = write!(self.out, "{s:?}");
+            }
+            ObjectValue::Integer(i) => {
+                let _ = write!(self.out, "{i}");
+            }
+            ObjectValue::Real(r) => {
+                let _ = write!(self.out, "{r:?}");
+            }
+            ObjectValue::Boolean(b) => {
+                let _ = write!(self.out, "{b}");
+            }
+            ObjectValue::Data(data) => self.data(data),
+            ObjectValue::Date(date) => self.out.push_str(&date.to_xml_format()),
+            ObjectValue::NullRef => self.out.push_str("$null"),
+            ObjectValue::Ref(value) => self.value(value, depth),
+            ObjectValue::RefArray(values) => {
+                if values.is_empty() {
+                    self.out.push_str("[]");
+                    return;
+                }
+                self.out.push_str("[\n");
+                for value in values {
+                    self.indent(depth + 1);
+                    self.value(value, depth + 1);
+                    self.out.push('\n');
+                }
+                self.indent(depth);
+                self.out.push(']');
+            }
+        }
+    }
+
+    fn data(&mut self, data: &[u8]) {
+        let _ = write!(self.out, "<{} bytes", data.len());
+        if !data.is_empty() {
+            self.out.push(':');
+        }
+        for byte in data {
+            let _ = write!(self.out, " {byte:02x}");
+        }
+        self.out.push('>');
+    }
+}
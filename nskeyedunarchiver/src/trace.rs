@@ -0,0 +1,19 @@
+//! Helpers for the optional `tracing` instrumentation.
+
+/// Enters a [tracing] span with a given level, name and fields until the end
+/// of the current scope. Does nothing if the `tracing` feature is disabled,
+/// field values aren't even evaluated in this case.
+macro_rules! span {
+    ($level:ident, $name:expr $(, $($fields:tt)*)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::span!(tracing::Level::$level, $name $(, $($fields)*)?).entered();
+    };
+}
+
+/// Emits a [tracing] event if the `tracing` feature is enabled.
+macro_rules! event {
+    ($level:ident, $($args:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!(tracing::Level::$level, $($args)*);
+    };
+}
@@ -0,0 +1,613 @@
+//! A decoder of legacy `NSArchiver` archives (typedstreams).
+//!
+//! Before keyed archives, Cocoa (and NeXTSTEP) used `NSArchiver`, which
+//! writes a binary "typedstream" instead of a plist. Such archives are still
+//! found in the wild, e.g. in the `attributedBody` column of the iMessage
+//! database. [KeyedArchive](crate::KeyedArchive) refuses them with
+//! [Error::UnsupportedLegacyArchiver].
+//!
+//! A typedstream is a sequence of values, each of them is preceded by its
+//! Objective-C type encoding. Values of objects are written by their classes
+//! and aren't named, so [TypedStream] only decodes a generic tree of values.
+//! For instance, an `NSString` object contains a single [TypedValue::String]
+//! value with UTF-8 bytes and an `NSArray` object contains its length
+//! followed by its elements.
+//!
+//! Like values of a keyed archive, objects are kept in a single list and
+//! values refer to them by their indices, since one object may be referenced
+//! several times.
+//!
+//! ```no_run
+//! use nskeyedunarchiver::typedstream::TypedStream;
+//!
+//! let bytes = std::fs::read("attributedBody.bin").unwrap();
+//! let stream = TypedStream::from_bytes(&bytes).unwrap();
+//! let root = stream.root_object().unwrap();
+//! println!("{}", root.class());
+//! ```
+
+use crate::{DEFAULT_MAX_DEPTH, Error};
+
+/// The signature of little-endian typedstreams.
+const SIGNATURE_LITTLE_ENDIAN: &[u8] = b"streamtyped";
+/// The signature of big-endian typedstreams.
+const SIGNATURE_BIG_ENDIAN: &[u8] = b"typedstream";
+/// The only known streamer version.
+const STREAMER_VERSION: u8 = 4;
+
+// Special head bytes. Other bytes are single-byte integers.
+const TAG_INTEGER_2: i8 = -127;
+const TAG_INTEGER_4: i8 = -126;
+const TAG_FLOATING_POINT: i8 = -125;
+const TAG_NEW: i8 = -124;
+const TAG_NIL: i8 = -123;
+const TAG_END_OF_OBJECT: i8 = -122;
+/// Head bytes from -128 up to this one are reserved for tags.
+const LAST_TAG: i8 = -111;
+/// Reference numbers start with this value (as a signed integer).
+const FIRST_REFERENCE_NUMBER: i64 = -110;
+
+/// Checks if `bytes` start with a typedstream header.
+pub fn is_typedstream(bytes: &[u8]) -> bool {
+    match bytes {
+        [STREAMER_VERSION, len, rest @ ..] => {
+            let signature = rest.get(..*len as usize);
+            signature == Some(SIGNATURE_LITTLE_ENDIAN) || signature == Some(SIGNATURE_BIG_ENDIAN)
+        }
+        _ => false,
+    }
+}
+
+/// A class of a [TypedObject] along with its version.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypedClass {
+    pub name: String,
+    pub version: i64,
+}
+
+/// A value of a typedstream.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypedValue {
+    /// A signed or unsigned integer of any size (`c`, `i`, `Q`, etc.).
+    Integer(i64),
+    /// A `float` or a `double`.
+    Float(f64),
+    /// A C string (`*`), a string of bytes (`+`, used by `NSString`) or
+    /// a selector (`:`). It's [None] if a string is nil.
+    String(Option<Vec<u8>>),
+    /// A fixed size array of chars (`[16c]`).
+    Bytes(Vec<u8>),
+    /// A fixed size array of other values.
+    Array(Vec<TypedValue>),
+    /// Fields of a struct.
+    Struct(Vec<TypedValue>),
+    /// An index of an object (see [TypedStream::object]). It's [None] if
+    /// an object is nil.
+    Object(Option<usize>),
+    /// A class with its superclasses. It's empty if a class is nil.
+    Class(Vec<TypedClass>),
+}
+
+impl TypedValue {
+    /// Returns an integer value.
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Self::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Returns a float value.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Self::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    /// Returns a string value if it's valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(Some(bytes)) => std::str::from_utf8(bytes).ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns bytes of a string or of a char array.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::String(Some(bytes)) | Self::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Returns an index of an object.
+    pub fn as_object(&self) -> Option<usize> {
+        match self {
+            Self::Object(index) => *index,
+            _ => None,
+        }
+    }
+}
+
+/// An object of a typedstream.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TypedObject {
+    classes: Vec<TypedClass>,
+    values: Vec<TypedValue>,
+}
+
+impl TypedObject {
+    /// Returns a class of the object.
+    pub fn class(&self) -> &str {
+        self.classes
+            .first()
+            .map(|c| c.name.as_str())
+            .unwrap_or_default()
+    }
+
+    /// Returns classes of the object. The first one is the actual class,
+    /// the other ones are its parents.
+    pub fn classes(&self) -> &[TypedClass] {
+        &self.classes
+    }
+
+    /// Returns values written by the object's classes in order.
+    pub fn values(&self) -> &[TypedValue] {
+        &self.values
+    }
+}
+
+/// A decoded typedstream. See the [module](self) docs.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypedStream {
+    system_version: i64,
+    values: Vec<TypedValue>,
+    objects: Vec<TypedObject>,
+}
+
+impl TypedStream {
+    /// Decodes a typedstream from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes)?;
+        let system_version = reader.read_integer(true)?;
+        let mut values = Vec::new();
+        while !reader.is_empty() {
+            reader.read_group(&mut values, 0)?;
+        }
+        Ok(Self {
+            system_version,
+            values,
+            objects: reader.objects,
+        })
+    }
+
+    /// Returns a version of a system the stream is written on.
+    pub fn system_version(&self) -> i64 {
+        self.system_version
+    }
+
+    /// Returns top-level values of the stream.
+    pub fn values(&self) -> &[TypedValue] {
+        &self.values
+    }
+
+    /// Returns an object with a given `index`.
+    pub fn object(&self, index: usize) -> Option<&TypedObject> {
+        self.objects.get(index)
+    }
+
+    /// Returns all objects of the stream.
+    pub fn objects(&self) -> &[TypedObject] {
+        &self.objects
+    }
+
+    /// Returns the first top-level object. Archives usually contain
+    /// a single root object.
+    pub fn root_object(&self) -> Option<&TypedObject> {
+        let index = self.values.iter().find_map(TypedValue::as_object)?;
+        self.object(index)
+    }
+}
+
+/// An Objective-C type encoding.
+#[derive(Debug)]
+enum Type {
+    Signed,
+    Unsigned,
+    Float,
+    Double,
+    CString,
+    Atom,
+    Selector,
+    Object,
+    Class,
+    Bytes(usize),
+    Array(usize, Box<Type>),
+    Struct(Vec<Type>),
+}
+
+impl Type {
+    /// Returns the least number of bytes a value of the type takes.
+    fn min_size(&self) -> usize {
+        match self {
+            Type::Bytes(len) => *len,
+            Type::Array(len, element) => len.saturating_mul(element.min_size()),
+            Type::Struct(fields) => fields
+                .iter()
+                .map(Type::min_size)
+                .fold(0, usize::saturating_add),
+            _ => 1,
+        }
+    }
+
+    /// Parses a sequence of type encodings.
+    fn parse_all(mut encoding: &[u8]) -> Result<Vec<Type>, Error> {
+        let mut types = Vec::new();
+        while !encoding.is_empty() {
+            let (ty, rest) = Self::parse(encoding, 0)?;
+            types.push(ty);
+            encoding = rest;
+        }
+        Ok(types)
+    }
+
+    fn parse(encoding: &[u8], depth: usize) -> Result<(Type, &[u8]), Error> {
+        if depth >= DEFAULT_MAX_DEPTH {
+            return Err(incorrect("Type encoding is nested too deep"));
+        }
+        let Some((first, rest)) = encoding.split_first() else {
+            return Err(incorrect("Unexpected end of a type encoding"));
+        };
+        let ty = match first {
+            b'c' | b's' | b'i' | b'l' | b'q' => Type::Signed,
+            b'C' | b'S' | b'I' | b'L' | b'Q' | b'B' => Type::Unsigned,
+            b'f' => Type::Float,
+            b'd' => Type::Double,
+            b'*' => Type::CString,
+            b'+' => Type::Atom,
+            b':' => Type::Selector,
+            b'@' => Type::Object,
+            b'#' => Type::Class,
+            b'[' => {
+                let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+                let len = std::str::from_utf8(&rest[..digits])
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| incorrect("Incorrect array length"))?;
+                let rest = &rest[digits..];
+                // Arrays of chars are read as is
+                let is_bytes = matches!(rest.first(), Some(b'c' | b'C'));
+                let (element, rest) = Self::parse(rest, depth + 1)?;
+                let Some(rest) = rest.strip_prefix(b"]") else {
+                    return Err(incorrect("Unterminated array type encoding"));
+                };
+                let ty = if is_bytes {
+                    Type::Bytes(len)
+                } else {
+                    Type::Array(len, Box::new(element))
+                };
+                return Ok((ty, rest));
+            }
+            b'{' => {
+                // `{name=types}`, the name is ignored
+                let name_len = rest
+                    .iter()
+                    .position(|b| *b == b'=' || *b == b'}')
+                    .ok_or_else(|| incorrect("Unterminated struct type encoding"))?;
+                let mut rest = &rest[name_len..];
+                let mut fields = Vec::new();
+                if let Some(fields_rest) = rest.strip_prefix(b"=") {
+                    rest = fields_rest;
+                    while !rest.starts_with(b"}") {
+                        let (field, field_rest) = Self::parse(rest, depth + 1)?;
+                        fields.push(field);
+                        rest = field_rest;
+                    }
+                }
+                return Ok((Type::Struct(fields), &rest[1..]));
+            }
+            other => {
+                return Err(incorrect(&format!(
+                    "Unsupported type encoding `{}`",
+                    *other as char
+                )));
+            }
+        };
+        Ok((ty, rest))
+    }
+}
+
+/// An entry of the shared object table. Objects, classes and C strings
+/// share the same reference numbers.
+enum Shared {
+    Object(usize),
+    Class(Vec<TypedClass>),
+    CString(Vec<u8>),
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+    shared_strings: Vec<Vec<u8>>,
+    shared_objects: Vec<Shared>,
+    objects: Vec<TypedObject>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        if !is_typedstream(bytes) {
+            return Err(incorrect("Missing a typedstream header"));
+        }
+        let signature_len = bytes[1] as usize;
+        let signature = &bytes[2..2 + signature_len];
+        Ok(Self {
+            bytes: &bytes[2 + signature_len..],
+            big_endian: signature == SIGNATURE_BIG_ENDIAN,
+            shared_strings: Vec::new(),
+            shared_objects: Vec::new(),
+            objects: Vec::new(),
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn read_exact<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let Some((bytes, rest)) = self.bytes.split_first_chunk::<N>() else {
+            return Err(incorrect("Unexpected end of a stream"));
+        };
+        self.bytes = rest;
+        Ok(*bytes)
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let Some((bytes, rest)) = self.bytes.split_at_checked(len) else {
+            return Err(incorrect("Unexpected end of a stream"));
+        };
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    fn read_head(&mut self) -> Result<i8, Error> {
+        Ok(self.read_exact::<1>()?[0] as i8)
+    }
+
+    fn read_integer(&mut self, signed: bool) -> Result<i64, Error> {
+        let head = self.read_head()?;
+        self.read_integer_with_head(head, signed)
+    }
+
+    fn read_integer_with_head(&mut self, head: i8, signed: bool) -> Result<i64, Error> {
+        Ok(match head {
+            TAG_INTEGER_2 => {
+                let bytes = self.read_exact::<2>()?;
+                let value = if self.big_endian {
+                    i16::from_be_bytes(bytes)
+                } else {
+                    i16::from_le_bytes(bytes)
+                };
+                if signed {
+                    value as i64
+                } else {
+                    value as u16 as i64
+                }
+            }
+            TAG_INTEGER_4 => {
+                let bytes = self.read_exact::<4>()?;
+                let value = if self.big_endian {
+                    i32::from_be_bytes(bytes)
+                } else {
+                    i32::from_le_bytes(bytes)
+                };
+                if signed {
+                    value as i64
+                } else {
+                    value as u32 as i64
+                }
+            }
+            head if head <= LAST_TAG => {
+                return Err(incorrect(&format!(
+                    "Unexpected tag {head} instead of an integer"
+                )));
+            }
+            head if signed => head as i64,
+            head => head as u8 as i64,
+        })
+    }
+
+    fn read_float(&mut self, double: bool) -> Result<f64, Error> {
+        let head = self.read_head()?;
+        if head != TAG_FLOATING_POINT {
+            return Ok(self.read_integer_with_head(head, true)? as f64);
+        }
+        Ok(match (double, self.big_endian) {
+            (true, true) => f64::from_be_bytes(self.read_exact()?),
+            (true, false) => f64::from_le_bytes(self.read_exact()?),
+            (false, true) => f32::from_be_bytes(self.read_exact()?) as f64,
+            (false, false) => f32::from_le_bytes(self.read_exact()?) as f64,
+        })
+    }
+
+    /// Converts a reference number into an index of a shared table.
+    fn reference_index(&mut self, head: i8) -> Result<usize, Error> {
+        let number = self.read_integer_with_head(head, true)?;
+        usize::try_from(number - FIRST_REFERENCE_NUMBER)
+            .map_err(|_| incorrect(&format!("Incorrect reference number {number}")))
+    }
+
+    fn read_unshared_string(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let head = self.read_head()?;
+        if head == TAG_NIL {
+            return Ok(None);
+        }
+        let len = self.read_integer_with_head(head, false)?;
+        let len = usize::try_from(len).map_err(|_| incorrect("Incorrect string length"))?;
+        Ok(Some(self.read_slice(len)?.to_vec()))
+    }
+
+    fn read_shared_string(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let head = self.read_head()?;
+        match head {
+            TAG_NIL => Ok(None),
+            TAG_NEW => {
+                let string = self
+                    .read_unshared_string()?
+                    .ok_or_else(|| incorrect("A new shared string is nil"))?;
+                self.shared_strings.push(string.clone());
+                Ok(Some(string))
+            }
+            head => {
+                let index = self.reference_index(head)?;
+                let string = self.shared_strings.get(index).ok_or_else(|| {
+                    incorrect(&format!("Incorrect shared string reference {index}"))
+                })?;
+                Ok(Some(string.clone()))
+            }
+        }
+    }
+
+    fn read_c_string(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let head = self.read_head()?;
+        match head {
+            TAG_NIL => Ok(None),
+            TAG_NEW => {
+                let string = self
+                    .read_shared_string()?
+                    .ok_or_else(|| incorrect("A new C string is nil"))?;
+                self.shared_objects.push(Shared::CString(string.clone()));
+                Ok(Some(string))
+            }
+            head => match self.shared_reference(head)? {
+                Shared::CString(string) => Ok(Some(string.clone())),
+                _ => Err(incorrect("A reference doesn't point to a C string")),
+            },
+        }
+    }
+
+    fn shared_reference(&mut self, head: i8) -> Result<&Shared, Error> {
+        let index = self.reference_index(head)?;
+        self.shared_objects
+            .get(index)
+            .ok_or_else(|| incorrect(&format!("Incorrect object reference {index}")))
+    }
+
+    /// Reads a class along with its superclasses.
+    fn read_class(&mut self) -> Result<Vec<TypedClass>, Error> {
+        let mut new_classes = Vec::new();
+        let tail = loop {
+            let head = self.read_head()?;
+            match head {
+                TAG_NEW => {
+                    let name = self
+                        .read_shared_string()?
+                        .ok_or_else(|| incorrect("A class name is nil"))?;
+                    let version = self.read_integer(true)?;
+                    new_classes.push((
+                        self.shared_objects.len(),
+                        TypedClass {
+                            name: String::from_utf8_lossy(&name).into_owned(),
+                            version,
+                        },
+                    ));
+                    // Filled once the whole chain is read
+                    self.shared_objects.push(Shared::Class(Vec::new()));
+                }
+                TAG_NIL => break Vec::new(),
+                head => match self.shared_reference(head)? {
+                    Shared::Class(classes) => break classes.clone(),
+                    _ => return Err(incorrect("A reference doesn't point to a class")),
+                },
+            }
+        };
+        let mut chain = tail;
+        for (index, class) in new_classes.into_iter().rev() {
+            chain.insert(0, class);
+            self.shared_objects[index] = Shared::Class(chain.clone());
+        }
+        Ok(chain)
+    }
+
+    fn read_object(&mut self, depth: usize) -> Result<Option<usize>, Error> {
+        let head = self.read_head()?;
+        match head {
+            TAG_NIL => Ok(None),
+            TAG_NEW => {
+                if depth >= DEFAULT_MAX_DEPTH {
+                    return Err(Error::LimitExceeded(format!(
+                        "Objects are nested deeper than {DEFAULT_MAX_DEPTH} levels"
+                    )));
+                }
+                // An object is registered before its class
+                let index = self.objects.len();
+                self.objects.push(TypedObject::default());
+                self.shared_objects.push(Shared::Object(index));
+                let classes = self.read_class()?;
+                let mut values = Vec::new();
+                loop {
+                    if self.bytes.first().map(|b| *b as i8) == Some(TAG_END_OF_OBJECT) {
+                        self.bytes = &self.bytes[1..];
+                        break;
+                    }
+                    self.read_group(&mut values, depth + 1)?;
+                }
+                self.objects[index] = TypedObject { classes, values };
+                Ok(Some(index))
+            }
+            head => match self.shared_reference(head)? {
+                Shared::Object(index) => Ok(Some(*index)),
+                _ => Err(incorrect("A reference doesn't point to an object")),
+            },
+        }
+    }
+
+    /// Reads a type encoding and values of these types.
+    fn read_group(&mut self, values: &mut Vec<TypedValue>, depth: usize) -> Result<(), Error> {
+        let encoding = self
+            .read_shared_string()?
+            .ok_or_else(|| incorrect("A type encoding is nil"))?;
+        for ty in Type::parse_all(&encoding)? {
+            values.push(self.read_value(&ty, depth)?);
+        }
+        Ok(())
+    }
+
+    fn read_value(&mut self, ty: &Type, depth: usize) -> Result<TypedValue, Error> {
+        Ok(match ty {
+            Type::Signed => TypedValue::Integer(self.read_integer(true)?),
+            Type::Unsigned => TypedValue::Integer(self.read_integer(false)?),
+            Type::Float => TypedValue::Float(self.read_float(false)?),
+            Type::Double => TypedValue::Float(self.read_float(true)?),
+            Type::CString => TypedValue::String(self.read_c_string()?),
+            Type::Atom => TypedValue::String(self.read_unshared_string()?),
+            Type::Selector => TypedValue::String(self.read_shared_string()?),
+            Type::Object => TypedValue::Object(self.read_object(depth)?),
+            Type::Class => TypedValue::Class(self.read_class()?),
+            Type::Bytes(len) => TypedValue::Bytes(self.read_slice(*len)?.to_vec()),
+            Type::Array(len, element) => {
+                // Checked in advance, so huge lengths can't exhaust memory
+                let size = element.min_size();
+                if size == 0 || len.saturating_mul(size) > self.bytes.len() {
+                    return Err(incorrect(&format!("Incorrect array length {len}")));
+                }
+                let mut array = Vec::with_capacity(*len);
+                for _ in 0..*len {
+                    array.push(self.read_value(element, depth)?);
+                }
+                TypedValue::Array(array)
+            }
+            Type::Struct(fields) => {
+                let mut values = Vec::with_capacity(fields.len());
+                for field in fields {
+                    values.push(self.read_value(field, depth)?);
+                }
+                TypedValue::Struct(values)
+            }
+        })
+    }
+}
+
+fn incorrect(message: &str) -> Error {
+    Error::IncorrectTypedStream(message.into())
+}
@@ -0,0 +1,368 @@
+use crate::{
+    ARCHIVER_KEY_NAME, ARCHIVER_VERSION, KeyedArchive, NULL_OBJECT_REFERENCE_NAME,
+    OBJECTS_KEY_NAME, ParseOptions, TOP_KEY_NAME, UniqueId, VERSION_KEY_NAME,
+};
+use plist::{Dictionary as PlistDictionary, Value as PlistValue};
+use std::fmt::Display;
+
+/// A single problem found while validating an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValidationIssue {
+    /// The archive header (`$archiver`, `$version`, `$top` or `$objects`)
+    /// is missing or incorrect.
+    Header(String),
+    /// A value inside of `$objects` has an unexpected structure.
+    MalformedValue { uid: UniqueId, reason: String },
+    /// A reference points outside of `$objects`. `from` is [None] for
+    /// `$top` entries.
+    UidOutOfRange {
+        from: Option<UniqueId>,
+        key: String,
+        uid: u64,
+    },
+    /// A `$class` of an object doesn't point to a `$classes` entry.
+    InvalidClass { object: UniqueId, class_uid: u64 },
+    /// A reference points to a `$classes` entry instead of a value.
+    /// `from` is [None] for `$top` entries.
+    ReferenceToClasses {
+        from: Option<UniqueId>,
+        key: String,
+        uid: u64,
+    },
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let location = |from: &Option<UniqueId>, key: &str| match from {
+            Some(from) => format!("object (uid: {})->{key}", from.get()),
+            None => format!("{TOP_KEY_NAME}->{key}"),
+        };
+        match self {
+            Self::Header(message) => write!(f, "Incorrect header: {message}"),
+            Self::MalformedValue { uid, reason } => {
+                write!(f, "Malformed value (uid: {}): {reason}", uid.get())
+            }
+            Self::UidOutOfRange { from, key, uid } => {
+                write!(f, "{}: uid {uid} is out of range", location(from, key))
+            }
+            Self::InvalidClass { object, class_uid } => write!(
+                f,
+                "Object (uid: {}): `$class` uid {class_uid} isn't a classes entry",
+                object.get()
+            ),
+            Self::ReferenceToClasses { from, key, uid } => write!(
+                f,
+                "{}: uid {uid} refers to a classes entry",
+                location(from, key)
+            ),
+        }
+    }
+}
+
+/// A result of [KeyedArchive::validate].
+///
+/// Unlike parsing, validation doesn't stop at the first problem, so it may
+/// be used to find out what is wrong with a damaged archive.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ValidationReport {
+    issues: Vec<ValidationIssue>,
+    orphans: Vec<UniqueId>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if no issues are found. Orphan objects aren't
+    /// considered as issues.
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns all found issues.
+    pub fn issues(&self) -> &[ValidationIssue] {
+        &self.issues
+    }
+
+    /// Returns ids of values that can't be reached from `$top`.
+    pub fn orphans(&self) -> &[UniqueId] {
+        &self.orphans
+    }
+}
+
+/// A kind of a value inside of `$objects`.
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Value,
+    Classes,
+    Malformed,
+}
+
+fn kind_of(value: &PlistValue) -> Result<Kind, String> {
+    match value {
+        PlistValue::Dictionary(dict) => {
+            if dict.contains_key("$class") {
+                Ok(Kind::Value)
+            } else if let Some(classes) = dict.get("$classes") {
+                let Some(classes) = classes.as_array() else {
+                    return Err("`$classes` isn't an array".into());
+                };
+                if classes.iter().any(|class| class.as_string().is_none()) {
+                    return Err("`$classes` should contain only strings".into());
+                }
+                if classes.is_empty() {
+                    return Err("`$classes` is empty".into());
+                }
+                let hints = dict.get("$classhints").map(|hints| hints.as_array());
+                if let Some(hints) = hints
+                    && !hints.is_some_and(|hints| hints.iter().all(|h| h.as_string().is_some()))
+                {
+                    return Err("`$classhints` should be an array of strings".into());
+                }
+                Ok(Kind::Classes)
+            } else {
+                Err("A dictionary is neither an object nor a classes entry".into())
+            }
+        }
+        PlistValue::Boolean(_)
+        | PlistValue::Data(_)
+        | PlistValue::Real(_)
+        | PlistValue::Integer(_)
+        | PlistValue::String(_) => Ok(Kind::Value),
+        _ => Err(format!("Unexpected value type: {value:?}")),
+    }
+}
+
+impl KeyedArchive {
+    /// Validates a plist with a keyed archive structure using default
+    /// [ParseOptions] and returns a [ValidationReport].
+    ///
+    /// It checks that all uids are in range, every object's `$class` points
+    /// to a classes entry and no references point to classes entries. It
+    /// also finds orphan values that can't be reached from `$top`.
+    pub fn validate(plist: &PlistValue) -> ValidationReport {
+        Self::validate_with_options(plist, &ParseOptions::default())
+    }
+
+    /// Validates a plist with a keyed archive structure using given
+    /// [ParseOptions] and returns a [ValidationReport].
+    ///
+    /// See [KeyedArchive::validate].
+    pub fn validate_with_options(plist: &PlistValue, options: &ParseOptions) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        let Some(dict) = plist.as_dictionary() else {
+            report.issues.push(ValidationIssue::Header(
+                "Expected root key to be a type of `Dictionary`".into(),
+            ));
+            return report;
+        };
+        validate_header(dict, options, &mut report.issues);
+
+        let Some(objects) = dict.get(OBJECTS_KEY_NAME).and_then(|v| v.as_array()) else {
+            report.issues.push(ValidationIssue::Header(format!(
+                "Expected `{OBJECTS_KEY_NAME}` key to be a type of `Array`"
+            )));
+            return report;
+        };
+
+        let mut kinds = Vec::with_capacity(objects.len());
+        for (index, value) in objects.iter().enumerate() {
+            kinds.push(kind_of(value).unwrap_or_else(|reason| {
+                report.issues.push(ValidationIssue::MalformedValue {
+                    uid: UniqueId::new(index),
+                    reason,
+                });
+                Kind::Malformed
+            }));
+        }
+
+        // Checks references of all objects, including unreachable ones
+        let mut edges = vec![Vec::new(); objects.len()];
+        for (index, value) in objects.iter().enumerate() {
+            let Some(object) = value.as_dictionary() else {
+                continue;
+            };
+            if kinds[index] != Kind::Value {
+                continue;
+            }
+            edges[index] =
+                object_references(object, UniqueId::new(index), &kinds, &mut report.issues);
+        }
+
+        // Walks through all references starting from `$top`
+        let mut reachable = vec![false; objects.len()];
+        let mut queue = Vec::new();
+        let top = dict.get(TOP_KEY_NAME).and_then(|v| v.as_dictionary());
+        for (key, value) in top.into_iter().flatten() {
+            let Some(uid) = value.as_uid() else {
+                // Plain values may be stored in `$top` as is
+                if matches!(
+                    value,
+                    PlistValue::Dictionary(_) | PlistValue::Array(_) | PlistValue::Date(_)
+                ) {
+                    report.issues.push(ValidationIssue::Header(format!(
+                        "`{TOP_KEY_NAME}` entry `{key}` is neither a reference nor a plain value"
+                    )));
+                }
+                continue;
+            };
+            if check_reference(&kinds, None, key, uid.get(), &mut report.issues) {
+                queue.push(uid.get() as usize);
+            }
+        }
+        while let Some(index) = queue.pop() {
+            if !std::mem::replace(&mut reachable[index], true) {
+                queue.extend_from_slice(&edges[index]);
+            }
+        }
+
+        report.orphans = reachable
+            .iter()
+            .enumerate()
+            .filter(|(index, reachable)| {
+                // `$null` is always at index 0 and it's never referenced explicitly
+                let is_null =
+                    *index == 0 && objects[0].as_string() == Some(NULL_OBJECT_REFERENCE_NAME);
+                !**reachable && !is_null
+            })
+            .map(|(index, _)| UniqueId::new(index))
+            .collect();
+        report
+    }
+}
+
+fn validate_header(
+    dict: &PlistDictionary,
+    options: &ParseOptions,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    match dict.get(ARCHIVER_KEY_NAME).map(|v| v.as_string()) {
+        None => issues.push(ValidationIssue::Header(format!(
+            "Missing `{ARCHIVER_KEY_NAME}` header key"
+        ))),
+        Some(None) => issues.push(ValidationIssue::Header(format!(
+            "Expected `{ARCHIVER_KEY_NAME}` key to be a type of `String`"
+        ))),
+        Some(Some(archiver)) if !options.is_supported_archiver(archiver) => issues.push(
+            ValidationIssue::Header(format!("Unsupported archiver `{archiver}`")),
+        ),
+        _ => (),
+    }
+    match dict.get(VERSION_KEY_NAME).map(|v| v.as_unsigned_integer()) {
+        None if options.is_strict() => issues.push(ValidationIssue::Header(format!(
+            "Missing `{VERSION_KEY_NAME}` header key"
+        ))),
+        Some(None) => issues.push(ValidationIssue::Header(format!(
+            "Expected `{VERSION_KEY_NAME}` key to be a type of 'Integer'"
+        ))),
+        Some(Some(version)) if options.is_strict() && version != ARCHIVER_VERSION => issues.push(
+            ValidationIssue::Header(format!("Unsupported archiver version `{version}`")),
+        ),
+        _ => (),
+    }
+    if dict
+        .get(TOP_KEY_NAME)
+        .and_then(|v| v.as_dictionary())
+        .is_none()
+    {
+        issues.push(ValidationIssue::Header(format!(
+            "Expected `{TOP_KEY_NAME}` key to be a type of `Dictionary`"
+        )));
+    }
+}
+
+/// Checks all references of an object and returns uids of valid ones
+/// (including its classes entry).
+fn object_references(
+    object: &PlistDictionary,
+    from: UniqueId,
+    kinds: &[Kind],
+    issues: &mut Vec<ValidationIssue>,
+) -> Vec<usize> {
+    let mut references = Vec::new();
+    match object.get("$class").and_then(|class| class.as_uid()) {
+        Some(class_uid) => {
+            let class_uid = class_uid.get();
+            match kinds.get(class_uid as usize) {
+                Some(Kind::Classes) => references.push(class_uid as usize),
+                Some(_) => issues.push(ValidationIssue::InvalidClass {
+                    object: from,
+                    class_uid,
+                }),
+                None => issues.push(ValidationIssue::UidOutOfRange {
+                    from: Some(from),
+                    key: "$class".into(),
+                    uid: class_uid,
+                }),
+            }
+        }
+        None => issues.push(ValidationIssue::MalformedValue {
+            uid: from,
+            reason: "`$class` isn't a reference".into(),
+        }),
+    }
+
+    for (key, value) in object {
+        if key == "$class" {
+            continue;
+        }
+        let uids = match value {
+            PlistValue::Uid(uid) => vec![uid.get()],
+            PlistValue::Array(array) => {
+                let uids: Vec<u64> = array
+                    .iter()
+                    .filter_map(|v| v.as_uid())
+                    .map(|uid| uid.get())
+                    .collect();
+                if uids.len() != array.len() {
+                    issues.push(ValidationIssue::MalformedValue {
+                        uid: from,
+                        reason: format!("Array `{key}` should contain only references"),
+                    });
+                }
+                uids
+            }
+            PlistValue::Dictionary(_) => {
+                issues.push(ValidationIssue::MalformedValue {
+                    uid: from,
+                    reason: format!("Unexpected value type of `{key}`"),
+                });
+                continue;
+            }
+            _ => continue,
+        };
+        for uid in uids {
+            if check_reference(kinds, Some(from), key, uid, issues) {
+                references.push(uid as usize);
+            }
+        }
+    }
+    references
+}
+
+/// Checks a reference and returns `true` if it points to a value.
+fn check_reference(
+    kinds: &[Kind],
+    from: Option<UniqueId>,
+    key: &str,
+    uid: u64,
+    issues: &mut Vec<ValidationIssue>,
+) -> bool {
+    match kinds.get(uid as usize) {
+        Some(Kind::Classes) => {
+            issues.push(ValidationIssue::ReferenceToClasses {
+                from,
+                key: key.into(),
+                uid,
+            });
+            false
+        }
+        Some(_) => true,
+        None => {
+            issues.push(ValidationIssue::UidOutOfRange {
+                from,
+                key: key.into(),
+                uid,
+            });
+            false
+        }
+    }
+}
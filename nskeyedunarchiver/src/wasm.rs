@@ -0,0 +1,141 @@
+//! WebAssembly bindings for browser-based archive inspectors.
+//!
+//! Archives are parsed from byte slices only, so neither `std::fs` nor
+//! seekable readers are required. Decoded archives are converted into JSON:
+//!
+//! - strings, numbers and booleans are kept as is, `$null` becomes `null`;
+//! - data becomes `{"$data": "<base64>"}`;
+//! - objects become `{"$class": "NSArray", "$uid": 3, ...fields}`;
+//! - an object that is already converted (a shared or circular reference)
+//!   or is nested too deep becomes `{"$ref": 3}`.
+
+use crate::{ArchiveValue, Base64, DEFAULT_MAX_DEPTH, KeyedArchive, ObjectValue};
+use std::collections::HashSet;
+use std::fmt::Write;
+use wasm_bindgen::prelude::*;
+
+/// Parses a keyed archive from bytes and converts its `$top` entries into
+/// a JS object.
+#[wasm_bindgen(js_name = decodeArchive)]
+pub fn decode_archive(bytes: &[u8]) -> Result<JsValue, JsError> {
+    let archive = KeyedArchive::from_bytes(bytes)?;
+    js_sys::JSON::parse(&to_json(&archive)).map_err(|_| JsError::new("Unable to create JSON"))
+}
+
+/// Converts `$top` entries of an archive into a JSON string.
+pub fn to_json(archive: &KeyedArchive) -> String {
+    let mut keys = archive.top_keys();
+    keys.sort();
+    let mut writer = JsonWriter::default();
+    writer.out.push('{');
+    for (i, key) in keys.into_iter().enumerate() {
+        if i > 0 {
+            writer.out.push(',');
+        }
+        write_string(&mut writer.out, key);
+        writer.out.push(':');
+        writer.write_value(&archive.top()[key], 0);
+    }
+    writer.out.push('}');
+    writer.out
+}
+
+#[derive(Default)]
+struct JsonWriter {
+    out: String,
+    visited: HashSet<usize>,
+}
+
+impl JsonWriter {
+    fn write_value(&mut self, value: &ArchiveValue, depth: usize) {
+        if let Some(s) = value.as_string() {
+            write_string(&mut self.out, s);
+        } else if let Some(i) = value.as_integer() {
+            let _ = write!(self.out, "{i}");
+        } else if let Some(f) = value.as_float() {
+            write_float(&mut self.out, f);
+        } else if let Some(b) = value.as_boolean() {
+            let _ = write!(self.out, "{b}");
+        } else if let Some(data) = value.as_data() {
+            write_data(&mut self.out, data);
+        } else if let Some(obj) = value.as_object() {
+            let uid = value.unique_id().get();
+            if depth >= DEFAULT_MAX_DEPTH || !self.visited.insert(uid) {
+                let _ = write!(self.out, "{{\"$ref\":{uid}}}");
+                return;
+            }
+            self.out.push_str("{\"$class\":");
+            write_string(&mut self.out, obj.class());
+            let _ = write!(self.out, ",\"$uid\":{uid}");
+            let mut keys = obj.keys();
+            keys.sort();
+            for key in keys {
+                self.out.push(',');
+                write_string(&mut self.out, key);
+                self.out.push(':');
+                self.write_field(&obj.as_map()[key], depth + 1);
+            }
+            self.out.push('}');
+        } else {
+            // Null references and classes (they're never referenced by fields)
+            self.out.push_str("null");
+        }
+    }
+
+    fn write_field(&mut self, value: &ObjectValue, depth: usize) {
+        match value {
+            ObjectValue::String(s) => write_string(&mut self.out, s),
+            ObjectValue::Integer(i) => {
+                let _ = write!(self.out, "{i}");
+            }
+            ObjectValue::Real(f) => write_float(&mut self.out, *f),
+            ObjectValue::Boolean(b) => {
+                let _ = write!(self.out, "{b}");
+            }
+            ObjectValue::Data(data) => write_data(&mut self.out, data),
+            ObjectValue::Date(date) => write_string(&mut self.out, &date.to_xml_format()),
+            ObjectValue::RefArray(values) => {
+                self.out.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push(',');
+                    }
+                    self.write_value(value, depth);
+                }
+                self.out.push(']');
+            }
+            ObjectValue::Ref(value) => self.write_value(value, depth),
+            ObjectValue::NullRef => self.out.push_str("null"),
+        }
+    }
+}
+
+fn write_float(out: &mut String, f: f64) {
+    if f.is_finite() {
+        let _ = write!(out, "{f}");
+    } else {
+        out.push_str("null");
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_data(out: &mut String, data: &[u8]) {
+    let _ = write!(out, "{{\"$data\":\"{}\"}}", Base64(data));
+}
@@ -0,0 +1,2022 @@
+mod common;
+
+use common::{archive_dict, archive_plist, classes, object, uid_value};
+use nskeyedunarchiver::raw;
+use nskeyedunarchiver::{
+    ClassSchema, Data, DeError, Decodable, DecodeContext, DuplicateKeys, DynamicValue, Entries,
+    Error, Format, KeyedArchive, KeyedArchiveStream, Limits, ObjectValue, ParseOptions, Patch,
+    PatchObject, REDACTED, Sanitizer, Schema, SchemaViolation, UniqueId, ValidationIssue,
+    ValueKind, ValueRef,
+};
+use plist::{Dictionary, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+
+#[test]
+fn custom_top_keys() {
+    let plist = archive_plist(
+        vec![("title", 1), ("count", 2)],
+        vec!["$null".into(), "Some title".into(), 42.into()],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+
+    let mut keys = archive.top_keys();
+    keys.sort();
+    assert_eq!(keys, vec!["count", "title"]);
+    assert!(archive.root().is_none());
+    assert_eq!(
+        archive.top_value("title").unwrap().as_string(),
+        Some("Some title")
+    );
+
+    let title: String = archive.decode_top("title").unwrap();
+    assert_eq!(title, "Some title");
+    let count: i64 = archive.decode_top("count").unwrap();
+    assert_eq!(count, 42);
+    assert!(matches!(
+        archive.decode_top::<String>("missing"),
+        Err(DeError::MissingObjectKey(_, _))
+    ));
+}
+
+#[test]
+fn root_errors() {
+    let archive = |top| {
+        let plist = archive_plist(top, vec!["$null".into(), "Title".into(), 42.into()]);
+        KeyedArchive::from_plist(plist).unwrap()
+    };
+
+    let root = archive(vec![("root", 1), ("count", 2)]);
+    assert_eq!(root.try_root().unwrap().as_string(), Some("Title"));
+    assert_eq!(root.root_or_single().unwrap().as_string(), Some("Title"));
+
+    let single = archive(vec![("title", 1)]);
+    assert!(matches!(
+        single.try_root(),
+        Err(Error::MissingTopKey(key)) if key == "root"
+    ));
+    assert_eq!(single.root_or_single().unwrap().as_string(), Some("Title"));
+
+    let several = archive(vec![("title", 1), ("count", 2)]);
+    assert!(matches!(
+        several.root_or_single(),
+        Err(Error::MissingTopKey(_))
+    ));
+
+    let empty = archive(vec![]);
+    assert!(matches!(empty.try_root(), Err(Error::EmptyTop)));
+    assert!(matches!(empty.root_or_single(), Err(Error::EmptyTop)));
+}
+
+#[test]
+fn lenient_header() {
+    let objects = vec!["$null".into(), "Some string".into()];
+
+    let mut missing_version = archive_dict(vec![("root", 1)], objects.clone());
+    missing_version.remove("$version");
+    let mut compat_archiver = archive_dict(vec![("root", 1)], objects.clone());
+    compat_archiver.insert("$archiver".into(), "NSKeyedArchiver_compat".into());
+    let mut unknown_version = archive_dict(vec![("root", 1)], objects.clone());
+    unknown_version.insert("$version".into(), 200000.into());
+
+    for dict in [missing_version, compat_archiver, unknown_version] {
+        let plist = Value::Dictionary(dict);
+        assert!(KeyedArchive::from_plist(plist.clone()).is_err());
+        let archive =
+            KeyedArchive::from_plist_with_options(plist, &ParseOptions::lenient()).unwrap();
+        assert_eq!(archive.root().unwrap().as_string(), Some("Some string"));
+    }
+
+    let mut other_archiver = archive_dict(vec![("root", 1)], objects);
+    other_archiver.insert("$archiver".into(), "NSArchiver".into());
+    assert!(
+        KeyedArchive::from_plist_with_options(other_archiver.into(), &ParseOptions::lenient())
+            .is_err()
+    );
+}
+
+#[test]
+fn custom_archivers() {
+    let objects = vec!["$null".into(), "Some string".into()];
+    let mut subclass = archive_dict(vec![("root", 1)], objects);
+    subclass.insert("$archiver".into(), "PLBatteryUsageKeyedArchiver".into());
+    let plist = Value::Dictionary(subclass);
+
+    for options in [ParseOptions::strict(), ParseOptions::lenient()] {
+        assert!(KeyedArchive::from_plist_with_options(plist.clone(), &options).is_err());
+        for options in [
+            options.clone().with_archiver_subclasses(true),
+            options.with_archivers(["PLBatteryUsageKeyedArchiver"]),
+        ] {
+            let archive = KeyedArchive::from_plist_with_options(plist.clone(), &options).unwrap();
+            assert_eq!(archive.root().unwrap().as_string(), Some("Some string"));
+        }
+    }
+
+    let options = ParseOptions::strict().with_archivers(["MyArchiver"]);
+    assert_eq!(options.archivers(), ["MyArchiver"]);
+    assert!(KeyedArchive::from_plist_with_options(plist, &options).is_err());
+}
+
+#[test]
+fn anonymous_objects() {
+    // -- an object without `$class`
+    //    -- NS.objects: ["item", $null]
+    //    -- count: 2
+    let mut anonymous = Dictionary::new();
+    anonymous.insert("NS.objects".into(), vec![uid_value(2), uid_value(0)].into());
+    anonymous.insert("count".into(), 2.into());
+    let plist = archive_plist(
+        vec![("root", 1), ("item", 3)],
+        vec![
+            "$null".into(),
+            anonymous.into(),
+            "item".into(),
+            object(4, vec![]),
+            classes(&["Item", "NSObject"]),
+        ],
+    );
+    assert!(matches!(
+        KeyedArchive::from_plist(plist.clone()),
+        Err(Error::IncorrectFormat(_))
+    ));
+
+    let options = ParseOptions::strict().with_anonymous_objects(true);
+    assert!(options.anonymous_objects());
+    let archive = KeyedArchive::from_plist_with_options(plist, &options).unwrap();
+    let root = archive.root().unwrap();
+    let obj = root.as_object().unwrap();
+    assert!(obj.is_anonymous());
+    assert_eq!(obj.class(), "");
+    assert!(obj.classes().is_empty());
+    assert_eq!(obj.decode_integer("count").unwrap(), 2.into());
+    let items = obj.decode_array("NS.objects").unwrap();
+    assert_eq!(items[0].as_string(), Some("item"));
+    assert!(items[1].is_null_ref());
+    assert!(!archive.top()["item"].as_object().unwrap().is_anonymous());
+
+    // Anonymous objects stay anonymous
+    let cloned = archive.clone();
+    assert!(cloned.root().unwrap().as_object().unwrap().is_anonymous());
+    let plist = archive.edit().into_plist();
+    let objects = plist.as_dictionary().unwrap()["$objects"]
+        .as_array()
+        .unwrap();
+    assert!(!objects[1].as_dictionary().unwrap().contains_key("$class"));
+    // Edited archives are parsed with the same options
+    let merged = archive.merge(&Patch::new()).unwrap();
+    assert!(merged.root().unwrap().as_object().unwrap().is_anonymous());
+    let extracted = archive.extract_subtree(UniqueId::new(1)).unwrap();
+    assert!(
+        extracted
+            .root()
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .is_anonymous()
+    );
+}
+
+/// An array that contains arrays of itself.
+#[derive(Debug)]
+struct Nested(#[allow(dead_code)] Vec<Nested>);
+
+impl Decodable for Nested {
+    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
+        DecodeContext::new().decode(value)
+    }
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        Ok(Self(Vec::decode_with(value, ctx)?))
+    }
+}
+
+/// Like `Nested`, but nested values are decoded without a context.
+#[derive(Debug)]
+struct ContextlessNested(#[allow(dead_code)] Vec<ContextlessNested>);
+
+impl Decodable for ContextlessNested {
+    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
+        Ok(Self(Vec::decode(value)?))
+    }
+}
+
+/// Builds an archive of `depth` arrays nested into each other.
+fn nested_arrays(depth: u64) -> Value {
+    let mut objects = vec!["$null".into(), classes(&["NSArray", "NSObject"])];
+    for i in 0..depth {
+        let items = if i + 1 < depth {
+            vec![uid_value(i + 3)]
+        } else {
+            vec![]
+        };
+        objects.push(object(1, vec![("NS.objects", Value::Array(items))]));
+    }
+    archive_plist(vec![("root", 2)], objects)
+}
+
+#[test]
+fn plain_top_values() {
+    let mut dict = archive_dict(vec![("root", 1)], vec!["$null".into(), "Root".into()]);
+    let top = dict.get_mut("$top").unwrap().as_dictionary_mut().unwrap();
+    top.insert("count".into(), 42.into());
+    top.insert("name".into(), "Name".into());
+    top.insert("scale".into(), 1.5.into());
+    top.insert("enabled".into(), true.into());
+    top.insert("bytes".into(), Value::Data(vec![1, 2, 3]));
+    let plist: Value = dict.into();
+    assert!(KeyedArchive::validate(&plist).is_valid());
+
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    assert_eq!(archive.top().len(), 6);
+    assert_eq!(archive.decode_root::<String>().unwrap(), "Root");
+    assert_eq!(archive.decode_top::<i64>("count").unwrap(), 42);
+    assert_eq!(archive.decode_top::<String>("name").unwrap(), "Name");
+    assert_eq!(archive.decode_top::<f64>("scale").unwrap(), 1.5);
+    assert!(archive.decode_top::<bool>("enabled").unwrap());
+    assert_eq!(
+        archive.decode_top::<Data>("bytes").unwrap().as_ref(),
+        [1, 2, 3]
+    );
+    // Plain values aren't a part of `$objects`
+    let count = archive.top_value("count").unwrap();
+    assert!(count.unique_id().get() >= archive.values().len());
+    assert!(archive.value_by_uid(*count.unique_id()).is_none());
+
+    // Plain values are kept as is after editing
+    let edited = archive.edit().into_archive().unwrap();
+    assert_eq!(edited.decode_top::<i64>("count").unwrap(), 42);
+    assert_eq!(edited.values().len(), 2);
+
+    let mut dict = archive_dict(vec![("root", 1)], vec!["$null".into(), "Root".into()]);
+    let top = dict.get_mut("$top").unwrap().as_dictionary_mut().unwrap();
+    top.insert("nested".into(), Value::Array(vec![]));
+    let plist: Value = dict.into();
+    assert!(!KeyedArchive::validate(&plist).is_valid());
+    assert!(matches!(
+        KeyedArchive::from_plist(plist),
+        Err(Error::IncorrectFormat(_))
+    ));
+}
+
+#[test]
+fn header_metadata() {
+    let mut dict = archive_dict(vec![("root", 1)], vec!["$null".into(), "Root".into()]);
+    let top = dict.get_mut("$top").unwrap().as_dictionary_mut().unwrap();
+    top.insert("count".into(), 42.into());
+    dict.insert("$vendor".into(), "Some vendor".into());
+    dict.insert("buildNumber".into(), 7.into());
+    let archive = KeyedArchive::from_plist(dict.into()).unwrap();
+
+    let top_raw = archive.top_raw();
+    assert_eq!(top_raw.len(), 2);
+    assert_eq!(top_raw["root"], uid_value(1));
+    assert_eq!(top_raw["count"], 42.into());
+
+    let header = archive.extra_header();
+    assert_eq!(header.len(), 2);
+    assert_eq!(header["$vendor"], "Some vendor".into());
+    assert_eq!(header["buildNumber"], 7.into());
+
+    // Extra keys are written back after editing
+    let mut editor = archive.edit();
+    editor
+        .extra_header_mut()
+        .insert("edited".into(), true.into());
+    let plist = editor.into_plist();
+    let dict = plist.as_dictionary().unwrap();
+    assert_eq!(dict["$vendor"], "Some vendor".into());
+    assert_eq!(dict["edited"], true.into());
+    let edited = KeyedArchive::from_plist(plist).unwrap();
+    assert_eq!(edited.extra_header().len(), 3);
+    assert_eq!(edited.decode_root::<String>().unwrap(), "Root");
+}
+
+#[test]
+fn limits() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            "Some string".into(),
+            Value::Data(vec![0; 16]),
+        ],
+    );
+    let options = |limits| ParseOptions::strict().with_limits(limits);
+
+    let max_objects = Limits {
+        max_objects: Some(2),
+        ..Default::default()
+    };
+    assert!(matches!(
+        KeyedArchive::from_plist_with_options(plist.clone(), &options(max_objects)),
+        Err(Error::LimitExceeded(_))
+    ));
+
+    let max_data_size = Limits {
+        max_data_size: Some(20),
+        ..Default::default()
+    };
+    assert!(matches!(
+        KeyedArchive::from_plist_with_options(plist.clone(), &options(max_data_size)),
+        Err(Error::LimitExceeded(_))
+    ));
+
+    let enough = Limits {
+        max_objects: Some(3),
+        max_data_size: Some(32),
+        max_depth: None,
+    };
+    assert!(KeyedArchive::from_plist_with_options(plist, &options(enough)).is_ok());
+}
+
+#[test]
+fn depth_limit() {
+    let archive = KeyedArchive::from_plist(nested_arrays(100)).unwrap();
+    assert!(archive.decode_top::<Nested>("root").is_ok());
+
+    let max_depth = Limits {
+        max_depth: Some(50),
+        ..Default::default()
+    };
+    let options = ParseOptions::strict().with_limits(max_depth);
+    let archive = KeyedArchive::from_plist_with_options(nested_arrays(100), &options).unwrap();
+    assert!(matches!(
+        archive.decode_top::<Nested>("root").unwrap_err().inner(),
+        DeError::LimitExceeded(_)
+    ));
+
+    // The default limit protects from a stack overflow
+    let archive = KeyedArchive::from_plist(nested_arrays(100_000)).unwrap();
+    assert!(matches!(
+        archive.decode_top::<Nested>("root").unwrap_err().inner(),
+        DeError::LimitExceeded(_)
+    ));
+    let root = archive.root().unwrap().into();
+    assert!(matches!(
+        Nested::decode(&root).unwrap_err().inner(),
+        DeError::LimitExceeded(_)
+    ));
+
+    // Contexts created by nested values continue counting the depth
+    assert!(matches!(
+        ContextlessNested::decode(&root).unwrap_err().inner(),
+        DeError::LimitExceeded(_)
+    ));
+    let archive = KeyedArchive::from_plist(nested_arrays(100)).unwrap();
+    assert!(archive.decode_top::<ContextlessNested>("root").is_ok());
+    assert!(archive.decode_top::<Nested>("root").is_ok());
+}
+
+#[test]
+fn unlimited_depth() {
+    // Built-in recursive types don't recurse, so there's no stack overflow
+    let options = ParseOptions::default().with_limits(Limits::unlimited());
+    let archive = KeyedArchive::from_plist_with_options(nested_arrays(10_000), &options).unwrap();
+    let mut value = archive.decode_root::<DynamicValue>().unwrap();
+    let mut depth = 1;
+    while let DynamicValue::Array(mut array) = value {
+        let Some(nested) = array.pop() else {
+            break;
+        };
+        value = nested;
+        depth += 1;
+    }
+    assert_eq!(depth, 10_000);
+    assert!(archive.decode_root::<Value>().is_ok());
+
+    // Limits are still applied
+    let max_depth = Limits {
+        max_depth: Some(50),
+        ..Default::default()
+    };
+    let options = ParseOptions::default().with_limits(max_depth);
+    let archive = KeyedArchive::from_plist_with_options(nested_arrays(100), &options).unwrap();
+    let error = archive.decode_root::<DynamicValue>().unwrap_err();
+    assert!(matches!(error.inner(), DeError::LimitExceeded(_)));
+    assert!(error.to_string().contains(&"[0]".repeat(50)));
+}
+
+#[test]
+fn dangling_references() {
+    // A truncated archive: the last values are lost
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("title", uid_value(3)),
+                ("author", uid_value(4)),
+                ("items", Value::Array(vec![uid_value(3), uid_value(5)])),
+            ],
+        ),
+        classes(&["Note", "NSObject"]),
+        "Title".into(),
+    ];
+    let plist = archive_plist(vec![("root", 1)], objects);
+    let Err(error) = KeyedArchive::from_plist(plist.clone()) else {
+        panic!("dangling references should fail");
+    };
+    assert!(matches!(
+        &error,
+        Error::DanglingReference { from_uid, key, to_uid: 4 }
+            if *from_uid == UniqueId::new(1) && key == "author"
+    ));
+    assert_eq!(
+        error.to_string(),
+        "Object (uid: 1) field `author` refers to a missing value (uid: 4)"
+    );
+
+    let options = ParseOptions::default().with_dangling_refs_as_null(true);
+    let archive = KeyedArchive::from_plist_with_options(plist.clone(), &options).unwrap();
+    let root = archive.root().unwrap();
+    let root = root.as_object().unwrap();
+    assert_eq!(root.decode_string("title").unwrap(), "Title");
+    assert!(root.is_null_ref("author").unwrap());
+    let items = root.decode_array("items").unwrap();
+    assert_eq!(items.len(), 2);
+    assert!(items[1].is_null_ref());
+    // Lost references are still reported by validation
+    assert!(!KeyedArchive::validate(&plist).is_valid());
+    assert_eq!(archive.clone().values(), archive.values());
+
+    // Array elements are nulled even if `$objects` doesn't start with `$null`
+    let objects = vec![
+        object(
+            1,
+            vec![("items", Value::Array(vec![uid_value(0), uid_value(5)]))],
+        ),
+        classes(&["Note", "NSObject"]),
+    ];
+    let plist = archive_plist(vec![("root", 0)], objects);
+    let archive = KeyedArchive::from_plist_with_options(plist, &options).unwrap();
+    let root = archive.root().unwrap();
+    let items = root.as_object().unwrap().decode_array("items").unwrap();
+    assert!(!items[0].is_null_ref());
+    assert!(items[1].is_null_ref());
+    // The placeholder doesn't share a uid with values of `$objects`
+    assert!(items[1].unique_id().get() >= archive.values().len());
+    assert_eq!(archive.incoming_ref_count(UniqueId::new(0)), 2);
+    let cloned = archive.clone();
+    assert_eq!(cloned.values(), archive.values());
+    let root = cloned.root().unwrap();
+    let items = root.as_object().unwrap().decode_array("items").unwrap();
+    assert!(items[0].is_object());
+    assert!(items[1].is_null_ref());
+
+    // Classes can't be nulled
+    let plist = archive_plist(vec![("root", 1)], vec!["$null".into(), object(9, vec![])]);
+    assert!(matches!(
+        KeyedArchive::from_plist_with_options(plist, &options),
+        Err(Error::DanglingReference { from_uid, key, to_uid: 9 })
+            if from_uid == UniqueId::new(1) && key == "$class"
+    ));
+}
+
+#[test]
+fn malformed_archives() {
+    let class = || classes(&["NSObject"]);
+    let cases = [
+        // A `$top` uid is out of range
+        archive_plist(vec![("root", 5)], vec!["$null".into()]),
+        // A `$class` uid doesn't point to a classes entry
+        archive_plist(vec![("root", 1)], vec!["$null".into(), object(0, vec![])]),
+        // Empty `$classes`
+        archive_plist(
+            vec![("root", 1)],
+            vec![
+                "$null".into(),
+                object(2, vec![]),
+                Value::Dictionary(Dictionary::from_iter([("$classes", Value::Array(vec![]))])),
+            ],
+        ),
+        // An array field contains a plain value
+        archive_plist(
+            vec![("root", 1)],
+            vec![
+                "$null".into(),
+                object(2, vec![("a", Value::Array(vec![1.into()]))]),
+                class(),
+            ],
+        ),
+    ];
+    for plist in cases {
+        assert!(matches!(
+            KeyedArchive::from_plist(plist),
+            Err(Error::IncorrectFormat(_))
+        ));
+    }
+}
+
+#[test]
+fn validation() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("title", uid_value(3))]),
+            classes(&["Note", "NSObject"]),
+            "Some title".into(),
+        ],
+    );
+    let report = KeyedArchive::validate(&plist);
+    assert!(report.is_valid());
+    assert!(report.orphans().is_empty());
+
+    let mut dict = archive_dict(
+        vec![("root", 1), ("missing", 10)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("title", uid_value(3)),
+                    ("items", Value::Array(vec![uid_value(3), uid_value(20)])),
+                    ("class", uid_value(2)),
+                ],
+            ),
+            classes(&["Note", "NSObject"]),
+            "Some title".into(),
+            // An orphan object with an incorrect class
+            object(3, vec![("title", uid_value(30))]),
+        ],
+    );
+    dict.remove("$version");
+    let report = KeyedArchive::validate(&dict.into());
+    assert!(!report.is_valid());
+    assert_eq!(report.orphans(), &[UniqueId::new(4)]);
+    let issues = report.issues();
+    assert!(issues.contains(&ValidationIssue::UidOutOfRange {
+        from: None,
+        key: "missing".into(),
+        uid: 10
+    }));
+    assert!(issues.contains(&ValidationIssue::UidOutOfRange {
+        from: Some(UniqueId::new(1)),
+        key: "items".into(),
+        uid: 20
+    }));
+    assert!(issues.contains(&ValidationIssue::ReferenceToClasses {
+        from: Some(UniqueId::new(1)),
+        key: "class".into(),
+        uid: 2
+    }));
+    assert!(issues.contains(&ValidationIssue::InvalidClass {
+        object: UniqueId::new(4),
+        class_uid: 3
+    }));
+    assert!(issues.contains(&ValidationIssue::UidOutOfRange {
+        from: Some(UniqueId::new(4)),
+        key: "title".into(),
+        uid: 30
+    }));
+    assert!(
+        issues
+            .iter()
+            .any(|issue| matches!(issue, ValidationIssue::Header(_)))
+    );
+    assert_eq!(issues.len(), 6);
+}
+
+#[test]
+fn schemas() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("title", uid_value(3)),
+                ("count", "five".into()),
+                ("parent", uid_value(0)),
+                ("extra", true.into()),
+            ],
+        ),
+        classes(&["Note", "NSObject"]),
+        "Title".into(),
+        object(5, vec![("name", 1.into())]),
+        classes(&["Other", "NSObject"]),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+
+    let note = ClassSchema::new()
+        .required("title", ValueKind::String)
+        .required("count", ValueKind::Integer)
+        .optional("parent", ValueKind::Object)
+        .optional("tags", ValueKind::Array);
+    let report = archive.check_schema(&Schema::new().class("Note", note.clone()));
+    assert!(!report.is_valid());
+    assert_eq!(
+        report.violations(),
+        [SchemaViolation::UnexpectedKind {
+            object: UniqueId::new(1),
+            key: "count".into(),
+            expected: ValueKind::Integer,
+            found: ValueKind::String,
+        }]
+    );
+    assert_eq!(
+        report.to_string(),
+        "Object (uid: 1)->count: expected integer, found string\n"
+    );
+
+    let strict = note
+        .required("count", ValueKind::Any)
+        .required("subtitle", ValueKind::String)
+        .deny_unknown_keys();
+    let report = archive.check_schema(&Schema::new().class("Note", strict));
+    assert_eq!(
+        report.violations(),
+        [
+            SchemaViolation::MissingKey {
+                object: UniqueId::new(1),
+                key: "subtitle".into(),
+            },
+            SchemaViolation::UnknownKey {
+                object: UniqueId::new(1),
+                key: "extra".into(),
+            },
+        ]
+    );
+
+    let other = ClassSchema::new().required("name", ValueKind::Integer);
+    assert!(
+        archive
+            .check_schema(&Schema::new().class("Other", other))
+            .is_valid()
+    );
+}
+
+#[test]
+fn large_archive() {
+    // Enough values to be split between threads with the `parallel` feature
+    let count = 10_000;
+    let mut objects = vec!["$null".into(), classes(&["NSArray", "NSObject"])];
+    let items = (0..count).map(|i| uid_value(i + 3)).collect();
+    objects.push(object(1, vec![("NS.objects", Value::Array(items))]));
+    objects.extend((0..count).map(|i| Value::from(format!("Item #{i}"))));
+    let plist = archive_plist(vec![("root", 2)], objects.clone());
+
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    for (index, value) in archive.values().iter().enumerate() {
+        assert_eq!(value.unique_id().get(), index);
+    }
+    let items: Vec<String> = archive.decode_top("root").unwrap();
+    assert_eq!(items.len(), count as usize);
+    assert_eq!(items[0], "Item #0");
+    assert_eq!(items[9999], "Item #9999");
+
+    objects[5000] = Value::Date(std::time::SystemTime::UNIX_EPOCH.into());
+    let plist = archive_plist(vec![("root", 2)], objects);
+    assert!(matches!(
+        KeyedArchive::from_plist(plist),
+        Err(Error::IncorrectFormat(_))
+    ));
+}
+
+#[test]
+fn embedded_archives() {
+    let archive = archive_plist(vec![("root", 1)], vec!["$null".into(), "Bookmark".into()]);
+    let mut bytes = Vec::new();
+    archive.to_writer_binary(&mut bytes).unwrap();
+
+    let mut item = plist::Dictionary::new();
+    item.insert("Bookmark".into(), Value::Data(bytes.clone()));
+    item.insert("Icon".into(), Value::Data(b"\x89PNG".to_vec()));
+    let mut broken = plist::Dictionary::new();
+    broken.insert("Bookmark".into(), Value::Data(b"bplist00".to_vec()));
+    let mut dict = plist::Dictionary::new();
+    dict.insert(
+        "items".into(),
+        Value::Array(vec![broken.into(), item.into()]),
+    );
+    dict.insert("version".into(), 1.into());
+    let plist: Value = dict.into();
+
+    let found = KeyedArchive::find_embedded(&plist);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].0, "items[1].Bookmark");
+    let title: String = found[0].1.decode_top("root").unwrap();
+    assert_eq!(title, "Bookmark");
+
+    let archive = KeyedArchive::from_plist_data(&Value::Data(bytes)).unwrap();
+    assert!(archive.root().is_some());
+    assert!(matches!(
+        KeyedArchive::from_plist_data(&Value::from("not data")),
+        Err(Error::IncorrectFormat(_))
+    ));
+}
+
+#[test]
+fn nested_archives() {
+    let inner = archive_plist(vec![("root", 1)], vec!["$null".into(), "Inner".into()]);
+    let mut bytes = Vec::new();
+    inner.to_writer_binary(&mut bytes).unwrap();
+
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("blob", Value::Data(bytes.clone())),
+                ("icon", Value::Data(b"\x89PNG".to_vec())),
+            ],
+        ),
+        classes(&["Defaults", "NSObject"]),
+        Value::Data(bytes.clone()),
+    ];
+    let archive =
+        KeyedArchive::from_plist(archive_plist(vec![("root", 1), ("blob", 3)], objects)).unwrap();
+    let root = archive.root().unwrap();
+    let root = root.as_object().unwrap();
+
+    let nested = root.decode_nested_archive("blob").unwrap();
+    assert_eq!(nested.decode_root::<String>().unwrap(), "Inner");
+    let nested = root
+        .decode_data("blob")
+        .unwrap()
+        .as_keyed_archive()
+        .unwrap();
+    assert_eq!(nested.decode_root::<String>().unwrap(), "Inner");
+    let nested: KeyedArchive = archive.decode_top("blob").unwrap();
+    assert_eq!(nested.decode_root::<String>().unwrap(), "Inner");
+    assert!(matches!(
+        root.decode_nested_archive("icon"),
+        Err(DeError::NestedArchive(_))
+    ));
+
+    let options = ParseOptions::default().with_limits(Limits {
+        max_data_size: Some(16),
+        ..Limits::default()
+    });
+    let Err(DeError::NestedArchive(error)) =
+        root.decode_nested_archive_with_options("blob", &options)
+    else {
+        panic!("Expected an error");
+    };
+    assert!(matches!(*error, Error::LimitExceeded(_)));
+}
+
+#[test]
+fn class_hints() {
+    let mut hinted = plist::Dictionary::new();
+    hinted.insert("$classname".into(), "MyArray".into());
+    hinted.insert(
+        "$classes".into(),
+        Value::Array(vec!["MyArray".into(), "NSObject".into()]),
+    );
+    hinted.insert("$classhints".into(), Value::Array(vec!["NSArray".into()]));
+    let objects = vec![
+        "$null".into(),
+        object(2, vec![("NS.objects", Value::Array(vec![uid_value(4)]))]),
+        hinted.into(),
+        object(5, vec![("NS.objects", Value::Array(vec![uid_value(4)]))]),
+        "item".into(),
+        classes(&["MyArray", "NSObject"]),
+    ];
+    let archive =
+        KeyedArchive::from_plist(archive_plist(vec![("root", 1), ("plain", 3)], objects)).unwrap();
+
+    let root = archive.root().unwrap();
+    let obj = root.as_object().unwrap();
+    assert_eq!(obj.class(), "MyArray");
+    assert_eq!(obj.class_hints(), ["NSArray"]);
+    assert!(obj.matches_class("NSArray"));
+    let items: Vec<String> = archive.decode_top("root").unwrap();
+    assert_eq!(items, ["item"]);
+
+    // Without hints an unknown class can't be decoded as an array
+    assert!(
+        archive
+            .top_value("plain")
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .class_hints()
+            .is_empty()
+    );
+    assert!(matches!(
+        archive
+            .decode_top::<Vec<String>>("plain")
+            .unwrap_err()
+            .inner(),
+        DeError::ClassMismatch { .. }
+    ));
+}
+
+#[test]
+fn uid_links() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("title", uid_value(3)),
+                ("subtitle", uid_value(3)),
+                ("count", 5.into()),
+                ("items", Value::Array(vec![uid_value(3), uid_value(0)])),
+            ],
+        ),
+        classes(&["Note", "NSObject"]),
+        "Shared".into(),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    let root = archive.root().unwrap();
+    let obj = root.as_object().unwrap();
+
+    assert_eq!(obj.field_uid("title"), Some(UniqueId::new(3)));
+    assert_eq!(obj.field_uid("subtitle"), obj.field_uid("title"));
+    assert_eq!(obj.field_uid("count"), None);
+    assert_eq!(obj.field_uid("missing"), None);
+    assert_eq!(
+        obj.field_uids("items"),
+        Some(vec![UniqueId::new(3), UniqueId::new(0)])
+    );
+    assert_eq!(obj.classes_uid(), UniqueId::new(2));
+
+    let title = archive.value_by_uid(UniqueId::new(3)).unwrap();
+    assert_eq!(title.as_string(), Some("Shared"));
+    assert!(archive.value_by_uid(UniqueId::new(4)).is_none());
+
+    assert_eq!(archive.objects_len(), 4);
+    assert!(!archive.is_empty());
+    assert!(archive.get(0).unwrap().is_null_ref());
+    assert!(archive.get(4).is_none());
+    let uids: Vec<UniqueId> = archive.iter().map(|(uid, _)| uid).collect();
+    assert_eq!(uids, (0..4).map(UniqueId::new).collect::<Vec<_>>());
+    let (uid, value) = archive.iter().find(|(_, value)| value.is_string()).unwrap();
+    assert_eq!(uid, UniqueId::new(3));
+    assert_eq!(value.unique_id(), &uid);
+}
+
+#[test]
+fn optional_fields() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("title", uid_value(3)),
+                ("count", 5.into()),
+                ("parent", uid_value(0)),
+            ],
+        ),
+        classes(&["Note", "NSObject"]),
+        "Title".into(),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    let root = archive.root().unwrap();
+    let obj = root.as_object().unwrap();
+
+    assert!(matches!(obj.get("count"), Some(ObjectValue::Integer(_))));
+    assert!(obj.get("missing").is_none());
+    assert_eq!(
+        obj.get_as::<String>("title").unwrap().as_deref(),
+        Some("Title")
+    );
+    assert_eq!(obj.get_as::<i64>("count").unwrap(), Some(5));
+    assert_eq!(obj.get_as::<String>("missing").unwrap(), None);
+    assert_eq!(obj.get_as::<String>("parent").unwrap(), None);
+    assert!(matches!(
+        obj.get_as::<String>("count"),
+        Err(DeError::ExpectedString)
+    ));
+}
+
+#[test]
+fn typed_arrays() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("items", Value::Array(vec![uid_value(3), uid_value(4)])),
+                ("names", Value::Array(vec![uid_value(3), uid_value(0)])),
+            ],
+        ),
+        classes(&["Note", "NSObject"]),
+        "First".into(),
+        42.into(),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    let root = archive.root().unwrap();
+    let obj = root.as_object().unwrap();
+
+    assert_eq!(
+        obj.decode_array_item_as::<String>("items", 0).unwrap(),
+        "First"
+    );
+    assert_eq!(obj.decode_array_item_as::<i64>("items", 1).unwrap(), 42);
+    let err = obj.decode_array_item_as::<String>("items", 2).unwrap_err();
+    assert!(err.to_string().contains("index 2 is out of bounds"));
+    assert!(obj.decode_array_item_as::<String>("missing", 0).is_err());
+
+    let err = obj.decode_array_as::<String>("items").unwrap_err();
+    assert!(err.to_string().starts_with("[1]"), "{err}");
+    let names: Vec<Option<String>> = obj.decode_array_as("names").unwrap();
+    assert_eq!(names, vec![Some("First".to_string()), None]);
+    let mut ctx = DecodeContext::new();
+    let names: Vec<Option<String>> = obj.decode_array_with("names", &mut ctx).unwrap();
+    assert_eq!(names.len(), 2);
+}
+
+#[test]
+fn editing() {
+    let objects = vec![
+        "$null".into(),
+        object(2, vec![("title", uid_value(3)), ("child", uid_value(4))]),
+        classes(&["Note", "NSObject"]),
+        "Old title".into(),
+        object(2, vec![("title", uid_value(5))]),
+        "Child".into(),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    let mut editor = archive.edit();
+    assert_eq!(editor.len(), 6);
+    assert_eq!(editor.top("root"), Some(UniqueId::new(1)));
+
+    editor
+        .set_value(UniqueId::new(3), "New title".into())
+        .unwrap();
+    editor
+        .set_field(UniqueId::new(1), "count", 5.into())
+        .unwrap();
+    let tag = editor.add_value("tag".into()).unwrap();
+    editor
+        .set_field(
+            UniqueId::new(1),
+            "tag",
+            Value::Uid(plist::Uid::new(tag.get() as u64)),
+        )
+        .unwrap();
+    editor.delete_subtree(UniqueId::new(4)).unwrap();
+    assert_eq!(editor.len(), 5);
+
+    assert!(matches!(
+        editor.set_value(UniqueId::new(1), "not an object".into()),
+        Err(Error::InvalidEdit(_))
+    ));
+    assert!(matches!(
+        editor.set_field(UniqueId::new(2), "key", 1.into()),
+        Err(Error::InvalidEdit(_))
+    ));
+    assert!(matches!(
+        editor.set_field(UniqueId::new(1), "key", uid_value(100)),
+        Err(Error::InvalidEdit(_))
+    ));
+
+    // The edited archive may be written and read again
+    let mut bytes = Vec::new();
+    editor
+        .clone()
+        .into_plist()
+        .to_writer_binary(&mut bytes)
+        .unwrap();
+    assert!(KeyedArchive::from_bytes(&bytes).is_ok());
+
+    let archive = editor.into_archive().unwrap();
+    let root = archive.root().unwrap();
+    let root = root.as_object().unwrap();
+    assert_eq!(root.decode_string("title").unwrap(), "New title");
+    assert_eq!(root.decode_integer("count").unwrap(), 5.into());
+    assert_eq!(root.decode_string("tag").unwrap(), "tag");
+    assert!(root.decode_object("child").unwrap().is_null_ref());
+    assert_eq!(archive.values().len(), 5);
+}
+
+#[test]
+fn subtree_extraction() {
+    let objects = vec![
+        "$null".into(),
+        object(2, vec![("title", uid_value(3)), ("child", uid_value(4))]),
+        classes(&["Note", "NSObject"]),
+        "Parent".into(),
+        object(2, vec![("title", uid_value(5)), ("parent", uid_value(1))]),
+        "Child".into(),
+        object(2, vec![("title", uid_value(7))]),
+        "Unrelated".into(),
+    ];
+    let archive =
+        KeyedArchive::from_plist(archive_plist(vec![("root", 1), ("other", 6)], objects)).unwrap();
+
+    let extracted = archive.extract_subtree(UniqueId::new(6)).unwrap();
+    assert_eq!(extracted.top_keys(), ["root"]);
+    assert_eq!(extracted.values().len(), 4);
+    let root = extracted.root().unwrap();
+    // Values keep their relative order: `$null`, the class, the note
+    assert_eq!(*root.unique_id(), UniqueId::new(2));
+    let root = root.as_object().unwrap();
+    assert_eq!(root.class(), "Note");
+    assert_eq!(root.decode_string("title").unwrap(), "Unrelated");
+
+    // Cycles are copied as well
+    let extracted = archive.extract_subtree(UniqueId::new(4)).unwrap();
+    assert_eq!(extracted.values().len(), 6);
+    let root = extracted.root().unwrap();
+    let root = root.as_object().unwrap();
+    assert_eq!(root.decode_string("title").unwrap(), "Child");
+    let parent = root.decode_object("parent").unwrap();
+    assert_eq!(
+        parent.as_object().unwrap().decode_string("title").unwrap(),
+        "Parent"
+    );
+
+    // The source archive is left intact
+    assert_eq!(archive.values().len(), 8);
+    assert!(matches!(
+        archive.extract_subtree(UniqueId::new(0)),
+        Err(Error::InvalidEdit(_))
+    ));
+    assert!(matches!(
+        archive.extract_subtree(UniqueId::new(8)),
+        Err(Error::InvalidEdit(_))
+    ));
+}
+
+#[test]
+fn patching() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("title", uid_value(3)),
+                ("count", 1.into()),
+                ("settings", uid_value(4)),
+                ("old", uid_value(9)),
+            ],
+        ),
+        classes(&["Note", "NSObject"]),
+        "Old title".into(),
+        object(
+            5,
+            vec![
+                ("NS.keys", Value::Array(vec![uid_value(6), uid_value(7)])),
+                ("NS.objects", Value::Array(vec![uid_value(8), uid_value(8)])),
+            ],
+        ),
+        classes(&["NSDictionary", "NSObject"]),
+        "volume".into(),
+        "theme".into(),
+        5.into(),
+        "Removed".into(),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    let patch = Patch::new()
+        .set("root.title", "New title")
+        .set("root.count", 2)
+        .set("root.settings.volume", 10)
+        .set("root.settings.language", "en")
+        .remove("root.settings.theme")
+        .remove("root.old")
+        .remove("root.missing")
+        .set(
+            "root.author",
+            PatchObject::new(&["Author", "NSObject"]).field("name", "Me"),
+        )
+        .set("root.copy", PatchObject::new(&["Note", "NSObject"]))
+        .set("settings", UniqueId::new(4));
+    assert_eq!(patch.len(), 10);
+    let merged = archive.merge(&patch).unwrap();
+
+    let root = merged.root().unwrap();
+    let root = root.as_object().unwrap();
+    assert_eq!(root.decode_string("title").unwrap(), "New title");
+    assert_eq!(root.decode_integer("count").unwrap(), 2.into());
+    assert!(root.get("old").is_none());
+    let author = root.decode_object("author").unwrap();
+    let author = author.as_object().unwrap();
+    assert_eq!(author.class(), "Author");
+    assert_eq!(author.decode_string("name").unwrap(), "Me");
+    // An existing chain of classes is reused
+    let copy = root.decode_object("copy").unwrap();
+    assert_eq!(copy.as_object().unwrap().classes_uid(), root.classes_uid());
+
+    let settings: HashMap<String, ValueRef> = root.get_as("settings").unwrap().unwrap();
+    assert_eq!(settings.len(), 2);
+    assert_eq!(i64::decode(&(&settings["volume"]).into()).unwrap(), 10);
+    assert_eq!(
+        String::decode(&(&settings["language"]).into()).unwrap(),
+        "en"
+    );
+    assert_eq!(
+        merged.top()["settings"].unique_id(),
+        root.decode_object("settings").unwrap().unique_id()
+    );
+    // Replaced and removed values are dropped
+    assert!(merged.unreachable_objects().is_empty());
+    assert!(
+        !merged
+            .values()
+            .iter()
+            .any(|v| v.as_string() == Some("Removed"))
+    );
+
+    for patch in [
+        Patch::new().set("missing.title", "Title"),
+        Patch::new().set("root.title.length", 1),
+        Patch::new().set("root.copy", PatchObject::new(&[])),
+        Patch::new().set("root.copy", UniqueId::new(100)),
+    ] {
+        assert!(matches!(archive.merge(&patch), Err(Error::InvalidEdit(_))));
+    }
+    // The source archive is left intact
+    assert_eq!(archive.values().len(), 10);
+
+    // A dictionary with more keys than values
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("NS.keys", Value::Array(vec![uid_value(3)])),
+                ("NS.objects", Value::Array(vec![])),
+            ],
+        ),
+        classes(&["NSDictionary", "NSObject"]),
+        "a".into(),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    for patch in [
+        Patch::new().remove("root.a"),
+        Patch::new().set("root.a", 1),
+        Patch::new().set("root.b", 1),
+    ] {
+        assert!(matches!(archive.merge(&patch), Err(Error::InvalidEdit(_))));
+    }
+}
+
+#[test]
+fn sanitizing() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("email", uid_value(3)),
+                ("title", "Title".into()),
+                ("photo", Value::Data(vec![1; 32])),
+                ("icon", Value::Data(vec![1; 4])),
+                ("secret", "key".into()),
+                ("empty", uid_value(0)),
+            ],
+        ),
+        classes(&["Note", "NSObject"]),
+        "user@example.com".into(),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    let sanitizer = Sanitizer::new()
+        .redact_data_over(16)
+        .redact_strings(|s| s.contains('@'))
+        .remove_fields(|class, key| class == "Note" && key == "secret");
+    let mut editor = archive.edit();
+    assert_eq!(editor.sanitize(&sanitizer), 3);
+    // Already sanitized values aren't counted again
+    assert_eq!(editor.sanitize(&sanitizer), 0);
+
+    let archive = editor.into_archive().unwrap();
+    let root = archive.root().unwrap();
+    let root = root.as_object().unwrap();
+    assert_eq!(root.decode_string("email").unwrap(), REDACTED);
+    assert_eq!(root.decode_string("title").unwrap(), "Title");
+    assert_eq!(root.decode_data("photo").unwrap(), vec![0; 32].into());
+    assert_eq!(root.decode_data("icon").unwrap(), vec![1; 4].into());
+    assert!(!root.contains_key("secret"));
+    assert!(root.decode_object("empty").unwrap().is_null_ref());
+}
+
+#[test]
+fn archive_stats() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("title", uid_value(3)),
+                ("tags", uid_value(4)),
+                ("data", Value::Data(vec![0; 20])),
+                ("inline", "abc".into()),
+            ],
+        ),
+        classes(&["Note", "NSObject"]),
+        "Some note".into(),
+        object(
+            5,
+            vec![(
+                "NS.objects",
+                Value::Array(vec![uid_value(3), uid_value(6), uid_value(1)]),
+            )],
+        ),
+        classes(&["NSArray", "NSObject"]),
+        Value::Data(vec![1; 4]),
+    ];
+    let mut dict = archive_dict(vec![("root", 1), ("title", 3)], objects);
+    let top = dict.get_mut("$top").unwrap().as_dictionary_mut().unwrap();
+    top.insert("count".into(), 42.into());
+    let archive = KeyedArchive::from_plist(dict.into()).unwrap();
+
+    let stats = archive.stats();
+    assert_eq!(stats.values, 7);
+    assert_eq!(stats.kinds["object"], 2);
+    assert_eq!(stats.kinds["classes"], 2);
+    assert_eq!(stats.kinds["string"], 1);
+    assert_eq!(stats.kinds["null"], 1);
+    assert_eq!(stats.kinds["data"], 1);
+    assert_eq!(stats.classes["NSArray"], 1);
+    assert_eq!(stats.data_bytes, 24);
+    assert_eq!(stats.string_bytes, 9 + 3);
+    // Note -> NSArray -> "Some note"; the reference back to the note is circular
+    assert_eq!(stats.max_depth, 3);
+    assert_eq!(
+        stats.most_referenced,
+        [
+            (UniqueId::new(3), 3),
+            (UniqueId::new(1), 2),
+            (UniqueId::new(4), 1),
+            (UniqueId::new(6), 1),
+        ]
+    );
+    assert!(stats.to_string().contains("Max depth: 3"));
+
+    assert_eq!(archive.incoming_ref_count(UniqueId::new(3)), 3);
+    assert_eq!(archive.incoming_ref_count(UniqueId::new(4)), 1);
+    assert_eq!(archive.incoming_ref_count(UniqueId::new(2)), 0);
+    assert_eq!(archive.incoming_ref_count(UniqueId::new(100)), 0);
+    assert_eq!(
+        archive.shared_values(),
+        [(UniqueId::new(1), 2), (UniqueId::new(3), 3)]
+    );
+}
+
+#[test]
+fn unreachable_objects() {
+    let objects = vec![
+        "$null".into(),
+        object(2, vec![("title", uid_value(3))]),
+        classes(&["Note", "NSObject"]),
+        "Some note".into(),
+        // Garbage left by an editor: a note and its title
+        object(2, vec![("title", uid_value(5))]),
+        "Old note".into(),
+        classes(&["Unused", "NSObject"]),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    assert_eq!(
+        archive.unreachable_objects(),
+        [UniqueId::new(4), UniqueId::new(5), UniqueId::new(6)]
+    );
+    assert_eq!(archive.stats().unreachable, 3);
+
+    let mut editor = archive.edit();
+    editor.compact();
+    let archive = editor.into_archive().unwrap();
+    assert!(archive.unreachable_objects().is_empty());
+    assert_eq!(archive.values().len(), 4);
+}
+
+#[test]
+fn class_index() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![("items", Value::Array(vec![uid_value(3), uid_value(5)]))],
+        ),
+        classes(&["NSArray", "NSObject"]),
+        object(4, vec![("title", "First".into())]),
+        classes(&["Note", "NSObject"]),
+        object(4, vec![("title", "Second".into())]),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+
+    let index = archive.class_index();
+    assert_eq!(index.len(), 2);
+    assert_eq!(index["Note"], [UniqueId::new(3), UniqueId::new(5)]);
+    assert_eq!(index["NSArray"], [UniqueId::new(1)]);
+    assert!(std::ptr::eq(index, archive.class_index()));
+
+    let titles: Vec<String> = archive
+        .objects_of_class("Note")
+        .map(|value| value.as_object().unwrap().decode_string("title").unwrap())
+        .collect();
+    assert_eq!(titles, ["First", "Second"]);
+    assert_eq!(archive.objects_of_class("NSObject").count(), 0);
+    assert_eq!(archive.clone().class_index(), index);
+}
+
+#[test]
+fn tables() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("title", uid_value(3)),
+                ("author", uid_value(4)),
+                ("date", uid_value(6)),
+                ("data", Value::Data(vec![0xca, 0xfe])),
+                ("tags", Value::Array(vec![uid_value(8), uid_value(9)])),
+            ],
+        ),
+        classes(&["Entry", "NSObject"]),
+        "Hello, \"world\"".into(),
+        object(5, vec![("name", "Ann".into()), ("age", 30.into())]),
+        classes(&["Author", "NSObject"]),
+        object(7, vec![("NS.time", 0.0.into())]),
+        classes(&["NSDate", "NSObject"]),
+        "a".into(),
+        true.into(),
+        object(
+            2,
+            vec![("title", "Second".into()), ("author", uid_value(0))],
+        ),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+
+    let fields = [
+        "title",
+        "author.name",
+        "author.age",
+        "date",
+        "data",
+        "tags",
+        "author",
+    ];
+    let table = archive.table("Entry", &fields);
+    assert_eq!(table.columns, fields);
+    assert_eq!(
+        table.rows,
+        [
+            vec![
+                "Hello, \"world\"",
+                "Ann",
+                "30",
+                "2001-01-01T00:00:00Z",
+                "cafe",
+                "a, true",
+                "<Author #4>"
+            ],
+            vec!["Second", "", "", "", "", "", ""],
+        ]
+    );
+    assert_eq!(
+        table.to_csv().lines().next(),
+        Some("title,author.name,author.age,date,data,tags,author")
+    );
+    assert!(table.to_csv().contains("\"Hello, \"\"world\"\"\",Ann,"));
+    assert_eq!(archive.table_rows("Entry", &["missing"]).count(), 2);
+    assert!(archive.table("Author", &[]).rows[0].is_empty());
+}
+
+#[test]
+fn dynamic_values() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("title", uid_value(3)),
+                ("tags", uid_value(4)),
+                ("count", 5.into()),
+                ("parent", uid_value(1)),
+                ("empty", uid_value(0)),
+            ],
+        ),
+        classes(&["Note", "NSObject"]),
+        "Some note".into(),
+        object(
+            5,
+            vec![(
+                "NS.objects",
+                Value::Array(vec![uid_value(3), uid_value(6), uid_value(8)]),
+            )],
+        ),
+        classes(&["NSArray", "NSObject"]),
+        object(
+            7,
+            vec![
+                ("NS.keys", Value::Array(vec![uid_value(3)])),
+                ("NS.objects", Value::Array(vec![uid_value(9)])),
+            ],
+        ),
+        classes(&["NSDictionary", "NSObject"]),
+        object(10, vec![]),
+        1.5.into(),
+        classes(&["NSNull", "NSObject"]),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    let value: DynamicValue = archive.decode_root().unwrap();
+    assert_eq!(value.class(), Some("Note"));
+    assert_eq!(value.get("title").unwrap().as_str(), Some("Some note"));
+    assert_eq!(value.get("count").unwrap().as_i64(), Some(5));
+    assert_eq!(
+        value.get("parent"),
+        Some(&DynamicValue::Ref(UniqueId::new(1)))
+    );
+    assert!(value.get("empty").unwrap().is_null());
+    let tags = value.get("tags").unwrap().as_array().unwrap();
+    assert_eq!(tags[0], DynamicValue::String("Some note".into()));
+    assert_eq!(tags[1].get("Some note").unwrap().as_f64(), Some(1.5));
+    assert!(tags[2].is_null());
+
+    // Values are owned, so they outlive the archive
+    drop(archive);
+    assert_eq!(value.clone(), value);
+}
+
+#[test]
+fn owned_trees() {
+    let objects = vec![
+        "$null".into(),
+        object(2, vec![("title", uid_value(3)), ("parent", uid_value(1))]),
+        classes(&["Note", "NSObject"]),
+        "Some note".into(),
+    ];
+    let archive =
+        KeyedArchive::from_plist(archive_plist(vec![("root", 1), ("title", 3)], objects)).unwrap();
+    let tree = archive.into_owned_tree().unwrap();
+    let handle = std::thread::spawn(move || {
+        let root = &tree["root"];
+        assert_eq!(root.get("title").unwrap().as_str(), Some("Some note"));
+        assert_eq!(
+            root.get("parent"),
+            Some(&DynamicValue::Ref(UniqueId::new(1)))
+        );
+        tree["title"].as_str().map(str::to_string)
+    });
+    assert_eq!(handle.join().unwrap().as_deref(), Some("Some note"));
+}
+
+#[test]
+fn plist_values() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("items", uid_value(3)),
+                ("parent", uid_value(1)),
+                ("empty", uid_value(0)),
+            ],
+        ),
+        classes(&["Note", "NSObject"]),
+        object(
+            5,
+            vec![(
+                "NS.objects",
+                Value::Array(vec![uid_value(4), uid_value(6), uid_value(7)]),
+            )],
+        ),
+        "Some note".into(),
+        classes(&["NSArray", "NSObject"]),
+        5.into(),
+        object(
+            8,
+            vec![
+                ("NS.keys", Value::Array(vec![uid_value(4)])),
+                ("NS.objects", Value::Array(vec![uid_value(6)])),
+            ],
+        ),
+        classes(&["NSDictionary", "NSObject"]),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    let value: Value = archive.decode_root().unwrap();
+    let dict = value.as_dictionary().unwrap();
+    assert_eq!(dict.get("$class"), Some(&Value::String("Note".into())));
+    assert_eq!(dict.get("parent"), Some(&Value::Uid(plist::Uid::new(1))));
+    assert!(!dict.contains_key("empty"));
+    let items = dict.get("items").unwrap().as_array().unwrap();
+    assert_eq!(items[0], Value::String("Some note".into()));
+    assert_eq!(items[1], Value::Integer(5.into()));
+    let nested = items[2].as_dictionary().unwrap();
+    assert_eq!(nested.get("Some note"), Some(&Value::Integer(5.into())));
+
+    // Nulls can't be array elements
+    let objects = vec![
+        "$null".into(),
+        object(2, vec![("NS.objects", Value::Array(vec![uid_value(0)]))]),
+        classes(&["NSArray", "NSObject"]),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    assert!(archive.decode_root::<Value>().is_err());
+}
+
+#[test]
+fn archive_streams() {
+    let note = std::fs::read("./tests_resources/plists/note.plist").unwrap();
+    // Magic bytes inside of data don't split a document
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec!["$null".into(), Value::Data(b"bplist00bplist00".to_vec())],
+    );
+    let mut data = Vec::new();
+    plist.to_writer_binary(&mut data).unwrap();
+
+    let mut bytes = note.clone();
+    bytes.extend(&data);
+    bytes.extend(&note);
+    let archives: Vec<_> = KeyedArchiveStream::new(&bytes)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(archives.len(), 3);
+    assert_eq!(
+        archives[1].decode_root::<Data>().unwrap().as_ref(),
+        b"bplist00bplist00"
+    );
+    assert_eq!(archives[0].values().len(), archives[2].values().len());
+
+    // Garbage stops the iteration
+    bytes.extend(b"garbage");
+    let mut stream = KeyedArchiveStream::new(&bytes);
+    assert_eq!(stream.by_ref().filter(|a| a.is_ok()).count(), 3);
+    assert_eq!(stream.offset(), bytes.len());
+    let results: Vec<_> = KeyedArchiveStream::new(&bytes).collect();
+    assert!(matches!(results[3], Err(Error::IncorrectFormat(_))));
+    assert_eq!(results.len(), 4);
+}
+
+#[test]
+fn source_formats() {
+    let path = "./tests_resources/plists/note.plist";
+    let binary = std::fs::read(path).unwrap();
+    let archive = KeyedArchive::from_file(path).unwrap();
+    assert_eq!(archive.source_format(), Some(Format::Binary));
+    assert_eq!(
+        KeyedArchive::from_bytes(&binary).unwrap().source_format(),
+        Some(Format::Binary)
+    );
+
+    // XML plists can't contain uids, so only plain `$top` values are used
+    let mut dict = archive_dict(vec![], vec!["$null".into()]);
+    let top = dict.get_mut("$top").unwrap().as_dictionary_mut().unwrap();
+    top.insert("count".into(), 42.into());
+    let mut xml = Vec::new();
+    Value::from(dict).to_writer_xml(&mut xml).unwrap();
+    assert_eq!(Format::detect(&xml), Format::Xml);
+    assert_eq!(
+        KeyedArchive::from_bytes(&xml).unwrap().source_format(),
+        Some(Format::Xml)
+    );
+    let archive = KeyedArchive::from_bytes_with_format(&xml, Format::Xml).unwrap();
+    assert_eq!(archive.source_format(), Some(Format::Xml));
+    assert!(KeyedArchive::from_bytes_with_format(&xml, Format::Binary).is_err());
+    assert!(KeyedArchive::from_bytes_with_format(&binary, Format::Xml).is_err());
+
+    let plist = Value::from_file(path).unwrap();
+    assert_eq!(
+        KeyedArchive::from_plist(plist).unwrap().source_format(),
+        None
+    );
+    assert_eq!(Format::detect(b"{ key = value; }"), Format::Ascii);
+}
+
+#[test]
+fn unsupported_plist_variants() {
+    let mut bplist16 = b"bplist16".to_vec();
+    bplist16.extend_from_slice(&[0xd0; 32]);
+    let is_variant = |result: Result<KeyedArchive, Error>| matches!(result, Err(Error::UnsupportedPlistVariant(variant)) if variant == "bplist16");
+    assert!(is_variant(KeyedArchive::from_bytes(&bplist16)));
+    assert!(is_variant(KeyedArchive::from_bytes_with_format(
+        &bplist16,
+        Format::Binary
+    )));
+    assert!(is_variant(KeyedArchive::from_stream(bplist16.as_slice())));
+
+    // Broken `bplist00` files are still reported as plist errors
+    let mut bplist00 = b"bplist00".to_vec();
+    bplist00.extend_from_slice(&[0xd0; 32]);
+    assert!(matches!(
+        KeyedArchive::from_bytes(&bplist00),
+        Err(Error::PlistError(_))
+    ));
+}
+
+#[test]
+fn pretty_printing() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("title", uid_value(3)),
+                ("tags", uid_value(4)),
+                ("data", Value::Data((0..20).collect())),
+                ("empty", uid_value(0)),
+            ],
+        ),
+        classes(&["Note", "NSObject"]),
+        "Some note".into(),
+        object(
+            5,
+            vec![("NS.objects", Value::Array(vec![uid_value(3), uid_value(1)]))],
+        ),
+        classes(&["NSArray", "NSObject"]),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    let expected = r#"Note #1 {
+  data: <20 bytes: 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f …>
+  empty: $null
+  tags: NSArray #4 {
+    NS.objects: [
+      [0]: "Some note" #3
+      [1]: ↩ ref #1
+    ]
+  }
+  title: "Some note" #3
+}"#;
+    assert_eq!(archive.root().unwrap().to_string(), expected);
+}
+
+#[test]
+fn borrowed_plists_and_clones() {
+    let plist = Value::from_file("./tests_resources/plists/note.plist").unwrap();
+    let archive = KeyedArchive::from_plist_ref(&plist).unwrap();
+    let owned = KeyedArchive::from_plist(plist.clone()).unwrap();
+    assert_eq!(archive.values(), owned.values());
+    assert_eq!(archive.top_raw(), owned.top_raw());
+    assert!(matches!(
+        KeyedArchive::from_plist_ref(&Value::Array(vec![])),
+        Err(Error::IncorrectFormat(_))
+    ));
+
+    let cloned = archive.clone();
+    assert_eq!(cloned.values(), archive.values());
+    assert_eq!(cloned.top_keys(), archive.top_keys());
+    let root = archive.root().unwrap();
+    let cloned_root = cloned.root().unwrap();
+    assert!(!std::rc::Rc::ptr_eq(&root, &cloned_root));
+    drop(archive);
+    assert_eq!(cloned.stats().values, owned.stats().values);
+
+    // Circular references and plain `$top` values
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("next", uid_value(1))]),
+            classes(&["Node", "NSObject"]),
+        ],
+    );
+    let mut dict = plist.into_dictionary().unwrap();
+    let top = dict.get_mut("$top").unwrap().as_dictionary_mut().unwrap();
+    top.insert("count".into(), 3.into());
+    let archive = KeyedArchive::from_plist(dict.into()).unwrap();
+    let cloned = archive.clone();
+    let root = cloned.root().unwrap();
+    let next = root.as_object().unwrap().as_map()["next"]
+        .as_value_ref()
+        .unwrap();
+    assert!(std::rc::Rc::ptr_eq(&root, next));
+    assert_eq!(cloned.decode_top::<i64>("count").unwrap(), 3);
+}
+
+#[test]
+fn structural_equality() {
+    let first = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("name", uid_value(3)), ("count", 1.into())]),
+            classes(&["Item", "NSObject"]),
+            "first".into(),
+        ],
+    );
+    // The same item with different uids and key order
+    let second = archive_plist(
+        vec![("root", 3)],
+        vec![
+            "$null".into(),
+            "first".into(),
+            classes(&["Item", "NSObject"]),
+            object(2, vec![("count", 1.into()), ("name", uid_value(1))]),
+        ],
+    );
+    let other = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("name", uid_value(3)), ("count", 2.into())]),
+            classes(&["Item", "NSObject"]),
+            "first".into(),
+        ],
+    );
+    let first = KeyedArchive::from_plist(first).unwrap().root().unwrap();
+    let second = KeyedArchive::from_plist(second).unwrap().root().unwrap();
+    let other = KeyedArchive::from_plist(other).unwrap().root().unwrap();
+    assert_ne!(first.unique_id(), second.unique_id());
+    assert_eq!(first, second);
+    assert_eq!(first.fingerprint(), second.fingerprint());
+    assert_ne!(first, other);
+    assert_ne!(first.fingerprint(), other.fingerprint());
+
+    let unique: HashSet<_> = [&first, &second, &other].into_iter().collect();
+    assert_eq!(unique.len(), 2);
+
+    // Circular references
+    let archive =
+        KeyedArchive::from_file("./tests_resources/plists/circularReference.plist").unwrap();
+    let cloned = archive.clone();
+    let root = archive.root().unwrap();
+    assert_eq!(root, cloned.root().unwrap());
+    assert_eq!(root.fingerprint(), cloned.root().unwrap().fingerprint());
+    assert_ne!(root, first);
+}
+
+#[test]
+fn decode_values_by_uid() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("title", uid_value(3)), ("tags", uid_value(4))]),
+            classes(&["Note", "NSObject"]),
+            "Some note".into(),
+            object(5, vec![("NS.objects", vec![uid_value(3)].into())]),
+            classes(&["NSArray", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let title: String = archive.decode_value_as(UniqueId::new(3)).unwrap();
+    assert_eq!(title, "Some note");
+    let tags: Vec<String> = archive.decode_value_as(UniqueId::new(4)).unwrap();
+    assert_eq!(tags, vec!["Some note"]);
+
+    let error = archive
+        .decode_value_as::<i64>(UniqueId::new(3))
+        .unwrap_err();
+    assert!(matches!(error.inner(), DeError::ExpectedInteger));
+    assert!(error.to_string().starts_with("uid: 3:"));
+    assert!(matches!(
+        archive.decode_value_as::<String>(UniqueId::new(6)),
+        Err(DeError::MissingValue(uid)) if uid.get() == 6
+    ));
+}
+
+#[test]
+fn field_order() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("title", uid_value(3)),
+                    ("count", 2.into()),
+                    ("tags", vec![uid_value(3)].into()),
+                    ("flag", true.into()),
+                ],
+            ),
+            classes(&["Note", "NSObject"]),
+            "Some note".into(),
+        ],
+    );
+    let expected = ["title", "count", "tags", "flag"];
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root = archive.root().unwrap();
+    let obj = root.as_object().unwrap();
+    assert_eq!(obj.keys(), expected);
+    let fields: Vec<_> = obj.iter().collect();
+    assert_eq!(fields[0].0, "title");
+    assert_eq!(fields[0].1.as_str(), Some("Some note"));
+    assert_eq!(fields[1].1.as_i64(), Some(2));
+    assert_eq!(fields[2].1.as_ref_array().unwrap().len(), 1);
+    assert_eq!(fields[3].1.as_bool(), Some(true));
+
+    let cloned = archive.clone();
+    assert_eq!(cloned.root().unwrap().as_object().unwrap().keys(), expected);
+
+    // Re-encoded objects keep the order
+    let plist = archive.edit().into_plist();
+    let objects = plist.as_dictionary().unwrap()["$objects"]
+        .as_array()
+        .unwrap();
+    let keys: Vec<_> = objects[1]
+        .as_dictionary()
+        .unwrap()
+        .keys()
+        .filter(|key| *key != "$class")
+        .cloned()
+        .collect();
+    assert_eq!(keys, expected);
+}
+
+#[test]
+fn duplicate_dictionary_keys() {
+    // -- NSDictionary: {"a": 1, "b": 2, "a": 3}
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    (
+                        "NS.keys",
+                        vec![uid_value(3), uid_value(4), uid_value(3)].into(),
+                    ),
+                    (
+                        "NS.objects",
+                        vec![uid_value(5), uid_value(6), uid_value(7)].into(),
+                    ),
+                ],
+            ),
+            classes(&["NSDictionary", "NSObject"]),
+            "a".into(),
+            "b".into(),
+            1.into(),
+            2.into(),
+            3.into(),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+
+    let map: HashMap<String, i64> = archive.decode_root().unwrap();
+    assert_eq!(map["a"], 3);
+    assert_eq!(map["b"], 2);
+
+    let mut ctx = archive.decode_context();
+    assert_eq!(ctx.duplicate_keys(), DuplicateKeys::LastWins);
+    ctx.set_duplicate_keys(DuplicateKeys::FirstWins);
+    let map: BTreeMap<String, i64> = archive.decode_root_with(&mut ctx).unwrap();
+    assert_eq!(map["a"], 1);
+
+    ctx.set_duplicate_keys(DuplicateKeys::Error);
+    let error = archive
+        .decode_root_with::<HashMap<String, i64>>(&mut ctx)
+        .unwrap_err();
+    assert!(matches!(error.inner(), DeError::DuplicateKeys));
+
+    let entries: Entries<String, i64> = archive.decode_root_with(&mut ctx).unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[2], ("a".to_string(), 3));
+    let grouped = entries.into_grouped();
+    assert_eq!(grouped["a"], vec![1, 3]);
+    assert_eq!(grouped["b"], vec![2]);
+}
+
+#[test]
+fn numeric_coercion() {
+    let plist = archive_plist(
+        vec![
+            ("flag", 1),
+            ("other", 2),
+            ("count", 3),
+            ("ratio", 4),
+            ("big", 5),
+        ],
+        vec![
+            "$null".into(),
+            1.into(),
+            2.into(),
+            3.0.into(),
+            2.5.into(),
+            1e19.into(),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    assert!(archive.decode_top::<bool>("flag").is_err());
+    assert!(archive.decode_top::<i32>("count").is_err());
+
+    let mut ctx = archive.decode_context();
+    ctx.set_coerce_numeric_bool(true);
+    ctx.set_coerce_real_to_int(true);
+    assert!(archive.decode_top_with::<bool>("flag", &mut ctx).unwrap());
+    assert!(archive.decode_top_with::<bool>("other", &mut ctx).is_err());
+    assert_eq!(
+        archive.decode_top_with::<i32>("count", &mut ctx).unwrap(),
+        3
+    );
+    assert!(archive.decode_top_with::<i64>("ratio", &mut ctx).is_err());
+    assert!(archive.decode_top_with::<i64>("big", &mut ctx).is_err());
+    assert_eq!(
+        archive.decode_top_with::<u64>("big", &mut ctx).unwrap(),
+        10_000_000_000_000_000_000
+    );
+}
+
+#[test]
+fn external_data() {
+    // -- Document
+    //    -- inline: NSData <01 02>
+    //    -- external: Placeholder { fileName: "image.png" }
+    //    -- missing: Placeholder { fileName: "missing.bin" }
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("inline", uid_value(3)),
+                    ("external", uid_value(5)),
+                    ("missing", uid_value(7)),
+                ],
+            ),
+            classes(&["Document", "NSObject"]),
+            object(4, vec![("NS.data", Value::Data(vec![1, 2]))]),
+            classes(&["NSData", "NSObject"]),
+            object(6, vec![("fileName", "image.png".into())]),
+            classes(&["Placeholder", "NSObject"]),
+            object(6, vec![("fileName", "missing.bin".into())]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root: ObjectValue = archive.root().unwrap().into();
+    let obj = root.as_object().unwrap();
+
+    let mut ctx = archive.decode_context();
+    assert!(
+        ctx.decode_field::<Data>(&obj.as_map()["external"], "external")
+            .is_err()
+    );
+
+    ctx.set_external_data(|obj| {
+        if !obj.matches_class("Placeholder") {
+            return Ok(None);
+        }
+        match obj.get_str("fileName")? {
+            "image.png" => Ok(Some(vec![0x89, b'P', b'N', b'G'])),
+            name => Err(DeError::Custom(format!("No such file: {name}"))),
+        }
+    });
+    let inline: Data = ctx.decode_field(&obj.as_map()["inline"], "inline").unwrap();
+    assert_eq!(inline.as_ref(), [1, 2]);
+    let external: Data = ctx
+        .decode_field(&obj.as_map()["external"], "external")
+        .unwrap();
+    assert_eq!(external.as_ref(), b"\x89PNG");
+    let error = ctx
+        .decode_field::<Data>(&obj.as_map()["missing"], "missing")
+        .unwrap_err();
+    assert!(error.to_string().contains("missing.bin"), "{error}");
+
+    // Decoding without a context never resolves placeholders
+    assert!(Data::decode(&obj.as_map()["external"]).is_err());
+}
+
+#[test]
+fn raw_object_tables() {
+    // A custom format with an object table and its own header keys
+    let table = vec![
+        "$null".into(),
+        object(2, vec![("title", uid_value(3)), ("next", uid_value(1))]),
+        classes(&["Entry", "NSObject"]),
+        "Title".into(),
+    ];
+    let plist = Value::Dictionary(Dictionary::from_iter([
+        ("format".to_string(), Value::from("Custom")),
+        ("table".to_string(), Value::Array(table.clone())),
+        ("entry".to_string(), uid_value(1)),
+    ]));
+    assert!(KeyedArchive::from_plist_ref(&plist).is_err());
+
+    let header = plist.as_dictionary().unwrap();
+    let objects = raw::parse_objects_ref(
+        header["table"].as_array().unwrap(),
+        &ParseOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(objects.len(), 4);
+    let entry = raw::resolve_uid(&header["entry"], &objects).unwrap();
+    let obj = entry.as_object().unwrap();
+    assert_eq!(obj.class(), "Entry");
+    assert_eq!(obj.decode_string("title").unwrap(), "Title");
+    // References are resolved, including circular ones
+    assert!(std::ptr::eq(
+        obj.decode_object("next").unwrap().as_ref(),
+        entry.as_ref()
+    ));
+    assert!(raw::resolve_uid(&uid_value(4), &objects).is_none());
+    assert!(raw::resolve_uid(&header["format"], &objects).is_none());
+
+    let objects = raw::parse_objects(table, &ParseOptions::default()).unwrap();
+    assert_eq!(objects[3].as_string(), Some("Title"));
+
+    let limits = Limits {
+        max_objects: Some(2),
+        ..Limits::default()
+    };
+    let options = ParseOptions::default().with_limits(limits);
+    assert!(raw::parse_objects_ref(header["table"].as_array().unwrap(), &options).is_err());
+    let broken = vec!["$null".into(), object(5, vec![])];
+    assert!(raw::parse_objects(broken, &ParseOptions::default()).is_err());
+}
+
+#[test]
+fn data_interop() {
+    let data = Data::from(&b"hello"[..]);
+    assert_eq!(data.len(), 5);
+    assert_eq!(&data[1..3], b"el");
+    assert!(data.starts_with(b"he"));
+    assert_eq!(data.base64().to_string(), "aGVsbG8=");
+    assert_eq!(Data::new(vec![]).base64().to_string(), "");
+    assert_eq!(Data::new(vec![0xff; 4]).base64().to_string(), "/////w==");
+    assert_eq!(format!("{data:?}"), "Data<5 bytes: 68 65 6c 6c 6f>");
+    let large = format!("{:?}", Data::new(vec![0; 1000]));
+    assert!(large.starts_with("Data<1000 bytes: 00 00"));
+    assert!(large.ends_with(" …>"));
+
+    let mut text = String::new();
+    data.into_reader().read_to_string(&mut text).unwrap();
+    assert_eq!(text, "hello");
+}
@@ -0,0 +1,189 @@
+mod common;
+
+use common::archive_plist;
+use nskeyedunarchiver::{
+    Error, KeyedArchive,
+    bookmark::{self, Bookmark, BookmarkValue, is_bookmark},
+};
+use plist::Value;
+use std::path::Path;
+
+/// Builds a data section of bookmark data.
+struct Builder {
+    // The first 4 bytes hold an offset of the first table of contents
+    data: Vec<u8>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self { data: vec![0; 4] }
+    }
+
+    /// Adds a value and returns its offset.
+    fn item(&mut self, ty: u32, bytes: &[u8]) -> u32 {
+        let offset = self.data.len() as u32;
+        self.data.extend((bytes.len() as u32).to_le_bytes());
+        self.data.extend(ty.to_le_bytes());
+        self.data.extend(bytes);
+        while !self.data.len().is_multiple_of(4) {
+            self.data.push(0);
+        }
+        offset
+    }
+
+    fn string(&mut self, s: &str) -> u32 {
+        self.item(0x0101, s.as_bytes())
+    }
+
+    /// Adds a table of contents and returns its offset.
+    fn toc(&mut self, entries: &[(u32, u32)], next: u32) -> u32 {
+        let offset = self.data.len() as u32;
+        for value in [(entries.len() * 12 + 12) as u32, 0xffff_fffe, 1, next] {
+            self.data.extend(value.to_le_bytes());
+        }
+        self.data.extend((entries.len() as u32).to_le_bytes());
+        for (key, value) in entries {
+            for value in [*key, *value, 0] {
+                self.data.extend(value.to_le_bytes());
+            }
+        }
+        offset
+    }
+
+    fn finish(mut self, toc: u32) -> Vec<u8> {
+        self.data[..4].copy_from_slice(&toc.to_le_bytes());
+        let mut bytes = b"book".to_vec();
+        bytes.extend(((self.data.len() + 48) as u32).to_le_bytes());
+        bytes.extend(0x1004_0000u32.to_le_bytes());
+        bytes.extend(48u32.to_le_bytes());
+        bytes.resize(48, 0);
+        bytes.extend(self.data);
+        bytes
+    }
+}
+
+fn bookmark_data() -> Vec<u8> {
+    let mut builder = Builder::new();
+    let components = [
+        builder.string("Users"),
+        builder.string("me"),
+        builder.string("Notes.txt"),
+    ];
+    let components: Vec<u8> = components.iter().flat_map(|c| c.to_le_bytes()).collect();
+    let path = builder.item(0x0601, &components);
+    let created = builder.item(0x0400, &86400.5f64.to_be_bytes());
+    let volume_name = builder.string("Macintosh HD");
+    let volume_path = builder.item(0x0901, b"file:///");
+    let volume_size = builder.item(0x0304, &500_000_000_000i64.to_le_bytes());
+    let was_boot = builder.item(0x0501, &[]);
+    let unknown = builder.item(0xab00, &[1, 2]);
+    let string_key = builder.string("Ignored");
+    let relative_url = builder.string("Documents/");
+    let url_offsets: Vec<u8> = [volume_path, relative_url]
+        .iter()
+        .flat_map(|c| c.to_le_bytes())
+        .collect();
+    let url = builder.item(0x0902, &url_offsets);
+
+    // The second table of contents repeats a key, the first value wins
+    let other_name = builder.string("Other");
+    let second = builder.toc(&[(0x2010, other_name), (0xf017, relative_url)], 0);
+    let first = builder.toc(
+        &[
+            (bookmark::PATH_COMPONENTS, path),
+            (bookmark::CREATION_DATE, created),
+            (bookmark::VOLUME_NAME, volume_name),
+            (bookmark::VOLUME_PATH, volume_path),
+            (bookmark::VOLUME_SIZE, volume_size),
+            (bookmark::VOLUME_IS_BOOT, was_boot),
+            (0x9999, unknown),
+            (0x8000_0000 | string_key, string_key),
+            (0x9998, url),
+        ],
+        second,
+    );
+    builder.finish(first)
+}
+
+#[test]
+fn bookmarks() {
+    let bytes = bookmark_data();
+    assert!(is_bookmark(&bytes));
+    let bookmark = Bookmark::parse(&bytes).unwrap();
+    assert_eq!(
+        bookmark.path_components(),
+        Some(vec!["Users", "me", "Notes.txt"])
+    );
+    assert_eq!(bookmark.path().unwrap(), Path::new("/Users/me/Notes.txt"));
+    assert_eq!(
+        bookmark
+            .creation_date()
+            .unwrap()
+            .seconds_since_reference_date(),
+        86400.5
+    );
+    assert_eq!(bookmark.volume_name(), Some("Macintosh HD"));
+    assert_eq!(bookmark.volume_path(), Some("file:///"));
+    assert_eq!(bookmark.volume_size(), Some(500_000_000_000));
+    assert_eq!(
+        bookmark.get(bookmark::VOLUME_IS_BOOT),
+        Some(&BookmarkValue::Boolean(true))
+    );
+    assert_eq!(
+        bookmark.get(0x9999),
+        Some(&BookmarkValue::Unknown {
+            ty: 0xab00,
+            bytes: vec![1, 2]
+        })
+    );
+    assert_eq!(
+        bookmark.get(0x9998),
+        Some(&BookmarkValue::Url("file:///Documents/".into()))
+    );
+    assert_eq!(
+        bookmark.get(bookmark::DISPLAY_NAME).unwrap().as_str(),
+        Some("Documents/")
+    );
+    assert_eq!(bookmark.entries().len(), 9);
+    assert!(bookmark.volume_uuid().is_none());
+
+    // Bookmarks are usually stored in data fields of archives
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec!["$null".into(), Value::Data(bytes.clone())],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    assert_eq!(archive.decode_root::<Bookmark>().unwrap(), bookmark);
+}
+
+#[test]
+fn malformed_bookmarks() {
+    let bytes = bookmark_data();
+    assert!(matches!(
+        Bookmark::parse(b"alis"),
+        Err(Error::IncorrectBookmark(_))
+    ));
+    assert!(matches!(
+        Bookmark::parse(&bytes[..bytes.len() - 8]),
+        Err(Error::IncorrectBookmark(_))
+    ));
+    assert!(!is_bookmark(b"bplist00"));
+
+    // A table of contents referring to itself
+    let mut builder = Builder::new();
+    let name = builder.string("Name");
+    let toc = builder.toc(&[(bookmark::VOLUME_NAME, name)], 0);
+    let mut bytes = builder.finish(toc);
+    let next = 48 + toc as usize + 12;
+    bytes[next..next + 4].copy_from_slice(&toc.to_le_bytes());
+    let bookmark = Bookmark::parse(&bytes).unwrap();
+    assert_eq!(bookmark.volume_name(), Some("Name"));
+
+    // An array containing itself (values start right after the offset of
+    // a table of contents)
+    let mut builder = Builder::new();
+    let array = builder.item(0x0601, &4u32.to_le_bytes());
+    assert_eq!(array, 4);
+    let toc = builder.toc(&[(bookmark::PATH_COMPONENTS, array)], 0);
+    assert!(Bookmark::parse(&builder.finish(toc)).is_err());
+}
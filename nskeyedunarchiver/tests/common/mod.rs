@@ -0,0 +1,47 @@
+#![allow(dead_code)]
+
+use plist::{Dictionary, Uid, Value};
+
+/// Builds a keyed archive header dictionary with given `$top` uids and `$objects`.
+pub fn archive_dict(top: Vec<(&str, u64)>, objects: Vec<Value>) -> Dictionary {
+    let mut top_dict = Dictionary::new();
+    for (key, uid) in top {
+        top_dict.insert(key.into(), uid_value(uid));
+    }
+    let mut dict = Dictionary::new();
+    dict.insert("$archiver".into(), "NSKeyedArchiver".into());
+    dict.insert("$version".into(), 100000.into());
+    dict.insert("$top".into(), top_dict.into());
+    dict.insert("$objects".into(), Value::Array(objects));
+    dict
+}
+
+/// Builds a keyed archive plist with given `$top` uids and `$objects`.
+pub fn archive_plist(top: Vec<(&str, u64)>, objects: Vec<Value>) -> Value {
+    archive_dict(top, objects).into()
+}
+
+pub fn uid_value(uid: u64) -> Value {
+    Value::Uid(Uid::new(uid))
+}
+
+/// Builds a `$classes` entry.
+pub fn classes(names: &[&str]) -> Value {
+    let mut dict = Dictionary::new();
+    dict.insert("$classname".into(), names[0].into());
+    dict.insert(
+        "$classes".into(),
+        Value::Array(names.iter().map(|n| Value::from(*n)).collect()),
+    );
+    dict.into()
+}
+
+/// Builds an object entry with a `$class` reference and given fields.
+pub fn object(class_uid: u64, fields: Vec<(&str, Value)>) -> Value {
+    let mut dict = Dictionary::new();
+    dict.insert("$class".into(), uid_value(class_uid));
+    for (key, value) in fields {
+        dict.insert(key.into(), value);
+    }
+    dict.into()
+}
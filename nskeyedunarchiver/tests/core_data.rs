@@ -0,0 +1,113 @@
+mod common;
+
+use common::{archive_plist, classes, object, uid_value};
+use nskeyedunarchiver::KeyedArchive;
+use nskeyedunarchiver::core_data::{self, Blob, PersistentHistoryToken};
+use plist::{Dictionary, Value};
+
+fn archive_bytes(root: &str) -> Vec<u8> {
+    let plist = archive_plist(vec![("root", 1)], vec!["$null".into(), root.into()]);
+    let mut bytes = Vec::new();
+    plist.to_writer_binary(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn blobs() {
+    let archive = archive_bytes("Plain");
+    assert_eq!(Blob::parse(&archive).unwrap(), Blob::Inline(&archive));
+
+    let mut inline = vec![1];
+    inline.extend_from_slice(&archive);
+    assert_eq!(Blob::parse(&inline).unwrap(), Blob::Inline(&archive));
+    let decoded = core_data::archive_from_blob(&inline).unwrap();
+    assert_eq!(decoded.root().unwrap().as_string(), Some("Plain"));
+
+    let external = b"\x02B5B3B4B4-2B2C-4C9A-9D53-3C7A1F0C3E11\x00";
+    assert_eq!(
+        Blob::parse(external).unwrap(),
+        Blob::External("B5B3B4B4-2B2C-4C9A-9D53-3C7A1F0C3E11")
+    );
+    let err = core_data::archive_from_blob(external).err().unwrap();
+    assert!(
+        err.to_string()
+            .contains("B5B3B4B4-2B2C-4C9A-9D53-3C7A1F0C3E11")
+    );
+}
+
+#[test]
+fn rows() {
+    let mut inline = vec![1];
+    inline.extend_from_slice(&archive_bytes("Second"));
+    let row = |pk: i64, payload: Vec<u8>| {
+        let mut row = Dictionary::new();
+        row.insert("Z_PK".into(), pk.into());
+        row.insert("Z_ENT".into(), 3.into());
+        row.insert("ZTITLE".into(), "Note".into());
+        row.insert("ZPAYLOAD".into(), Value::Data(payload));
+        row.insert("ZTHUMBNAIL".into(), Value::Data(vec![0xff, 0xd8]));
+        Value::Dictionary(row)
+    };
+    let mut not_a_row = Dictionary::new();
+    not_a_row.insert("ZPAYLOAD".into(), Value::Data(archive_bytes("Skipped")));
+    let plist = Value::Array(vec![
+        row(1, archive_bytes("First")),
+        row(2, inline),
+        Value::Dictionary(not_a_row),
+    ]);
+
+    assert!(core_data::is_row(
+        plist.as_array().unwrap()[0].as_dictionary().unwrap()
+    ));
+    let archives = core_data::find_archives(&plist);
+    let found: Vec<(&str, String)> = archives
+        .iter()
+        .map(|(path, archive)| {
+            let root = archive.root().unwrap();
+            (path.as_str(), root.as_string().unwrap().to_string())
+        })
+        .collect();
+    assert_eq!(
+        found,
+        [
+            ("[0].ZPAYLOAD", "First".to_string()),
+            ("[1].ZPAYLOAD", "Second".to_string())
+        ]
+    );
+}
+
+#[test]
+fn persistent_history_tokens() {
+    // -- _NSPersistentHistoryToken
+    //    -- storeTokens: NSDictionary {"F3A1...": 42}
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("storeTokens", uid_value(3))]),
+            classes(&[
+                "_NSPersistentHistoryToken",
+                "NSPersistentHistoryToken",
+                "NSObject",
+            ]),
+            object(
+                6,
+                vec![
+                    ("NS.keys", vec![uid_value(4)].into()),
+                    ("NS.objects", vec![uid_value(5)].into()),
+                ],
+            ),
+            "F3A1C2D4-5E6F-4A7B-8C9D-0E1F2A3B4C5D".into(),
+            42.into(),
+            classes(&["NSDictionary", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let token: PersistentHistoryToken = archive.decode_root().unwrap();
+    assert_eq!(token.store_tokens().len(), 1);
+    assert_eq!(
+        token.transaction_number("F3A1C2D4-5E6F-4A7B-8C9D-0E1F2A3B4C5D"),
+        Some(42)
+    );
+    assert_eq!(token.transaction_number("missing"), None);
+}
@@ -1,7 +1,14 @@
 #![cfg(feature = "derive")]
 
-use nskeyedunarchiver::{Data, Decodable, KeyedArchive, ObjectValue, derive::Decodable};
+mod common;
+
+use common::{archive_plist, classes, object, uid_value};
+use nskeyedunarchiver::{
+    ConditionalRef, Data, DeError, Decodable, DecodeContext, DecodeWarning, DynamicValue,
+    KeyedArchive, Lazy, Object, ObjectValue, SkipNulls, UniqueId, ValueRef, derive::Decodable,
+};
 use std::collections::HashMap;
+use std::rc::Rc;
 
 #[derive(Decodable, Debug, PartialEq)]
 struct NSAffineTransform {
@@ -161,3 +168,1057 @@ fn simple_dict_derive() {
     ]);
     assert_eq!(decoded_data, dict);
 }
+
+#[derive(Decodable, Debug, PartialEq)]
+struct View {
+    tag: i64,
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+struct Window {
+    content: Rc<View>,
+    focused: Rc<View>,
+    other: Rc<View>,
+    views: Vec<Rc<View>>,
+}
+
+#[test]
+fn shared_instances() {
+    // -- Window
+    //    -- content -> View (uid 3)  <-|
+    //    -- focused --------------------|
+    //    -- other   -> View (uid 5)
+    //    -- views   -> NSArray [uid 3, uid 5]
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("content", uid_value(3)),
+                    ("focused", uid_value(3)),
+                    ("other", uid_value(5)),
+                    ("views", uid_value(6)),
+                ],
+            ),
+            classes(&["Window", "NSObject"]),
+            object(4, vec![("tag", 1.into())]),
+            classes(&["View", "NSObject"]),
+            object(4, vec![("tag", 1.into())]),
+            object(
+                7,
+                vec![("NS.objects", vec![uid_value(3), uid_value(5)].into())],
+            ),
+            classes(&["NSArray", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let window = Window::decode(&archive.root().unwrap().into()).unwrap();
+
+    assert!(Rc::ptr_eq(&window.content, &window.focused));
+    assert!(!Rc::ptr_eq(&window.content, &window.other));
+    assert_eq!(window.content, window.other);
+    assert!(Rc::ptr_eq(&window.content, &window.views[0]));
+    assert!(Rc::ptr_eq(&window.other, &window.views[1]));
+}
+
+#[derive(Decodable, Debug)]
+#[decodable(rename = "View")]
+struct LazyView {
+    name: String,
+    superview: Option<Lazy<LazyView>>,
+    subviews: Vec<LazyView>,
+}
+
+#[derive(Decodable, Debug)]
+#[decodable(rename = "View")]
+#[allow(dead_code)]
+struct EagerView {
+    name: String,
+    superview: Option<Rc<EagerView>>,
+    subviews: Vec<EagerView>,
+}
+
+#[test]
+fn circular_references() {
+    // -- View "window" <-------|
+    //    -- subviews           |
+    //       -- View "button"   |
+    //          -- superview ---|
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![("name", "window".into()), ("subviews", uid_value(3))],
+            ),
+            classes(&["View", "NSObject"]),
+            object(4, vec![("NS.objects", vec![uid_value(5)].into())]),
+            classes(&["NSArray", "NSObject"]),
+            object(
+                2,
+                vec![
+                    ("name", "button".into()),
+                    ("superview", uid_value(1)),
+                    ("subviews", uid_value(6)),
+                ],
+            ),
+            object(4, vec![("NS.objects", Vec::<plist::Value>::new().into())]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root: ObjectValue = archive.root().unwrap().into();
+
+    let error = EagerView::decode(&root).unwrap_err();
+    assert!(error.to_string().contains("Circular reference"));
+
+    let window = LazyView::decode(&root).unwrap();
+    assert_eq!(window.name, "window");
+    assert!(window.superview.is_none());
+    let button = &window.subviews[0];
+    assert_eq!(button.name, "button");
+    let superview = button.superview.as_ref().unwrap().get().unwrap();
+    assert_eq!(superview.name, "window");
+    assert_eq!(superview.subviews[0].name, "button");
+}
+
+#[derive(Decodable, Debug)]
+#[decodable(rename = "View")]
+struct ConditionalView {
+    name: String,
+    superview: ConditionalRef<ConditionalView>,
+    subviews: Vec<ConditionalView>,
+}
+
+#[test]
+fn conditional_references() {
+    // -- View "window" <-------|
+    //    -- superview: $null   |
+    //    -- subviews           |
+    //       -- View "button"   |
+    //          -- superview ---|
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("name", "window".into()),
+                    ("superview", uid_value(0)),
+                    ("subviews", uid_value(3)),
+                ],
+            ),
+            classes(&["View", "NSObject"]),
+            object(4, vec![("NS.objects", vec![uid_value(5)].into())]),
+            classes(&["NSArray", "NSObject"]),
+            object(
+                2,
+                vec![
+                    ("name", "button".into()),
+                    ("superview", uid_value(1)),
+                    ("subviews", uid_value(6)),
+                ],
+            ),
+            object(4, vec![("NS.objects", Vec::<plist::Value>::new().into())]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root = archive.root().unwrap();
+    let strong_count = Rc::strong_count(&root);
+    let window = archive.decode_root::<ConditionalView>().unwrap();
+    // Weak pointers don't keep values alive
+    assert_eq!(Rc::strong_count(&root), strong_count);
+    assert_eq!(Rc::weak_count(&root), 1);
+    assert_eq!(window.name, "window");
+    assert!(window.superview.is_null());
+    assert!(window.superview.decode().is_none());
+
+    let superview = &window.subviews[0].superview;
+    assert_eq!(superview.unique_id().map(|id| id.get()), Some(1));
+    let decoded = superview.decode().unwrap().unwrap();
+    assert_eq!(decoded.name, "window");
+    assert_eq!(decoded.subviews[0].superview, *superview);
+    assert!(Rc::ptr_eq(&superview.upgrade().unwrap(), &root));
+}
+
+#[derive(Decodable, Debug)]
+#[allow(dead_code)]
+struct Item {
+    name: String,
+}
+
+#[derive(Decodable, Debug)]
+#[allow(dead_code)]
+struct ItemList {
+    items: Vec<Item>,
+}
+
+#[test]
+fn error_path() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("items", uid_value(3))]),
+            classes(&["ItemList", "NSObject"]),
+            object(4, vec![("NS.objects", vec![uid_value(5)].into())]),
+            classes(&["NSArray", "NSObject"]),
+            object(6, vec![("name", uid_value(7))]),
+            classes(&["Item", "NSObject"]),
+            42.into(),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let error = archive.decode_top::<ItemList>("root").unwrap_err();
+    assert_eq!(error.path(), Some("root.items[0].name"));
+    assert_eq!(error.unique_id().unwrap().get(), 7);
+    assert!(matches!(error.inner(), DeError::ExpectedString));
+    assert_eq!(
+        error.to_string(),
+        "root.items[0].name (uid: 7): Expected string"
+    );
+}
+
+#[derive(Decodable, Debug)]
+#[decodable(rename = "Item")]
+struct LossyItem {
+    name: String,
+    subtitle: Option<String>,
+    #[decodable(default)]
+    count: i64,
+}
+
+#[test]
+fn lossy_decoding() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("name", uid_value(3)),
+                    ("subtitle", 42.into()),
+                    ("count", uid_value(3)),
+                ],
+            ),
+            classes(&["Item", "NSObject"]),
+            "Some name".into(),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    assert!(archive.decode_top::<LossyItem>("root").is_err());
+
+    let (item, errors) = archive.decode_top_lossy::<LossyItem>("root").unwrap();
+    assert_eq!(item.name, "Some name");
+    assert_eq!(item.subtitle, None);
+    assert_eq!(item.count, 0);
+    let mut paths: Vec<_> = errors.iter().map(|e| e.path().unwrap()).collect();
+    paths.sort();
+    assert_eq!(paths, vec!["root.count", "root.subtitle"]);
+
+    let root = archive.root().unwrap().into();
+    let (_, errors) = LossyItem::decode_lossy(&root).unwrap();
+    assert_eq!(errors.len(), 2);
+}
+
+struct Scale {
+    factor: f64,
+    scaled: usize,
+}
+
+fn scaled(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<f64, DeError> {
+    let length: f64 = ctx.decode(value)?;
+    let scale = ctx.require_user::<Scale>()?;
+    scale.scaled += 1;
+    Ok(length * scale.factor)
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+#[decodable(context = "Scale")]
+struct Size {
+    #[decodable(with = "scaled")]
+    width: f64,
+    #[decodable(with = "scaled")]
+    height: Option<f64>,
+    #[decodable(with = "scaled", default)]
+    depth: f64,
+}
+
+#[test]
+fn user_context() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("width", 2.0.into()), ("height", 1.5.into())]),
+            classes(&["Size", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+
+    let error = archive.decode_root::<Size>().unwrap_err();
+    assert!(matches!(error.inner(), DeError::MissingContext(_)));
+
+    let mut ctx = archive.decode_context();
+    ctx.insert_user(Scale {
+        factor: 2.0,
+        scaled: 0,
+    });
+    let size: Size = archive.decode_root_with(&mut ctx).unwrap();
+    assert_eq!(
+        size,
+        Size {
+            width: 4.0,
+            height: Some(3.0),
+            depth: 0.0,
+        }
+    );
+    assert_eq!(ctx.user::<Scale>().unwrap().scaled, 2);
+    assert_eq!(ctx.remove_user::<Scale>().unwrap().factor, 2.0);
+    assert!(ctx.user::<Scale>().is_none());
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+#[decodable(rename = "Note")]
+struct DynamicNote {
+    title: String,
+    #[decodable(unhandled = "dynamic")]
+    unhandled: HashMap<String, DynamicValue>,
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+#[decodable(rename = "Note")]
+struct DebugNote {
+    title: String,
+    #[decodable(unhandled = "debug")]
+    unhandled: HashMap<String, String>,
+}
+
+#[test]
+fn unhandled_modes() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("title", uid_value(3)),
+                    ("count", 5.into()),
+                    ("subtitle", uid_value(3)),
+                    ("parent", uid_value(1)),
+                ],
+            ),
+            classes(&["Note", "NSObject"]),
+            "Title".into(),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+
+    let note: DynamicNote = archive.decode_root().unwrap();
+    let debug_note: DebugNote = archive.decode_root().unwrap();
+    drop(archive);
+
+    assert_eq!(note.title, "Title");
+    assert_eq!(note.unhandled.len(), 3);
+    assert_eq!(note.unhandled["count"].as_i64(), Some(5));
+    assert_eq!(note.unhandled["subtitle"].as_str(), Some("Title"));
+    assert_eq!(note.unhandled["parent"].class(), Some("Note"));
+
+    assert_eq!(debug_note.unhandled["subtitle"], "\"Title\" #3");
+    assert!(debug_note.unhandled["parent"].starts_with("Note #1 {"));
+    assert_eq!(debug_note.unhandled.len(), 3);
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+#[decodable(rename = "Note", deny_unknown_fields)]
+struct StrictNote {
+    title: String,
+    #[decodable(rename = "count")]
+    number: Option<i64>,
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+#[decodable(rename = "Note", deny_unknown_fields)]
+struct EmptyNote {}
+
+#[test]
+fn deny_unknown_fields() {
+    let note = |fields| {
+        let plist = archive_plist(
+            vec![("root", 1)],
+            vec![
+                "$null".into(),
+                object(2, fields),
+                classes(&["Note", "NSObject"]),
+            ],
+        );
+        KeyedArchive::from_plist(plist).unwrap()
+    };
+
+    let archive = note(vec![("title", "Title".into()), ("count", 5.into())]);
+    let decoded: StrictNote = archive.decode_root().unwrap();
+    assert_eq!(decoded.number, Some(5));
+
+    let archive = note(vec![("title", "Title".into()), ("author", "Me".into())]);
+    let error = archive.decode_root::<StrictNote>().unwrap_err();
+    assert!(matches!(
+        error.inner(),
+        DeError::UnknownObjectKey(class, key) if class == "Note" && key == "author"
+    ));
+    assert!(archive.decode_root::<EmptyNote>().is_err());
+    assert!(note(vec![]).decode_root::<EmptyNote>().is_ok());
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+enum Shape {
+    Note(StrictNote),
+    ColorSpace(Foo),
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+enum ShapeOrText {
+    Shape(Shape),
+    Text(String),
+}
+
+struct Probing(bool);
+
+impl Decodable for Probing {
+    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
+        DecodeContext::new().decode(value)
+    }
+
+    fn decode_with(_value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        Ok(Self(ctx.is_probing()))
+    }
+}
+
+#[test]
+fn enum_errors() {
+    let plist = archive_plist(
+        vec![("root", 1), ("note", 3)],
+        vec![
+            "$null".into(),
+            object(2, vec![("radius", 5.into())]),
+            classes(&["Circle", "NSObject"]),
+            object(4, vec![("title", "Title".into())]),
+            classes(&["Note", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    assert!(matches!(
+        archive.decode_top::<Shape>("note").unwrap(),
+        Shape::Note(_)
+    ));
+
+    let error = archive.decode_root::<Shape>().unwrap_err();
+    let DeError::Multiple(errors) = error.inner() else {
+        panic!("{error:?}");
+    };
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(
+        errors[0].inner(),
+        DeError::ClassMismatch { expected, found, uid }
+            if expected == &["Note"] && found == &["Circle", "NSObject"] && uid.get() == 1
+    ));
+    assert!(matches!(
+        errors[1].inner(),
+        DeError::ClassMismatch { expected, .. } if expected == &["NSColorSpace"]
+    ));
+    assert_eq!(
+        errors[0].inner().to_string(),
+        "Expected class `Note`, found `Circle : NSObject` (uid: 1)"
+    );
+
+    // Errors of a nested enum are collected as well
+    let error = archive.decode_root::<ShapeOrText>().unwrap_err();
+    let DeError::Multiple(errors) = error.inner() else {
+        panic!("{error:?}");
+    };
+    assert!(matches!(errors[0].inner(), DeError::Multiple(errors) if errors.len() == 2));
+    assert!(
+        error.to_string().contains("found `Circle : NSObject`"),
+        "{error}"
+    );
+
+    let root: ObjectValue = archive.root().unwrap().into();
+    let mut ctx = DecodeContext::new();
+    assert!(ctx.try_decode::<Probing>(&root).unwrap().0);
+    assert!(!ctx.decode::<Probing>(&root).unwrap().0);
+    assert!(!ctx.is_probing());
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+#[decodable(rename = "NSValue")]
+struct PointValue {
+    #[decodable(rename = "NS.pointval")]
+    point: String,
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+#[decodable(rename = "NSValue")]
+struct SizeValue {
+    #[decodable(rename = "NS.sizeval")]
+    size: String,
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+enum SpecialValue {
+    Point(PointValue),
+    #[decodable(requires_keys("NS.sizeval", "NS.special"))]
+    Size(SizeValue),
+    // Keys may contain spaces
+    #[decodable(requires_keys("NS.spaced key"))]
+    Spaced(DynamicValue),
+    // Decodes any object, so it has to be picked by its keys
+    #[decodable(requires_keys("NS.special"))]
+    Other(DynamicValue),
+}
+
+#[test]
+fn variants_by_keys() {
+    let plist = archive_plist(
+        vec![
+            ("point", 1),
+            ("size", 3),
+            ("other", 4),
+            ("broken", 5),
+            ("spaced", 6),
+        ],
+        vec![
+            "$null".into(),
+            object(2, vec![("NS.pointval", "{1, 2}".into())]),
+            classes(&["NSValue", "NSObject"]),
+            object(
+                2,
+                vec![("NS.sizeval", "{3, 4}".into()), ("NS.special", 2.into())],
+            ),
+            object(2, vec![("NS.special", 12.into())]),
+            object(2, vec![("NS.sizeval", "{3, 4}".into())]),
+            object(2, vec![("NS.spaced key", 1.into())]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    assert_eq!(
+        archive.decode_top::<SpecialValue>("point").unwrap(),
+        SpecialValue::Point(PointValue {
+            point: "{1, 2}".into()
+        })
+    );
+    assert_eq!(
+        archive.decode_top::<SpecialValue>("size").unwrap(),
+        SpecialValue::Size(SizeValue {
+            size: "{3, 4}".into()
+        })
+    );
+    assert!(matches!(
+        archive.decode_top::<SpecialValue>("other").unwrap(),
+        SpecialValue::Other(DynamicValue::Object { .. })
+    ));
+    assert!(matches!(
+        archive.decode_top::<SpecialValue>("spaced").unwrap(),
+        SpecialValue::Spaced(DynamicValue::Object { .. })
+    ));
+
+    let error = archive.decode_top::<SpecialValue>("broken").unwrap_err();
+    let DeError::Multiple(errors) = error.inner() else {
+        panic!("{error:?}");
+    };
+    assert_eq!(errors.len(), 4);
+    for (error, expected) in errors[1..]
+        .iter()
+        .zip(["NS.special", "NS.spaced key", "NS.special"])
+    {
+        assert!(matches!(
+            error,
+            DeError::MissingObjectKey(class, key) if class == "NSValue" && key == expected
+        ));
+    }
+}
+
+#[derive(Decodable, Debug, PartialEq, Default)]
+#[decodable(rename = "State")]
+struct StateSnapshot {
+    title: String,
+    tags: Vec<String>,
+    subtitle: Option<String>,
+    #[decodable(default)]
+    count: i64,
+    #[decodable(skip)]
+    cached: String,
+}
+
+#[test]
+fn decode_into() {
+    let state = |title: &str, tags: &[&str], subtitle: Option<&str>| {
+        let mut fields = vec![("title", title.into()), ("tags", uid_value(3))];
+        if let Some(subtitle) = subtitle {
+            fields.push(("subtitle", subtitle.into()));
+        }
+        let mut objects = vec![
+            "$null".into(),
+            object(2, fields),
+            classes(&["State", "NSObject"]),
+            object(
+                4,
+                vec![(
+                    "NS.objects",
+                    (0..tags.len())
+                        .map(|i| uid_value(5 + i as u64))
+                        .collect::<Vec<_>>()
+                        .into(),
+                )],
+            ),
+            classes(&["NSArray", "NSObject"]),
+        ];
+        objects.extend(tags.iter().map(|tag| (*tag).into()));
+        KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap()
+    };
+
+    let first = state("A long first title", &["one", "two", "three"], Some("Sub"));
+    let mut decoded: StateSnapshot = first.decode_root().unwrap();
+    decoded.cached = "Cached".into();
+    let title_ptr = decoded.title.as_ptr();
+    let tags_ptr = decoded.tags.as_ptr();
+    let tag_ptr = decoded.tags[0].as_ptr();
+
+    let second = state("Second", &["four", "five"], None);
+    let root: ObjectValue = second.root().unwrap().into();
+    decoded.decode_into(&root).unwrap();
+    assert_eq!(decoded, second.decode_root::<StateSnapshot>().unwrap());
+    assert_eq!(decoded.title, "Second");
+    assert_eq!(decoded.tags, ["four", "five"]);
+    assert_eq!(decoded.subtitle, None);
+    assert!(decoded.cached.is_empty());
+    // Buffers are reused
+    assert_eq!(decoded.title.as_ptr(), title_ptr);
+    assert_eq!(decoded.tags.as_ptr(), tags_ptr);
+    assert_eq!(decoded.tags[0].as_ptr(), tag_ptr);
+
+    let third = state("Third", &["six", "seven", "eight", "nine"], Some("Sub"));
+    let mut ctx = third.decode_context();
+    let root: ObjectValue = third.root().unwrap().into();
+    ctx.decode_into(&mut decoded, &root).unwrap();
+    assert_eq!(decoded.tags.len(), 4);
+    assert_eq!(decoded.subtitle.as_deref(), Some("Sub"));
+
+    let error = decoded
+        .decode_into(&ObjectValue::Integer(1.into()))
+        .unwrap_err();
+    assert!(matches!(error, DeError::ExpectedObject));
+}
+
+fn is_legacy(obj: &Object) -> bool {
+    obj.contains_key("legacyTitle")
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+struct Collection {
+    #[decodable(skip_if = "is_legacy")]
+    title: String,
+    #[decodable(only_classes("MutableCollection", "GrowingCollection"))]
+    capacity: i64,
+}
+
+#[test]
+fn conditional_fields() {
+    let collection = |class: &[&str], fields| {
+        let plist = archive_plist(
+            vec![("root", 1)],
+            vec!["$null".into(), object(2, fields), classes(class)],
+        );
+        KeyedArchive::from_plist(plist).unwrap()
+    };
+
+    let archive = collection(
+        &["Collection", "MutableCollection", "NSObject"],
+        vec![("title", "Title".into()), ("capacity", 8.into())],
+    );
+    let decoded: Collection = archive.decode_root().unwrap();
+    assert_eq!(
+        decoded,
+        Collection {
+            title: "Title".into(),
+            capacity: 8
+        }
+    );
+
+    // Fields are skipped, even though their keys exist
+    let archive = collection(
+        &["Collection", "NSObject"],
+        vec![("legacyTitle", "Old".into()), ("capacity", 8.into())],
+    );
+    let decoded: Collection = archive.decode_root().unwrap();
+    assert_eq!(decoded.title, "");
+    assert_eq!(decoded.capacity, 0);
+
+    // A field that should be present isn't optional
+    let archive = collection(
+        &["Collection", "GrowingCollection", "NSObject"],
+        vec![("title", "Title".into())],
+    );
+    let error = archive.decode_root::<Collection>().unwrap_err();
+    assert!(matches!(
+        error.inner(),
+        DeError::MissingObjectKey(_, key) if key == "capacity"
+    ));
+
+    let mut decoded = Collection {
+        title: "Title".into(),
+        capacity: 8,
+    };
+    let archive = collection(&["Collection", "NSObject"], vec![("title", "New".into())]);
+    decoded
+        .decode_into(&archive.root().unwrap().into())
+        .unwrap();
+    assert_eq!(decoded.title, "New");
+    assert_eq!(decoded.capacity, 0);
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+struct MyView {
+    title: String,
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+struct NSView {
+    title: String,
+}
+
+#[test]
+fn class_swappers() {
+    // -- NSClassSwapper (MyView, archived as NSView)
+    //    -- title: "Hello"
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("NSClassName", uid_value(3)),
+                ("NSOriginalClassName", uid_value(4)),
+                ("title", uid_value(5)),
+            ],
+        ),
+        classes(&["NSClassSwapper", "NSObject"]),
+        "MyView".into(),
+        "NSView".into(),
+        "Hello".into(),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    let mut ctx = DecodeContext::new();
+    let view: MyView = archive.decode_root_with(&mut ctx).unwrap();
+    assert_eq!(view.title, "Hello");
+    let swapper = ctx.class_swapper(UniqueId::new(1)).unwrap();
+    assert_eq!(swapper.class_name(), "MyView");
+    assert_eq!(swapper.original_class_name(), "NSView");
+    assert!(ctx.class_swapper(UniqueId::new(2)).is_none());
+
+    // The original class matches as well
+    let view: NSView = archive.decode_root().unwrap();
+    assert_eq!(view.title, "Hello");
+    assert!(archive.decode_root::<String>().is_err());
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+struct Settings {
+    flag: bool,
+    count: i64,
+    items: SkipNulls<String>,
+}
+
+#[test]
+fn warnings() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("flag", 1.into()),
+                ("count", 3.0.into()),
+                ("items", uid_value(3)),
+                ("legacy", true.into()),
+            ],
+        ),
+        classes(&["Settings", "NSObject"]),
+        object(
+            4,
+            vec![("NS.objects", vec![uid_value(5), uid_value(0)].into())],
+        ),
+        classes(&["NSArray", "NSObject"]),
+        "first".into(),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    let mut ctx = archive.decode_context();
+    ctx.set_coerce_numeric_bool(true);
+    ctx.set_coerce_real_to_int(true);
+    let settings: Settings = archive.decode_root_with(&mut ctx).unwrap();
+    assert!(settings.flag);
+    assert_eq!(*settings.items, ["first"]);
+    // Warnings aren't collected by default
+    assert!(ctx.take_warnings().is_empty());
+
+    ctx.set_collect_warnings(true);
+    archive.decode_root_with::<Settings>(&mut ctx).unwrap();
+    let warnings = ctx.take_warnings();
+    assert_eq!(
+        warnings,
+        [
+            DecodeWarning::UnknownKey {
+                path: "root".into(),
+                class: "Settings".into(),
+                key: "legacy".into(),
+            },
+            DecodeWarning::NumericCoercion {
+                path: "root.flag".into(),
+                value: "1".into(),
+                ty: "bool",
+            },
+            DecodeWarning::NumericCoercion {
+                path: "root.count".into(),
+                value: "3".into(),
+                ty: "integer",
+            },
+            DecodeWarning::SkippedNull {
+                path: "root.items".into(),
+                index: 1,
+            },
+        ]
+    );
+    assert_eq!(
+        warnings[3].to_string(),
+        "root.items: Skipped a null element at index 1"
+    );
+    assert!(ctx.take_warnings().is_empty());
+
+    // Warnings of failed attempts are discarded: `1` is coerced, but `2` fails
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![("NS.objects", vec![uid_value(3), uid_value(4)].into())],
+        ),
+        classes(&["NSArray", "NSObject"]),
+        1.into(),
+        2.into(),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(vec![("root", 1)], objects)).unwrap();
+    let root = archive.root().unwrap().into();
+    assert!(ctx.try_decode::<Vec<bool>>(&root).is_none());
+    assert!(ctx.take_warnings().is_empty());
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+#[decodable(rename = "Note", case_insensitive, deny_unknown_fields)]
+struct AnyCaseNote {
+    #[decodable(rename = "NSString")]
+    string: String,
+    #[decodable(rename = "NSCount")]
+    count: Option<i64>,
+}
+
+#[test]
+fn case_insensitive_keys() {
+    let note = |fields| {
+        let plist = archive_plist(
+            vec![("root", 1)],
+            vec![
+                "$null".into(),
+                object(2, fields),
+                classes(&["Note", "NSObject"]),
+            ],
+        );
+        KeyedArchive::from_plist(plist).unwrap()
+    };
+
+    let expected = AnyCaseNote {
+        string: "Text".into(),
+        count: Some(2),
+    };
+    let archive = note(vec![("NSString", "Text".into()), ("NSCount", 2.into())]);
+    assert_eq!(archive.decode_root::<AnyCaseNote>().unwrap(), expected);
+    let archive = note(vec![("nsstring", "Text".into()), ("nscount", 2.into())]);
+    assert_eq!(archive.decode_root::<AnyCaseNote>().unwrap(), expected);
+    let root = archive.root().unwrap();
+    let obj = root.as_object().unwrap();
+    assert!(obj.get("NSString").is_none());
+    assert_eq!(
+        obj.get_ignore_case("NSString").unwrap().as_str(),
+        Some("Text")
+    );
+
+    // An exact match wins over other spellings
+    let archive = note(vec![
+        ("nsstring", "Old".into()),
+        ("NSString", "Text".into()),
+    ]);
+    assert_eq!(archive.decode_root::<AnyCaseNote>().unwrap().string, "Text");
+
+    let archive = note(vec![("nsstring", "Text".into()), ("author", "Me".into())]);
+    assert!(archive.decode_root::<AnyCaseNote>().is_err());
+}
+
+fn untitled() -> String {
+    "Untitled".into()
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+#[decodable(rename = "Item")]
+struct ItemWithDefaults {
+    #[decodable(default = "untitled")]
+    name: String,
+    #[decodable(default = "1.5")]
+    scale: f64,
+    #[decodable(default = "Some(3)")]
+    count: Option<i64>,
+    #[decodable(default = "None")]
+    note: Option<String>,
+    #[decodable(default = "i64::MAX")]
+    limit: i64,
+    #[decodable(default = "vec![1, 2]")]
+    sizes: Vec<i64>,
+}
+
+#[test]
+fn default_expressions() {
+    let item = |fields| {
+        let plist = archive_plist(
+            vec![("root", 1)],
+            vec![
+                "$null".into(),
+                object(2, fields),
+                classes(&["Item", "NSObject"]),
+            ],
+        );
+        KeyedArchive::from_plist(plist).unwrap()
+    };
+
+    let decoded: ItemWithDefaults = item(vec![]).decode_root().unwrap();
+    let defaults = ItemWithDefaults {
+        name: "Untitled".into(),
+        scale: 1.5,
+        count: Some(3),
+        note: None,
+        limit: i64::MAX,
+        sizes: vec![1, 2],
+    };
+    assert_eq!(decoded, defaults);
+
+    let archive = item(vec![
+        ("name", "Name".into()),
+        ("scale", 2.0.into()),
+        ("count", 4.into()),
+    ]);
+    let decoded: ItemWithDefaults = archive.decode_root().unwrap();
+    assert_eq!(decoded.name, "Name");
+    assert_eq!(decoded.scale, 2.0);
+    assert_eq!(decoded.count, Some(4));
+
+    // In a lossy mode values that can't be decoded are replaced as well
+    let archive = item(vec![("name", 42.into()), ("scale", "Big".into())]);
+    assert!(archive.decode_root::<ItemWithDefaults>().is_err());
+    let (decoded, errors) = archive
+        .decode_top_lossy::<ItemWithDefaults>("root")
+        .unwrap();
+    assert_eq!(decoded, defaults);
+    assert_eq!(errors.len(), 2);
+}
+
+#[derive(Decodable, Debug, PartialEq)]
+#[decodable(require_parent = "NSControl")]
+struct AnyControl {
+    title: String,
+}
+
+#[test]
+fn class_ancestry() {
+    // -- NSButton : NSControl : NSView : NSResponder : NSObject
+    //    -- title: "OK"
+    // -- NSTextView : NSText : NSView : NSResponder : NSObject
+    //    -- title: "Text"
+    let plist = archive_plist(
+        vec![("button", 1), ("text", 3)],
+        vec![
+            "$null".into(),
+            object(2, vec![("title", "OK".into())]),
+            classes(&["NSButton", "NSControl", "NSView", "NSResponder", "NSObject"]),
+            object(4, vec![("title", "Text".into())]),
+            classes(&["NSTextView", "NSText", "NSView", "NSResponder", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let button = archive.top()["button"].as_object().unwrap();
+    assert_eq!(
+        button.superclasses(),
+        ["NSControl", "NSView", "NSResponder", "NSObject"]
+    );
+    assert!(button.inherits_from("NSButton"));
+    assert!(button.inherits_from("NSResponder"));
+    assert!(!button.inherits_from("NSText"));
+    assert!(!button.matches_class("NSResponder"));
+
+    let control: AnyControl = archive.decode_top("button").unwrap();
+    assert_eq!(control.title, "OK");
+    let error = archive.decode_top::<AnyControl>("text").unwrap_err();
+    assert!(matches!(
+        error.inner(),
+        DeError::ClassMismatch { expected, .. } if expected == &["NSControl"]
+    ));
+}
+
+/// Records a strong count of a referenced value while it's decoded.
+#[derive(Debug, PartialEq)]
+struct StrongCount(usize);
+
+impl Decodable for StrongCount {
+    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
+        match value {
+            ObjectValue::Ref(value) => Ok(Self(Rc::strong_count(value))),
+            _ => Err(DeError::ExpectedObject),
+        }
+    }
+
+    fn decode_ref(value: &ValueRef, _ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        Ok(Self(Rc::strong_count(value)))
+    }
+}
+
+#[derive(Decodable, Debug)]
+struct Shelf {
+    titles: Vec<StrongCount>,
+}
+
+#[test]
+fn borrowed_references() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("titles", uid_value(3)), ("first", uid_value(4))]),
+            classes(&["Shelf", "NSObject"]),
+            object(
+                5,
+                vec![("NS.objects", vec![uid_value(4), uid_value(6)].into())],
+            ),
+            "A".into(),
+            classes(&["NSArray", "NSObject"]),
+            "B".into(),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let count = |uid| {
+        let value = archive.value_by_uid(UniqueId::new(uid)).unwrap();
+        StrongCount(Rc::strong_count(value))
+    };
+
+    // Elements and object fields aren't cloned into new values
+    let shelf: Shelf = archive.decode_root().unwrap();
+    assert_eq!(shelf.titles, vec![count(4), count(6)]);
+    let root = archive.root().unwrap();
+    let first: StrongCount = root.as_object().unwrap().decode_object_as("first").unwrap();
+    assert_eq!(first, count(4));
+}
@@ -0,0 +1,937 @@
+mod common;
+
+use common::{archive_plist, classes, object, uid_value};
+use nskeyedunarchiver::{
+    DeError, Decodable, KeyedArchive, ObjectValue,
+    foundation::{
+        AppleTime, ColorSpace, ComparisonOperator, CompoundKind, NSAttributedString, NSColor,
+        NSDate, NSEdgeInsets, NSExpression, NSLocale, NSPoint, NSPredicate, NSRange, NSRect,
+        NSSize, NSSortDescriptor, NSTextAttachment, NSTimeZone, NSURL, NSURLRequest, NSUUID,
+        NSUserActivity, NSValue, Rgba, UIApplicationShortcutItem,
+    },
+};
+use plist::Value;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const PLIST_PATH: &str = "./tests_resources/plists/";
+
+fn open_root(name: &str) -> ObjectValue {
+    let archive = KeyedArchive::from_file(format!("{PLIST_PATH}{name}")).unwrap();
+    archive.root().unwrap().into()
+}
+
+#[test]
+fn attributed_string() {
+    // -- NSMutableAttributedString: "firstsecondthird"
+    //    -- 0..5  -> NSColor
+    //    -- 5..16 -> no attributes
+    let root = open_root("NSMutableAttributedString.plist");
+    let decoded: NSAttributedString = Decodable::decode(&root).unwrap();
+    assert_eq!(decoded.string(), "firstsecondthird");
+    assert_eq!(decoded.attributes().len(), 2);
+
+    let runs: Vec<_> = decoded.runs().collect();
+    assert_eq!(runs.len(), 2);
+    assert_eq!(runs[0].0, 0..5);
+    assert!(runs[0].1.contains_key("NSColor"));
+    assert_eq!(runs[1].0, 5..16);
+    assert!(runs[1].1.is_empty());
+
+    assert_eq!(decoded.substring(runs[0].0.clone()), Some("first"));
+    assert_eq!(decoded.substring(runs[1].0.clone()), Some("secondthird"));
+    assert_eq!(decoded.substring(10..20), None);
+
+    // Run lengths overflowing usize
+    let mut attribute_info = vec![0xff; 9];
+    attribute_info.extend([0x01, 0x00, 0x01, 0x00]);
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("NSString", uid_value(3)),
+                    ("NSAttributes", uid_value(4)),
+                    ("NSAttributeInfo", Value::Data(attribute_info)),
+                ],
+            ),
+            classes(&["NSAttributedString", "NSObject"]),
+            "text".into(),
+            object(5, vec![("NS.objects", Value::Array(vec![uid_value(6)]))]),
+            classes(&["NSArray", "NSObject"]),
+            object(
+                7,
+                vec![
+                    ("NS.keys", Value::Array(vec![])),
+                    ("NS.objects", Value::Array(vec![])),
+                ],
+            ),
+            classes(&["NSDictionary", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    assert!(archive.decode_root::<NSAttributedString>().is_err());
+
+    // A broken string points at its key
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("NSString", 12.into())]),
+            classes(&["NSAttributedString", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let err = archive.decode_root::<NSAttributedString>().unwrap_err();
+    assert_eq!(err.path(), Some("root.NSString"));
+}
+
+#[test]
+fn text_attachments() {
+    // -- NSAttributedString: "a\u{FFFC}"
+    //    -- 0..1 -> no attributes
+    //    -- 1..2 -> NSAttachment: NSTextAttachment (an inline PNG)
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("NSString", uid_value(3)),
+                    ("NSAttributes", uid_value(4)),
+                    ("NSAttributeInfo", Value::Data(vec![1, 0, 1, 1])),
+                ],
+            ),
+            classes(&["NSAttributedString", "NSObject"]),
+            "a\u{FFFC}".into(),
+            object(
+                5,
+                vec![("NS.objects", vec![uid_value(6), uid_value(8)].into())],
+            ),
+            classes(&["NSArray", "NSObject"]),
+            object(
+                7,
+                vec![
+                    ("NS.keys", Value::Array(vec![])),
+                    ("NS.objects", Value::Array(vec![])),
+                ],
+            ),
+            classes(&["NSDictionary", "NSObject"]),
+            object(
+                7,
+                vec![
+                    ("NS.keys", vec![uid_value(9)].into()),
+                    ("NS.objects", vec![uid_value(10)].into()),
+                ],
+            ),
+            "NSAttachment".into(),
+            object(
+                11,
+                vec![
+                    ("NSContents", Value::Data(b"\x89PNG".to_vec())),
+                    ("NSFileType", uid_value(12)),
+                    ("NSBounds", uid_value(13)),
+                    ("NSFileWrapper", uid_value(14)),
+                ],
+            ),
+            classes(&["NSTextAttachment", "NSObject"]),
+            "public.png".into(),
+            "{{0, -2}, {16, 16}}".into(),
+            object(
+                15,
+                vec![(
+                    "NSFileWrapperSerializedRepresentation",
+                    Value::Data(b"rtfd".to_vec()),
+                )],
+            ),
+            classes(&["NSFileWrapper", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let string: NSAttributedString = archive.decode_root().unwrap();
+    let attachments = string.attachments().unwrap();
+    assert_eq!(attachments.len(), 1);
+    let (range, attachment) = &attachments[0];
+    assert_eq!(*range, 1..2);
+    assert_eq!(attachment.contents().unwrap().as_ref(), b"\x89PNG");
+    assert_eq!(attachment.file_type(), Some("public.png"));
+    assert_eq!(attachment.bounds().unwrap().origin.y, -2.0);
+    assert_eq!(
+        attachment
+            .file_wrapper()
+            .unwrap()
+            .serialized_representation(),
+        b"rtfd"
+    );
+    assert!(attachment.image().is_none());
+
+    let attachment = archive.get(10).unwrap();
+    assert!(NSTextAttachment::decode(&attachment.into()).is_ok());
+}
+
+#[test]
+fn geometry() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("point", "{3, 4}".into()),
+                    ("size", "{10.5, 20}".into()),
+                    ("rect", "{{1, 2}, {3, 4}}".into()),
+                    ("pointValue", uid_value(3)),
+                    ("rangeValue", uid_value(5)),
+                    ("xy", uid_value(6)),
+                    ("broken", "{1, 2".into()),
+                ],
+            ),
+            classes(&["Shape", "NSObject"]),
+            object(
+                4,
+                vec![
+                    ("NS.special", 1.into()),
+                    ("NS.pointval", "{5.5, -6}".into()),
+                ],
+            ),
+            classes(&["NSValue", "NSObject"]),
+            object(
+                4,
+                vec![
+                    ("NS.special", 4.into()),
+                    ("NS.rangeval.location", 2.into()),
+                    ("NS.rangeval.length", 5.into()),
+                ],
+            ),
+            object(7, vec![("x", 1.into()), ("y", 2.5.into())]),
+            classes(&["Coordinate", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root = archive.root().unwrap();
+    let fields = root.as_object().unwrap().as_map();
+
+    assert_eq!(
+        NSPoint::decode(&fields["point"]).unwrap(),
+        NSPoint { x: 3.0, y: 4.0 }
+    );
+    assert_eq!(
+        NSSize::decode(&fields["size"]).unwrap(),
+        NSSize {
+            width: 10.5,
+            height: 20.0
+        }
+    );
+    assert_eq!(
+        NSRect::decode(&fields["rect"]).unwrap(),
+        NSRect {
+            origin: NSPoint { x: 1.0, y: 2.0 },
+            size: NSSize {
+                width: 3.0,
+                height: 4.0
+            }
+        }
+    );
+    assert_eq!(
+        NSPoint::decode(&fields["pointValue"]).unwrap(),
+        NSPoint { x: 5.5, y: -6.0 }
+    );
+    assert_eq!(
+        NSRange::decode(&fields["rangeValue"]).unwrap(),
+        NSRange {
+            location: 2,
+            length: 5
+        }
+    );
+    assert_eq!(
+        NSPoint::decode(&fields["xy"]).unwrap(),
+        NSPoint { x: 1.0, y: 2.5 }
+    );
+    assert!(NSPoint::decode(&fields["broken"]).is_err());
+    assert!(NSRect::decode(&fields["point"]).is_err());
+    assert_eq!("{2, 3}".parse::<NSRange>().unwrap().length, 3);
+    assert!("{".repeat(100_000).parse::<NSRect>().is_err());
+}
+
+#[test]
+fn special_values() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![(
+                    "NS.objects",
+                    Value::Array(vec![uid_value(3), uid_value(4), uid_value(5), uid_value(6)]),
+                )],
+            ),
+            classes(&["NSArray", "NSObject"]),
+            object(
+                7,
+                vec![
+                    ("NS.special", 3.into()),
+                    ("NS.rectval", "{{0, 0}, {320, 480}}".into()),
+                ],
+            ),
+            object(
+                7,
+                vec![
+                    ("NS.special", 12.into()),
+                    ("NS.edgeval.top", 20.into()),
+                    ("NS.edgeval.left", 0.into()),
+                    ("NS.edgeval.bottom", 34.5.into()),
+                    ("NS.edgeval.right", 0.into()),
+                ],
+            ),
+            object(7, vec![("NS.special", 6.into())]),
+            object(7, vec![("NS.pointval", "{1, 2}".into())]),
+            classes(&["NSValue", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root = archive.root().unwrap();
+    let values = root.as_object().unwrap().as_map()["NS.objects"]
+        .as_ref_array()
+        .unwrap();
+    let decode = |i: usize| NSValue::decode(&ObjectValue::Ref(values[i].clone()));
+
+    let rect = decode(0).unwrap();
+    assert_eq!(rect.special(), 3);
+    assert_eq!(
+        rect,
+        NSValue::Rect(NSRect {
+            origin: NSPoint::default(),
+            size: NSSize {
+                width: 320.0,
+                height: 480.0
+            }
+        })
+    );
+    assert_eq!(
+        decode(1).unwrap(),
+        NSValue::EdgeInsets(NSEdgeInsets {
+            top: 20.0,
+            left: 0.0,
+            bottom: 34.5,
+            right: 0.0
+        })
+    );
+    let err = decode(2).unwrap_err();
+    assert!(err.to_string().contains("special type 6"), "{err}");
+    assert!(decode(3).is_err());
+    assert_eq!("{1, 2, 3, 4}".parse::<NSEdgeInsets>().unwrap().right, 4.0);
+
+    // Errors of nested values point at a failed field
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![("NS.special", 12.into()), ("NS.edgeval.top", "wide".into())],
+            ),
+            classes(&["NSValue", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let err = archive.decode_root::<NSValue>().unwrap_err();
+    assert_eq!(err.path(), Some("root.NS.edgeval.top"));
+    assert!(matches!(err.inner(), DeError::ExpectedFloat));
+}
+
+#[test]
+fn color() {
+    let root = open_root("NSMutableAttributedString.plist");
+    let decoded: NSAttributedString<HashMap<String, NSColor>> = Decodable::decode(&root).unwrap();
+    let color = &decoded.attributes()[0]["NSColor"];
+    assert_eq!(color.color_space(), &ColorSpace::CalibratedRgb);
+    assert_eq!(color.components(), &[1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(color.custom_color_space_id(), Some(7));
+    assert_eq!(
+        color.rgba(),
+        Some(Rgba {
+            red: 0.9859541655,
+            green: 0.0,
+            blue: 0.02694000863,
+            alpha: 1.0
+        })
+    );
+
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("white", uid_value(3)),
+                    ("catalog", uid_value(5)),
+                    ("uikit", uid_value(6)),
+                ],
+            ),
+            classes(&["Palette", "NSObject"]),
+            object(
+                4,
+                vec![
+                    ("NSColorSpace", 3.into()),
+                    ("NSWhite", plist::Value::Data(b"0.5 0.25\0".to_vec())),
+                ],
+            ),
+            classes(&["NSColor", "NSObject"]),
+            object(
+                4,
+                vec![
+                    ("NSColorSpace", 6.into()),
+                    ("NSCatalogName", "System".into()),
+                    ("NSColorName", "textColor".into()),
+                    ("NSColor", uid_value(3)),
+                ],
+            ),
+            object(
+                7,
+                vec![
+                    ("UIRed", 1.0.into()),
+                    ("UIGreen", 0.5.into()),
+                    ("UIBlue", 0.0.into()),
+                ],
+            ),
+            classes(&["UIColor", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root = archive.root().unwrap();
+    let fields = root.as_object().unwrap().as_map();
+
+    let gray = Rgba {
+        red: 0.5,
+        green: 0.5,
+        blue: 0.5,
+        alpha: 0.25,
+    };
+    let white = NSColor::decode(&fields["white"]).unwrap();
+    assert_eq!(white.color_space(), &ColorSpace::CalibratedWhite);
+    assert_eq!(white.rgba(), Some(gray));
+
+    let catalog = NSColor::decode(&fields["catalog"]).unwrap();
+    assert_eq!(
+        catalog.color_space(),
+        &ColorSpace::Catalog {
+            catalog: "System".into(),
+            name: "textColor".into()
+        }
+    );
+    assert_eq!(catalog.rgba(), Some(gray));
+
+    let uikit = NSColor::decode(&fields["uikit"]).unwrap();
+    assert_eq!(uikit.color_space(), &ColorSpace::DeviceRgb);
+    assert_eq!(
+        uikit.rgba(),
+        Some(Rgba {
+            red: 1.0,
+            green: 0.5,
+            blue: 0.0,
+            alpha: 1.0
+        })
+    );
+
+    // A catalog color falling back to itself
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("NSColorSpace", 6.into()),
+                    ("NSCatalogName", "System".into()),
+                    ("NSColorName", "textColor".into()),
+                    ("NSColor", uid_value(1)),
+                ],
+            ),
+            classes(&["NSColor", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let err = archive.decode_root::<NSColor>().unwrap_err();
+    assert!(
+        matches!(err.inner(), DeError::CircularReference(_)),
+        "{err}"
+    );
+}
+
+#[test]
+fn predicate() {
+    // name ==[c] "Notes" AND count > 2
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("NSCompoundPredicateType", 1.into()),
+                    ("NSSubpredicates", uid_value(3)),
+                ],
+            ),
+            classes(&["NSCompoundPredicate", "NSPredicate", "NSObject"]),
+            object(
+                4,
+                vec![(
+                    "NS.objects",
+                    Value::Array(vec![uid_value(5), uid_value(14)]),
+                )],
+            ),
+            classes(&["NSArray", "NSObject"]),
+            object(
+                6,
+                vec![
+                    ("NSLeftExpression", uid_value(7)),
+                    ("NSRightExpression", uid_value(11)),
+                    ("NSPredicateOperator", uid_value(12)),
+                ],
+            ),
+            classes(&["NSComparisonPredicate", "NSPredicate", "NSObject"]),
+            object(
+                8,
+                vec![
+                    ("NSExpressionType", 3.into()),
+                    ("NSSelectorName", "valueForKeyPath:".into()),
+                    ("NSOperand", uid_value(9)),
+                    ("NSArguments", uid_value(17)),
+                ],
+            ),
+            classes(&[
+                "NSKeyPathExpression",
+                "NSFunctionExpression",
+                "NSExpression",
+                "NSObject",
+            ]),
+            object(10, vec![("NSExpressionType", 1.into())]),
+            classes(&["NSSelfExpression", "NSExpression", "NSObject"]),
+            object(
+                16,
+                vec![
+                    ("NSExpressionType", 0.into()),
+                    ("NSConstantValue", "Notes".into()),
+                ],
+            ),
+            object(
+                13,
+                vec![("NSOperatorType", 4.into()), ("NSFlags", 1.into())],
+            ),
+            classes(&[
+                "NSEqualityPredicateOperator",
+                "NSPredicateOperator",
+                "NSObject",
+            ]),
+            object(
+                6,
+                vec![
+                    ("NSLeftExpression", uid_value(15)),
+                    ("NSRightExpression", uid_value(19)),
+                    ("NSPredicateOperator", uid_value(20)),
+                ],
+            ),
+            object(
+                21,
+                vec![
+                    ("NSExpressionType", 3.into()),
+                    ("NSKeyPath", "count".into()),
+                ],
+            ),
+            classes(&["NSConstantValueExpression", "NSExpression", "NSObject"]),
+            object(4, vec![("NS.objects", Value::Array(vec![uid_value(18)]))]),
+            object(
+                16,
+                vec![
+                    ("NSExpressionType", 0.into()),
+                    ("NSConstantValue", "name".into()),
+                ],
+            ),
+            object(
+                16,
+                vec![
+                    ("NSExpressionType", 0.into()),
+                    ("NSConstantValue", 2.into()),
+                ],
+            ),
+            object(
+                22,
+                vec![("NSOperatorType", 2.into()), ("NSModifier", 0.into())],
+            ),
+            classes(&["NSKeyPathSpecifierExpression", "NSExpression", "NSObject"]),
+            classes(&[
+                "NSComparisonPredicateOperator",
+                "NSPredicateOperator",
+                "NSObject",
+            ]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let predicate: NSPredicate = archive.decode_top("root").unwrap();
+    let NSPredicate::Compound {
+        kind,
+        subpredicates,
+    } = &predicate
+    else {
+        panic!("Expected a compound predicate");
+    };
+    assert_eq!(*kind, CompoundKind::And);
+    assert!(matches!(
+        &subpredicates[0],
+        NSPredicate::Comparison {
+            left: NSExpression::KeyPath(path),
+            operator: ComparisonOperator::EqualTo,
+            options: NSPredicate::CASE_INSENSITIVE,
+            ..
+        } if path == "name"
+    ));
+    assert_eq!(predicate.to_string(), r#"name ==[c] "Notes" AND count > 2"#);
+}
+
+#[test]
+fn bookmark_types() {
+    let uuid: Vec<u8> = (0..16).collect();
+    let plist = archive_plist(
+        vec![("url", 1), ("uuid", 5), ("date", 7), ("sort", 9)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![("NS.base", uid_value(3)), ("NS.relative", "notes/1".into())],
+            ),
+            classes(&["NSURL", "NSObject"]),
+            object(
+                2,
+                vec![("NS.base", uid_value(0)), ("NS.relative", uid_value(4))],
+            ),
+            "https://example.com/".into(),
+            object(6, vec![("NS.uuidbytes", Value::Data(uuid))]),
+            classes(&["NSUUID", "NSObject"]),
+            object(8, vec![("NS.time", 86400.5.into())]),
+            classes(&["NSDate", "NSObject"]),
+            object(
+                10,
+                vec![
+                    ("NSKey", "title".into()),
+                    ("NSAscending", false.into()),
+                    ("NSSelector", "localizedCompare:".into()),
+                ],
+            ),
+            classes(&["NSSortDescriptor", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+
+    let url: NSURL = archive.decode_top("url").unwrap();
+    assert_eq!(url.relative(), "notes/1");
+    assert_eq!(url.base().unwrap().relative(), "https://example.com/");
+    assert_eq!(url.absolute(), "https://example.com/notes/1");
+
+    let uuid: NSUUID = archive.decode_top("uuid").unwrap();
+    assert_eq!(uuid.to_string(), "00010203-0405-0607-0809-0A0B0C0D0E0F");
+
+    let date: NSDate = archive.decode_top("date").unwrap();
+    assert_eq!(date.time_interval_since_1970(), 978_393_600.5);
+    assert!(date.to_system_time().is_some());
+
+    let sort: NSSortDescriptor = archive.decode_top("sort").unwrap();
+    assert_eq!(sort.key(), Some("title"));
+    assert!(!sort.ascending());
+    assert_eq!(sort.selector(), Some("localizedCompare:"));
+}
+
+#[test]
+fn file_paths() {
+    let plist = archive_plist(
+        vec![
+            ("url", 1),
+            ("web", 5),
+            ("string", 6),
+            ("plain", 7),
+            ("loop", 8),
+        ],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("NS.base", uid_value(3)),
+                    ("NS.relative", "My%20Notes/%C3%A9t%C3%A9.txt".into()),
+                ],
+            ),
+            classes(&["NSURL", "NSObject"]),
+            object(
+                2,
+                vec![("NS.base", uid_value(0)), ("NS.relative", uid_value(4))],
+            ),
+            "file:///Users/me/".into(),
+            object(
+                2,
+                vec![
+                    ("NS.base", uid_value(0)),
+                    ("NS.relative", "https://example.com/a%20b".into()),
+                ],
+            ),
+            "file://localhost/tmp/100%25".into(),
+            "/tmp/100%25".into(),
+            // A URL based on itself
+            object(
+                2,
+                vec![("NS.base", uid_value(8)), ("NS.relative", "a".into())],
+            ),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+
+    let path: PathBuf = archive.decode_top("url").unwrap();
+    assert_eq!(path, Path::new("/Users/me/My Notes/été.txt"));
+    let url: NSURL = archive.decode_top("url").unwrap();
+    assert_eq!(url.to_file_path(), Some(path));
+    let web: NSURL = archive.decode_top("web").unwrap();
+    assert_eq!(web.to_file_path(), None);
+    assert!(archive.decode_top::<PathBuf>("web").is_err());
+
+    // Strings are decoded as URLs only if they look like ones
+    let string: PathBuf = archive.decode_top("string").unwrap();
+    assert_eq!(string, Path::new("/tmp/100%"));
+    let plain: OsString = archive.decode_top("plain").unwrap();
+    assert_eq!(plain, "/tmp/100%25");
+
+    let err = archive.decode_top::<NSURL>("loop").unwrap_err();
+    assert!(
+        matches!(err.inner(), DeError::CircularReference(_)),
+        "{err}"
+    );
+    let err = archive.decode_top::<PathBuf>("loop").unwrap_err();
+    assert!(
+        matches!(err.inner(), DeError::CircularReference(_)),
+        "{err}"
+    );
+}
+
+#[test]
+fn locales_and_time_zones() {
+    let tzif = b"TZif2".to_vec();
+    let plist = archive_plist(
+        vec![("locale", 1), ("zone", 4), ("utc", 6)],
+        vec![
+            "$null".into(),
+            object(2, vec![("NS.identifier", uid_value(3))]),
+            classes(&["NSLocale", "NSObject"]),
+            "de_DE".into(),
+            object(
+                5,
+                vec![
+                    ("NS.name", "Europe/Berlin".into()),
+                    ("NS.data", Value::Data(tzif.clone())),
+                ],
+            ),
+            classes(&["NSTimeZone", "NSObject"]),
+            object(5, vec![("NS.name", "GMT".into())]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+
+    let locale: NSLocale = archive.decode_top("locale").unwrap();
+    assert_eq!(locale.identifier(), "de_DE");
+
+    let zone: NSTimeZone = archive.decode_top("zone").unwrap();
+    assert_eq!(zone.name(), "Europe/Berlin");
+    assert_eq!(zone.data(), Some(tzif.as_slice()));
+    let utc: NSTimeZone = archive.decode_top("utc").unwrap();
+    assert_eq!(utc.name(), "GMT");
+    assert_eq!(utc.data(), None);
+    assert!(archive.decode_top::<NSLocale>("zone").is_err());
+}
+
+#[test]
+fn plain_dates() {
+    // 1 minute after 1 January 2001
+    let created = SystemTime::UNIX_EPOCH + Duration::from_secs(978_307_260);
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("created", Value::Date(created.into())),
+                    ("modified", uid_value(3)),
+                ],
+            ),
+            classes(&["Note", "NSObject"]),
+            object(4, vec![("NS.time", 120.0.into())]),
+            classes(&["NSDate", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root = archive.root().unwrap();
+    let obj = root.as_object().unwrap();
+    let fields = obj.as_map();
+
+    assert_eq!(
+        SystemTime::from(obj.decode_date("created").unwrap()),
+        created
+    );
+    let date = NSDate::decode(&fields["created"]).unwrap();
+    assert_eq!(date.time_interval_since_reference_date(), 60.0);
+    assert_eq!(SystemTime::decode(&fields["created"]).unwrap(), created);
+    assert_eq!(
+        SystemTime::decode(&fields["modified"]).unwrap(),
+        created + Duration::from_secs(60)
+    );
+    assert!(obj.decode_date("modified").is_err());
+    assert!(root.to_string().contains("created: 2001-01-01T00:01:00Z"));
+}
+
+#[test]
+fn app_payloads() {
+    let plist = archive_plist(
+        vec![("activity", 1), ("shortcut", 7), ("request", 9)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("activityType", "com.example.notes.viewing".into()),
+                    ("title", "Groceries".into()),
+                    ("webpageURL", uid_value(3)),
+                    ("persistentIdentifier", uid_value(0)),
+                    ("userInfo", uid_value(5)),
+                ],
+            ),
+            classes(&["NSUserActivity", "NSObject"]),
+            object(
+                4,
+                vec![
+                    ("NS.base", uid_value(0)),
+                    ("NS.relative", "https://example.com/notes/1".into()),
+                ],
+            ),
+            classes(&["NSURL", "NSObject"]),
+            object(
+                6,
+                vec![
+                    ("NS.keys", Value::Array(vec![uid_value(12)])),
+                    ("NS.objects", Value::Array(vec![uid_value(13)])),
+                ],
+            ),
+            classes(&["NSDictionary", "NSObject"]),
+            object(
+                8,
+                vec![
+                    ("type", "com.example.notes.new".into()),
+                    ("localizedTitle", "New Note".into()),
+                ],
+            ),
+            classes(&["UIApplicationShortcutItem", "NSObject"]),
+            object(
+                10,
+                vec![
+                    ("NS.url", uid_value(3)),
+                    ("NS.httpMethod", "POST".into()),
+                    ("NS.allHTTPHeaderFields", uid_value(11)),
+                    ("NS.httpBody", Value::Data(b"{}".to_vec())),
+                ],
+            ),
+            classes(&["NSMutableURLRequest", "NSURLRequest", "NSObject"]),
+            object(
+                6,
+                vec![
+                    ("NS.keys", Value::Array(vec![uid_value(14)])),
+                    ("NS.objects", Value::Array(vec![uid_value(15)])),
+                ],
+            ),
+            "id".into(),
+            42.into(),
+            "Content-Type".into(),
+            "application/json".into(),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+
+    let activity: NSUserActivity = archive.decode_top("activity").unwrap();
+    assert_eq!(activity.activity_type(), "com.example.notes.viewing");
+    assert_eq!(activity.title(), Some("Groceries"));
+    assert_eq!(
+        activity.webpage_url().unwrap().absolute(),
+        "https://example.com/notes/1"
+    );
+    assert_eq!(activity.persistent_identifier(), None);
+    assert_eq!(activity.user_info()["id"].as_i64(), Some(42));
+
+    let shortcut: UIApplicationShortcutItem = archive.decode_top("shortcut").unwrap();
+    assert_eq!(shortcut.item_type(), "com.example.notes.new");
+    assert_eq!(shortcut.localized_title(), "New Note");
+    assert_eq!(shortcut.localized_subtitle(), None);
+    assert!(shortcut.user_info().is_empty());
+
+    let request: NSURLRequest = archive.decode_top("request").unwrap();
+    assert_eq!(
+        request.url().unwrap().absolute(),
+        "https://example.com/notes/1"
+    );
+    assert_eq!(request.method(), "POST");
+    assert_eq!(request.header("content-type"), Some("application/json"));
+    assert_eq!(request.body(), Some(&b"{}"[..]));
+    assert_eq!(request.timeout_interval(), 60.0);
+
+    // Required fields
+    assert!(archive.decode_top::<NSUserActivity>("shortcut").is_err());
+}
+
+#[test]
+fn apple_timestamps() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("created", 60.25.into()),
+                    ("modified", 120.into()),
+                    ("date", uid_value(3)),
+                    ("name", "Note".into()),
+                ],
+            ),
+            classes(&["Note", "NSObject"]),
+            object(4, vec![("NS.time", 30.0.into())]),
+            classes(&["NSDate", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root = archive.root().unwrap();
+    let fields = root.as_object().unwrap().as_map();
+
+    let created = AppleTime::decode(&fields["created"]).unwrap();
+    assert_eq!(created.seconds_since_reference_date(), 60.25);
+    assert_eq!(created.seconds_since_1970(), 978_307_260.25);
+    assert_eq!(
+        created.to_system_time(),
+        Some(SystemTime::UNIX_EPOCH + Duration::from_millis(978_307_260_250))
+    );
+    assert_eq!(fields["created"].as_apple_timestamp(), Some(created));
+
+    let modified = AppleTime::decode(&fields["modified"]).unwrap();
+    assert_eq!(modified, AppleTime::from_seconds(120.0));
+    assert!(created < modified);
+
+    let date = AppleTime::decode(&fields["date"]).unwrap();
+    assert_eq!(date.seconds_since_reference_date(), 30.0);
+    assert_eq!(
+        NSDate::from(date).time_interval_since_reference_date(),
+        30.0
+    );
+
+    assert!(fields["name"].as_apple_timestamp().is_none());
+    assert!(AppleTime::decode(&fields["name"]).is_err());
+}
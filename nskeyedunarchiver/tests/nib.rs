@@ -0,0 +1,157 @@
+mod common;
+
+use common::{archive_plist, classes, object, uid_value};
+use nskeyedunarchiver::{
+    Decodable, KeyedArchive,
+    nib::{ConnectionKind, NSClassSwapper, NibObjectData, effective_class},
+};
+use plist::Value;
+
+fn array(class_uid: u64, uids: &[u64]) -> Value {
+    let items = uids.iter().map(|uid| uid_value(*uid)).collect();
+    object(class_uid, vec![("NS.objects", Value::Array(items))])
+}
+
+#[test]
+fn appkit_nib() {
+    let objects = vec![
+        "$null".into(),
+        object(
+            2,
+            vec![
+                ("NSRoot", uid_value(3)),
+                ("NSObjectsKeys", uid_value(5)),
+                ("NSObjectsValues", uid_value(6)),
+                ("NSNamesKeys", uid_value(7)),
+                ("NSNamesValues", uid_value(8)),
+                ("NSConnections", uid_value(9)),
+                ("NSVisibleWindows", uid_value(10)),
+                ("NSOidsKeys", uid_value(5)),
+                ("NSOidsValues", uid_value(16)),
+            ],
+        ),
+        classes(&["NSIBObjectData", "NSObject"]),
+        object(11, vec![("NSClassName", uid_value(12))]),
+        object(
+            13,
+            vec![
+                ("NSClassName", uid_value(14)),
+                ("NSOriginalClassName", uid_value(15)),
+            ],
+        ),
+        array(23, &[3, 4]),
+        array(23, &[0, 3]),
+        array(23, &[3]),
+        array(23, &[17]),
+        array(23, &[18]),
+        array(24, &[4]),
+        classes(&["NSCustomObject", "NSObject"]),
+        "NSApplication".into(),
+        classes(&["NSClassSwapper", "NSObject"]),
+        "MyWindow".into(),
+        "NSWindow".into(),
+        array(23, &[19, 20]),
+        "File's Owner".into(),
+        object(
+            21,
+            vec![
+                ("NSSource", uid_value(3)),
+                ("NSDestination", uid_value(4)),
+                ("NSLabel", uid_value(22)),
+            ],
+        ),
+        1.into(),
+        2.into(),
+        classes(&["NSNibOutletConnector", "NSNibConnector", "NSObject"]),
+        "mainWindow".into(),
+        classes(&["NSArray", "NSObject"]),
+        classes(&["NSSet", "NSObject"]),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(
+        vec![("IB.objectdata", 1), ("IB.systemFontUpdateVersion", 19)],
+        objects,
+    ))
+    .unwrap();
+    let nib = NibObjectData::from_archive(&archive).unwrap();
+
+    assert_eq!(nib.root().unwrap().unique_id().get(), 3);
+    assert_eq!(nib.objects().len(), 2);
+    let owner = &nib.objects()[0];
+    assert_eq!(owner.name(), Some("File's Owner"));
+    assert_eq!(owner.oid(), Some(1));
+    assert!(owner.parent().is_none());
+    let window = &nib.objects()[1];
+    assert_eq!(window.parent().unwrap().unique_id().get(), 3);
+    assert_eq!(window.oid(), Some(2));
+    assert_eq!(nib.top_level_objects().len(), 1);
+
+    assert_eq!(effective_class(window.object()), Some("MyWindow"));
+    let swapper = NSClassSwapper::decode(&window.object().into()).unwrap();
+    assert_eq!(swapper.class_name(), "MyWindow");
+    assert_eq!(swapper.original_class_name(), "NSWindow");
+
+    let connection = &nib.connections()[0];
+    assert_eq!(connection.kind(), ConnectionKind::Outlet);
+    assert_eq!(connection.label(), Some("mainWindow"));
+    assert_eq!(connection.source().unwrap().unique_id().get(), 3);
+    assert_eq!(connection.destination().unwrap().unique_id().get(), 4);
+    assert_eq!(nib.visible_windows()[0].unique_id().get(), 4);
+}
+
+#[test]
+fn uikit_nib() {
+    let objects = vec![
+        "$null".into(),
+        array(2, &[4, 5]),
+        classes(&["NSArray", "NSObject"]),
+        array(2, &[6]),
+        object(7, vec![]),
+        object(8, vec![]),
+        object(
+            9,
+            vec![
+                ("UISource", uid_value(4)),
+                ("UIDestination", uid_value(5)),
+                ("UILabel", uid_value(10)),
+                ("UIEventMask", 64.into()),
+            ],
+        ),
+        classes(&["UIProxyObject", "NSObject"]),
+        classes(&["UIButton", "UIControl", "UIView", "NSObject"]),
+        classes(&[
+            "UIRuntimeEventConnection",
+            "UIRuntimeConnection",
+            "NSObject",
+        ]),
+        "buttonTapped:".into(),
+    ];
+    let archive = KeyedArchive::from_plist(archive_plist(
+        vec![
+            ("UINibObjectsKey", 1),
+            ("UINibTopLevelObjectsKey", 1),
+            ("UINibConnectionsKey", 3),
+        ],
+        objects,
+    ))
+    .unwrap();
+    let nib = NibObjectData::from_archive(&archive).unwrap();
+
+    assert!(nib.root().is_none());
+    assert_eq!(nib.objects().len(), 2);
+    assert_eq!(nib.top_level_objects().len(), 2);
+    assert!(nib.visible_windows().is_empty());
+    let connection = &nib.connections()[0];
+    assert_eq!(connection.kind(), ConnectionKind::Action);
+    assert_eq!(connection.label(), Some("buttonTapped:"));
+    assert_eq!(
+        effective_class(connection.destination().unwrap()),
+        Some("UIButton")
+    );
+
+    let not_nib = KeyedArchive::from_plist(archive_plist(
+        vec![("root", 1)],
+        vec!["$null".into(), "string".into()],
+    ))
+    .unwrap();
+    assert!(NibObjectData::from_archive(&not_nib).is_err());
+}
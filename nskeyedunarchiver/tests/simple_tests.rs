@@ -1,7 +1,11 @@
-use std::collections::HashMap;
+mod common;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::num::{NonZeroI32, NonZeroU8, NonZeroU16};
 use std::rc::{Rc, Weak};
 
-use nskeyedunarchiver::{ArchiveValue, DeError, ObjectValue, ValueRef, Data, Decodable, KeyedArchive};
+use common::{archive_plist, classes, object, uid_value};
+use nskeyedunarchiver::{ArchiveValue, DeError, ObjectValue, ValueRef, Data, DecodeContext, Decodable, KeyedArchive, SkipNulls};
 
 const PLIST_PATH: &str = "./tests_resources/plists/";
 
@@ -10,7 +14,7 @@ fn open_file(name: &str) -> (ValueRef, Vec<Weak<ArchiveValue>>) {
     let weak_refs: Vec<Weak<ArchiveValue>> = archive
         .values()
         .iter()
-        .map(|v| Rc::downgrade(v))
+        .map(Rc::downgrade)
         .collect();
     (archive.root().unwrap(), weak_refs)
 }
@@ -34,6 +38,80 @@ fn plain_string() {
     assert_eq!(decoded_string, "Some string!");
 }
 
+#[test]
+fn decode_file_and_bytes() {
+    let path = format!("{PLIST_PATH}plainString.plist");
+    let decoded_string: String = nskeyedunarchiver::decode_file(&path).unwrap();
+    assert_eq!(decoded_string, "Some string!");
+
+    let bytes = std::fs::read(&path).unwrap();
+    let decoded_string: String = nskeyedunarchiver::decode_bytes(&bytes).unwrap();
+    assert_eq!(decoded_string, "Some string!");
+
+    let reader = std::io::Cursor::new(&bytes);
+    let decoded_string: String = nskeyedunarchiver::decode_reader(reader).unwrap();
+    assert_eq!(decoded_string, "Some string!");
+
+    assert!(nskeyedunarchiver::decode_bytes::<String>(b"not a plist").is_err());
+    assert!(matches!(
+        nskeyedunarchiver::decode_file::<i64>(&path),
+        Err(nskeyedunarchiver::Error::DecodeError(e)) if matches!(e.inner(), DeError::ExpectedInteger)
+    ));
+}
+
+#[test]
+fn decode_root() {
+    let path = format!("{PLIST_PATH}plainString.plist");
+    let archive = KeyedArchive::from_file(&path).unwrap();
+    assert_eq!(archive.file(), Some(std::path::Path::new(&path)));
+    assert_eq!(archive.decode_root::<String>().unwrap(), "Some string!");
+
+    // Errors mention the file
+    let e = archive.decode_root::<i64>().unwrap_err();
+    assert_eq!(e.file(), Some(std::path::Path::new(&path)));
+    assert_eq!(e.path(), Some("root"));
+    assert!(matches!(e.inner(), DeError::ExpectedInteger));
+    assert!(e.to_string().starts_with(&path));
+
+    let archive = KeyedArchive::from_bytes(&std::fs::read(&path).unwrap()).unwrap();
+    assert!(archive.file().is_none());
+    assert!(archive.decode_root::<i64>().unwrap_err().file().is_none());
+}
+
+/// A reader that doesn't implement [std::io::Seek], like a socket.
+struct Stream<'a>(&'a [u8]);
+
+impl std::io::Read for Stream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+#[test]
+fn decode_stream() {
+    let bytes = std::fs::read(format!("{PLIST_PATH}plainString.plist")).unwrap();
+    let archive = KeyedArchive::from_stream(Stream(&bytes)).unwrap();
+    let decoded_string: String = archive.decode_top("root").unwrap();
+    assert_eq!(decoded_string, "Some string!");
+
+    // An archive followed by other data
+    let mut message = bytes.clone();
+    message.extend_from_slice(b"next message");
+    let reader = std::io::Read::take(Stream(&message), bytes.len() as u64);
+    assert!(KeyedArchive::from_stream(reader).is_ok());
+
+    struct Failing;
+    impl std::io::Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::ConnectionReset.into())
+        }
+    }
+    assert!(matches!(
+        KeyedArchive::from_stream(Failing),
+        Err(nskeyedunarchiver::Error::IoError(_))
+    ));
+}
+
 #[test]
 fn ns_data() {
     let (root, weak_refs) = open_file("nsData.plist");
@@ -102,6 +180,68 @@ fn circular_reference() {
     check_rc_strong_count(&weak_refs);
 }
 
+#[test]
+fn array_with_nulls() {
+    // -- NSArray
+    //    -- String: "first"
+    //    -- $null
+    //    -- String: "second"
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![(
+                    "NS.objects",
+                    vec![uid_value(3), uid_value(0), uid_value(4)].into(),
+                )],
+            ),
+            classes(&["NSArray", "NSObject"]),
+            "first".into(),
+            "second".into(),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root: ObjectValue = archive.root().unwrap().into();
+
+    assert!(Vec::<String>::decode(&root).is_err());
+    assert_eq!(
+        Vec::<Option<String>>::decode(&root).unwrap(),
+        vec![Some("first".into()), None, Some("second".into())]
+    );
+    assert_eq!(
+        SkipNulls::<String>::decode(&root).unwrap().into_inner(),
+        vec!["first".to_string(), "second".to_string()]
+    );
+}
+
+#[test]
+fn circular_reference_decoding() {
+    // -- NSMutableArray   <-|
+    //    -- NSMutableArray -^
+    #[derive(Debug)]
+    struct Nested(#[allow(dead_code)] Vec<Nested>);
+    impl Decodable for Nested {
+        fn decode(value: &ObjectValue) -> Result<Self, DeError> {
+            DecodeContext::new().decode(value)
+        }
+
+        fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+            Ok(Self(Vec::decode_with(value, ctx)?))
+        }
+    }
+
+    let (root, _) = open_file("circularReference.plist");
+    let root = root.into();
+    let error = Nested::decode(&root).unwrap_err();
+    assert!(matches!(error.inner(), DeError::CircularReference(_)));
+
+    // Decoding the same object as a different type is fine
+    let array = Vec::<Vec<ValueRef>>::decode(&root).unwrap();
+    assert_eq!(array.len(), 1);
+}
+
 /* SimpleDict */
 
 #[derive(PartialEq, Debug)]
@@ -154,6 +294,103 @@ fn simple_dict() {
     check_rc_strong_count(&weak_refs);
 }
 
+#[test]
+fn ordered_collections() {
+    let (root, weak_refs) = open_file("simpleDict.plist");
+    let decoded_data = BTreeMap::<String, SimpleDictItem>::decode(&root.into()).unwrap();
+    let keys: Vec<&str> = decoded_data.keys().map(|k| k.as_str()).collect();
+    assert_eq!(keys, vec!["Array key", "First key", "Second key"]);
+    check_rc_strong_count(&weak_refs);
+}
+
+#[test]
+fn sets() {
+    // -- NSSet
+    //    -- String: "b"
+    //    -- String: "a"
+    //    -- String: "b"
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![(
+                    "NS.objects",
+                    vec![uid_value(3), uid_value(4), uid_value(3)].into(),
+                )],
+            ),
+            classes(&["NSSet", "NSObject"]),
+            "b".into(),
+            "a".into(),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root: ObjectValue = archive.root().unwrap().into();
+
+    let hash_set = HashSet::<String>::decode(&root).unwrap();
+    assert_eq!(hash_set, HashSet::from(["a".to_string(), "b".to_string()]));
+    let btree_set = BTreeSet::<String>::decode(&root).unwrap();
+    assert_eq!(
+        btree_set.into_iter().collect::<Vec<_>>(),
+        vec!["a".to_string(), "b".to_string()]
+    );
+}
+
+#[test]
+fn primitives() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("zero", 0.into()),
+                    ("big", 300.into()),
+                    ("negative", (-5).into()),
+                    ("real", 0.5.into()),
+                    ("hugeReal", 1e300.into()),
+                    ("unichar", 0x44F.into()),
+                    ("char", uid_value(3)),
+                    ("string", uid_value(4)),
+                ],
+            ),
+            classes(&["Primitives", "NSObject"]),
+            "ü".into(),
+            "two".into(),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root = archive.root().unwrap();
+    let fields = root.as_object().unwrap().as_map();
+
+    assert_eq!(usize::decode(&fields["big"]).unwrap(), 300);
+    assert_eq!(isize::decode(&fields["negative"]).unwrap(), -5);
+    assert!(usize::decode(&fields["negative"]).is_err());
+    assert_eq!(f32::decode(&fields["real"]).unwrap(), 0.5);
+    assert_eq!(
+        f32::decode(&fields["hugeReal"]).unwrap_err().to_string(),
+        "Float 1e300 is out of range for f32"
+    );
+    assert_eq!(char::decode(&fields["unichar"]).unwrap(), 'я');
+    assert_eq!(char::decode(&fields["char"]).unwrap(), 'ü');
+    assert!(char::decode(&fields["string"]).is_err());
+    assert_eq!(
+        NonZeroU16::decode(&fields["big"]).unwrap(),
+        NonZeroU16::new(300).unwrap()
+    );
+    assert_eq!(
+        NonZeroU16::decode(&fields["zero"]).unwrap_err().to_string(),
+        "Expected non-zero u16"
+    );
+    assert!(NonZeroU8::decode(&fields["big"]).is_err());
+    assert_eq!(
+        NonZeroI32::decode(&fields["negative"]).unwrap(),
+        NonZeroI32::new(-5).unwrap()
+    );
+}
+
 /* Note */
 
 #[derive(PartialEq, Debug)]
@@ -267,3 +504,155 @@ fn note() {
     };
     assert_eq!(note, decoded);
 }
+
+#[test]
+fn archive_classes() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("NS.objects", plist::Value::Array(vec![uid_value(3), uid_value(4)]))]),
+            classes(&["NSArray", "NSObject"]),
+            object(5, vec![]),
+            object(5, vec![]),
+            classes(&["Note", "NSObject"]),
+            classes(&["Unused", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let classes = archive.classes();
+    assert_eq!(
+        classes.into_iter().collect::<Vec<_>>(),
+        [("NSArray".into(), 1), ("Note".into(), 2), ("Unused".into(), 0)]
+    );
+}
+
+#[test]
+fn shared_strings_and_data() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("title", uid_value(3)),
+                    ("blob", uid_value(4)),
+                    ("inline", plist::Value::Data(vec![1, 2])),
+                ],
+            ),
+            classes(&["Note", "NSObject"]),
+            "Some title".into(),
+            plist::Value::Data(vec![0; 1024]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root = archive.root().unwrap();
+    let obj = root.as_object().unwrap();
+    assert_eq!(obj.get_str("title").unwrap(), "Some title");
+    assert_eq!(obj.decode_string("title").unwrap(), "Some title");
+    assert_eq!(obj.get_bytes("inline").unwrap(), &[1, 2]);
+    assert_eq!(obj.decode_data("blob").unwrap().as_ref().len(), 1024);
+    assert!(matches!(
+        obj.get_str("missing"),
+        Err(DeError::MissingObjectKey(_, _))
+    ));
+
+    // Values are shared with the archive rather than copied
+    let title: Rc<str> = obj.decode_object_as("title").unwrap();
+    let blob: Rc<[u8]> = obj.decode_object_as("blob").unwrap();
+    let archived_blob = archive.values()[4].as_shared_data().unwrap();
+    assert_eq!(&*title, "Some title");
+    assert!(Rc::ptr_eq(&blob, &archived_blob));
+}
+
+#[test]
+fn object_value_accessors() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("title", uid_value(3)),
+                    ("count", 5.into()),
+                    ("scale", uid_value(4)),
+                    ("enabled", true.into()),
+                    ("inline", plist::Value::Data(vec![1, 2])),
+                    ("empty", uid_value(0)),
+                ],
+            ),
+            classes(&["Note", "NSObject"]),
+            "Some title".into(),
+            1.5.into(),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root = ObjectValue::Ref(archive.root().unwrap());
+    let obj = root.as_object().unwrap();
+    let field = |key| obj.as_map().get(key).unwrap();
+
+    assert_eq!(field("title").as_str(), Some("Some title"));
+    assert_eq!(field("count").as_i64(), Some(5));
+    assert_eq!(field("scale").as_f64(), Some(1.5));
+    assert_eq!(field("enabled").as_bool(), Some(true));
+    assert_eq!(field("inline").as_bytes(), Some(&[1, 2][..]));
+    assert!(field("empty").is_null_ref());
+    assert_eq!(field("count").as_str(), None);
+    assert!(root.as_value_ref().is_some());
+
+    let title: &str = field("title").try_into().unwrap();
+    assert_eq!(title, "Some title");
+    let count: i64 = field("count").try_into().unwrap();
+    assert_eq!(count, 5);
+    assert!(matches!(
+        f64::try_from(field("title")),
+        Err(DeError::ExpectedFloat)
+    ));
+}
+
+/// Keeps an error of decoding a value as `T`.
+struct DecodeError<T>(DeError, std::marker::PhantomData<T>);
+
+impl<T: Decodable> Decodable for DecodeError<T> {
+    fn decode(value: &ObjectValue) -> Result<Self, DeError> {
+        DecodeContext::new().decode(value)
+    }
+
+    fn decode_with(value: &ObjectValue, ctx: &mut DecodeContext) -> Result<Self, DeError> {
+        match ctx.decode::<T>(value) {
+            Ok(_) => Err(DeError::Custom("Expected an error".into())),
+            Err(e) => Ok(Self(e, std::marker::PhantomData)),
+        }
+    }
+}
+
+#[test]
+fn probing_class_mismatch() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("NS.objects", vec![].into())]),
+            classes(&["NSArray", "NSObject"]),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    let root = ObjectValue::Ref(archive.root().unwrap());
+    let mut ctx = DecodeContext::new();
+
+    // Errors of failed attempts are thrown away, so they are cheap
+    let error = ctx.try_decode::<DecodeError<String>>(&root).unwrap().0;
+    assert!(matches!(error, DeError::ExpectedClass("NSString")));
+    let error = ctx.try_decode::<DecodeError<HashMap<String, String>>>(&root).unwrap().0;
+    assert!(matches!(error, DeError::ExpectedClass("NSDictionary")));
+    let error = ctx.try_decode::<DecodeError<Data>>(&root).unwrap().0;
+    assert!(matches!(error, DeError::ExpectedClass("NSData")));
+
+    let error = ctx.decode::<DecodeError<String>>(&root).unwrap().0;
+    assert!(matches!(
+        error.inner(),
+        DeError::ClassMismatch { expected, .. } if expected == &["NSString", "NSMutableString"]
+    ));
+}
@@ -0,0 +1,84 @@
+#![cfg(feature = "test-util")]
+
+mod common;
+
+use common::{archive_plist, classes, object, uid_value};
+use nskeyedunarchiver::{
+    KeyedArchive,
+    test_util::{snapshot, snapshot_archive, snapshot_field},
+};
+use plist::Value;
+
+const NOTE_SNAPSHOT: &str = r#"root: Note @1 {
+  data: <3 bytes: 00 01 02>
+  tags: NSArray @2 {
+    NS.objects: [
+      "work"
+      ↩ @1
+    ]
+  }
+  title: "Some note"
+}
+title: "Some note"
+"#;
+
+#[test]
+fn snapshots() {
+    let first = archive_plist(
+        vec![("root", 1), ("title", 3)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("title", uid_value(3)),
+                    ("tags", uid_value(4)),
+                    ("data", Value::Data(vec![0, 1, 2])),
+                ],
+            ),
+            classes(&["Note", "NSObject"]),
+            "Some note".into(),
+            object(
+                5,
+                vec![("NS.objects", vec![uid_value(6), uid_value(1)].into())],
+            ),
+            classes(&["NSArray", "NSObject"]),
+            "work".into(),
+        ],
+    );
+    // The same graph with different uids and key order
+    let second = archive_plist(
+        vec![("root", 6), ("title", 1)],
+        vec![
+            "$null".into(),
+            "Some note".into(),
+            "work".into(),
+            classes(&["NSArray", "NSObject"]),
+            object(
+                3,
+                vec![("NS.objects", vec![uid_value(2), uid_value(6)].into())],
+            ),
+            classes(&["Note", "NSObject"]),
+            object(
+                5,
+                vec![
+                    ("data", Value::Data(vec![0, 1, 2])),
+                    ("tags", uid_value(4)),
+                    ("title", uid_value(1)),
+                ],
+            ),
+        ],
+    );
+    let first = KeyedArchive::from_plist(first).unwrap();
+    let second = KeyedArchive::from_plist(second).unwrap();
+    assert_eq!(snapshot_archive(&first), NOTE_SNAPSHOT);
+    assert_eq!(snapshot_archive(&second), NOTE_SNAPSHOT);
+
+    let root = first.root().unwrap();
+    let tags = root.as_object().unwrap().as_map()["tags"].clone();
+    assert_eq!(
+        snapshot_field(&tags),
+        "NSArray @1 {\n  NS.objects: [\n    \"work\"\n    Note @2 {\n      data: <3 bytes: 00 01 02>\n      tags: ↩ @1\n      title: \"Some note\"\n    }\n  ]\n}"
+    );
+    assert_eq!(snapshot(&second.top()["title"]), "\"Some note\"");
+}
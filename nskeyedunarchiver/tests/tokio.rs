@@ -0,0 +1,110 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use common::archive_plist;
+use nskeyedunarchiver::{AsyncKeyedArchiveStream, Data, Error, KeyedArchive};
+use plist::Value;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// A reader that returns at most `chunk` bytes per read.
+struct Chunked<'a> {
+    bytes: &'a [u8],
+    chunk: usize,
+}
+
+impl AsyncRead for Chunked<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let len = self.chunk.min(self.bytes.len()).min(buf.remaining());
+        let (chunk, rest) = self.bytes.split_at(len);
+        buf.put_slice(chunk);
+        self.bytes = rest;
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn stream_bytes() -> Vec<u8> {
+    let note = std::fs::read("./tests_resources/plists/note.plist").unwrap();
+    // Magic bytes inside of data don't split a document
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec!["$null".into(), Value::Data(b"bplist00bplist00".to_vec())],
+    );
+    let mut data = Vec::new();
+    plist.to_writer_binary(&mut data).unwrap();
+
+    let mut bytes = note.clone();
+    bytes.extend(&data);
+    bytes.extend(&note);
+    bytes
+}
+
+#[tokio::test]
+async fn async_reader() {
+    let bytes = std::fs::read("./tests_resources/plists/note.plist").unwrap();
+    let archive = KeyedArchive::from_async_reader(Chunked {
+        bytes: &bytes,
+        chunk: 100,
+    })
+    .await
+    .unwrap();
+    let expected = KeyedArchive::from_bytes(&bytes).unwrap();
+    assert_eq!(archive.values().len(), expected.values().len());
+
+    let result = KeyedArchive::from_async_reader(&b"garbage"[..]).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn async_archive_streams() {
+    let bytes = stream_bytes();
+    for chunk in [1, 7, 100, bytes.len()] {
+        let mut stream = AsyncKeyedArchiveStream::new(Chunked {
+            bytes: &bytes,
+            chunk,
+        });
+        let mut archives = Vec::new();
+        while let Some(archive) = stream.next().await {
+            archives.push(archive.unwrap());
+        }
+        assert_eq!(archives.len(), 3);
+        assert_eq!(
+            archives[1].decode_root::<Data>().unwrap().as_ref(),
+            b"bplist00bplist00"
+        );
+        assert_eq!(archives[0].values().len(), archives[2].values().len());
+        assert_eq!(stream.offset(), bytes.len());
+    }
+}
+
+#[tokio::test]
+async fn async_archive_stream_garbage() {
+    let mut bytes = stream_bytes();
+    let len = bytes.len();
+    bytes.extend(b"garbage");
+    let mut stream = AsyncKeyedArchiveStream::new(Chunked {
+        bytes: &bytes,
+        chunk: 64,
+    });
+    let mut documents = Vec::new();
+    while let Some(document) = stream.next_document().await {
+        documents.push(document);
+    }
+    assert_eq!(documents.len(), 4);
+    assert!(documents[..3].iter().all(|d| d.is_ok()));
+    assert!(matches!(documents[3], Err(Error::IncorrectFormat(_))));
+    assert_eq!(stream.offset(), len);
+    assert_eq!(
+        documents[..3]
+            .iter()
+            .map(|d| d.as_ref().unwrap().len())
+            .sum::<usize>(),
+        len
+    );
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "tracing")]
+
+mod common;
+
+use common::{archive_plist, classes, object, uid_value};
+use nskeyedunarchiver::KeyedArchive;
+use plist::Value;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{
+    Mutex,
+    atomic::{AtomicU64, Ordering},
+};
+use tracing::{
+    Dispatch, Event, Metadata, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+};
+
+/// Collects span names with their fields and event messages.
+#[derive(Default)]
+struct Collector {
+    next_id: AtomicU64,
+    spans: Mutex<Vec<(String, HashMap<String, String>)>>,
+    events: Mutex<Vec<HashMap<String, String>>>,
+}
+
+#[derive(Default)]
+struct Fields(HashMap<String, String>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name().into(), format!("{value:?}"));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields::default();
+        span.record(&mut fields);
+        let name = span.metadata().name().to_string();
+        self.spans.lock().unwrap().push((name, fields.0));
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        self.events.lock().unwrap().push(fields.0);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn spans() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(2, vec![("NS.objects", Value::Array(vec![uid_value(3)]))]),
+            classes(&["NSArray", "NSObject"]),
+            object(4, vec![]),
+            classes(&["Unknown", "NSObject"]),
+        ],
+    );
+    let dispatch = Dispatch::new(Collector::default());
+    let result = tracing::dispatcher::with_default(&dispatch, || {
+        let archive = KeyedArchive::from_plist(plist).unwrap();
+        archive.decode_root::<Vec<String>>()
+    });
+    assert!(result.is_err());
+    let collector = dispatch.downcast_ref::<Collector>().unwrap();
+
+    let spans = collector.spans.lock().unwrap();
+    let names: Vec<&str> = spans.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "parse_archive",
+            "decode_objects",
+            "decode_top",
+            "decode",
+            "decode"
+        ]
+    );
+    let (_, array) = &spans[3];
+    assert_eq!(array["uid"], "1");
+    assert_eq!(array["class"], "NSArray");
+    assert_eq!(array["path"], "root");
+    let (_, element) = &spans[4];
+    assert_eq!(element["uid"], "3");
+    assert_eq!(element["class"], "Unknown");
+    assert_eq!(element["ty"], "alloc::string::String");
+    assert_eq!(element["path"], "root[0]");
+
+    // Only the innermost error is reported
+    let events = collector.events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["message"], "Unable to decode a value");
+}
@@ -0,0 +1,114 @@
+use nskeyedunarchiver::{
+    Error, KeyedArchive,
+    typedstream::{TypedStream, TypedValue, is_typedstream},
+};
+
+/// A typedstream with an `NSAttributedString` root object.
+fn attributed_string() -> Vec<u8> {
+    let mut bytes = vec![0x04, 0x0b];
+    bytes.extend(b"streamtyped");
+    // System version 1000
+    bytes.extend([0x81, 0xe8, 0x03]);
+    // A new `@` type encoding and a new object with a new class chain
+    bytes.extend([0x84, 0x01, b'@', 0x84, 0x84, 0x84, 0x12]);
+    bytes.extend(b"NSAttributedString");
+    bytes.extend([0x00, 0x84, 0x84, 0x08]);
+    bytes.extend(b"NSObject");
+    bytes.extend([0x00, 0x85]);
+    // A `@` type encoding reference, a new NSString object and a reference
+    // to the NSObject class
+    bytes.extend([0x92, 0x84, 0x84, 0x84, 0x08]);
+    bytes.extend(b"NSString");
+    bytes.extend([0x01, 0x94]);
+    bytes.extend([0x84, 0x01, b'+', 0x05]);
+    bytes.extend(b"Hello");
+    bytes.extend([0x86]);
+    // Integers: a 2-byte one and a 1-byte unsigned one
+    bytes.extend([0x84, 0x02, b'i', b'I', 0x81, 0x00, 0x01, 0xc8]);
+    // A reference to the NSString object
+    bytes.extend([0x92, 0x95]);
+    // A double and a char array
+    bytes.extend([0x84, 0x01, b'd', 0x83]);
+    bytes.extend(1.5f64.to_le_bytes());
+    bytes.extend([0x84, 0x04]);
+    bytes.extend(b"[4c]");
+    bytes.extend([1, 2, 3, 4, 0x86]);
+    // A top-level C string
+    bytes.extend([0x84, 0x01, b'*', 0x84, 0x84, 0x03]);
+    bytes.extend(b"abc");
+    bytes
+}
+
+#[test]
+fn typedstream() {
+    let bytes = attributed_string();
+    assert!(is_typedstream(&bytes));
+    let stream = TypedStream::from_bytes(&bytes).unwrap();
+    assert_eq!(stream.system_version(), 1000);
+    assert_eq!(stream.values().len(), 2);
+    assert_eq!(stream.values()[1].as_str(), Some("abc"));
+
+    let root = stream.root_object().unwrap();
+    assert_eq!(root.class(), "NSAttributedString");
+    let classes: Vec<_> = root.classes().iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(classes, ["NSAttributedString", "NSObject"]);
+    assert_eq!(
+        root.values(),
+        [
+            TypedValue::Object(Some(1)),
+            TypedValue::Integer(256),
+            TypedValue::Integer(200),
+            TypedValue::Object(Some(1)),
+            TypedValue::Float(1.5),
+            TypedValue::Bytes(vec![1, 2, 3, 4]),
+        ]
+    );
+
+    let string = stream.object(1).unwrap();
+    assert_eq!(string.class(), "NSString");
+    assert_eq!(string.classes()[0].version, 1);
+    assert_eq!(string.classes()[1].name, "NSObject");
+    assert_eq!(string.values()[0].as_str(), Some("Hello"));
+}
+
+#[test]
+fn malformed_typedstreams() {
+    let bytes = attributed_string();
+    for len in [0, 5, 20, 60, bytes.len() - 2] {
+        assert!(TypedStream::from_bytes(&bytes[..len]).is_err());
+    }
+
+    // Array lengths are checked before reading elements
+    let encodings: [&[u8]; 2] = [
+        b"[999999999i]",
+        b"[2{x=[18446744073709551615i][18446744073709551615i]}]",
+    ];
+    for encoding in encodings {
+        let mut malformed = bytes[..16].to_vec();
+        malformed.extend([0x84, encoding.len() as u8]);
+        malformed.extend(encoding);
+        assert!(matches!(
+            TypedStream::from_bytes(&malformed),
+            Err(Error::IncorrectTypedStream(_))
+        ));
+    }
+}
+
+#[test]
+fn legacy_archives_are_detected() {
+    let bytes = attributed_string();
+    assert!(matches!(
+        KeyedArchive::from_bytes(&bytes),
+        Err(Error::UnsupportedLegacyArchiver)
+    ));
+    assert!(matches!(
+        KeyedArchive::from_bytes(b"not a plist"),
+        Err(Error::PlistError(_))
+    ));
+
+    let path = std::env::temp_dir().join("nskeyedunarchiver_typedstream.bin");
+    std::fs::write(&path, &bytes).unwrap();
+    let result = KeyedArchive::from_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(Error::UnsupportedLegacyArchiver)));
+}
@@ -0,0 +1,38 @@
+#![cfg(feature = "wasm")]
+
+mod common;
+
+use common::{archive_plist, classes, object, uid_value};
+use nskeyedunarchiver::{KeyedArchive, wasm::to_json};
+use plist::Value;
+
+#[test]
+fn archive_to_json() {
+    let plist = archive_plist(
+        vec![("root", 1)],
+        vec![
+            "$null".into(),
+            object(
+                2,
+                vec![
+                    ("title", uid_value(3)),
+                    ("count", 3.into()),
+                    ("items", Value::Array(vec![uid_value(4), uid_value(0)])),
+                    ("data", Value::Data(b"hello".to_vec())),
+                    ("parent", uid_value(1)),
+                ],
+            ),
+            classes(&["Note", "NSObject"]),
+            "Quote \" and\nnewline".into(),
+            1.5.into(),
+        ],
+    );
+    let archive = KeyedArchive::from_plist(plist).unwrap();
+    assert_eq!(
+        to_json(&archive),
+        concat!(
+            r#"{"root":{"$class":"Note","$uid":1,"count":3,"data":{"$data":"aGVsbG8="},"#,
+            r#""items":[1.5,null],"parent":{"$ref":1},"title":"Quote \" and\nnewline"}}"#
+        )
+    );
+}
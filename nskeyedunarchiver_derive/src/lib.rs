@@ -7,13 +7,32 @@ use syn::{Error, Result, spanned::Spanned};
 
 // All possible attributes
 // #[decodable(rename = "foo")], #[decodable(skip)]
-const BOOL_ATTRS: [&str; 3] = ["skip", "unhandled", "default"];
-const STR_ATTRS: [&str; 1] = ["rename"];
+const BOOL_ATTRS: [&str; 5] = [
+    "skip",
+    "unhandled",
+    "default",
+    "deny_unknown_fields",
+    "case_insensitive",
+];
+const STR_ATTRS: [&str; 7] = [
+    "rename",
+    "default",
+    "context",
+    "with",
+    "unhandled",
+    "skip_if",
+    "require_parent",
+];
+// #[decodable(requires_keys("NS.special", "NS.sizeval"))]
+const LIST_ATTRS: [&str; 2] = ["only_classes", "requires_keys"];
+// #[decodable(unhandled = "...")] modes
+const UNHANDLED_MODES: [&str; 3] = ["value", "dynamic", "debug"];
 
 /// Parses all attributes that come from #[decodable(...)]
 #[derive(Debug, Default)]
 struct MacroAttributes {
     str_attrs: HashMap<String, String>,
+    list_attrs: HashMap<String, Vec<String>>,
     bool_attrs: Vec<String>,
 }
 
@@ -34,93 +53,116 @@ impl TryFrom<&[syn::Attribute]> for MacroAttributes {
             return Ok(Self::default());
         };
 
-        let syn::Meta::List(list) = &decodable_attr.meta else {
+        if !matches!(decodable_attr.meta, syn::Meta::List(_)) {
             return Err(Error::new(
                 decodable_attr.path().span(),
                 "Unable to parse attributes",
             ));
-        };
+        }
 
-        // Make a plain string out of an attribute contents
-        let raw_attr = list.tokens.to_string();
         let mut str_attrs = HashMap::new();
+        let mut list_attrs = HashMap::new();
         let mut bool_attrs = Vec::new();
 
-        // Split it with a comma "," and iterate
-        for param in raw_attr.split(",") {
-            // Split a pair with "=" (name and contents)
-            let mut pair: Vec<&str> = param.split("=").collect();
-            for s in &mut pair {
-                *s = s.trim();
+        // Parse a comma separated list of `name`, `name = "value"` and
+        // `name("value", ...)` attributes
+        decodable_attr.parse_nested_meta(|meta| {
+            let name = meta
+                .path
+                .get_ident()
+                .map(|ident| ident.to_string())
+                .unwrap_or_default();
+            if str_attrs.contains_key(&name)
+                || list_attrs.contains_key(&name)
+                || bool_attrs.contains(&name)
+            {
+                return Err(meta.error("An attribute cannot be set more than once"));
             }
-
-            // There may be bool attributes (like `skip`, without "="), so we handle it
-            if pair.len() == 1 {
-                if bool_attrs.contains(&pair[0].to_string()) {
-                    return Err(Error::new(
-                        decodable_attr.path().span(),
-                        "An attribute cannot be set more than once",
-                    ));
+            if meta.input.peek(syn::token::Paren) {
+                if !LIST_ATTRS.contains(&name.as_str()) {
+                    return Err(meta.error(format!("Unknown attribute `{name}`")));
                 }
-                if !BOOL_ATTRS.contains(&pair[0]) {
-                    return Err(Error::new(
-                        decodable_attr.path().span(),
-                        format!("Unknown attribute `{}`", pair[0]),
-                    ));
+                let content;
+                syn::parenthesized!(content in meta.input);
+                let values = content
+                    .parse_terminated(<syn::LitStr as syn::parse::Parse>::parse, syn::Token![,])
+                    .map_err(|e| {
+                        Error::new(e.span(), "Attribute values should be string literals")
+                    })?;
+                if values.is_empty() {
+                    return Err(meta.error(format!("`{name}` should contain at least one value")));
                 }
-                bool_attrs.push(pair[0].to_string());
-                continue;
+                list_attrs.insert(name, values.iter().map(syn::LitStr::value).collect());
+                return Ok(());
             }
-
-            // Handle cases like `rename = = "smh"`
-            if pair.len() != 2 {
-                return Err(Error::new(
-                    decodable_attr.path().span(),
-                    "Incorrect attribute",
-                ));
+            if meta.input.peek(syn::Token![=]) {
+                if !STR_ATTRS.contains(&name.as_str()) {
+                    return Err(meta.error(format!("Unknown attribute `{name}`")));
+                }
+                let value: syn::LitStr = meta.value()?.parse().map_err(|e| {
+                    Error::new(e.span(), "An attribute value should be a string literal")
+                })?;
+                str_attrs.insert(name, value.value());
+            } else {
+                // There may be bool attributes (like `skip`, without "=")
+                if !BOOL_ATTRS.contains(&name.as_str()) {
+                    return Err(meta.error(format!("Unknown attribute `{name}`")));
+                }
+                bool_attrs.push(name);
             }
+            Ok(())
+        })?;
 
-            let attr_name = pair[0];
-            let attr_value = pair[1];
-            if !attr_value.starts_with("\"") || !attr_value.ends_with("\"") {
-                return Err(Error::new(
-                    decodable_attr.path().span(),
-                    "An attribute value should start and end with a quote",
-                ));
-            }
-            let attr_value = &attr_value[1..attr_value.len() - 1]; // remove quotes ""
-            if str_attrs.contains_key(pair[0]) {
+        if (bool_attrs.contains(&"skip".to_string())
+            || bool_attrs.contains(&"unhandled".to_string()))
+            && (!str_attrs.is_empty() || !list_attrs.is_empty() || bool_attrs.len() > 1)
+        {
+            return Err(Error::new(
+                decodable_attr.path().span(),
+                "`skip` cannot be used with other arguments",
+            ));
+        }
+        if let Some(mode) = str_attrs.get("unhandled") {
+            if str_attrs.len() > 1 || !list_attrs.is_empty() || !bool_attrs.is_empty() {
                 return Err(Error::new(
                     decodable_attr.path().span(),
-                    "An attribute cannot be set more than once",
+                    "`unhandled` cannot be used with other arguments",
                 ));
             }
-            if !STR_ATTRS.contains(&pair[0]) {
+            if !UNHANDLED_MODES.contains(&mode.as_str()) {
                 return Err(Error::new(
                     decodable_attr.path().span(),
-                    format!("Unknown attribute `{}`", pair[0]),
+                    format!("Unknown `unhandled` mode `{mode}`"),
                 ));
             }
-            str_attrs.insert(attr_name.to_string(), attr_value.to_string());
-        }
-
-        if (bool_attrs.contains(&"skip".to_string())
-            || bool_attrs.contains(&"unhandled".to_string()))
-            && (!str_attrs.is_empty() || bool_attrs.len() > 1)
-        {
-            return Err(Error::new(
-                decodable_attr.path().span(),
-                "`skip` cannot be used with other arguments",
-            ));
         }
 
         Ok(Self {
             str_attrs,
+            list_attrs,
             bool_attrs,
         })
     }
 }
 
+/// Checks if a path names a function by its snake_case last segment,
+/// unlike constants (`i64::MAX`) and unit variants or structs (`None`)
+fn is_function_path(path: &syn::Path) -> bool {
+    path.segments
+        .last()
+        .is_some_and(|segment| !segment.ident.to_string().chars().any(char::is_uppercase))
+}
+
+impl MacroAttributes {
+    /// Returns a mode of an `unhandled` field if it's one
+    fn unhandled_mode(&self) -> Option<&str> {
+        if self.bool_attrs.contains(&"unhandled".to_string()) {
+            return Some("value");
+        }
+        self.str_attrs.get("unhandled").map(|mode| mode.as_str())
+    }
+}
+
 // Implements Dedocable and ObjectMember for structs
 fn decodable_struct(input: &DeriveInput) -> Result<TokenStream> {
     let syn::Data::Struct(cur_struct) = &input.data else {
@@ -140,27 +182,92 @@ fn decodable_struct(input: &DeriveInput) -> Result<TokenStream> {
     if let Some(new_name) = struct_attrs.str_attrs.get("rename") {
         struct_name = new_name.to_string();
     }
+    // #[decodable(require_parent = "Class")]
+    // Objects of any class inheriting from the given one are decoded
+    let required_parent = struct_attrs.str_attrs.get("require_parent");
+    if required_parent.is_some() && struct_attrs.str_attrs.contains_key("rename") {
+        return Err(Error::new(
+            input.attrs[0].path().span(),
+            "`rename` cannot be used with `require_parent`",
+        ));
+    }
+    let (class_check, expected_class) = match required_parent {
+        Some(parent) => (quote!(value.inherits_from(#parent)), parent.clone()),
+        None => (quote!(value.matches_class(#struct_name)), struct_name),
+    };
 
     if struct_attrs.bool_attrs.contains(&"skip".to_string())
-        || struct_attrs.bool_attrs.contains(&"unhandled".to_string())
+        || struct_attrs.unhandled_mode().is_some()
         || struct_attrs.bool_attrs.contains(&"default".to_string())
+        || struct_attrs.str_attrs.contains_key("default")
     {
         return Err(Error::new(
             input.attrs[0].path().span(),
             "`skip`, `unhandled`, `default` can only be used for fields",
         ));
     }
+    let deny_unknown_fields = struct_attrs
+        .bool_attrs
+        .contains(&"deny_unknown_fields".to_string());
+    // #[decodable(case_insensitive)]
+    // Keys are looked up ignoring ASCII case
+    let case_insensitive = struct_attrs
+        .bool_attrs
+        .contains(&"case_insensitive".to_string());
+    let get = if case_insensitive {
+        quote!(get_ignore_case)
+    } else {
+        quote!(get)
+    };
+    let is_field = if case_insensitive {
+        quote!(fields.iter().any(|field| field.eq_ignore_ascii_case(key)))
+    } else {
+        quote!(fields.contains(&key))
+    };
+    if struct_attrs.str_attrs.contains_key("with")
+        || struct_attrs.str_attrs.contains_key("skip_if")
+        || struct_attrs.list_attrs.contains_key("only_classes")
+    {
+        return Err(Error::new(
+            input.attrs[0].path().span(),
+            "`with`, `skip_if`, `only_classes` can only be used for fields",
+        ));
+    }
+    if struct_attrs.list_attrs.contains_key("requires_keys") {
+        return Err(Error::new(
+            input.attrs[0].path().span(),
+            "`requires_keys` can only be used for enum variants",
+        ));
+    }
+
+    // #[decodable(context = "Type")]
+    // A user context of the type should be set before decoding fields
+    let context_check = match struct_attrs.str_attrs.get("context") {
+        Some(context) => {
+            let context_type: syn::Type = syn::parse_str(context)
+                .map_err(|_| Error::new(input.attrs[0].path().span(), "Incorrect context type"))?;
+            quote! {
+                ctx.require_user::<#context_type>()?;
+            }
+        }
+        None => quote!(),
+    };
 
     let mut field_inits: Vec<proc_macro2::TokenStream> =
         Vec::with_capacity(named_fields.named.len());
+    // Statements updating fields of an existing value for `decode_into_with`
+    let mut field_updates: Vec<proc_macro2::TokenStream> =
+        Vec::with_capacity(named_fields.named.len());
 
     // First interator over fields to collect all field names
     let mut field_names = Vec::with_capacity(named_fields.named.len());
+    let mut has_unhandled = false;
     for f in &named_fields.named {
         // hangle things like Vec<u8> (brackets like <u8>)
         let field_attrs = MacroAttributes::try_from(f.attrs.as_slice())?;
+        has_unhandled |= field_attrs.unhandled_mode().is_some();
         if field_attrs.bool_attrs.contains(&"skip".to_string())
-            || field_attrs.bool_attrs.contains(&"unhandled".to_string())
+            || field_attrs.unhandled_mode().is_some()
         {
             continue;
         }
@@ -173,8 +280,40 @@ fn decodable_struct(input: &DeriveInput) -> Result<TokenStream> {
         field_names.push(quote!(#field_name));
     }
 
+    // #[decodable(deny_unknown_fields)]
+    // Keys that aren't mapped to any field result in an error
+    let unknown_fields_check = if deny_unknown_fields {
+        quote! {
+            let fields: &[&str] = &[#(#field_names),*];
+            if let Some(key) = value.keys().into_iter().find(|&key| !(#is_field)) {
+                return Err(nskeyedunarchiver::DeError::UnknownObjectKey(
+                    value.class().into(),
+                    key.into(),
+                ));
+            }
+        }
+    } else if !has_unhandled {
+        // Otherwise they're reported as warnings if the context collects them
+        quote! {
+            if ctx.collects_warnings() {
+                let fields: &[&str] = &[#(#field_names),*];
+                for key in value.keys() {
+                    if !(#is_field) {
+                        ctx.warn(nskeyedunarchiver::DecodeWarning::UnknownKey {
+                            path: ctx.path().into(),
+                            class: value.class().into(),
+                            key: key.into(),
+                        });
+                    }
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
     // Second iterator over fields. Now we build field initializators:
-    // fieldName: Type::decode(value)
+    // fieldName: ctx.decode_field::<Type>(value, "fieldName")
     // We put them all inside Self {...}
     for f in &named_fields.named {
         let mut field_name = f.ident.as_ref().unwrap().to_string();
@@ -183,6 +322,26 @@ fn decodable_struct(input: &DeriveInput) -> Result<TokenStream> {
         let mut found_skip = false;
 
         let field_attrs = MacroAttributes::try_from(f.attrs.as_slice())?;
+        if field_attrs.str_attrs.contains_key("context")
+            || field_attrs.str_attrs.contains_key("require_parent")
+            || field_attrs
+                .bool_attrs
+                .contains(&"deny_unknown_fields".to_string())
+            || field_attrs
+                .bool_attrs
+                .contains(&"case_insensitive".to_string())
+        {
+            return Err(Error::new(
+                f.attrs[0].path().span(),
+                "`context`, `deny_unknown_fields`, `case_insensitive`, `require_parent` can only be used for structs",
+            ));
+        }
+        if field_attrs.list_attrs.contains_key("requires_keys") {
+            return Err(Error::new(
+                f.attrs[0].path().span(),
+                "`requires_keys` can only be used for enum variants",
+            ));
+        }
 
         if let Some(new_name) = field_attrs.str_attrs.get("rename") {
             field_name = new_name.to_string();
@@ -196,20 +355,47 @@ fn decodable_struct(input: &DeriveInput) -> Result<TokenStream> {
                 #field_ident: Default::default()
             };
             field_inits.push(inner);
+            field_updates.push(quote! {
+                self.#field_ident = Default::default();
+            });
             continue;
         }
 
         // #[decodable(unhandled)]
-        // Find all unhandled fields and create HashMap<String, ValueRef>
-        // of them and their values
-        if field_attrs.bool_attrs.contains(&"unhandled".to_string()) {
-            let inner = quote! {
-                #field_ident: {
+        // Find all unhandled fields and create HashMap<String, ObjectValue>
+        // of them and their values. With `unhandled = "dynamic"` values are
+        // decoded as DynamicValue, with `unhandled = "debug"` they're printed
+        if let Some(mode) = field_attrs.unhandled_mode() {
+            if deny_unknown_fields {
+                return Err(Error::new(
+                    f.attrs[0].path().span(),
+                    "`unhandled` cannot be used with `deny_unknown_fields`",
+                ));
+            }
+            let unhandled_value = match mode {
+                "dynamic" => quote! {
+                    ctx.decode_field::<nskeyedunarchiver::DynamicValue>(value, field)?
+                },
+                "debug" => quote! {
+                    match value {
+                        nskeyedunarchiver::ObjectValue::Ref(value) => value.pretty().to_string(),
+                        nskeyedunarchiver::ObjectValue::RefArray(values) => {
+                            let values: Vec<String> =
+                                values.iter().map(|v| v.pretty().to_string()).collect();
+                            format!("[{}]", values.join(", "))
+                        }
+                        value => format!("{value:?}"),
+                    }
+                },
+                _ => quote!(value.clone()),
+            };
+            let unhandled_block = quote! {
+                {
                     let mut unhandled_fields = vec![];
                     let keys = value.keys();
                     let fields = vec![#(#field_names),*];
                     for key in keys {
-                        if !fields.contains(&key.as_str()) {
+                        if !(#is_field) {
                             unhandled_fields.push(key);
                         }
                     }
@@ -222,104 +408,249 @@ fn decodable_struct(input: &DeriveInput) -> Result<TokenStream> {
 
                         unhandled.insert(
                             field.to_string(),
-                            value.clone(),
+                            #unhandled_value,
                         );
                     }
 
                     unhandled
                 }
             };
-            field_inits.push(inner);
+            field_inits.push(quote!(#field_ident: #unhandled_block));
+            field_updates.push(quote! {
+                self.#field_ident = #unhandled_block;
+            });
             continue;
         }
 
-        // hangle things like Vec<u8> (brackets like <u8>)
-        if let syn::Type::Path(b) = field_type {
-            let last_segment = b.path.segments.last().unwrap();
-            let last_segment_ident = &last_segment.ident;
-            if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments {
-                let a = args.to_token_stream();
-                let mut inner = quote! {
-                    #field_ident: {
-                        let v = value
-                            .as_map()
-                            .get(#field_name)
-                            .ok_or(nskeyedunarchiver::DeError::MissingObjectKey(value.class().into(), #field_name.into()))?;
-                        nskeyedunarchiver::error_beautifier(#last_segment_ident::#a::decode(v), #struct_name, #field_name)?
-                    }
-                };
-
-                // This is hacky, it panics if there's a custom defined struct/enum
-                // with the same `Option` name
-                // May be replaced with TypeId::of::<std::option::Option<T>>() I guess...
-                let mut is_option = false;
-                if last_segment_ident.to_string().trim() == "Option" {
-                    is_option = true;
-                }
+        // This is hacky, it panics if there's a custom defined struct/enum
+        // with the same `Option` name
+        // May be replaced with TypeId::of::<std::option::Option<T>>() I guess...
+        let mut is_option = false;
+        if let syn::Type::Path(b) = field_type
+            && b.path.segments.last().unwrap().ident == "Option"
+        {
+            is_option = true;
+        }
 
-                // Handle #[decodable(default)] and Option<T>
-                // Default::default() for Option is None
-                if field_attrs.bool_attrs.contains(&"default".to_string()) || is_option {
-                    inner = quote! {
-                        #field_ident: {
-                            if let Some(v) = value.as_map().get(#field_name) {
-                                nskeyedunarchiver::error_beautifier(#last_segment_ident::#a::decode(v), #struct_name, #field_name)?
-                            }
-                            else {
-                                Default::default()
-                            }
-                        }
-                    };
+        // #[decodable(default = "expr")]
+        // A missing field is set to an expression instead of
+        // Default::default(). A path to a function (a snake_case name, like
+        // `Vec::new`) is called, other paths (like `None` or `i64::MAX`) are
+        // used as is
+        let default_expr = match field_attrs.str_attrs.get("default") {
+            Some(default) => {
+                let expr = syn::parse_str::<syn::Expr>(default)
+                    .map_err(|_| Error::new(f.attrs[0].path().span(), "Incorrect default value"))?;
+                Some(match expr {
+                    syn::Expr::Path(path) if is_function_path(&path.path) => quote!(#path()),
+                    expr => expr.to_token_stream(),
+                })
+            }
+            None => None,
+        };
+        let default_value = default_expr
+            .clone()
+            .unwrap_or_else(|| quote!(Default::default()));
+
+        // #[decodable(with = "path")]
+        // A field is decoded with a function instead of its Decodable impl.
+        // For Option<T> fields the function returns T
+        let has_with = field_attrs.str_attrs.contains_key("with");
+        let with_path = match field_attrs.str_attrs.get("with") {
+            Some(with) => {
+                let mut with_path = syn::parse_str::<syn::ExprPath>(with)
+                    .map_err(|_| Error::new(f.attrs[0].path().span(), "Incorrect function path"))?
+                    .to_token_stream();
+                if is_option {
+                    with_path = quote!(|v, ctx| #with_path(v, ctx).map(Some));
                 }
-                field_inits.push(inner);
-                continue;
+                Some(with_path)
             }
-        }
+            None => None,
+        };
+        let (decode_field, decode_optional_field) = match (&with_path, &default_expr) {
+            (_, Some(default_expr)) => {
+                let with_path = with_path.clone().unwrap_or_else(
+                    || quote!(<#field_type as nskeyedunarchiver::Decodable>::decode_with),
+                );
+                (
+                    quote!(ctx.decode_field_with::<#field_type, _>(v, #field_name, #with_path)?),
+                    quote!(ctx.decode_optional_field_or_else::<#field_type, _, _>(v, #field_name, #with_path, || #default_expr)?),
+                )
+            }
+            (Some(with_path), None) => (
+                quote!(ctx.decode_field_with::<#field_type, _>(v, #field_name, #with_path)?),
+                quote!(ctx.decode_optional_field_with::<#field_type, _>(v, #field_name, #with_path)?),
+            ),
+            (None, None) => (
+                quote!(ctx.decode_field::<#field_type>(v, #field_name)?),
+                quote!(ctx.decode_optional_field::<#field_type>(v, #field_name)?),
+            ),
+        };
 
         // regular types
-        let mut inner = quote! {
-            #field_ident: {
-                let v = value.as_map().get(#field_name)
-                .ok_or(nskeyedunarchiver::DeError::MissingObjectKey(value.class().into(), #field_name.into()))?;
-                nskeyedunarchiver::error_beautifier(#field_type::decode(v), #struct_name, #field_name)?
+        let get_required = quote! {
+            let v = match value.as_map().#get(#field_name) {
+                Some(v) => v,
+                None if ctx.is_probing() => {
+                    return Err(nskeyedunarchiver::DeError::MissingKey(#field_name));
+                }
+                None => {
+                    return Err(nskeyedunarchiver::DeError::MissingObjectKey(
+                        value.class().into(),
+                        #field_name.into(),
+                    ));
+                }
+            };
+        };
+        let mut init = quote! {
+            {
+                #get_required
+                #decode_field
             }
         };
-        // Handle #[decodable(default)]
-        if field_attrs.bool_attrs.contains(&"default".to_string()) {
-            inner = quote! {
-                #field_ident: {
-                    if let Some(v) = value.as_map().get(#field_name) {
-                        nskeyedunarchiver::error_beautifier(#field_type::decode(v), #struct_name, #field_name)?
+        // Fields decoded with a function are assigned, others are decoded
+        // into existing values
+        let mut update = if has_with {
+            quote! {
+                self.#field_ident = {
+                    #get_required
+                    #decode_field
+                };
+            }
+        } else {
+            quote! {
+                {
+                    #get_required
+                    ctx.decode_field_into(&mut self.#field_ident, v, #field_name)?;
+                }
+            }
+        };
+        // Handle #[decodable(default)] and Option<T>
+        // Default::default() for Option is None
+        if field_attrs.bool_attrs.contains(&"default".to_string())
+            || default_expr.is_some()
+            || is_option
+        {
+            init = quote! {
+                {
+                    if let Some(v) = value.as_map().#get(#field_name) {
+                        #decode_optional_field
                     }
                     else {
-                        Default::default()
+                        #default_value
                     }
                 }
             };
+            let decode_optional_field_into = if has_with || default_expr.is_some() {
+                quote!(self.#field_ident = #decode_optional_field;)
+            } else {
+                quote!(ctx.decode_optional_field_into(&mut self.#field_ident, v, #field_name)?;)
+            };
+            update = quote! {
+                if let Some(v) = value.as_map().#get(#field_name) {
+                    #decode_optional_field_into
+                }
+                else {
+                    self.#field_ident = #default_value;
+                }
+            };
+        }
+
+        // #[decodable(skip_if = "path")] and #[decodable(only_classes("A", "B"))]
+        // A field is decoded only if a function returns false for an object
+        // and any of the classes is in its class chain, otherwise it's set
+        // to a default value
+        let mut skip_checks = Vec::new();
+        if let Some(skip_if) = field_attrs.str_attrs.get("skip_if") {
+            let skip_if = syn::parse_str::<syn::ExprPath>(skip_if)
+                .map_err(|_| Error::new(f.attrs[0].path().span(), "Incorrect function path"))?;
+            skip_checks.push(quote!(#skip_if(value)));
+        }
+        if let Some(classes) = field_attrs.list_attrs.get("only_classes") {
+            skip_checks.push(quote! {
+                ![#(#classes),*].iter().any(|class| value.inherits_from(class))
+            });
+        }
+        if !skip_checks.is_empty() {
+            init = quote! {
+                if #(#skip_checks)||* {
+                    #default_value
+                } else #init
+            };
+            update = quote! {
+                if #(#skip_checks)||* {
+                    self.#field_ident = #default_value;
+                } else {
+                    #update
+                }
+            };
         }
-        field_inits.push(inner);
+        field_inits.push(quote!(#field_ident: #init));
+        field_updates.push(update);
     }
 
+    // Checks of an object shared by `decode_ref` and `decode_into_with`
+    let object_checks = quote! {
+        let value = value_ref.as_object().ok_or(nskeyedunarchiver::DeError::ExpectedObject)?;
+        if !#class_check {
+            if ctx.is_probing() {
+                return Err(nskeyedunarchiver::DeError::ExpectedClass(#expected_class));
+            }
+            return Err(nskeyedunarchiver::DeError::class_mismatch(value_ref, &[#expected_class]));
+        }
+        #context_check
+        #unknown_fields_check
+    };
+
     let expanded = quote! {
         impl nskeyedunarchiver::Decodable for #struct_ident {
-            fn decode(value: &nskeyedunarchiver::ObjectValue) -> Result<Self, nskeyedunarchiver::DeError> {
-                use nskeyedunarchiver::Decodable;
-                let nskeyedunarchiver::ObjectValue::Ref(value) = value else {
+            fn decode(
+                value: &nskeyedunarchiver::ObjectValue,
+            ) -> Result<Self, nskeyedunarchiver::DeError> {
+                nskeyedunarchiver::DecodeContext::new().decode(value)
+            }
+
+            fn decode_with(
+                value: &nskeyedunarchiver::ObjectValue,
+                ctx: &mut nskeyedunarchiver::DecodeContext,
+            ) -> Result<Self, nskeyedunarchiver::DeError> {
+                let nskeyedunarchiver::ObjectValue::Ref(value_ref) = value else {
                     return Err(nskeyedunarchiver::DeError::ExpectedObject);
                 };
-                let value = value.as_object().ok_or(nskeyedunarchiver::DeError::ExpectedObject)?;
-                if #struct_name != value.class() {
-                    return Err(nskeyedunarchiver::DeError::UnexpectedClass(
-                        value.class().into(),
-                        #struct_name.into(),
-                    ).into());
-                }
+                Self::decode_ref(value_ref, ctx)
+            }
+
+            // Fields are borrowed from a referenced object, so decoding
+            // elements of collections doesn't clone their references
+            #[allow(unused_variables)]
+            fn decode_ref(
+                value_ref: &nskeyedunarchiver::ValueRef,
+                ctx: &mut nskeyedunarchiver::DecodeContext,
+            ) -> Result<Self, nskeyedunarchiver::DeError> {
+                use nskeyedunarchiver::Decodable;
+                #object_checks
                 Ok(
                     Self {
                         #(#field_inits),*
                     }
                 )
             }
+
+            #[allow(unused_variables)]
+            fn decode_into_with(
+                &mut self,
+                value: &nskeyedunarchiver::ObjectValue,
+                ctx: &mut nskeyedunarchiver::DecodeContext,
+            ) -> Result<(), nskeyedunarchiver::DeError> {
+                use nskeyedunarchiver::Decodable;
+                let nskeyedunarchiver::ObjectValue::Ref(value_ref) = value else {
+                    return Err(nskeyedunarchiver::DeError::ExpectedObject);
+                };
+                #object_checks
+                #(#field_updates)*
+                Ok(())
+            }
         }
     };
 
@@ -332,10 +663,12 @@ fn decodable_enum(input: &DeriveInput) -> Result<TokenStream> {
         unreachable!()
     };
     let enum_ident = &input.ident;
-    let enum_ident_str = enum_ident.to_string();
 
     let enum_attrs = MacroAttributes::try_from(input.attrs.as_slice())?;
-    if !enum_attrs.bool_attrs.is_empty() || !enum_attrs.str_attrs.is_empty() {
+    if !enum_attrs.bool_attrs.is_empty()
+        || !enum_attrs.str_attrs.is_empty()
+        || !enum_attrs.list_attrs.is_empty()
+    {
         return Err(Error::new(
             input.span(),
             "Attributes for enums are not supported",
@@ -344,6 +677,7 @@ fn decodable_enum(input: &DeriveInput) -> Result<TokenStream> {
 
     let variants = &cur_enum.variants;
     let mut variants_inits: Vec<proc_macro2::TokenStream> = Vec::with_capacity(variants.len());
+    let mut variants_errors: Vec<proc_macro2::TokenStream> = Vec::with_capacity(variants.len());
 
     // First interator over variants. We find all their types to build a Vec<ObjectType>
     // to pass it to `decode` methods
@@ -354,10 +688,15 @@ fn decodable_enum(input: &DeriveInput) -> Result<TokenStream> {
         if field_attrs.bool_attrs.contains(&"skip".to_string()) {
             continue;
         }
-        if !field_attrs.str_attrs.is_empty() {
+        if !field_attrs.str_attrs.is_empty()
+            || field_attrs
+                .list_attrs
+                .keys()
+                .any(|attr| attr != "requires_keys")
+        {
             return Err(Error::new(
                 v.attrs[0].path().span(),
-                "Only `skip` attribute is valid for enum variants",
+                "Only `skip` and `requires_keys` attributes are valid for enum variants",
             ));
         }
 
@@ -387,60 +726,95 @@ fn decodable_enum(input: &DeriveInput) -> Result<TokenStream> {
 
     // Second iterator over variant. We build if statements that check if
     // an underlying object is decodable into a type of each variant.
-    // if let Ok(v) = Type::decode(value.clone(), types) {
+    // if let Some(v) = ctx.try_decode::<Type>(value) {
     //    return Ok(Self::Variant(v));
     // }
+    // If none of them matches, variants are decoded once again to collect
+    // detailed errors, so failed attempts are cheap on the happy path.
     for v in variants {
         let field_ident = &v.ident;
         let field_type = &v.fields.iter().next().unwrap().ty;
 
         let field_attrs = MacroAttributes::try_from(v.attrs.as_slice())?;
-
-        if !field_attrs.str_attrs.is_empty() {
-            return Err(Error::new(
-                v.attrs[0].path().span(),
-                "This attribute is not supported for enum variants",
-            ));
-        }
         if field_attrs.bool_attrs.contains(&"skip".to_string()) {
             continue;
         }
 
-        // hangle things like Vec<u8> (brackets like <u8>)
-        if let syn::Type::Path(b) = field_type {
-            let last_segment = b.path.segments.last().unwrap();
-            let last_segment_ident = &last_segment.ident;
-            if let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments {
-                let a = args.to_token_stream();
-                let inner = quote! {
-                    if let Ok(v) = #last_segment_ident::#a::decode(value) {
-                        return Ok(Self::#field_ident(v));
-                    }
-                };
-                variants_inits.push(inner);
-                continue;
-            }
-        }
-
-        // regular types
+        // #[decodable(requires_keys("NS.pointval", "NS.special"))]
+        // A variant is only attempted if an object has all of the keys
+        let Some(keys) = field_attrs.list_attrs.get("requires_keys") else {
+            let inner = quote! {
+                if let Some(v) = ctx.try_decode::<#field_type>(value) {
+                    return Ok(Self::#field_ident(v));
+                }
+            };
+            variants_inits.push(inner);
+            let inner = quote! {
+                match ctx.attempt::<#field_type>(value) {
+                    Ok(v) => return Ok(Self::#field_ident(v)),
+                    Err(e) => errors.push(e),
+                }
+            };
+            variants_errors.push(inner);
+            continue;
+        };
         let inner = quote! {
-            if let Ok(v) = #field_type::decode(value) {
-                return Ok(Self::#field_ident(v));
+            if object.is_some_and(|obj| #(obj.contains_key(#keys))&&*) {
+                if let Some(v) = ctx.try_decode::<#field_type>(value) {
+                    return Ok(Self::#field_ident(v));
+                }
             }
         };
         variants_inits.push(inner);
+        let inner = quote! {
+            let missing = match object {
+                Some(obj) => [#(#keys),*]
+                    .into_iter()
+                    .find(|key| !obj.contains_key(key))
+                    .map(|key| nskeyedunarchiver::DeError::MissingObjectKey(
+                        obj.class().into(),
+                        key.into(),
+                    )),
+                None => Some(nskeyedunarchiver::DeError::ExpectedObject),
+            };
+            match missing {
+                Some(e) => errors.push(e),
+                None => match ctx.attempt::<#field_type>(value) {
+                    Ok(v) => return Ok(Self::#field_ident(v)),
+                    Err(e) => errors.push(e),
+                },
+            }
+        };
+        variants_errors.push(inner);
     }
 
     let expanded = quote! {
         impl nskeyedunarchiver::Decodable for #enum_ident {
-            fn decode(value: &nskeyedunarchiver::ObjectValue) -> Result<Self, nskeyedunarchiver::DeError>
+            fn decode(
+                value: &nskeyedunarchiver::ObjectValue,
+            ) -> Result<Self, nskeyedunarchiver::DeError> {
+                nskeyedunarchiver::DecodeContext::new().decode(value)
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            fn decode_with(
+                value: &nskeyedunarchiver::ObjectValue,
+                ctx: &mut nskeyedunarchiver::DecodeContext,
+            ) -> Result<Self, nskeyedunarchiver::DeError>
             where
                 Self: Sized {
+                let object = match value {
+                    nskeyedunarchiver::ObjectValue::Ref(value_ref) => value_ref.as_object(),
+                    _ => None,
+                };
                 #(#variants_inits)*
 
-                Err(nskeyedunarchiver::DeError::Custom(format!(
-                    "Undecodable object for enum {}: {value:?}", #enum_ident_str
-                )))
+                if ctx.is_probing() {
+                    return Err(nskeyedunarchiver::DeError::Multiple(Vec::new()));
+                }
+                let mut errors = Vec::new();
+                #(#variants_errors)*
+                Err(nskeyedunarchiver::DeError::Multiple(errors))
             }
         }
     };